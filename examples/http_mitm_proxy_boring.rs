@@ -3,8 +3,9 @@
 //! Note that this MITM proxy is not production ready, and is only meant
 //! to show you how one might start. You might want to address the following:
 //!
-//! - Load in your tls mitm cert/key pair from file or ACME
-//! - Make sure your clients trust the MITM cert
+//! - Load in your tls mitm CA from file, e.g. created using `rama tls ca init`,
+//!   instead of generating a new one on each start
+//! - Make sure your clients trust the MITM CA cert
 //! - Do not enforce the Application protocol and instead convert requests when needed,
 //!   e.g. in this example we _always_ map the protocol between two ends,
 //!   even though it might be better to be able to map bidirectionaly between http versions
@@ -60,7 +61,9 @@ use rama::{
         tls::{
             ApplicationProtocol, SecureTransport,
             client::ServerVerifyMode,
-            server::{SelfSignedData, ServerAuth, ServerConfig},
+            server::{
+                KeyAlgorithm, SelfSignedData, ServerAuth, ServerCertIssuerData, ServerConfig,
+            },
         },
         user::Basic,
     },
@@ -69,7 +72,7 @@ use rama::{
     tcp::server::TcpListener,
     telemetry::tracing::{self, level_filters::LevelFilter},
     tls::boring::client::{EmulateTlsProfileLayer, TlsConnectorDataBuilder},
-    tls::boring::server::{TlsAcceptorData, TlsAcceptorLayer},
+    tls::boring::server::{CaCertIssuer, TlsAcceptorData, TlsAcceptorLayer},
     ua::{
         emulate::{
             UserAgentEmulateHttpConnectModifier, UserAgentEmulateHttpRequestModifier,
@@ -102,7 +105,7 @@ async fn main() -> Result<(), BoxError> {
         .init();
 
     let mitm_tls_service_data =
-        new_mitm_tls_service_data().context("generate self-signed mitm tls cert")?;
+        new_mitm_tls_service_data().context("generate mitm tls service data")?;
 
     let state = State {
         mitm_tls_service_data,
@@ -278,17 +281,25 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
     }
 }
 
-// NOTE: for a production service you ideally use
-// an issued TLS cert (if possible via ACME). Or at the very least
-// load it in from memory/file, so that your clients can install the certificate for trust.
+// NOTE: for a production service you ideally load the CA in from file
+// (e.g. using `CaCertIssuer::try_from_pem`), so that your clients can install the CA certificate for trust.
 fn new_mitm_tls_service_data() -> Result<TlsAcceptorData, OpaqueError> {
+    let ca_issuer = CaCertIssuer::generate(
+        SelfSignedData {
+            organisation_name: Some("Example Server Acceptor".to_owned()),
+            ..Default::default()
+        },
+        KeyAlgorithm::EcdsaP256,
+    )
+    .context("generate mitm CA")?;
+
     let tls_server_config = ServerConfig {
         application_layer_protocol_negotiation: Some(vec![
             ApplicationProtocol::HTTP_2,
             ApplicationProtocol::HTTP_11,
         ]),
-        ..ServerConfig::new(ServerAuth::SelfSigned(SelfSignedData {
-            organisation_name: Some("Example Server Acceptor".to_owned()),
+        ..ServerConfig::new(ServerAuth::CertIssuer(ServerCertIssuerData {
+            kind: ca_issuer.into(),
             ..Default::default()
         }))
     };
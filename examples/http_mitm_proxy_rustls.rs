@@ -3,8 +3,9 @@
 //! Note that this MITM proxy is not production ready, and is only meant
 //! to show you how one might start. You might want to address the following:
 //!
-//! - Load in your tls mitm cert/key pair from file or ACME
-//! - Make sure your clients trust the MITM cert
+//! - Load in your tls mitm CA from file, e.g. created using `rama tls ca init`,
+//!   instead of generating a new one on each start
+//! - Make sure your clients trust the MITM CA cert
 //! - Do not enforce the Application protocol and instead convert requests when needed,
 //!   e.g. in this example we _always_ map the protocol between two ends,
 //!   even though it might be better to be able to map bidirectionaly between http versions
@@ -53,8 +54,11 @@ use rama::{
     },
     layer::ConsumeErrLayer,
     net::{
-        http::RequestContext, proxy::ProxyTarget, stream::layer::http::BodyLimitLayer,
-        tls::server::SelfSignedData, user::Basic,
+        http::RequestContext,
+        proxy::ProxyTarget,
+        stream::layer::http::BodyLimitLayer,
+        tls::server::{CacheKind, DynamicIssuer, KeyAlgorithm, SelfSignedData},
+        user::Basic,
     },
     rt::Executor,
     service::service_fn,
@@ -62,11 +66,15 @@ use rama::{
     telemetry::tracing::{self, level_filters::LevelFilter},
     tls::rustls::{
         client::TlsConnectorDataBuilder,
-        server::{TlsAcceptorData, TlsAcceptorDataBuilder, TlsAcceptorLayer},
+        dep::rustls,
+        server::{
+            CaCertIssuer, CertIssuerConfigProvider, TlsAcceptorData, TlsAcceptorDataBuilder,
+            TlsAcceptorLayer,
+        },
     },
 };
 
-use std::{convert::Infallible, time::Duration};
+use std::{convert::Infallible, sync::Arc, time::Duration};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[derive(Debug, Clone)]
@@ -88,7 +96,7 @@ async fn main() -> Result<(), BoxError> {
         .init();
 
     let mitm_tls_service_data =
-        new_mitm_tls_service_data().context("generate self-signed mitm tls cert")?;
+        new_mitm_tls_service_data().context("generate mitm tls service data")?;
 
     let state = State {
         mitm_tls_service_data,
@@ -237,19 +245,32 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
     }
 }
 
-// NOTE: for a production service you ideally use
-// an issued TLS cert (if possible via ACME). Or at the very least
-// load it in from memory/file, so that your clients can install the certificate for trust.
+// NOTE: for a production service you ideally load the CA in from file
+// (e.g. using `CaCertIssuer::try_from_pem`), so that your clients can install the CA certificate for trust.
 fn new_mitm_tls_service_data() -> Result<TlsAcceptorData, OpaqueError> {
-    let data = TlsAcceptorDataBuilder::new_self_signed(SelfSignedData {
-        organisation_name: Some("Example Server Acceptor".to_owned()),
-        ..Default::default()
-    })
-    .context("self signed builder")?
+    let ca_issuer = CaCertIssuer::generate(
+        SelfSignedData {
+            organisation_name: Some("Example Server Acceptor".to_owned()),
+            ..Default::default()
+        },
+        KeyAlgorithm::EcdsaP256,
+    )
+    .context("generate mitm CA")?;
+
+    // the cert resolver is replaced by the issued cert for each connection
+    let base_config = TlsAcceptorDataBuilder::from(
+        rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(rustls::server::ResolvesServerCertUsingSni::new())),
+    )
     .with_alpn_protocols_http_auto()
     .with_env_key_logger()
     .context("with env key logger")?
-    .build();
+    .into_rustls_config();
+
+    let provider =
+        CertIssuerConfigProvider::new(DynamicIssuer::new(ca_issuer), CacheKind::default())
+            .with_base_config(base_config);
 
-    Ok(data)
+    Ok(provider.into())
 }
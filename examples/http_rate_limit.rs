@@ -16,6 +16,7 @@
 //! ```sh
 //! curl -v http://127.0.0.1:62008/limit
 //! curl -v http://127.0.0.1:62008/limit/slow
+//! curl -v http://127.0.0.1:62008/rate
 //! ```
 //!
 //! You should see a response with `HTTP/1.1 200 OK` and a JSON body with the method and path of the request.
@@ -32,12 +33,15 @@
 //! curl -v http://127.0.0.1:62008/api/slow
 //! ```
 //!
+//! The `/rate/*` paths are limited to 2 requests per second, with a burst of 5.
+//! Once exceeded you'll get a `429` response with a `Retry-After` header.
+//!
 //! Consult your ip address to reach your server from another machine connected to the same network.
 
 use std::{convert::Infallible, sync::Arc, time::Duration};
 
 use rama::{
-    combinators::Either,
+    combinators::Either3,
     error::BoxError,
     http::service::web::response::{IntoResponse, Json},
     http::{
@@ -46,7 +50,7 @@ use rama::{
     },
     layer::{
         Layer, LimitLayer, MapResultLayer, TraceErrLayer,
-        limit::policy::{ConcurrentPolicy, LimitReached},
+        limit::policy::{ConcurrentPolicy, LimitReached, RateLimited, RatePolicy},
     },
     net::stream::matcher::SocketMatcher,
     rt::Executor,
@@ -75,6 +79,15 @@ async fn main() {
                                 StatusCode::TOO_MANY_REQUESTS,
                             )
                                 .into_response())
+                        } else if let Some(err) = box_error.downcast_ref::<RateLimited>() {
+                            Ok((
+                                [(
+                                    HeaderName::from_static("retry-after"),
+                                    HeaderValue::from(err.retry_after().as_secs().max(1)),
+                                )],
+                                StatusCode::TOO_MANY_REQUESTS,
+                            )
+                                .into_response())
                         } else {
                             Ok((
                                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -90,7 +103,7 @@ async fn main() {
                 // using the [`Either`] combinator you can make tree-like structures,
                 // to make as complex rate limiting logic as you wish.
                 //
-                // For more then 2 variants you can use [`Either3`], [`Either4`], and so on.
+                // For more then 3 variants you can use [`Either4`], [`Either5`], and so on.
                 // Keep it as simple as possible for your own sanity however...
                 LimitLayer::new(Arc::new(vec![
                     // external addresses are limited to 1 connection at a time,
@@ -98,7 +111,7 @@ async fn main() {
                     // but you can make them also optional to not use backoff for some, while using it for others
                     (
                         HttpMatcher::socket(SocketMatcher::loopback()).negate(),
-                        Some(Either::A(ConcurrentPolicy::max_with_backoff(1, None))),
+                        Some(Either3::A(ConcurrentPolicy::max_with_backoff(1, None))),
                     ),
                     // you can also use options for the policy itself, in case you want to disable
                     // the limit for some
@@ -108,16 +121,21 @@ async fn main() {
                     // > property you want.
                     (
                         HttpMatcher::path("/limit/*"),
-                        Some(Either::A(ConcurrentPolicy::max_with_backoff(
+                        Some(Either3::A(ConcurrentPolicy::max_with_backoff(
                             2,
                             Some(ExponentialBackoff::default()),
                         ))),
                     ),
+                    // rate limiting can be combined with concurrency limits in the same policy map
+                    (
+                        HttpMatcher::path("/rate/*"),
+                        Some(Either3::C(RatePolicy::per_second(2).with_burst(5))),
+                    ),
                     // this one is the reason why we are using the (Vec<M, P>, P) approach from above,
                     // as we want to have a default policy for all other requests
                     (
                         HttpMatcher::path("/api/*"),
-                        Some(Either::B((
+                        Some(Either3::B((
                             vec![
                                 (
                                     HttpMatcher::path("/api/slow"),
@@ -38,8 +38,16 @@ use rama::{
         service::web::response::IntoResponse,
     },
     layer::ConsumeErrLayer,
-    net::{http::RequestContext, proxy::ProxyTarget, stream::ClientSocketInfo, user::Basic},
-    proxy::socks5::{Socks5Acceptor, Socks5Auth, server::Socks5PeekRouter},
+    net::{
+        http::RequestContext,
+        proxy::ProxyTarget,
+        stream::{
+            ClientSocketInfo,
+            service::{ProtocolPeekRouter, StreamProtocol},
+        },
+        user::Basic,
+    },
+    proxy::socks5::{Socks5Acceptor, Socks5Auth},
     rt::Executor,
     service::service_fn,
     tcp::{client::service::Forwarder, server::TcpListener},
@@ -85,9 +93,13 @@ async fn main() {
             .into_layer(service_fn(http_plain_proxy)),
     );
 
-    let auto_socks5_acceptor = Socks5PeekRouter::new(socks5_acceptor).with_fallback(http_service);
+    let protocol_router = ProtocolPeekRouter::new()
+        .with_service(StreamProtocol::Socks5, socks5_acceptor)
+        // anything that is not socks5 is served by the http proxy
+        .with_fallback(http_service)
+        .with_peek_timeout(Duration::from_secs(5));
 
-    graceful.spawn_task_fn(|guard| tcp_service.serve_graceful(guard, auto_socks5_acceptor));
+    graceful.spawn_task_fn(|guard| tcp_service.serve_graceful(guard, protocol_router));
 
     graceful
         .shutdown_with_limit(Duration::from_secs(30))
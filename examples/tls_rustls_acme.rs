@@ -0,0 +1,155 @@
+//! This example demonstrates how to obtain certificates on demand from an ACME server,
+//! using the TLS-ALPN-01 challenge served by the tls acceptor itself.
+//!
+//! The example uses [Pebble](https://github.com/letsencrypt/pebble) as a local ACME server,
+//! use `rama::tls::rustls::acme::LETS_ENCRYPT_PRODUCTION_DIRECTORY` for a publicly reachable server instead.
+//!
+//! # Run the example
+//!
+//! Start Pebble, skipping the actual validation of challenges:
+//!
+//! ```sh
+//! PEBBLE_VA_ALWAYS_VALID=1 pebble -config ./test/config/pebble-config.json
+//! ```
+//!
+//! ```sh
+//! cargo run --example tls_rustls_acme --features=acme,http-full
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:5001`, the default TLS-ALPN-01 port of Pebble.
+//! A certificate is ordered for the first request of each server name,
+//! and is stored in `./rama-acme-certs` for later requests (and restarts).
+//! It is renewed in the background once 2/3 of its validity has passed:
+//!
+//! ```sh
+//! curl -vik --resolve example.com:5001:127.0.0.1 https://example.com:5001
+//! ```
+//!
+//! Output:
+//!
+//! ```
+//! * Server certificate:
+//! *  subject: [NONE]
+//! *  subjectAltName: host "example.com" matched cert's "example.com"
+//! *  issuer: CN=Pebble Intermediate CA ...
+//! ```
+
+// rama provides everything out of the box to build a TLS server using ACME certificates
+use rama::{
+    Context, Layer,
+    graceful::Shutdown,
+    http::{
+        Request, Response, client::EasyHttpWebClient, server::HttpServer,
+        service::web::response::IntoResponse,
+    },
+    layer::ConsumeErrLayer,
+    net::{
+        address::Domain,
+        tls::{
+            ApplicationProtocol,
+            server::{CacheKind, DiskCertCache, DynamicCertCache, DynamicIssuer},
+        },
+    },
+    rt::Executor,
+    service::service_fn,
+    tcp::server::TcpListener,
+    telemetry::tracing::{self, level_filters::LevelFilter},
+    tls::rustls::{
+        acme::{AccountKey, AcmeIssuer, ChallengeType},
+        client::TlsConnectorDataBuilder,
+        dep::rustls::{ALL_VERSIONS, ServerConfig, server::ResolvesServerCertUsingSni},
+        server::{CertIssuerConfigProvider, TlsAcceptorLayer},
+    },
+};
+
+// everything else is provided by the standard library, community crates or tokio
+use std::{convert::Infallible, sync::Arc, time::Duration};
+use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+
+const PEBBLE_DIRECTORY: &str = "https://localhost:14000/dir";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let shutdown = Shutdown::default();
+
+    // Pebble uses a self-signed certificate for its api,
+    // trust it (e.g. using trust anchors) instead of disabling verification in production
+    let acme_client = EasyHttpWebClient::builder()
+        .with_default_transport_connector()
+        .without_tls_proxy_support()
+        .without_proxy_support()
+        .with_tls_support_using_rustls(Some(
+            TlsConnectorDataBuilder::new()
+                .with_no_cert_verifier()
+                .build(),
+        ))
+        .build();
+
+    // persist the account key in order to re-use the same account across restarts
+    let account_key = AccountKey::generate().expect("generate acme account key");
+    let acme_issuer = AcmeIssuer::new(PEBBLE_DIRECTORY, acme_client, account_key)
+        .with_contact(vec!["mailto:admin@example.com".to_owned()])
+        .with_challenge_type(ChallengeType::TlsAlpn01)
+        .with_allowed_domains(vec![Domain::from_static("example.com")])
+        .with_cert_cache(DynamicCertCache::new(DiskCertCache::new(
+            "./rama-acme-certs",
+        )));
+    let acme_challenges = acme_issuer.acme_challenges().clone();
+
+    let mut base_config = ServerConfig::builder_with_protocol_versions(ALL_VERSIONS)
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::new(ResolvesServerCertUsingSni::new()));
+    base_config.alpn_protocols = vec![
+        ApplicationProtocol::HTTP_2.as_bytes().to_vec(),
+        ApplicationProtocol::HTTP_11.as_bytes().to_vec(),
+    ];
+
+    // the acme issuer caches (and renews) the certs itself
+    let config_provider = Arc::new(
+        CertIssuerConfigProvider::new(DynamicIssuer::new(acme_issuer), CacheKind::Disabled)
+            .with_base_config(base_config)
+            .with_acme_challenges(acme_challenges),
+    );
+
+    // create http server
+    shutdown.spawn_task_fn(async |guard| {
+        let exec = Executor::graceful(guard.clone());
+        let http_service = HttpServer::auto(exec).service(service_fn(http_service));
+
+        let tcp_service = (
+            ConsumeErrLayer::default(),
+            TlsAcceptorLayer::new(config_provider.into()),
+        )
+            .into_layer(http_service);
+
+        tracing::info!("listening on 127.0.0.1:5001");
+        TcpListener::bind("127.0.0.1:5001")
+            .await
+            .expect("bind TCP Listener: https")
+            .serve_graceful(guard, tcp_service)
+            .await;
+    });
+
+    shutdown
+        .shutdown_with_limit(Duration::from_secs(3))
+        .await
+        .expect("graceful shutdown");
+}
+
+async fn http_service<S>(_ctx: Context<S>, _request: Request) -> Result<Response, Infallible> {
+    Ok(
+        "hello client, you were served using a certificate issued by an ACME server"
+            .into_response(),
+    )
+}
@@ -209,10 +209,28 @@ pub(super) fn get_ja4h_info<B>(req: &Request<B>) -> Option<Ja4HInfo> {
         })
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct Http2FingerprintInfo {
+    pub(super) hash: String,
+    pub(super) raw: String,
+}
+
+fn get_h2_fingerprint_info(h2_settings: &Http2Settings) -> Option<Http2FingerprintInfo> {
+    h2_settings
+        .fingerprint()
+        .inspect_err(|err| tracing::debug!("http2 fingerprint compute failure: {err:?}"))
+        .ok()
+        .map(|fp| Http2FingerprintInfo {
+            hash: fp.hash(),
+            raw: fp.to_string(),
+        })
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub(super) struct HttpInfo {
     pub(super) headers: Vec<(String, String)>,
     pub(super) h2_settings: Option<Http2Settings>,
+    pub(super) h2_fingerprint: Option<Http2FingerprintInfo>,
 }
 
 pub(super) async fn get_and_store_http_info(
@@ -341,9 +359,12 @@ pub(super) async fn get_and_store_http_info(
         })
         .collect();
 
+    let h2_fingerprint = h2_settings.as_ref().and_then(get_h2_fingerprint_info);
+
     Ok(HttpInfo {
         headers,
         h2_settings,
+        h2_fingerprint,
     })
 }
 
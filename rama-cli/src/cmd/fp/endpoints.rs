@@ -138,6 +138,16 @@ pub(super) async fn get_report(
         })
     }
 
+    if let Some(h2_fingerprint) = http_info.h2_fingerprint {
+        tables.push(Table {
+            title: "🆔 HTTP/2 (Akamai)".to_owned(),
+            rows: vec![
+                ("HTTP/2 Client Fingerprint".to_owned(), h2_fingerprint.raw),
+                ("Hash".to_owned(), h2_fingerprint.hash),
+            ],
+        })
+    }
+
     if let Some(h2_settings) = http_info.h2_settings {
         extend_tables_with_h2_settings(h2_settings, &mut tables);
     }
@@ -406,6 +416,7 @@ pub(super) async fn post_api_fetch_number(
             "http_info": json!({
                 "headers": http_info.headers,
                 "h2": http_info.h2_settings,
+                "h2_fingerprint": http_info.h2_fingerprint,
                 "ja4h": ja4h,
             }),
             "js_web_apis": request.js_web_apis,
@@ -461,6 +472,7 @@ pub(super) async fn post_api_xml_http_request_number(
             "http_info": json!({
                 "headers": http_info.headers,
                 "h2": http_info.h2_settings,
+                "h2_fingerprint": http_info.h2_fingerprint,
                 "ja4h": ja4h,
             }),
         }
@@ -540,6 +552,16 @@ pub(super) async fn form(mut ctx: Context<Arc<State>>, req: Request) -> Result<H
         })
     }
 
+    if let Some(h2_fingerprint) = http_info.h2_fingerprint {
+        tables.push(Table {
+            title: "🆔 HTTP/2 (Akamai)".to_owned(),
+            rows: vec![
+                ("HTTP/2 Client Fingerprint".to_owned(), h2_fingerprint.raw),
+                ("Hash".to_owned(), h2_fingerprint.hash),
+            ],
+        })
+    }
+
     if let Some(h2_settings) = http_info.h2_settings {
         extend_tables_with_h2_settings(h2_settings, &mut tables);
     }
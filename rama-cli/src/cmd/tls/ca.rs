@@ -0,0 +1,248 @@
+//! create and manage a certificate authority (CA) on disk
+
+use clap::{Args, Subcommand, ValueEnum};
+use rama::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    net::{
+        address::Host,
+        tls::server::{KeyAlgorithm, SelfSignedData},
+    },
+    telemetry::tracing,
+    tls::boring::{core::x509::X509, server::CaCertIssuer},
+};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const CA_CERT_FILE_NAME: &str = "ca.crt.pem";
+const CA_KEY_FILE_NAME: &str = "ca.key.pem";
+
+#[derive(Args, Debug, Clone)]
+/// manage a certificate authority (CA), e.g. to issue certificates for a MITM proxy
+pub(super) struct CliCommandTlsCa {
+    #[command(subcommand)]
+    cmd: CliTlsCaCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum CliTlsCaCommands {
+    Init(CliCommandTlsCaInit),
+    Export(CliCommandTlsCaExport),
+    Sign(CliCommandTlsCaSign),
+}
+
+#[derive(Args, Debug, Clone)]
+/// create a new root CA certificate and private key
+struct CliCommandTlsCaInit {
+    #[arg(long, short = 'd', default_value = "rama-ca")]
+    /// directory to store the CA certificate and private key in
+    dir: PathBuf,
+
+    #[arg(long, default_value_t = KeyAlgorithm::EcdsaP256)]
+    /// key algorithm of the CA: rsa2048, rsa4096, ecdsa-p256 or ecdsa-p384
+    key_algorithm: KeyAlgorithm,
+
+    #[arg(long)]
+    /// organisation name of the CA
+    organisation: Option<String>,
+
+    #[arg(long, default_value_t = CaCertIssuer::DEFAULT_CA_VALIDITY_DAYS)]
+    /// amount of days the CA certificate is valid for
+    days: u32,
+
+    #[arg(long, short = 'f')]
+    /// overwrite an existing CA in the directory
+    force: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+/// export the root CA certificate, to be installed in the trust store of clients
+struct CliCommandTlsCaExport {
+    #[arg(long, short = 'd', default_value = "rama-ca")]
+    /// directory where the CA is stored
+    dir: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = ExportFormat::Pem)]
+    /// encoding of the exported certificate
+    format: ExportFormat,
+
+    #[arg(long, short = 'o')]
+    /// file to write the certificate to, written to stdout if not defined
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Pem,
+    Der,
+}
+
+#[derive(Args, Debug, Clone)]
+/// issue a certificate signed by the root CA for each of the given hosts
+struct CliCommandTlsCaSign {
+    /// hosts (domains or ip addresses) to issue a certificate for
+    #[arg(required = true)]
+    hosts: Vec<Host>,
+
+    #[arg(long, short = 'd', default_value = "rama-ca")]
+    /// directory where the CA is stored
+    dir: PathBuf,
+
+    #[arg(long, short = 'o', default_value = ".")]
+    /// directory to store the issued certificate chains and private keys in
+    out_dir: PathBuf,
+
+    #[arg(long, default_value_t = KeyAlgorithm::EcdsaP256)]
+    /// key algorithm of the issued certificates: rsa2048, rsa4096, ecdsa-p256, ecdsa-p384 or ed25519
+    key_algorithm: KeyAlgorithm,
+
+    #[arg(long, default_value_t = CaCertIssuer::DEFAULT_VALIDITY_DAYS)]
+    /// amount of days the issued certificates are valid for
+    days: u32,
+}
+
+/// Run the tls ca command
+pub(super) fn run(cfg: CliCommandTlsCa) -> Result<(), BoxError> {
+    match cfg.cmd {
+        CliTlsCaCommands::Init(cfg) => init(cfg),
+        CliTlsCaCommands::Export(cfg) => export(cfg),
+        CliTlsCaCommands::Sign(cfg) => sign(cfg),
+    }
+}
+
+fn init(cfg: CliCommandTlsCaInit) -> Result<(), BoxError> {
+    let cert_path = cfg.dir.join(CA_CERT_FILE_NAME);
+    let key_path = cfg.dir.join(CA_KEY_FILE_NAME);
+    if !cfg.force && (cert_path.exists() || key_path.exists()) {
+        return Err(OpaqueError::from_display(format!(
+            "CA already exists in {}, use --force to overwrite it",
+            cfg.dir.display()
+        ))
+        .into());
+    }
+
+    let issuer = CaCertIssuer::generate_with_validity(
+        SelfSignedData {
+            organisation_name: cfg.organisation,
+            ..Default::default()
+        },
+        cfg.key_algorithm,
+        cfg.days,
+    )
+    .context("generate CA")?;
+
+    fs::create_dir_all(&cfg.dir).context("create CA directory")?;
+    write_private_file(&key_path, &issuer.ca_key_pem()?).context("write CA private key")?;
+    fs::write(&cert_path, issuer.ca_cert_pem()?).context("write CA cert")?;
+
+    tracing::info!(
+        ca.cert = %cert_path.display(),
+        ca.key = %key_path.display(),
+        "created {} CA, valid for {} days",
+        cfg.key_algorithm,
+        cfg.days,
+    );
+    Ok(())
+}
+
+fn export(cfg: CliCommandTlsCaExport) -> Result<(), BoxError> {
+    let cert_pem = fs::read(cfg.dir.join(CA_CERT_FILE_NAME)).context("read CA cert")?;
+    let data = match cfg.format {
+        ExportFormat::Pem => cert_pem,
+        ExportFormat::Der => X509::from_pem(&cert_pem)
+            .context("parse CA cert from PEM")?
+            .to_der()
+            .context("encode CA cert as DER")?,
+    };
+
+    match cfg.output {
+        Some(path) => {
+            fs::write(&path, data).context("write exported CA cert")?;
+            tracing::info!(
+                ca.cert = %path.display(),
+                "exported CA cert as {:?}",
+                cfg.format,
+            );
+        }
+        None => std::io::stdout()
+            .write_all(&data)
+            .context("write exported CA cert to stdout")?,
+    }
+    Ok(())
+}
+
+fn sign(cfg: CliCommandTlsCaSign) -> Result<(), BoxError> {
+    let issuer = load_ca(&cfg.dir)?
+        .with_key_algorithm(cfg.key_algorithm)
+        .with_validity_days(cfg.days);
+
+    fs::create_dir_all(&cfg.out_dir).context("create output directory")?;
+    for host in cfg.hosts {
+        let (cert_chain, key) = issuer
+            .issue(&host)
+            .with_context(|| format!("issue cert for {host}"))?;
+
+        let mut cert_chain_pem = Vec::new();
+        for cert in cert_chain {
+            cert_chain_pem.extend(cert.to_pem().context("encode issued cert as PEM")?);
+        }
+        let key_pem = key
+            .private_key_to_pem_pkcs8()
+            .context("encode issued private key as PEM")?;
+
+        let file_name = host_file_name(&host);
+        let cert_path = cfg.out_dir.join(format!("{file_name}.crt.pem"));
+        let key_path = cfg.out_dir.join(format!("{file_name}.key.pem"));
+        write_private_file(&key_path, &key_pem).context("write issued private key")?;
+        fs::write(&cert_path, cert_chain_pem).context("write issued cert chain")?;
+
+        tracing::info!(
+            cert = %cert_path.display(),
+            key = %key_path.display(),
+            "issued {} cert for {host}, valid for {} days",
+            cfg.key_algorithm,
+            cfg.days,
+        );
+    }
+    Ok(())
+}
+
+fn load_ca(dir: &Path) -> Result<CaCertIssuer, OpaqueError> {
+    let cert_pem = fs::read(dir.join(CA_CERT_FILE_NAME)).context("read CA cert")?;
+    let key_pem = fs::read(dir.join(CA_KEY_FILE_NAME)).context("read CA private key")?;
+    CaCertIssuer::try_from_pem(&cert_pem, &key_pem).context("load CA")
+}
+
+/// write a file only readable by the current user (on unix)
+fn write_private_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // the mode only applies to newly created files, so also restrict an overwritten one
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)
+}
+
+/// file name (stem) for the given host, safe to use on all platforms
+///
+/// IPv6 addresses contain `:`, which is not allowed in windows file names.
+fn host_file_name(host: &Host) -> String {
+    host.to_string()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -1,6 +1,6 @@
 #![allow(clippy::print_stdout)]
 
-use clap::Args;
+use clap::{Args, Subcommand};
 use rama::{
     Context, Layer, Service,
     error::{BoxError, ErrorContext},
@@ -21,25 +21,42 @@ use rama::{
 };
 use tokio::net::TcpStream;
 
+mod ca;
+
 #[derive(Args, Debug, Clone)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 /// rama tls support
 pub struct CliCommandTls {
+    #[command(subcommand)]
+    cmd: Option<CliTlsCommands>,
+
     /// The address to connect to
     /// e.g. "example.com" or "example.com:8443"
     /// if no port is provided, the default port 443 will be used
-    address: String,
+    #[arg(required = true)]
+    address: Option<String>,
 
     #[arg(long, short = 'k')]
     /// Wether to skip certificate verification
     insecure: bool,
 }
 
+#[derive(Subcommand, Debug, Clone)]
+enum CliTlsCommands {
+    Ca(ca::CliCommandTlsCa),
+}
+
 /// Run the tls command
 pub async fn run(cfg: CliCommandTls) -> Result<(), BoxError> {
     crate::trace::init_tracing(LevelFilter::INFO);
 
-    let address = cfg.address.trim();
-    let authority = if cfg.address.contains(':') {
+    if let Some(CliTlsCommands::Ca(cfg)) = cfg.cmd {
+        return ca::run(cfg);
+    }
+
+    let address = cfg.address.context("tls command requires an address")?;
+    let address = address.trim();
+    let authority = if address.contains(':') {
         address
             .parse()
             .context("parse config address as authority")?
@@ -0,0 +1,47 @@
+use std::fmt;
+
+/// A [`FailureClassifier`] is used by the [`CircuitBreaker`] to determine
+/// whether or not the result of the inner service has to be considered a failure.
+///
+/// It is implemented for any `Fn(&Result<Response, Error>) -> bool`,
+/// and the default [`ErrorsAsFailures`] classifier considers all errors as failures.
+///
+/// [`CircuitBreaker`]: super::CircuitBreaker
+pub trait FailureClassifier<Response, Error>: Send + Sync + 'static {
+    /// Returns `true` if the result has to be recorded as a failure.
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool;
+}
+
+impl<Response, Error, F> FailureClassifier<Response, Error> for F
+where
+    F: Fn(&Result<Response, Error>) -> bool + Send + Sync + 'static,
+{
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool {
+        (self)(result)
+    }
+}
+
+/// A [`FailureClassifier`] which considers all errors as failures,
+/// and all responses as successes.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct ErrorsAsFailures;
+
+impl ErrorsAsFailures {
+    /// Create a new [`ErrorsAsFailures`] classifier.
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl fmt::Debug for ErrorsAsFailures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorsAsFailures").finish()
+    }
+}
+
+impl<Response, Error> FailureClassifier<Response, Error> for ErrorsAsFailures {
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool {
+        result.is_err()
+    }
+}
@@ -0,0 +1,55 @@
+//! Error type for the circuit breaker middleware.
+
+use super::CircuitState;
+use std::{error, fmt, time::Duration};
+
+/// Error returned by the [`CircuitBreaker`] when a request is short-circuited,
+/// meaning that it was rejected without calling the inner service.
+///
+/// [`CircuitBreaker`]: super::CircuitBreaker
+#[derive(Debug, Clone)]
+pub struct CircuitOpen {
+    state: CircuitState,
+    retry_after: Option<Duration>,
+}
+
+impl CircuitOpen {
+    pub(super) const fn new(state: CircuitState, retry_after: Option<Duration>) -> Self {
+        Self { state, retry_after }
+    }
+
+    /// The state of the circuit at the time the request was rejected.
+    ///
+    /// This is [`CircuitState::Open`] when the circuit is tripped,
+    /// or [`CircuitState::HalfOpen`] when the circuit is being probed
+    /// and no more trial requests are allowed.
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// The time after which the circuit will allow trial requests again,
+    /// if known.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.state, self.retry_after) {
+            (CircuitState::HalfOpen, _) => {
+                write!(
+                    f,
+                    "request short-circuited: circuit is half-open and probing"
+                )
+            }
+            (_, Some(retry_after)) => write!(
+                f,
+                "request short-circuited: circuit is open (retry after {retry_after:?})"
+            ),
+            (_, None) => write!(f, "request short-circuited: circuit is open"),
+        }
+    }
+}
+
+impl error::Error for CircuitOpen {}
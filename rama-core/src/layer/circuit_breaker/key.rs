@@ -0,0 +1,31 @@
+use crate::Context;
+
+/// A [`BreakerKey`] is used by the [`CircuitBreaker`] to partition requests
+/// over independent circuits, e.g. one per upstream authority.
+///
+/// It is implemented for any `Fn(&Context<State>, &Request) -> Option<K>`,
+/// as well as for `()`, which puts all requests in a single circuit.
+///
+/// Requests for which no key is returned bypass the circuit breaker.
+///
+/// [`CircuitBreaker`]: super::CircuitBreaker
+pub trait BreakerKey<State, Request, K>: Send + Sync + 'static {
+    /// Returns the key of the circuit to use for the given request,
+    /// or `None` in case the request is not to be guarded.
+    fn breaker_key(&self, ctx: &Context<State>, req: &Request) -> Option<K>;
+}
+
+impl<State, Request> BreakerKey<State, Request, ()> for () {
+    fn breaker_key(&self, _ctx: &Context<State>, _req: &Request) -> Option<()> {
+        Some(())
+    }
+}
+
+impl<State, Request, K, F> BreakerKey<State, Request, K> for F
+where
+    F: Fn(&Context<State>, &Request) -> Option<K> + Send + Sync + 'static,
+{
+    fn breaker_key(&self, ctx: &Context<State>, req: &Request) -> Option<K> {
+        (self)(ctx, req)
+    }
+}
@@ -0,0 +1,167 @@
+use super::{Breakers, CircuitBreaker, ErrorsAsFailures};
+use crate::Layer;
+use rama_utils::macros::generate_set_and_with;
+use std::{fmt, hash::Hash, time::Duration};
+
+/// A [`Layer`] that produces [`CircuitBreaker`] services.
+///
+/// All services produced by the same layer (or its clones)
+/// share the same circuits.
+///
+/// See [the module docs](super) for more information.
+pub struct CircuitBreakerLayer<C = ErrorsAsFailures, F = (), K = ()> {
+    breakers: Breakers<C, F, K>,
+}
+
+impl<C: fmt::Debug, F, K> fmt::Debug for CircuitBreakerLayer<C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreakerLayer")
+            .field("breakers", &self.breakers)
+            .finish()
+    }
+}
+
+impl<C: Clone, F: Clone, K> Clone for CircuitBreakerLayer<C, F, K> {
+    fn clone(&self) -> Self {
+        Self {
+            breakers: self.breakers.clone(),
+        }
+    }
+}
+
+impl CircuitBreakerLayer {
+    /// Creates a new [`CircuitBreakerLayer`], which uses a single circuit
+    /// for all requests and considers all errors as failures.
+    pub fn new() -> Self {
+        Self {
+            breakers: Breakers::new(ErrorsAsFailures::new(), ()),
+        }
+    }
+}
+
+impl Default for CircuitBreakerLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, F, K> CircuitBreakerLayer<C, F, K>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    /// Use the given [`FailureClassifier`] to determine
+    /// which results are to be considered failures.
+    ///
+    /// [`FailureClassifier`]: super::FailureClassifier
+    pub fn with_classifier<C2>(self, classifier: C2) -> CircuitBreakerLayer<C2, F, K> {
+        CircuitBreakerLayer {
+            breakers: Breakers {
+                classifier,
+                key_fn: self.breakers.key_fn,
+                settings: self.breakers.settings,
+                capacity: self.breakers.capacity,
+                circuits: self.breakers.circuits,
+            },
+        }
+    }
+
+    /// Use the given [`BreakerKey`] to partition requests over independent circuits,
+    /// e.g. one circuit per upstream authority.
+    ///
+    /// Note that this resets all circuits tracked so far.
+    ///
+    /// [`BreakerKey`]: super::BreakerKey
+    pub fn with_key_fn<F2, K2>(self, key_fn: F2) -> CircuitBreakerLayer<C, F2, K2>
+    where
+        K2: Hash + Eq + Send + Sync + 'static,
+    {
+        CircuitBreakerLayer {
+            breakers: Breakers {
+                classifier: self.breakers.classifier,
+                key_fn,
+                settings: self.breakers.settings,
+                capacity: self.breakers.capacity,
+                circuits: Breakers::<C, F2, K2>::new_cache(self.breakers.capacity),
+            },
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the amount of consecutive failures after which the circuit opens.
+        ///
+        /// Defaults to `5`. A threshold of zero is treated as a threshold of one.
+        pub fn failure_threshold(mut self, threshold: u32) -> Self {
+            self.breakers.settings.failure_threshold = threshold.max(1);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the time the circuit remains open before allowing trial requests.
+        ///
+        /// Defaults to 30 seconds.
+        pub fn open_timeout(mut self, timeout: Duration) -> Self {
+            self.breakers.settings.open_timeout = timeout;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum amount of concurrent trial requests allowed
+        /// while the circuit is half-open.
+        ///
+        /// Defaults to `1`. A value of zero is treated as one.
+        pub fn half_open_max_calls(mut self, max: u32) -> Self {
+            self.breakers.settings.half_open_max_calls = max.max(1);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the amount of successful trial requests required
+        /// to close a half-open circuit again.
+        ///
+        /// Defaults to `1`. A value of zero is treated as one.
+        pub fn success_threshold(mut self, threshold: u32) -> Self {
+            self.breakers.settings.success_threshold = threshold.max(1);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum amount of circuits to keep track of,
+        /// only relevant when using a key function.
+        ///
+        /// Once reached, the least recently used circuits are evicted.
+        /// Defaults to `10_000`.
+        ///
+        /// Note that this resets all circuits tracked so far.
+        pub fn capacity(mut self, capacity: u64) -> Self {
+            self.breakers.capacity = capacity;
+            self.breakers.circuits = Breakers::<C, F, K>::new_cache(capacity);
+            self
+        }
+    }
+}
+
+impl<S, C, F, K> Layer<S> for CircuitBreakerLayer<C, F, K>
+where
+    C: Clone,
+    F: Clone,
+{
+    type Service = CircuitBreaker<S, C, F, K>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreaker {
+            inner,
+            breakers: self.breakers.clone(),
+        }
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        CircuitBreaker {
+            inner,
+            breakers: self.breakers,
+        }
+    }
+}
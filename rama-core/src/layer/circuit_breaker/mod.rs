@@ -0,0 +1,357 @@
+//! Middleware that short-circuits requests to an inner service that keeps failing.
+//!
+//! A circuit starts out [`Closed`], allowing all requests to pass through.
+//! Once a configurable amount of consecutive failures is reached the circuit
+//! [`Open`]s, rejecting all requests with a [`CircuitOpen`] error without
+//! calling the inner service. After a timeout the circuit becomes [`HalfOpen`],
+//! allowing a limited amount of trial requests through. Depending on their outcome
+//! the circuit closes again or re-opens.
+//!
+//! What is considered a failure is determined by a [`FailureClassifier`],
+//! by default all errors are failures. For http services you can use
+//! the classifiers found in `rama_http::layer::classify`.
+//!
+//! Requests can be partitioned over independent circuits using a [`BreakerKey`],
+//! e.g. to have a circuit per upstream authority.
+//!
+//! The [`CircuitState`] of the circuit used for a request is inserted
+//! in the [`Context`] of requests that are allowed to proceed.
+//!
+//! [`Closed`]: CircuitState::Closed
+//! [`Open`]: CircuitState::Open
+//! [`HalfOpen`]: CircuitState::HalfOpen
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, service::service_fn};
+//! use rama_core::layer::circuit_breaker::{CircuitBreakerLayer, CircuitOpen};
+//! use rama_core::error::BoxError;
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = CircuitBreakerLayer::new()
+//!     .with_failure_threshold(1)
+//!     .with_open_timeout(Duration::from_secs(30))
+//!     .into_layer(service_fn(async |_, _: ()| {
+//!         Err::<(), _>(BoxError::from("upstream failure"))
+//!     }));
+//!
+//! // the first failure trips the circuit...
+//! let err = service.serve(Context::default(), ()).await.unwrap_err();
+//! assert!(err.downcast_ref::<CircuitOpen>().is_none());
+//!
+//! // ...such that the next request is short-circuited
+//! let err = service.serve(Context::default(), ()).await.unwrap_err();
+//! assert!(err.downcast_ref::<CircuitOpen>().is_some());
+//! # }
+//! ```
+
+use crate::{Context, Service, error::BoxError};
+use moka::sync::Cache;
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, hash::Hash, sync::Arc};
+
+mod classify;
+#[doc(inline)]
+pub use classify::{ErrorsAsFailures, FailureClassifier};
+
+mod key;
+#[doc(inline)]
+pub use key::BreakerKey;
+
+mod error;
+#[doc(inline)]
+pub use error::CircuitOpen;
+
+mod state;
+#[doc(inline)]
+pub use state::CircuitState;
+use state::{Breaker, Settings};
+
+mod layer;
+#[doc(inline)]
+pub use layer::CircuitBreakerLayer;
+
+/// Shared configuration and circuits,
+/// used by both [`CircuitBreakerLayer`] and [`CircuitBreaker`].
+struct Breakers<C, F, K> {
+    classifier: C,
+    key_fn: F,
+    settings: Settings,
+    capacity: u64,
+    circuits: Cache<K, Arc<Breaker>>,
+}
+
+impl<C, F, K> Breakers<C, F, K>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    const DEFAULT_CAPACITY: u64 = 10_000;
+
+    fn new(classifier: C, key_fn: F) -> Self {
+        Self {
+            classifier,
+            key_fn,
+            settings: Settings::default(),
+            capacity: Self::DEFAULT_CAPACITY,
+            circuits: Self::new_cache(Self::DEFAULT_CAPACITY),
+        }
+    }
+
+    fn new_cache(capacity: u64) -> Cache<K, Arc<Breaker>> {
+        Cache::new(capacity)
+    }
+}
+
+impl<C: fmt::Debug, F, K> fmt::Debug for Breakers<C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Breakers")
+            .field("classifier", &self.classifier)
+            .field("key_fn", &std::any::type_name::<F>())
+            .field("settings", &self.settings)
+            .field("capacity", &self.capacity)
+            .field("circuits", &self.circuits.entry_count())
+            .finish()
+    }
+}
+
+impl<C: Clone, F: Clone, K> Clone for Breakers<C, F, K> {
+    fn clone(&self) -> Self {
+        Self {
+            classifier: self.classifier.clone(),
+            key_fn: self.key_fn.clone(),
+            settings: self.settings,
+            capacity: self.capacity,
+            circuits: self.circuits.clone(),
+        }
+    }
+}
+
+/// Middleware that short-circuits requests to an inner service that keeps failing.
+///
+/// Created using the [`CircuitBreakerLayer`].
+/// See [the module docs](self) for more information.
+pub struct CircuitBreaker<S, C = ErrorsAsFailures, F = (), K = ()> {
+    inner: S,
+    breakers: Breakers<C, F, K>,
+}
+
+impl<S, C, F, K> CircuitBreaker<S, C, F, K> {
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, C: fmt::Debug, F, K> fmt::Debug for CircuitBreaker<S, C, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("inner", &self.inner)
+            .field("breakers", &self.breakers)
+            .finish()
+    }
+}
+
+impl<S: Clone, C: Clone, F: Clone, K> Clone for CircuitBreaker<S, C, F, K> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            breakers: self.breakers.clone(),
+        }
+    }
+}
+
+impl<T, C, F, K, State, Request> Service<State, Request> for CircuitBreaker<T, C, F, K>
+where
+    T: Service<State, Request, Error: Into<BoxError>>,
+    C: FailureClassifier<T::Response, T::Error>,
+    F: BreakerKey<State, Request, K>,
+    K: Hash + Eq + Send + Sync + 'static,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = T::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(key) = self.breakers.key_fn.breaker_key(&ctx, &req) else {
+            return self.inner.serve(ctx, req).await.map_err(Into::into);
+        };
+
+        let breaker = self.breakers.circuits.get_with(key, Default::default);
+        let (permit, state) = breaker.try_acquire(&self.breakers.settings)?;
+        ctx.insert(state);
+
+        let result = self.inner.serve(ctx, req).await;
+        permit.record(
+            self.breakers.classifier.is_failure(&result),
+            &self.breakers.settings,
+        );
+        result.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layer, service::service_fn};
+    use std::time::Duration;
+
+    async fn fail_on_true(ctx: Context<()>, fail: bool) -> Result<CircuitState, BoxError> {
+        if fail {
+            Err("failure".into())
+        } else {
+            Ok(*ctx.get::<CircuitState>().unwrap())
+        }
+    }
+
+    fn is_short_circuited<T>(result: Result<T, BoxError>) -> bool {
+        match result {
+            Ok(_) => false,
+            Err(err) => err.downcast_ref::<CircuitOpen>().is_some(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_trip_and_recover() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(2)
+            .with_open_timeout(Duration::from_millis(20))
+            .into_layer(service_fn(fail_on_true));
+
+        assert_eq!(
+            CircuitState::Closed,
+            service.serve(Context::default(), false).await.unwrap()
+        );
+
+        // a success resets the failure count
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), true).await
+        ));
+        service.serve(Context::default(), false).await.unwrap();
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), true).await
+        ));
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), true).await
+        ));
+
+        // circuit is now open
+        assert!(is_short_circuited(
+            service.serve(Context::default(), false).await
+        ));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        // half-open: trial request fails and re-opens the circuit
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), true).await
+        ));
+        assert!(is_short_circuited(
+            service.serve(Context::default(), false).await
+        ));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        // half-open: trial request succeeds and closes the circuit
+        assert_eq!(
+            CircuitState::HalfOpen,
+            service.serve(Context::default(), false).await.unwrap()
+        );
+        assert_eq!(
+            CircuitState::Closed,
+            service.serve(Context::default(), false).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_half_open_max_calls() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_open_timeout(Duration::from_millis(10))
+            .into_layer(service_fn(async |_, delay: Option<Duration>| match delay {
+                Some(delay) => {
+                    tokio::time::sleep(delay).await;
+                    Ok(())
+                }
+                None => Err::<(), BoxError>("failure".into()),
+            }));
+
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), None).await
+        ));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let (trial, other) = crate::futures::zip(
+            service.serve(Context::default(), Some(Duration::from_millis(50))),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                service
+                    .serve(Context::default(), Some(Duration::ZERO))
+                    .await
+            },
+        )
+        .await;
+
+        assert!(trial.is_ok());
+        let err = other.unwrap_err().downcast::<CircuitOpen>().unwrap();
+        assert_eq!(CircuitState::HalfOpen, err.state());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_classifier() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_classifier(
+                |result: &Result<u16, BoxError>| matches!(result, Ok(status) if *status >= 500),
+            )
+            .into_layer(service_fn(async |_, status: u16| Ok::<_, BoxError>(status)));
+
+        service.serve(Context::default(), 404).await.unwrap();
+        service.serve(Context::default(), 503).await.unwrap();
+        assert!(is_short_circuited(
+            service.serve(Context::default(), 200).await
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_keyed() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_key_fn(|_: &Context<()>, req: &(&'static str, bool)| {
+                (!req.0.is_empty()).then_some(req.0)
+            })
+            .into_layer(service_fn(async |_, (_, fail): (&'static str, bool)| {
+                if fail {
+                    Err::<(), BoxError>("failure".into())
+                } else {
+                    Ok(())
+                }
+            }));
+
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), ("a", true)).await
+        ));
+        assert!(is_short_circuited(
+            service.serve(Context::default(), ("a", false)).await
+        ));
+
+        // other keys are not affected
+        service
+            .serve(Context::default(), ("b", false))
+            .await
+            .unwrap();
+
+        // no key, no circuit breaker
+        assert!(!is_short_circuited(
+            service.serve(Context::default(), ("", true)).await
+        ));
+        service
+            .serve(Context::default(), ("", false))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use super::CircuitOpen;
+use crate::telemetry::tracing;
+use parking_lot::Mutex;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// The state of a circuit guarded by a [`CircuitBreaker`].
+///
+/// It is also inserted as an extension in the [`Context`]
+/// of requests that are allowed to proceed, such that
+/// inner services can know whether or not they are being probed.
+///
+/// [`CircuitBreaker`]: super::CircuitBreaker
+/// [`Context`]: crate::Context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// The circuit is closed, requests flow through as normal.
+    Closed,
+    /// The circuit is open (tripped), requests are rejected.
+    Open,
+    /// The circuit allows a limited amount of trial requests through,
+    /// to probe whether the inner service has recovered.
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "closed"),
+            Self::Open => write!(f, "open"),
+            Self::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Settings {
+    pub(super) failure_threshold: u32,
+    pub(super) open_timeout: Duration,
+    pub(super) half_open_max_calls: u32,
+    pub(super) success_threshold: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_timeout: Duration::from_secs(30),
+            half_open_max_calls: 1,
+            success_threshold: 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, successes: u32 },
+}
+
+/// Shared state of a single circuit.
+#[derive(Debug)]
+pub(super) struct Breaker(Mutex<Circuit>);
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self(Mutex::new(Circuit::Closed { failures: 0 }))
+    }
+}
+
+impl Breaker {
+    /// Try to get permission to call the inner service.
+    pub(super) fn try_acquire(
+        self: Arc<Self>,
+        settings: &Settings,
+    ) -> Result<(Permit, CircuitState), CircuitOpen> {
+        let now = Instant::now();
+        let mut circuit = self.0.lock();
+        let state = match &mut *circuit {
+            Circuit::Closed { .. } => CircuitState::Closed,
+            Circuit::Open { until } => {
+                if now < *until {
+                    return Err(CircuitOpen::new(CircuitState::Open, Some(*until - now)));
+                }
+                tracing::debug!("circuit breaker: open timeout passed, moving to half-open");
+                *circuit = Circuit::HalfOpen {
+                    in_flight: 1,
+                    successes: 0,
+                };
+                CircuitState::HalfOpen
+            }
+            Circuit::HalfOpen { in_flight, .. } => {
+                if *in_flight >= settings.half_open_max_calls {
+                    return Err(CircuitOpen::new(CircuitState::HalfOpen, None));
+                }
+                *in_flight += 1;
+                CircuitState::HalfOpen
+            }
+        };
+        drop(circuit);
+
+        Ok((
+            Permit {
+                breaker: self,
+                probe: state == CircuitState::HalfOpen,
+                done: false,
+            },
+            state,
+        ))
+    }
+}
+
+/// Permission to call the inner service,
+/// used to record the outcome of that call.
+pub(super) struct Permit {
+    breaker: Arc<Breaker>,
+    probe: bool,
+    done: bool,
+}
+
+impl Permit {
+    /// Record the outcome of the call.
+    pub(super) fn record(mut self, failure: bool, settings: &Settings) {
+        self.done = true;
+
+        let mut circuit = self.breaker.0.lock();
+        match &mut *circuit {
+            Circuit::Closed { failures } if !self.probe => {
+                if !failure {
+                    *failures = 0;
+                    return;
+                }
+                *failures += 1;
+                if *failures >= settings.failure_threshold {
+                    tracing::debug!(
+                        "circuit breaker: failure threshold ({}) reached, opening circuit",
+                        settings.failure_threshold
+                    );
+                    *circuit = Circuit::Open {
+                        until: Instant::now() + settings.open_timeout,
+                    };
+                }
+            }
+            Circuit::HalfOpen {
+                in_flight,
+                successes,
+            } if self.probe => {
+                *in_flight = in_flight.saturating_sub(1);
+                if failure {
+                    tracing::debug!("circuit breaker: trial request failed, re-opening circuit");
+                    *circuit = Circuit::Open {
+                        until: Instant::now() + settings.open_timeout,
+                    };
+                    return;
+                }
+                *successes += 1;
+                if *successes >= settings.success_threshold {
+                    tracing::debug!("circuit breaker: trial requests succeeded, closing circuit");
+                    *circuit = Circuit::Closed { failures: 0 };
+                }
+            }
+            // the circuit moved on since this permit was given out,
+            // so its outcome is no longer relevant
+            _ => (),
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.done || !self.probe {
+            return;
+        }
+        // trial request was cancelled, release its slot
+        if let Circuit::HalfOpen { in_flight, .. } = &mut *self.breaker.0.lock() {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-//! A middleware that limits the number of in-flight requests.
+//! A middleware that limits the number or rate of in-flight requests.
 //!
 //! See [`Limit`].
 
@@ -0,0 +1,669 @@
+//! A [`Policy`] that adapts its concurrency limit based on observed latency and failures.
+//!
+//! See [`AdaptivePolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rama_core::layer::limit::{Limit, policy::{AdaptiveErrorFeedback, AdaptivePolicy, Aimd}};
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service};
+//! # use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//!
+//! let service = service_fn(async |_, _| {
+//!     Ok::<_, Infallible>(())
+//! });
+//! let policy = AdaptivePolicy::new(Aimd::default().with_initial_limit(1));
+//! // report errors of the inner service as failures to the policy
+//! let service = Limit::new(AdaptiveErrorFeedback::new(service), policy.clone());
+//!
+//! let response = service.serve(Context::default(), ()).await;
+//! assert!(response.is_ok());
+//! assert_eq!(2, policy.limit());
+//! # }
+//! ```
+
+use super::{LimitReached, Policy, PolicyOutput, PolicyResult};
+use crate::{Context, Layer, Service};
+use parking_lot::Mutex;
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// A single observation of a request guarded by an [`AdaptivePolicy`],
+/// used by a [`LimitAlgorithm`] to update the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// The time it took for the request to complete.
+    pub rtt: Duration,
+    /// The amount of requests in flight when the request started,
+    /// including the request itself.
+    pub in_flight: usize,
+    /// Whether or not the request was reported as failed using
+    /// [`AdaptiveFeedback::report_failure`] (e.g. by [`AdaptiveErrorFeedback`]
+    /// in case the inner service returned an error).
+    pub failed: bool,
+}
+
+/// An algorithm used by an [`AdaptivePolicy`] to adjust its concurrency limit.
+pub trait LimitAlgorithm: Send + 'static {
+    /// The limit to start with.
+    fn initial_limit(&self) -> usize;
+
+    /// Compute the new limit given the current limit and a new sample.
+    fn update(&mut self, limit: usize, sample: Sample) -> usize;
+}
+
+/// Additive-increase/multiplicative-decrease [`LimitAlgorithm`].
+///
+/// The limit is increased by one for each successful request
+/// which started while the limit was (nearly) reached,
+/// and multiplied by a backoff ratio for each failed request,
+/// with requests that took longer than the configured timeout
+/// also considered as failed.
+#[derive(Debug, Clone)]
+pub struct Aimd {
+    initial_limit: usize,
+    min_limit: usize,
+    max_limit: usize,
+    backoff_ratio: f64,
+    timeout: Option<Duration>,
+}
+
+impl Default for Aimd {
+    fn default() -> Self {
+        Self {
+            initial_limit: 20,
+            min_limit: 1,
+            max_limit: 1000,
+            backoff_ratio: 0.9,
+            timeout: None,
+        }
+    }
+}
+
+impl Aimd {
+    /// Create a new [`Aimd`] algorithm with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the limit to start with (20 by default).
+        pub fn initial_limit(mut self, limit: usize) -> Self {
+            self.initial_limit = limit;
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the minimum limit (1 by default).
+        ///
+        /// The minimum limit is at least 1, and raises
+        /// the maximum limit if it is larger than it.
+        pub fn min_limit(mut self, limit: usize) -> Self {
+            self.min_limit = limit.max(1);
+            self.max_limit = self.max_limit.max(self.min_limit);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the maximum limit (1000 by default).
+        ///
+        /// The maximum limit is at least 1, and lowers
+        /// the minimum limit if it is smaller than it.
+        pub fn max_limit(mut self, limit: usize) -> Self {
+            self.max_limit = limit.max(1);
+            self.min_limit = self.min_limit.min(self.max_limit);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the ratio the limit is multiplied by on failure (0.9 by default),
+        /// clamped to `[0.5, 1.0)`.
+        pub fn backoff_ratio(mut self, ratio: f64) -> Self {
+            self.backoff_ratio = ratio.clamp(0.5, 0.999);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Consider requests that take longer than the given timeout as failed.
+        pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+            self.timeout = timeout;
+            self
+        }
+    }
+}
+
+impl LimitAlgorithm for Aimd {
+    fn initial_limit(&self) -> usize {
+        self.initial_limit.clamp(self.min_limit, self.max_limit)
+    }
+
+    fn update(&mut self, limit: usize, sample: Sample) -> usize {
+        let failed = sample.failed || self.timeout.is_some_and(|timeout| sample.rtt > timeout);
+        let limit = if failed {
+            (limit as f64 * self.backoff_ratio) as usize
+        } else if sample.in_flight * 2 >= limit {
+            limit + 1
+        } else {
+            limit
+        };
+        limit.clamp(self.min_limit, self.max_limit)
+    }
+}
+
+/// Latency gradient based [`LimitAlgorithm`], similar to TCP Vegas.
+///
+/// It compares a short term round-trip time (the latest sample)
+/// with a long term exponentially averaged round-trip time. While the
+/// short term latency stays within the tolerance of the long term one,
+/// the limit grows with a queue allowance of `sqrt(limit)`. When latency
+/// increases, indicating requests are queueing up, the limit shrinks
+/// proportionally to the latency gradient.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    initial_limit: usize,
+    min_limit: usize,
+    max_limit: usize,
+    smoothing: f64,
+    tolerance: f64,
+    long_window: u32,
+    long_rtt: Option<f64>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            initial_limit: 20,
+            min_limit: 1,
+            max_limit: 1000,
+            smoothing: 0.2,
+            tolerance: 1.5,
+            long_window: 600,
+            long_rtt: None,
+        }
+    }
+}
+
+impl Gradient {
+    /// Create a new [`Gradient`] algorithm with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the limit to start with (20 by default).
+        pub fn initial_limit(mut self, limit: usize) -> Self {
+            self.initial_limit = limit;
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the minimum limit (1 by default).
+        ///
+        /// The minimum limit is at least 1, and raises
+        /// the maximum limit if it is larger than it.
+        pub fn min_limit(mut self, limit: usize) -> Self {
+            self.min_limit = limit.max(1);
+            self.max_limit = self.max_limit.max(self.min_limit);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the maximum limit (1000 by default).
+        ///
+        /// The maximum limit is at least 1, and lowers
+        /// the minimum limit if it is smaller than it.
+        pub fn max_limit(mut self, limit: usize) -> Self {
+            self.max_limit = limit.max(1);
+            self.min_limit = self.min_limit.min(self.max_limit);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the factor (within `(0, 1]`) with which a newly computed limit
+        /// is blended into the current one (0.2 by default).
+        pub fn smoothing(mut self, smoothing: f64) -> Self {
+            self.smoothing = smoothing.clamp(0.01, 1.0);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set how much higher than the long term latency the short term latency
+        /// may be before the limit is decreased (1.5 by default).
+        pub fn tolerance(mut self, tolerance: f64) -> Self {
+            self.tolerance = tolerance.max(1.0);
+            self
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the amount of samples over which the long term latency is averaged (600 by default).
+        pub fn long_window(mut self, samples: u32) -> Self {
+            self.long_window = samples.max(1);
+            self
+        }
+    }
+}
+
+impl LimitAlgorithm for Gradient {
+    fn initial_limit(&self) -> usize {
+        self.initial_limit.clamp(self.min_limit, self.max_limit)
+    }
+
+    fn update(&mut self, limit: usize, sample: Sample) -> usize {
+        let short_rtt = (sample.rtt.as_nanos() as f64).max(1.0);
+        let long_rtt = match self.long_rtt {
+            Some(long_rtt) => {
+                let factor = 1.0 / self.long_window as f64;
+                let mut long_rtt = long_rtt.mul_add(1.0 - factor, short_rtt * factor);
+                // recover faster from a latency spike
+                if long_rtt / short_rtt > 2.0 {
+                    long_rtt *= 0.95;
+                }
+                long_rtt
+            }
+            None => short_rtt,
+        };
+        self.long_rtt = Some(long_rtt);
+
+        let current = limit as f64;
+        let gradient = if sample.failed {
+            0.5
+        } else {
+            (self.tolerance * long_rtt / short_rtt).clamp(0.5, 1.0)
+        };
+
+        // don't grow the limit while the application isn't using it
+        if gradient >= 1.0 && sample.in_flight * 2 < limit {
+            return limit;
+        }
+
+        let target = current.mul_add(gradient, current.sqrt());
+        let new_limit = current.mul_add(1.0 - self.smoothing, target * self.smoothing);
+        (new_limit.round() as usize).clamp(self.min_limit, self.max_limit)
+    }
+}
+
+/// Handle inserted in the [`Context`] by the [`AdaptivePolicy`],
+/// which can be used to report that the request failed,
+/// e.g. because the upstream service signalled it was overloaded.
+///
+/// Failed requests cause the limit to decrease. Use [`AdaptiveErrorFeedback`]
+/// to report requests for which the inner service returns an error as failed.
+#[derive(Debug, Clone)]
+pub struct AdaptiveFeedback(Arc<AtomicBool>);
+
+impl AdaptiveFeedback {
+    /// Report that the request failed.
+    pub fn report_failure(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+struct State<A> {
+    algorithm: A,
+    limit: usize,
+    in_flight: usize,
+}
+
+/// A [`Policy`] that limits the number of concurrent requests,
+/// using a limit that adapts itself using a [`LimitAlgorithm`].
+///
+/// Requests are aborted with a [`LimitReached`] error once the limit is reached.
+/// All clones of the policy share the same limit.
+pub struct AdaptivePolicy<A> {
+    state: Arc<Mutex<State<A>>>,
+}
+
+impl<A: LimitAlgorithm> AdaptivePolicy<A> {
+    /// Create a new [`AdaptivePolicy`] using the given [`LimitAlgorithm`].
+    pub fn new(algorithm: A) -> Self {
+        let limit = algorithm.initial_limit();
+        Self {
+            state: Arc::new(Mutex::new(State {
+                algorithm,
+                limit,
+                in_flight: 0,
+            })),
+        }
+    }
+}
+
+impl<A> AdaptivePolicy<A> {
+    /// The current concurrency limit.
+    pub fn limit(&self) -> usize {
+        self.state.lock().limit
+    }
+
+    /// The amount of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().in_flight
+    }
+}
+
+impl<A> Clone for AdaptivePolicy<A> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<A> fmt::Debug for AdaptivePolicy<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("AdaptivePolicy")
+            .field("limit", &state.limit)
+            .field("in_flight", &state.in_flight)
+            .finish()
+    }
+}
+
+impl<A, State, Request> Policy<State, Request> for AdaptivePolicy<A>
+where
+    A: LimitAlgorithm,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = AdaptiveGuard<A>;
+    type Error = LimitReached;
+
+    async fn check(
+        &self,
+        mut ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let in_flight = {
+            let mut state = self.state.lock();
+            if state.in_flight >= state.limit {
+                return PolicyResult {
+                    ctx,
+                    request,
+                    output: PolicyOutput::Abort(LimitReached),
+                };
+            }
+            state.in_flight += 1;
+            state.in_flight
+        };
+
+        let failed = Arc::new(AtomicBool::new(false));
+        ctx.insert(AdaptiveFeedback(failed.clone()));
+
+        PolicyResult {
+            ctx,
+            request,
+            output: PolicyOutput::Ready(AdaptiveGuard {
+                state: self.state.clone(),
+                start: Instant::now(),
+                in_flight,
+                failed,
+            }),
+        }
+    }
+}
+
+/// The guard of an [`AdaptivePolicy`],
+/// which updates the limit when the request completes (is dropped).
+pub struct AdaptiveGuard<A: LimitAlgorithm> {
+    state: Arc<Mutex<State<A>>>,
+    start: Instant,
+    in_flight: usize,
+    failed: Arc<AtomicBool>,
+}
+
+impl<A: LimitAlgorithm> AdaptiveGuard<A> {
+    /// Report that the request failed.
+    ///
+    /// See [`AdaptiveFeedback::report_failure`].
+    pub fn report_failure(&self) {
+        self.failed.store(true, Ordering::Release);
+    }
+}
+
+impl<A: LimitAlgorithm> fmt::Debug for AdaptiveGuard<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdaptiveGuard")
+            .field("start", &self.start)
+            .field("in_flight", &self.in_flight)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+/// Middleware which reports requests for which the inner service
+/// returns an error as failed to the [`AdaptivePolicy`] guarding it,
+/// using the [`AdaptiveFeedback`] found in the [`Context`].
+///
+/// It is meant to be wrapped by the [`Limit`] service using the [`AdaptivePolicy`].
+///
+/// [`Limit`]: crate::layer::Limit
+pub struct AdaptiveErrorFeedback<S> {
+    inner: S,
+}
+
+impl<S> AdaptiveErrorFeedback<S> {
+    /// Create a new [`AdaptiveErrorFeedback`] wrapping the given service.
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for AdaptiveErrorFeedback<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdaptiveErrorFeedback")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for AdaptiveErrorFeedback<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for AdaptiveErrorFeedback<S>
+where
+    S: Service<State, Request>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let feedback = ctx.get::<AdaptiveFeedback>().cloned();
+        let result = self.inner.serve(ctx, request).await;
+        if let (Err(_), Some(feedback)) = (&result, feedback) {
+            feedback.report_failure();
+        }
+        result
+    }
+}
+
+/// A [`Layer`] which wraps services in an [`AdaptiveErrorFeedback`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct AdaptiveErrorFeedbackLayer;
+
+impl AdaptiveErrorFeedbackLayer {
+    /// Create a new [`AdaptiveErrorFeedbackLayer`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for AdaptiveErrorFeedbackLayer {
+    type Service = AdaptiveErrorFeedback<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AdaptiveErrorFeedback::new(inner)
+    }
+}
+
+impl<A: LimitAlgorithm> Drop for AdaptiveGuard<A> {
+    fn drop(&mut self) {
+        let sample = Sample {
+            rtt: self.start.elapsed(),
+            in_flight: self.in_flight,
+            failed: self.failed.load(Ordering::Acquire),
+        };
+        let mut state = self.state.lock();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        let limit = state.limit;
+        state.limit = state.algorithm.update(limit, sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(rtt_ms: u64, in_flight: usize, failed: bool) -> Sample {
+        Sample {
+            rtt: Duration::from_millis(rtt_ms),
+            in_flight,
+            failed,
+        }
+    }
+
+    fn assert_ready<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> (Context<S>, G) {
+        match result.output {
+            PolicyOutput::Ready(guard) => (result.ctx, guard),
+            _ => panic!("unexpected output, expected ready"),
+        }
+    }
+
+    #[test]
+    fn aimd() {
+        let mut aimd = Aimd::new()
+            .with_min_limit(5)
+            .with_max_limit(11)
+            .with_timeout(Duration::from_secs(1));
+
+        // application limited, no increase
+        assert_eq!(10, aimd.update(10, sample(10, 1, false)));
+        assert_eq!(11, aimd.update(10, sample(10, 10, false)));
+        assert_eq!(11, aimd.update(11, sample(10, 11, false)));
+        assert_eq!(9, aimd.update(10, sample(10, 10, true)));
+        assert_eq!(9, aimd.update(10, sample(2000, 10, false)));
+        assert_eq!(5, aimd.update(5, sample(10, 5, true)));
+    }
+
+    #[test]
+    fn min_max_limit_validated() {
+        let mut aimd = Aimd::new().with_min_limit(0);
+        assert_eq!(1, aimd.update(1, sample(10, 1, true)));
+
+        let aimd = Aimd::new().with_max_limit(10).with_min_limit(20);
+        assert_eq!(20, aimd.initial_limit());
+        let aimd = Aimd::new().with_min_limit(20).with_max_limit(10);
+        assert_eq!(10, aimd.initial_limit());
+
+        let mut gradient = Gradient::new().with_max_limit(0).with_min_limit(0);
+        assert_eq!(1, gradient.initial_limit());
+        assert_eq!(1, gradient.update(1, sample(10, 1, true)));
+    }
+
+    #[test]
+    fn gradient() {
+        let mut gradient = Gradient::new();
+        let mut limit = gradient.initial_limit();
+
+        // steady latency grows the limit
+        for _ in 0..20 {
+            limit = gradient.update(limit, sample(10, limit, false));
+        }
+        assert!(limit > 20, "limit: {limit}");
+
+        // increasing latency shrinks it again
+        let grown = limit;
+        for _ in 0..20 {
+            limit = gradient.update(limit, sample(100, limit, false));
+        }
+        assert!(limit < grown, "limit: {limit}, grown: {grown}");
+    }
+
+    #[tokio::test]
+    async fn adaptive_policy() {
+        let policy = AdaptivePolicy::new(Aimd::new().with_initial_limit(2));
+
+        let (_, guard_1) = assert_ready(policy.check(Context::default(), ()).await);
+        let (ctx, guard_2) = assert_ready(policy.check(Context::default(), ()).await);
+        assert!(matches!(
+            policy.check(Context::default(), ()).await.output,
+            PolicyOutput::Abort(LimitReached)
+        ));
+        assert_eq!(2, policy.in_flight());
+
+        // success at the limit increases it
+        drop(guard_1);
+        assert_eq!(3, policy.limit());
+
+        // reported failure decreases it
+        ctx.get::<AdaptiveFeedback>().unwrap().report_failure();
+        drop(guard_2);
+        assert_eq!(2, policy.limit());
+        assert_eq!(0, policy.in_flight());
+    }
+
+    #[tokio::test]
+    async fn adaptive_policy_inner_error_is_failure() {
+        use crate::{layer::Limit, service::service_fn};
+
+        let policy = AdaptivePolicy::new(Aimd::new().with_initial_limit(10));
+        let service = Limit::new(
+            AdaptiveErrorFeedbackLayer::new().into_layer(service_fn(async |_, fail: bool| {
+                if fail { Err("failed") } else { Ok(()) }
+            })),
+            policy.clone(),
+        );
+
+        service.serve(Context::default(), true).await.unwrap_err();
+        assert_eq!(9, policy.limit());
+        service.serve(Context::default(), false).await.unwrap();
+        assert_eq!(9, policy.limit());
+    }
+
+    #[tokio::test]
+    async fn adaptive_policy_inner_error_without_feedback_layer() {
+        use crate::{layer::Limit, service::service_fn};
+
+        let policy = AdaptivePolicy::new(Aimd::new().with_initial_limit(10));
+        let service = Limit::new(
+            service_fn(async |_, ()| Err::<(), _>("failed")),
+            policy.clone(),
+        );
+
+        // the limit service itself does not interpret errors
+        service.serve(Context::default(), ()).await.unwrap_err();
+        assert_eq!(10, policy.limit());
+    }
+}
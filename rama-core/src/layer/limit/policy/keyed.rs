@@ -0,0 +1,288 @@
+//! A [`Policy`] that partitions requests by a key,
+//! applying a separate inner [`Policy`] for each key.
+//!
+//! See [`KeyedPolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rama_core::layer::limit::{Limit, policy::{ConcurrentPolicy, KeyedPolicy}};
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service};
+//! # use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//!
+//! let service = service_fn(async |_, _| {
+//!     Ok::<_, Infallible>(())
+//! });
+//! // allow at most 2 concurrent requests per user
+//! let policy = KeyedPolicy::new(
+//!     |_ctx: &Context<()>, req: &&'static str| Some(req.to_string()),
+//!     || ConcurrentPolicy::max(2),
+//! );
+//! let mut service = Limit::new(service, policy);
+//!
+//! let response = service.serve(Context::default(), "john").await;
+//! assert!(response.is_ok());
+//! # }
+//! ```
+
+use super::{Policy, PolicyOutput, PolicyResult};
+use crate::Context;
+use moka::sync::Cache;
+use rama_utils::macros::generate_set_and_with;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A [`Policy`] which applies a separate inner [`Policy`] per key,
+/// such that each key (e.g. a peer ip, user id or domain) is limited independently.
+///
+/// The key is extracted from the [`Context`] and request using the provided function.
+/// Requests for which no key is extracted are allowed to proceed without any limit.
+///
+/// Inner policies are created on demand using the provided factory function,
+/// and kept in a bounded cache, which evicts the least recently used keys
+/// once its capacity is reached, as well as keys that have been idle for too long.
+///
+/// Note that an evicted key starts with a fresh policy the next time it is seen.
+/// For stateful policies such as the [`ConcurrentPolicy`] this means that
+/// in-flight requests of an evicted key are no longer taken into account,
+/// so make sure to pick a capacity and idle timeout that match your traffic.
+///
+/// [`ConcurrentPolicy`]: super::ConcurrentPolicy
+pub struct KeyedPolicy<K, F, M, P> {
+    key_fn: F,
+    make_policy: M,
+    policies: Cache<K, Arc<P>>,
+    capacity: u64,
+    idle_timeout: Option<Duration>,
+}
+
+impl<K, F, M, P> fmt::Debug for KeyedPolicy<K, F, M, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedPolicy")
+            .field("key_fn", &std::any::type_name::<F>())
+            .field("make_policy", &std::any::type_name::<M>())
+            .field("keys", &self.policies.entry_count())
+            .field("capacity", &self.capacity)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+impl<K, F, M, P> Clone for KeyedPolicy<K, F, M, P>
+where
+    F: Clone,
+    M: Clone,
+{
+    fn clone(&self) -> Self {
+        KeyedPolicy {
+            key_fn: self.key_fn.clone(),
+            make_policy: self.make_policy.clone(),
+            policies: self.policies.clone(),
+            capacity: self.capacity,
+            idle_timeout: self.idle_timeout,
+        }
+    }
+}
+
+impl<K, F, M, P> KeyedPolicy<K, F, M, P>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    P: Send + Sync + 'static,
+{
+    /// The default maximum amount of keys tracked by a [`KeyedPolicy`].
+    pub const DEFAULT_CAPACITY: u64 = 10_000;
+
+    /// The default time after which an unused key is evicted by a [`KeyedPolicy`].
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+    /// Create a new [`KeyedPolicy`], using the given key extraction function
+    /// and the given factory function to create a [`Policy`] for each new key.
+    ///
+    /// The policy tracks at most [`Self::DEFAULT_CAPACITY`] keys, and evicts
+    /// keys unused for [`Self::DEFAULT_IDLE_TIMEOUT`].
+    pub fn new(key_fn: F, make_policy: M) -> Self {
+        let capacity = Self::DEFAULT_CAPACITY;
+        let idle_timeout = Some(Self::DEFAULT_IDLE_TIMEOUT);
+        KeyedPolicy {
+            key_fn,
+            make_policy,
+            policies: new_cache(capacity, idle_timeout),
+            capacity,
+            idle_timeout,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum amount of keys to keep track of.
+        ///
+        /// Once reached, the least recently used keys are evicted.
+        ///
+        /// Note that this resets all state tracked so far.
+        pub fn capacity(mut self, capacity: u64) -> Self {
+            self.capacity = capacity;
+            self.policies = new_cache(self.capacity, self.idle_timeout);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the time after which a key that is not used is evicted,
+        /// or `None` to only evict keys based on the capacity.
+        ///
+        /// Note that this resets all state tracked so far.
+        pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+            self.idle_timeout = timeout;
+            self.policies = new_cache(self.capacity, self.idle_timeout);
+            self
+        }
+    }
+
+    /// Returns the (approximate) amount of keys currently tracked.
+    pub fn key_count(&self) -> u64 {
+        self.policies.run_pending_tasks();
+        self.policies.entry_count()
+    }
+}
+
+fn new_cache<K, P>(capacity: u64, idle_timeout: Option<Duration>) -> Cache<K, Arc<P>>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    P: Send + Sync + 'static,
+{
+    let builder = Cache::builder().max_capacity(capacity);
+    match idle_timeout {
+        Some(timeout) => builder.time_to_idle(timeout).build(),
+        None => builder.build(),
+    }
+}
+
+impl<K, F, M, P, State, Request> Policy<State, Request> for KeyedPolicy<K, F, M, P>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    F: Fn(&Context<State>, &Request) -> Option<K> + Send + Sync + 'static,
+    M: Fn() -> P + Send + Sync + 'static,
+    P: Policy<State, Request>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = Option<P::Guard>;
+    type Error = P::Error;
+
+    async fn check(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let Some(key) = (self.key_fn)(&ctx, &request) else {
+            return PolicyResult {
+                ctx,
+                request,
+                output: PolicyOutput::Ready(None),
+            };
+        };
+
+        let policy = self
+            .policies
+            .get_with(key, || Arc::new((self.make_policy)()));
+
+        let result = policy.check(ctx, request).await;
+        match result.output {
+            PolicyOutput::Ready(guard) => PolicyResult {
+                ctx: result.ctx,
+                request: result.request,
+                output: PolicyOutput::Ready(Some(guard)),
+            },
+            PolicyOutput::Abort(err) => PolicyResult {
+                ctx: result.ctx,
+                request: result.request,
+                output: PolicyOutput::Abort(err),
+            },
+            PolicyOutput::Retry => PolicyResult {
+                ctx: result.ctx,
+                request: result.request,
+                output: PolicyOutput::Retry,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::limit::policy::{ConcurrentPolicy, RatePolicy};
+
+    fn assert_ready<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> G {
+        match result.output {
+            PolicyOutput::Ready(guard) => guard,
+            _ => panic!("unexpected output, expected ready"),
+        }
+    }
+
+    fn assert_abort<S, R, G, E>(result: PolicyResult<S, R, G, E>) {
+        match result.output {
+            PolicyOutput::Abort(_) => (),
+            _ => panic!("unexpected output, expected abort"),
+        }
+    }
+
+    fn key_fn(_ctx: &Context<()>, req: &&'static str) -> Option<&'static str> {
+        (!req.is_empty()).then_some(*req)
+    }
+
+    #[tokio::test]
+    async fn keyed_concurrent_policy() {
+        let policy = KeyedPolicy::new(key_fn, || ConcurrentPolicy::max(1));
+
+        let guard_a = assert_ready(policy.check(Context::default(), "a").await);
+        assert!(guard_a.is_some());
+        assert_abort(policy.check(Context::default(), "a").await);
+
+        let _guard_b = assert_ready(policy.check(Context::default(), "b").await);
+        assert_abort(policy.check(Context::default(), "b").await);
+
+        // no key, no limit
+        assert!(assert_ready(policy.check(Context::default(), "").await).is_none());
+        assert!(assert_ready(policy.check(Context::default(), "").await).is_none());
+
+        drop(guard_a);
+        assert_ready(policy.check(Context::default(), "a").await);
+    }
+
+    #[tokio::test]
+    async fn keyed_rate_policy() {
+        let policy = KeyedPolicy::new(key_fn, || RatePolicy::per_minute(1));
+
+        assert_ready(policy.check(Context::default(), "a").await);
+        assert_abort(policy.check(Context::default(), "a").await);
+
+        assert_ready(policy.check(Context::default(), "b").await);
+        assert_abort(policy.check(Context::default(), "b").await);
+    }
+
+    #[tokio::test]
+    async fn keyed_policy_clone_shares_state() {
+        let policy = KeyedPolicy::new(key_fn, || RatePolicy::per_minute(1));
+        let policy_clone = policy.clone();
+
+        assert_ready(policy.check(Context::default(), "a").await);
+        assert_abort(policy_clone.check(Context::default(), "a").await);
+    }
+
+    #[tokio::test]
+    async fn keyed_policy_capacity() {
+        let policy = KeyedPolicy::new(key_fn, || RatePolicy::per_minute(1))
+            .with_capacity(2)
+            .without_idle_timeout();
+
+        for key in ["a", "b", "c", "d", "e"] {
+            assert_ready(policy.check(Context::default(), key).await);
+        }
+        assert!(policy.key_count() <= 2);
+    }
+}
@@ -35,6 +35,21 @@ mod concurrent;
 #[doc(inline)]
 pub use concurrent::{ConcurrentCounter, ConcurrentPolicy, ConcurrentTracker, LimitReached};
 
+mod rate;
+#[doc(inline)]
+pub use rate::{RateLimited, RatePolicy};
+
+mod adaptive;
+#[doc(inline)]
+pub use adaptive::{
+    AdaptiveErrorFeedback, AdaptiveErrorFeedbackLayer, AdaptiveFeedback, AdaptiveGuard,
+    AdaptivePolicy, Aimd, Gradient, LimitAlgorithm, Sample,
+};
+
+mod keyed;
+#[doc(inline)]
+pub use keyed::KeyedPolicy;
+
 mod matcher;
 
 /// The full result of a limit policy.
@@ -0,0 +1,290 @@
+//! A [`Policy`] that limits the rate of requests.
+//!
+//! See [`RatePolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rama_core::layer::limit::{Limit, policy::RatePolicy};
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service};
+//! # use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//!
+//! let service = service_fn(async |_, _| {
+//!     Ok::<_, Infallible>(())
+//! });
+//! // 10 requests per second, with a burst of up to 20 requests
+//! let mut service = Limit::new(service, RatePolicy::per_second(10).with_burst(20));
+//!
+//! let response = service.serve(Context::default(), ()).await;
+//! assert!(response.is_ok());
+//! # }
+//! ```
+
+use super::{Policy, PolicyOutput, PolicyResult};
+use crate::Context;
+use parking_lot::Mutex;
+use rama_utils::macros::generate_set_and_with;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A [`Policy`] that limits the rate of requests.
+///
+/// The rate is enforced using the Generic Cell Rate Algorithm (GCRA),
+/// which behaves as a token bucket with a capacity equal to the configured burst,
+/// refilled at the configured rate. Contrary to a naive token bucket
+/// it only has to keep track of a single timestamp.
+///
+/// By default a request is aborted with a [`RateLimited`] error,
+/// containing the time to wait until the request would be allowed,
+/// as soon as the rate is exceeded. Use [`RatePolicy::with_max_wait`]
+/// to instead wait for a permit, for as long as that wait time is acceptable.
+///
+/// Cloning a [`RatePolicy`] shares its state,
+/// meaning that all clones draw from the same rate budget.
+pub struct RatePolicy {
+    emission_interval: Duration,
+    tolerance: Duration,
+    max_wait: Option<Duration>,
+    state: Arc<Mutex<Option<Instant>>>,
+}
+
+impl fmt::Debug for RatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RatePolicy")
+            .field("emission_interval", &self.emission_interval)
+            .field("tolerance", &self.tolerance)
+            .field("max_wait", &self.max_wait)
+            .finish()
+    }
+}
+
+impl Clone for RatePolicy {
+    fn clone(&self) -> Self {
+        RatePolicy {
+            emission_interval: self.emission_interval,
+            tolerance: self.tolerance,
+            max_wait: self.max_wait,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl RatePolicy {
+    /// Create a new [`RatePolicy`] which allows `count` requests per `period`.
+    ///
+    /// The burst defaults to `count`, meaning that all requests of a single
+    /// period can be made at once. Use [`RatePolicy::with_burst`] to change this.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero or `period` is zero.
+    pub fn new(count: u32, period: Duration) -> Self {
+        assert!(count > 0, "rate policy count has to be non-zero");
+        assert!(!period.is_zero(), "rate policy period has to be non-zero");
+        let emission_interval = period / count;
+        RatePolicy {
+            emission_interval,
+            tolerance: emission_interval * count,
+            max_wait: None,
+            state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Create a new [`RatePolicy`] which allows `count` requests per second.
+    ///
+    /// See [`RatePolicy::new`] for more information.
+    pub fn per_second(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(1))
+    }
+
+    /// Create a new [`RatePolicy`] which allows `count` requests per minute.
+    ///
+    /// See [`RatePolicy::new`] for more information.
+    pub fn per_minute(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(60))
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum amount of requests that can be made at once,
+        /// after a period of inactivity.
+        ///
+        /// A burst of zero is treated as a burst of one.
+        pub fn burst(mut self, burst: u32) -> Self {
+            self.tolerance = self.emission_interval * burst.max(1);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum time a request is allowed to wait for a permit.
+        ///
+        /// When the wait time for a request is within this limit,
+        /// the policy will sleep for that time and ask for the request to be retried,
+        /// otherwise it is aborted with a [`RateLimited`] error.
+        ///
+        /// By default no waiting is done at all.
+        pub fn max_wait(mut self, max_wait: Option<Duration>) -> Self {
+            self.max_wait = max_wait;
+            self
+        }
+    }
+
+    /// Try to acquire a permit at the given instant,
+    /// returning the time to wait in case the rate is exceeded.
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut tat = self.state.lock();
+        let next_tat = tat.map(|tat| tat.max(now)).unwrap_or(now) + self.emission_interval;
+        // checked_sub can only fail for the first tolerance-sized window after (monotonic) clock start,
+        // in which case the request is always within the allowed burst
+        match next_tat.checked_sub(self.tolerance) {
+            Some(allow_at) if allow_at > now => Err(allow_at - now),
+            _ => {
+                *tat = Some(next_tat);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<State, Request> Policy<State, Request> for RatePolicy
+where
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = ();
+    type Error = RateLimited;
+
+    async fn check(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let output = match self.try_acquire(Instant::now()) {
+            Ok(()) => PolicyOutput::Ready(()),
+            Err(wait) => match self.max_wait {
+                Some(max_wait) if wait <= max_wait => {
+                    tokio::time::sleep(wait).await;
+                    PolicyOutput::Retry
+                }
+                _ => PolicyOutput::Abort(RateLimited { retry_after: wait }),
+            },
+        };
+
+        PolicyResult {
+            ctx,
+            request,
+            output,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Error returned by [`RatePolicy`] when a request is aborted
+/// because the rate limit is exceeded.
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl RateLimited {
+    /// The (minimum) time to wait before the request would be allowed.
+    ///
+    /// Useful for example to populate a `Retry-After` http header.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request aborted due to exceeded rate limit (retry after {:?})",
+            self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ready<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> G {
+        match result.output {
+            PolicyOutput::Ready(guard) => guard,
+            _ => panic!("unexpected output, expected ready"),
+        }
+    }
+
+    fn assert_abort<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> E {
+        match result.output {
+            PolicyOutput::Abort(err) => err,
+            _ => panic!("unexpected output, expected abort"),
+        }
+    }
+
+    fn assert_retry<S, R, G, E>(result: PolicyResult<S, R, G, E>) {
+        match result.output {
+            PolicyOutput::Retry => (),
+            _ => panic!("unexpected output, expected retry"),
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_policy_burst() {
+        let policy = RatePolicy::per_minute(60).with_burst(3);
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+
+        let err = assert_abort(policy.check(Context::default(), ()).await);
+        assert!(err.retry_after() > Duration::from_millis(900));
+        assert!(err.retry_after() <= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn rate_policy_clone() {
+        let policy = RatePolicy::per_minute(2);
+        let policy_clone = policy.clone();
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy_clone.check(Context::default(), ()).await);
+
+        assert_abort(policy.check(Context::default(), ()).await);
+        assert_abort(policy_clone.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test]
+    async fn rate_policy_refill() {
+        let policy = RatePolicy::new(1, Duration::from_millis(20));
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_abort(policy.check(Context::default(), ()).await);
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert_ready(policy.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test]
+    async fn rate_policy_max_wait() {
+        let policy =
+            RatePolicy::new(1, Duration::from_millis(20)).with_max_wait(Duration::from_millis(100));
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_retry(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+
+        let policy = RatePolicy::per_minute(1).with_max_wait(Duration::from_millis(100));
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_abort(policy.check(Context::default(), ()).await);
+    }
+}
@@ -866,6 +866,12 @@ pub use timeout::{Timeout, TimeoutLayer};
 pub mod limit;
 pub use limit::{Limit, LimitLayer};
 
+pub mod circuit_breaker;
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerLayer};
+
+pub mod queue;
+pub use queue::{Queue, QueueLayer};
+
 pub mod add_extension;
 pub use add_extension::{AddExtension, AddExtensionLayer};
 
@@ -0,0 +1,90 @@
+use super::{Queue, Shared};
+use crate::Layer;
+use std::{fmt, sync::Arc, time::Duration};
+
+#[cfg(feature = "opentelemetry")]
+use crate::telemetry::opentelemetry::MeterOptions;
+
+/// A [`Layer`] which wraps services in a [`Queue`].
+///
+/// Each service produced by this layer has its own queue,
+/// clone the produced [`Queue`] service to share it instead.
+#[derive(Clone)]
+pub struct QueueLayer {
+    max_concurrency: usize,
+    max_queue: usize,
+    max_wait: Option<Duration>,
+    #[cfg(feature = "opentelemetry")]
+    metrics: Option<MeterOptions>,
+}
+
+impl QueueLayer {
+    /// Create a new [`QueueLayer`] which allows up to `max_concurrency` requests in flight,
+    /// queueing up to `max_queue` more requests.
+    pub const fn new(max_concurrency: usize, max_queue: usize) -> Self {
+        Self {
+            max_concurrency,
+            max_queue,
+            max_wait: None,
+            #[cfg(feature = "opentelemetry")]
+            metrics: None,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the maximum amount of time a request can wait in the queue.
+        ///
+        /// A [`Deadline`] found in the request [`Context`] is respected
+        /// regardless of this setting.
+        ///
+        /// [`Deadline`]: crate::layer::timeout::Deadline
+        /// [`Context`]: crate::Context
+        pub fn max_wait(mut self, max_wait: Option<Duration>) -> Self {
+            self.max_wait = max_wait;
+            self
+        }
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    rama_utils::macros::generate_set_and_with! {
+        /// Export queue depth, wait time and shed request metrics
+        /// using the global OpenTelemetry meter provider.
+        pub fn metrics(mut self, opts: Option<MeterOptions>) -> Self {
+            self.metrics = opts;
+            self
+        }
+    }
+
+    fn shared(&self) -> Arc<Shared> {
+        let mut shared = Shared::new(self.max_concurrency, self.max_queue);
+        shared.max_wait = self.max_wait;
+        #[cfg(feature = "opentelemetry")]
+        {
+            shared.metrics = self.metrics.clone().map(super::metrics::Metrics::new);
+        }
+        Arc::new(shared)
+    }
+}
+
+impl fmt::Debug for QueueLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("QueueLayer");
+        d.field("max_concurrency", &self.max_concurrency)
+            .field("max_queue", &self.max_queue)
+            .field("max_wait", &self.max_wait);
+        #[cfg(feature = "opentelemetry")]
+        d.field("metrics", &self.metrics);
+        d.finish()
+    }
+}
+
+impl<S> Layer<S> for QueueLayer {
+    type Service = Queue<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Queue {
+            inner,
+            shared: self.shared(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::telemetry::opentelemetry::semantic_conventions::resource::{
+    SERVICE_NAME, SERVICE_VERSION,
+};
+use crate::telemetry::opentelemetry::{
+    InstrumentationScope, KeyValue, MeterOptions, ServiceInfo, global,
+    metrics::{Counter, Histogram, Meter, UpDownCounter},
+    semantic_conventions,
+};
+use std::{borrow::Cow, time::Duration};
+
+const QUEUE_DEPTH: &str = "queue.depth";
+const QUEUE_WAIT_DURATION: &str = "queue.wait_duration";
+const QUEUE_SHED_REQUESTS: &str = "queue.shed_requests";
+
+/// Records request queue metrics.
+#[derive(Debug)]
+pub(super) struct Metrics {
+    depth: UpDownCounter<i64>,
+    wait_duration: Histogram<f64>,
+    shed_requests: Counter<u64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl Metrics {
+    pub(super) fn new(opts: MeterOptions) -> Self {
+        let service_info = opts.service.unwrap_or_else(|| ServiceInfo {
+            name: rama_utils::info::NAME.to_owned(),
+            version: rama_utils::info::VERSION.to_owned(),
+        });
+
+        let mut attributes = opts.attributes.unwrap_or_else(|| Vec::with_capacity(2));
+        attributes.push(KeyValue::new(SERVICE_NAME, service_info.name));
+        attributes.push(KeyValue::new(SERVICE_VERSION, service_info.version));
+
+        let meter = get_versioned_meter();
+        let prefix = opts.metric_prefix;
+        let name = |name: &'static str| match &prefix {
+            Some(prefix) => Cow::Owned(format!("{prefix}.{name}")),
+            None => Cow::Borrowed(name),
+        };
+
+        Self {
+            depth: meter
+                .i64_up_down_counter(name(QUEUE_DEPTH))
+                .with_description("Measures the amount of requests waiting in the queue.")
+                .build(),
+            wait_duration: meter
+                .f64_histogram(name(QUEUE_WAIT_DURATION))
+                .with_description("Measures the time requests spent waiting in the queue.")
+                .with_unit("s")
+                .build(),
+            shed_requests: meter
+                .u64_counter(name(QUEUE_SHED_REQUESTS))
+                .with_description(
+                    "Measures the amount of requests rejected because the queue was full or the wait timed out.",
+                )
+                .build(),
+            attributes,
+        }
+    }
+
+    pub(super) fn record_queued(&self, delta: i64) {
+        self.depth.add(delta, &self.attributes);
+    }
+
+    pub(super) fn record_wait(&self, wait: Duration) {
+        self.wait_duration
+            .record(wait.as_secs_f64(), &self.attributes);
+    }
+
+    pub(super) fn record_shed(&self, reason: &'static str) {
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new("reason", reason));
+        self.shed_requests.add(1, &attributes);
+    }
+}
+
+fn get_versioned_meter() -> Meter {
+    global::meter_with_scope(
+        InstrumentationScope::builder(format!("{}-queue", rama_utils::info::NAME))
+            .with_version(rama_utils::info::VERSION)
+            .with_schema_url(semantic_conventions::SCHEMA_URL)
+            .build(),
+    )
+}
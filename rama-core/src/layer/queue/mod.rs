@@ -0,0 +1,348 @@
+//! Middleware that queues requests in excess of a concurrency limit,
+//! shedding load once the queue is full.
+//!
+//! Unlike a [`Limit`] with a [`ConcurrentPolicy`], which either rejects
+//! or immediately retries requests once the limit is reached,
+//! the [`Queue`] middleware lets excess requests wait (in FIFO order)
+//! for a slot to become available. Only when the bounded queue is full
+//! are requests rejected, with a [`QueueFull`] error.
+//!
+//! Requests wait at most for the configured maximum wait time,
+//! or until the [`Deadline`] inserted by a [`Timeout`] middleware
+//! (if any) expires, whichever comes first. Requests that were not
+//! able to proceed in time fail with a [`QueueTimeout`] error.
+//!
+//! The current queue depth can be inspected using [`Queue::queue_depth`],
+//! and is also exported as an OpenTelemetry metric when
+//! [`QueueLayer::with_metrics`] is used (requires the `opentelemetry` feature).
+//!
+//! [`Limit`]: crate::layer::Limit
+//! [`ConcurrentPolicy`]: crate::layer::limit::policy::ConcurrentPolicy
+//! [`Deadline`]: crate::layer::timeout::Deadline
+//! [`Timeout`]: crate::layer::Timeout
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, service::service_fn};
+//! use rama_core::layer::queue::{QueueLayer, QueueFull};
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = QueueLayer::new(1, 0)
+//!     .with_max_wait(Duration::from_secs(1))
+//!     .into_layer(service_fn(async || {
+//!         tokio::time::sleep(Duration::from_millis(50)).await;
+//!         Ok::<_, Infallible>(())
+//!     }));
+//!
+//! // one request is served, while the other is shed as the queue has no room
+//! let (a, b) = tokio::join!(
+//!     service.serve(Context::default(), ()),
+//!     service.serve(Context::default(), ()),
+//! );
+//! assert!(a.is_ok());
+//! assert!(b.unwrap_err().downcast_ref::<QueueFull>().is_some());
+//! # }
+//! ```
+
+use crate::{Context, Service, error::BoxError, layer::timeout::Deadline};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{sync::Semaphore, time::Instant};
+
+mod layer;
+#[doc(inline)]
+pub use layer::QueueLayer;
+
+#[cfg(feature = "opentelemetry")]
+mod metrics;
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "request shed due to the request queue being full"]
+    pub struct QueueFull;
+}
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "request timed out while waiting in the request queue"]
+    pub struct QueueTimeout;
+}
+
+/// State shared by all clones of a [`Queue`] (and the [`QueueLayer`] it was created with).
+struct Shared {
+    semaphore: Semaphore,
+    max_queue: usize,
+    queued: AtomicUsize,
+    max_wait: Option<Duration>,
+    #[cfg(feature = "opentelemetry")]
+    metrics: Option<metrics::Metrics>,
+}
+
+impl Shared {
+    fn new(max_concurrency: usize, max_queue: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(max_concurrency),
+            max_queue,
+            queued: AtomicUsize::new(0),
+            max_wait: None,
+            #[cfg(feature = "opentelemetry")]
+            metrics: None,
+        }
+    }
+
+    #[cfg_attr(not(feature = "opentelemetry"), expect(clippy::unused_self))]
+    fn record_queued(&self, _delta: i64) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record_queued(_delta);
+        }
+    }
+
+    #[cfg_attr(not(feature = "opentelemetry"), expect(clippy::unused_self))]
+    fn record_shed(&self, _reason: &'static str) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record_shed(_reason);
+        }
+    }
+
+    #[cfg_attr(not(feature = "opentelemetry"), expect(clippy::unused_self))]
+    fn record_wait(&self, _wait: Duration) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record_wait(_wait);
+        }
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("available_permits", &self.semaphore.available_permits())
+            .field("max_queue", &self.max_queue)
+            .field("queued", &self.queued)
+            .field("max_wait", &self.max_wait)
+            .finish()
+    }
+}
+
+/// Decrements the queue depth when dropped,
+/// such that cancelled requests leave the queue as well.
+struct QueuedGuard<'a>(&'a Shared);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.queued.fetch_sub(1, Ordering::AcqRel);
+        self.0.record_queued(-1);
+    }
+}
+
+/// Middleware that queues requests in excess of a concurrency limit.
+///
+/// See [the module docs](self) for more information.
+pub struct Queue<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S> Queue<S> {
+    /// Create a new [`Queue`] which allows up to `max_concurrency` requests in flight,
+    /// queueing up to `max_queue` more requests.
+    pub fn new(inner: S, max_concurrency: usize, max_queue: usize) -> Self {
+        Self {
+            inner,
+            shared: Arc::new(Shared::new(max_concurrency, max_queue)),
+        }
+    }
+
+    /// The amount of requests currently waiting in the queue.
+    pub fn queue_depth(&self) -> usize {
+        self.shared.queued.load(Ordering::Acquire)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for Queue<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("inner", &self.inner)
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for Queue<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for Queue<S>
+where
+    S: Service<State, Request, Error: Into<BoxError>>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let shared = &*self.shared;
+
+        let _permit = match shared.semaphore.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                if shared
+                    .queued
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                        (queued < shared.max_queue).then_some(queued + 1)
+                    })
+                    .is_err()
+                {
+                    shared.record_shed("queue_full");
+                    return Err(QueueFull.into());
+                }
+                shared.record_queued(1);
+                let _queued = QueuedGuard(shared);
+
+                // a max wait too large to be represented is treated as no max wait
+                let max_wait = shared
+                    .max_wait
+                    .and_then(|max_wait| Instant::now().checked_add(max_wait));
+                let deadline = match (max_wait, ctx.get::<Deadline>()) {
+                    (Some(max_wait), Some(deadline)) => Some(deadline.instant().min(max_wait)),
+                    (Some(max_wait), None) => Some(max_wait),
+                    (None, Some(deadline)) => Some(deadline.instant()),
+                    (None, None) => None,
+                };
+
+                let start = Instant::now();
+                let acquire = shared.semaphore.acquire();
+                let result = match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, acquire)
+                        .await
+                        .map_err(|_| QueueTimeout),
+                    None => Ok(acquire.await),
+                };
+                shared.record_wait(start.elapsed());
+
+                match result {
+                    Ok(Ok(permit)) => permit,
+                    // the semaphore is never closed
+                    Ok(Err(err)) => return Err(err.into()),
+                    Err(err) => {
+                        shared.record_shed("timeout");
+                        return Err(err.into());
+                    }
+                }
+            }
+        };
+
+        self.inner.serve(ctx, request).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layer, layer::TimeoutLayer, service::service_fn};
+    use std::convert::Infallible;
+
+    fn slow_service() -> impl Service<(), (), Response = (), Error = Infallible> + Clone {
+        service_fn(async || {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, Infallible>(())
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_requests_are_served_in_order() {
+        let service = Queue::new(slow_service(), 1, 2);
+
+        let (a, b, c, d) = tokio::join!(
+            service.serve(Context::default(), ()),
+            service.serve(Context::default(), ()),
+            service.serve(Context::default(), ()),
+            service.serve(Context::default(), ()),
+        );
+        assert!(a.is_ok());
+        assert!(b.is_ok());
+        assert!(c.is_ok());
+        assert!(d.unwrap_err().downcast_ref::<QueueFull>().is_some());
+        assert_eq!(0, service.queue_depth());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_wait_elapsed() {
+        let service = QueueLayer::new(1, 1)
+            .with_max_wait(Duration::from_millis(50))
+            .into_layer(slow_service());
+
+        let (a, b) = tokio::join!(
+            service.serve(Context::default(), ()),
+            service.serve(Context::default(), ()),
+        );
+        assert!(a.is_ok());
+        assert!(b.unwrap_err().downcast_ref::<QueueTimeout>().is_some());
+        assert_eq!(0, service.queue_depth());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_wait_overflow_is_no_max_wait() {
+        let service = QueueLayer::new(1, 1)
+            .with_max_wait(Duration::MAX)
+            .into_layer(slow_service());
+
+        let (a, b) = tokio::join!(
+            service.serve(Context::default(), ()),
+            service.serve(Context::default(), ()),
+        );
+        assert!(a.is_ok());
+        assert!(b.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_respected() {
+        let service = Queue::new(slow_service(), 1, 1);
+
+        let mut ctx = Context::default();
+        ctx.insert(Deadline::after(Duration::from_millis(50)));
+
+        let (a, b) = tokio::join!(
+            service.serve(Context::default(), ()),
+            service.serve(ctx, ()),
+        );
+        assert!(a.is_ok());
+        assert!(b.unwrap_err().downcast_ref::<QueueTimeout>().is_some());
+    }
+
+    #[tokio::test]
+    async fn timeout_inserts_deadline() {
+        let service = TimeoutLayer::new(Duration::from_secs(5)).into_layer(
+            TimeoutLayer::new(Duration::from_secs(10)).into_layer(service_fn(
+                async |ctx: Context<()>, ()| {
+                    Ok::<_, BoxError>(ctx.get::<Deadline>().unwrap().remaining())
+                },
+            )),
+        );
+
+        let remaining = service.serve(Context::default(), ()).await.unwrap();
+        assert!(remaining <= Duration::from_secs(5));
+    }
+}
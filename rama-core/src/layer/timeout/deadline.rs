@@ -0,0 +1,211 @@
+use crate::Context;
+use std::{fmt, time::Duration};
+use tokio::time::Instant;
+
+/// The point in time by which a request is expected to be served.
+///
+/// It is inserted in the [`Context`] by the [`Timeout`] middleware,
+/// such that inner services can take the remaining time into account,
+/// e.g. to not wait on a queue for longer than the request is allowed to take.
+///
+/// When nested, the earliest deadline is retained.
+///
+/// Other components respect it as well, such as the tcp connector,
+/// tls connectors, connection pools and retry middleware, such that
+/// the total time spent on a request remains within its budget.
+///
+/// [`Context`]: crate::Context
+/// [`Timeout`]: super::Timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Create a new [`Deadline`] at the given instant.
+    pub const fn new(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    /// Create a new [`Deadline`] which expires after the given duration from now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting point in time cannot be represented,
+    /// see [`Deadline::try_after`] for a non-panicking alternative.
+    pub fn after(duration: Duration) -> Self {
+        Self(Instant::now() + duration)
+    }
+
+    /// Create a new [`Deadline`] which expires after the given duration from now,
+    /// returning `None` if the resulting point in time cannot be represented,
+    /// in which case there is effectively no deadline.
+    pub fn try_after(duration: Duration) -> Option<Self> {
+        Instant::now().checked_add(duration).map(Self)
+    }
+
+    /// Insert a [`Deadline`] which expires after the given duration from now
+    /// in the given [`Context`], unless an earlier [`Deadline`] is already present.
+    ///
+    /// Returns the [`Deadline`] which is in effect, if any. A duration too large
+    /// to be represented is treated as no deadline.
+    pub fn insert_after<State>(duration: Duration, ctx: &mut Context<State>) -> Option<Self> {
+        match Self::try_after(duration) {
+            Some(deadline) => Some(deadline.insert_into(ctx)),
+            None => ctx.get::<Self>().copied(),
+        }
+    }
+
+    /// The instant at which this [`Deadline`] expires.
+    pub const fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// The time remaining until this [`Deadline`] expires,
+    /// which is zero if it already expired.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns `true` if this [`Deadline`] has expired.
+    pub fn is_expired(&self) -> bool {
+        self.0 <= Instant::now()
+    }
+
+    /// Insert this [`Deadline`] in the given [`Context`],
+    /// unless an earlier [`Deadline`] is already present.
+    ///
+    /// Returns the [`Deadline`] which is in effect.
+    pub fn insert_into<State>(self, ctx: &mut Context<State>) -> Self {
+        match ctx.get::<Self>() {
+            Some(existing) if *existing <= self => *existing,
+            _ => {
+                ctx.insert(self);
+                self
+            }
+        }
+    }
+
+    /// Cap the given duration to the time remaining until this [`Deadline`] expires.
+    pub fn cap(&self, duration: Duration) -> Duration {
+        duration.min(self.remaining())
+    }
+
+    /// Await the given future, failing with [`DeadlineExceeded`]
+    /// in case it did not complete before this [`Deadline`] expired.
+    pub async fn run<F: Future>(self, future: F) -> Result<F::Output, DeadlineExceeded> {
+        tokio::time::timeout_at(self.0, future)
+            .await
+            .map_err(|_| DeadlineExceeded)
+    }
+
+    /// Await the given future within the [`Deadline`] found in the [`Context`], if any.
+    ///
+    /// See [`Deadline::run`] for more information.
+    pub async fn run_within<State, F: Future>(
+        ctx: &Context<State>,
+        future: F,
+    ) -> Result<F::Output, DeadlineExceeded> {
+        match ctx.get::<Self>().copied() {
+            Some(deadline) => deadline.run(future).await,
+            None => Ok(future.await),
+        }
+    }
+}
+
+/// Error returned when a [`Deadline`] expired before the work was done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_into_retains_earliest_deadline() {
+        let mut ctx = Context::default();
+
+        let first = Deadline::after(Duration::from_secs(10));
+        assert_eq!(first, first.insert_into(&mut ctx));
+
+        let later = Deadline::after(Duration::from_secs(20));
+        assert_eq!(first, later.insert_into(&mut ctx));
+        assert_eq!(Some(&first), ctx.get::<Deadline>());
+
+        let earlier = Deadline::after(Duration::from_secs(1));
+        assert_eq!(earlier, earlier.insert_into(&mut ctx));
+        assert_eq!(Some(&earlier), ctx.get::<Deadline>());
+    }
+
+    #[tokio::test]
+    async fn duration_max_is_no_deadline() {
+        assert_eq!(None, Deadline::try_after(Duration::MAX));
+
+        let mut ctx = Context::default();
+        assert_eq!(None, Deadline::insert_after(Duration::MAX, &mut ctx));
+        assert!(ctx.get::<Deadline>().is_none());
+
+        let deadline = Deadline::after(Duration::from_secs(1));
+        ctx.insert(deadline);
+        assert_eq!(
+            Some(deadline),
+            Deadline::insert_after(Duration::MAX, &mut ctx)
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout_duration_max() {
+        use crate::{Service, error::BoxError, layer::timeout::Timeout, service::service_fn};
+
+        let service = Timeout::with_error_fn(
+            service_fn(async |ctx: Context<()>, ()| {
+                assert!(ctx.get::<Deadline>().is_none());
+                Ok::<_, BoxError>(())
+            }),
+            Duration::MAX,
+            || BoxError::from("timeout"),
+        );
+        service.serve(Context::default(), ()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_within() {
+        let mut ctx = Context::default();
+        assert_eq!(Ok(1), Deadline::run_within(&ctx, async { 1 }).await);
+
+        ctx.insert(Deadline::after(Duration::from_millis(10)));
+        assert_eq!(
+            Err(DeadlineExceeded),
+            Deadline::run_within(&ctx, tokio::time::sleep(Duration::from_secs(1))).await
+        );
+    }
+
+    #[tokio::test]
+    async fn inner_timeout_respects_outer_deadline() {
+        use crate::{Service, error::BoxError, layer::timeout::Timeout, service::service_fn};
+
+        let inner = Timeout::with_error_fn(
+            service_fn(async |ctx: Context<()>, ()| {
+                let remaining = ctx.get::<Deadline>().unwrap().remaining();
+                assert!(remaining <= Duration::from_millis(50));
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok::<_, BoxError>(())
+            }),
+            Duration::from_secs(5),
+            || BoxError::from("inner"),
+        );
+        let outer =
+            Timeout::with_error_fn(inner, Duration::from_millis(50), || BoxError::from("outer"));
+
+        let err = outer.serve(Context::default(), ()).await.unwrap_err();
+        // both expire at the same instant, either error is fine,
+        // as long as we did not wait for the inner timeout
+        assert!(["inner", "outer"].contains(&err.to_string().as_str()));
+    }
+}
@@ -2,6 +2,10 @@
 //!
 //! If the response does not complete within the specified timeout, the response
 //! will be aborted.
+//!
+//! The [`Deadline`] of the request is inserted in the [`Context`],
+//! such that inner services can take the remaining time into account.
+//! When nested, an inner [`Timeout`] never waits longer than the outer [`Deadline`].
 
 use super::{LayerErrorFn, LayerErrorStatic, MakeLayerError};
 use crate::{Context, Service};
@@ -12,6 +16,10 @@ mod error;
 #[doc(inline)]
 pub use error::Elapsed;
 
+mod deadline;
+#[doc(inline)]
+pub use deadline::{Deadline, DeadlineExceeded};
+
 mod layer;
 #[doc(inline)]
 pub use layer::TimeoutLayer;
@@ -122,12 +130,17 @@ where
 
     async fn serve(
         &self,
-        ctx: Context<S>,
+        mut ctx: Context<S>,
         request: Request,
     ) -> Result<Self::Response, Self::Error> {
+        // an outer deadline which expires earlier takes precedence
+        let Some(deadline) = Deadline::insert_after(self.timeout, &mut ctx) else {
+            return self.inner.serve(ctx, request).await;
+        };
+
         tokio::select! {
             res = self.inner.serve(ctx, request) => res,
-            _ = tokio::time::sleep(self.timeout) => Err(self.into_error.make_layer_error().into()),
+            _ = tokio::time::sleep_until(deadline.instant()) => Err(self.into_error.make_layer_error().into()),
         }
     }
 }
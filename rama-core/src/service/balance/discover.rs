@@ -0,0 +1,247 @@
+use super::Endpoint;
+use crate::error::{BoxError, ErrorExt, OpaqueError};
+use std::{
+    fmt,
+    hash::Hash,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// A [`Discover`] produces the (changing) set of endpoints
+/// to be load balanced over by a [`Balance`] service.
+///
+/// Each yielded set replaces the previous one in full.
+/// Returning `None` signals that no more updates will follow,
+/// in which case the last known set remains in use.
+///
+/// [`Balance`]: super::Balance
+pub trait Discover: Send + 'static {
+    /// The key used to identify an endpoint across updates.
+    type Key: Hash + Eq + Clone + Send + Sync + 'static;
+    /// The service used to serve requests for an endpoint.
+    type Service: Send + Sync + 'static;
+    /// The error returned in case the endpoints could not be discovered.
+    type Error: Into<BoxError> + Send + 'static;
+
+    /// Wait for the next set of endpoints.
+    fn next_endpoints(
+        &mut self,
+    ) -> impl Future<Output = Option<Result<Vec<Endpoint<Self::Key, Self::Service>>, Self::Error>>>
+    + Send
+    + '_;
+}
+
+/// A [`Discover`] which yields a fixed set of endpoints once.
+pub struct StaticDiscovery<K, S> {
+    endpoints: Option<Vec<Endpoint<K, S>>>,
+}
+
+impl<K, S> StaticDiscovery<K, S> {
+    /// Create a new [`StaticDiscovery`] for the given endpoints.
+    pub fn new(endpoints: Vec<Endpoint<K, S>>) -> Self {
+        Self {
+            endpoints: Some(endpoints),
+        }
+    }
+}
+
+impl<K: fmt::Debug, S: fmt::Debug> fmt::Debug for StaticDiscovery<K, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticDiscovery")
+            .field("endpoints", &self.endpoints)
+            .finish()
+    }
+}
+
+impl<K, S> Discover for StaticDiscovery<K, S>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+{
+    type Key = K;
+    type Service = S;
+    type Error = std::convert::Infallible;
+
+    async fn next_endpoints(&mut self) -> Option<Result<Vec<Endpoint<K, S>>, Self::Error>> {
+        self.endpoints.take().map(Ok)
+    }
+}
+
+/// A [`Discover`] which reads the endpoints from a file,
+/// yielding a new set each time the file is modified.
+///
+/// The file is polled for modifications at the configured interval,
+/// (1 second by default) and its content is turned into endpoints
+/// using the given parse function.
+pub struct FileDiscovery<F> {
+    path: PathBuf,
+    parse: F,
+    interval: Duration,
+    modified: Option<SystemTime>,
+}
+
+impl<F> FileDiscovery<F> {
+    /// Create a new [`FileDiscovery`] for the file at the given path,
+    /// using the given function to parse the file content into endpoints.
+    pub fn new(path: impl Into<PathBuf>, parse: F) -> Self {
+        Self {
+            path: path.into(),
+            parse,
+            interval: Duration::from_secs(1),
+            modified: None,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the interval at which the file is polled for modifications.
+        pub fn interval(mut self, interval: Duration) -> Self {
+            self.interval = interval;
+            self
+        }
+    }
+}
+
+impl<F> fmt::Debug for FileDiscovery<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileDiscovery")
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .field("modified", &self.modified)
+            .finish()
+    }
+}
+
+impl<F, K, S, E> Discover for FileDiscovery<F>
+where
+    F: Fn(&str) -> Result<Vec<Endpoint<K, S>>, E> + Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    E: Into<BoxError> + 'static,
+{
+    type Key = K;
+    type Service = S;
+    type Error = OpaqueError;
+
+    async fn next_endpoints(&mut self) -> Option<Result<Vec<Endpoint<K, S>>, Self::Error>> {
+        loop {
+            if self.modified.is_some() {
+                tokio::time::sleep(self.interval).await;
+            }
+
+            let modified = match tokio::fs::metadata(&self.path)
+                .await
+                .and_then(|metadata| metadata.modified())
+            {
+                Ok(modified) => modified,
+                Err(err) => {
+                    // avoid busy looping on a missing file
+                    self.modified.get_or_insert(SystemTime::UNIX_EPOCH);
+                    return Some(Err(err.context("read endpoints file metadata")));
+                }
+            };
+            if self.modified == Some(modified) {
+                continue;
+            }
+
+            let content = match tokio::fs::read_to_string(&self.path).await {
+                Ok(content) => content,
+                Err(err) => {
+                    // only record the modification time once read,
+                    // so that the read is retried at the next interval
+                    self.modified.get_or_insert(SystemTime::UNIX_EPOCH);
+                    return Some(Err(err.context("read endpoints file")));
+                }
+            };
+            self.modified = Some(modified);
+            return Some(
+                (self.parse)(&content)
+                    .map_err(|err| OpaqueError::from_boxed(err.into()).context("parse endpoints")),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Vec<Endpoint<String, ()>>, std::convert::Infallible> {
+        Ok(content
+            .lines()
+            .map(|line| Endpoint::new(line.to_owned(), ()))
+            .collect())
+    }
+
+    fn keys(endpoints: Vec<Endpoint<String, ()>>) -> Vec<String> {
+        endpoints.into_iter().map(|e| e.key).collect()
+    }
+
+    #[tokio::test]
+    async fn static_discovery() {
+        let mut discovery = StaticDiscovery::new(vec![Endpoint::new(1, ())]);
+        assert_eq!(1, discovery.next_endpoints().await.unwrap().unwrap().len());
+        assert!(discovery.next_endpoints().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn file_discovery() {
+        let dir = std::env::temp_dir().join(format!("rama-file-discovery-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("endpoints.txt");
+        std::fs::write(&path, "a\nb\n").unwrap();
+
+        let mut discovery =
+            FileDiscovery::new(&path, parse).with_interval(Duration::from_millis(10));
+        assert_eq!(
+            vec!["a", "b"],
+            keys(discovery.next_endpoints().await.unwrap().unwrap())
+        );
+
+        // ensure the modification time differs on coarse grained file systems
+        std::fs::write(&path, "c\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(
+            vec!["c"],
+            keys(discovery.next_endpoints().await.unwrap().unwrap())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn file_discovery_retries_failed_read() {
+        let dir =
+            std::env::temp_dir().join(format!("rama-file-discovery-retry-{}", std::process::id()));
+        let path = dir.join("endpoints.txt");
+        // a directory has metadata, but cannot be read as a file
+        std::fs::create_dir_all(&path).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        let mut discovery =
+            FileDiscovery::new(&path, parse).with_interval(Duration::from_millis(10));
+        assert!(discovery.next_endpoints().await.unwrap().is_err());
+
+        // same modification time as the failed read
+        std::fs::remove_dir(&path).unwrap();
+        std::fs::write(&path, "a\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        assert_eq!(
+            vec!["a"],
+            keys(discovery.next_endpoints().await.unwrap().unwrap())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
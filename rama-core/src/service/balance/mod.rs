@@ -0,0 +1,478 @@
+//! Service which balances requests over a (dynamic) set of endpoint services.
+//!
+//! A [`Balance`] service uses a [`Strategy`] to pick the endpoint
+//! to serve each request with. The following strategies are available:
+//!
+//! - [`RoundRobin`]: picks the endpoints one after the other;
+//! - [`Weighted`]: picks endpoints randomly, proportional to their weight;
+//! - [`PowerOfTwoChoices`]: picks the least loaded out of two random endpoints;
+//! - [`PeakEwma`]: picks the fastest out of two random endpoints,
+//!   taking into account their latency as well as their load.
+//!
+//! The set of endpoints can be updated at runtime, either manually
+//! using [`Balance::set_endpoints`] or by driving a [`Discover`]
+//! implementation using [`Balance::discover`]. Statistics of endpoints
+//! are retained across updates for as long as their key remains in the set.
+//!
+//! Endpoints which fail for a configurable amount of consecutive requests
+//! are ejected from the set for a while, unless all endpoints are ejected.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Service, service::service_fn};
+//! use rama_core::service::balance::{Balance, Endpoint, RoundRobin};
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let balance = Balance::from_endpoints(
+//!     RoundRobin::new(),
+//!     vec![
+//!         Endpoint::new("a", service_fn(async || Ok::<_, Infallible>("a")).boxed()),
+//!         Endpoint::new("b", service_fn(async || Ok::<_, Infallible>("b")).boxed()),
+//!     ],
+//! );
+//!
+//! assert_eq!("a", balance.serve(Context::default(), ()).await.unwrap());
+//! assert_eq!("b", balance.serve(Context::default(), ()).await.unwrap());
+//! # }
+//! ```
+
+use crate::{
+    Context, Service,
+    error::BoxError,
+    rt::Executor,
+    telemetry::tracing::{self, Instrument},
+};
+use parking_lot::RwLock;
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+mod stats;
+#[doc(inline)]
+pub use stats::EndpointStats;
+
+mod strategy;
+#[doc(inline)]
+pub use strategy::{PeakEwma, PowerOfTwoChoices, RoundRobin, Strategy, Weighted};
+
+mod discover;
+#[doc(inline)]
+pub use discover::{Discover, FileDiscovery, StaticDiscovery};
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "no endpoints available"]
+    pub struct NoEndpoints;
+}
+
+/// An endpoint to be balanced over by a [`Balance`] service.
+pub struct Endpoint<K, S> {
+    key: K,
+    service: S,
+    weight: u32,
+}
+
+impl<K, S> Endpoint<K, S> {
+    /// Create a new [`Endpoint`], identified by the given key,
+    /// which serves requests using the given service.
+    ///
+    /// The endpoint has a weight of 1 by default.
+    pub fn new(key: K, service: S) -> Self {
+        Self {
+            key,
+            service,
+            weight: 1,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the (relative) weight of the endpoint,
+        /// used by the [`Weighted`] strategy.
+        pub fn weight(mut self, weight: u32) -> Self {
+            self.weight = weight;
+            self
+        }
+    }
+
+    /// The key identifying the endpoint.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// The service used to serve requests for the endpoint.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+}
+
+impl<K: fmt::Debug, S: fmt::Debug> fmt::Debug for Endpoint<K, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("key", &self.key)
+            .field("service", &self.service)
+            .field("weight", &self.weight)
+            .finish()
+    }
+}
+
+impl<K: Clone, S: Clone> Clone for Endpoint<K, S> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            service: self.service.clone(),
+            weight: self.weight,
+        }
+    }
+}
+
+struct Entry<K, S> {
+    key: K,
+    service: S,
+    stats: Arc<EndpointStats>,
+}
+
+struct Shared<K, S, P> {
+    strategy: P,
+    endpoints: RwLock<Arc<[Entry<K, S>]>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ejection {
+    consecutive_failures: u32,
+    duration: Duration,
+}
+
+/// A [`Service`] which balances requests over a set of endpoints,
+/// using a [`Strategy`] to pick the endpoint for each request.
+///
+/// See [the module docs](self) for more information.
+pub struct Balance<K, S, P> {
+    shared: Arc<Shared<K, S, P>>,
+    ejection: Ejection,
+}
+
+impl<K, S, P> Balance<K, S, P>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    P: Strategy,
+{
+    /// Create a new [`Balance`] service without any endpoints,
+    /// using the given strategy to pick endpoints.
+    pub fn new(strategy: P) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                strategy,
+                endpoints: RwLock::new(Arc::new([])),
+            }),
+            ejection: Ejection {
+                consecutive_failures: 5,
+                duration: Duration::from_secs(30),
+            },
+        }
+    }
+
+    /// Create a new [`Balance`] service for the given endpoints,
+    /// using the given strategy to pick endpoints.
+    pub fn from_endpoints(strategy: P, endpoints: Vec<Endpoint<K, S>>) -> Self {
+        let balance = Self::new(strategy);
+        balance.set_endpoints(endpoints);
+        balance
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Eject endpoints for the given duration
+        /// once they failed for the given amount of consecutive requests.
+        ///
+        /// By default endpoints are ejected for 30 seconds after 5 consecutive failures.
+        /// A failure threshold of zero disables ejection.
+        pub fn ejection(mut self, consecutive_failures: u32, duration: Duration) -> Self {
+            self.ejection = Ejection {
+                consecutive_failures,
+                duration,
+            };
+            self
+        }
+    }
+
+    /// Replace the endpoints of this [`Balance`] service.
+    ///
+    /// Statistics are retained for endpoints of which the key was already known.
+    pub fn set_endpoints(&self, endpoints: Vec<Endpoint<K, S>>) {
+        let mut current = self.shared.endpoints.write();
+        let mut known: HashMap<&K, &Arc<EndpointStats>> = current
+            .iter()
+            .map(|entry| (&entry.key, &entry.stats))
+            .collect();
+        let entries: Arc<[Entry<K, S>]> = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                let stats = match known.remove(&endpoint.key) {
+                    Some(stats) => {
+                        stats.set_weight(endpoint.weight);
+                        stats.clone()
+                    }
+                    None => Arc::new(EndpointStats::new(endpoint.weight)),
+                };
+                Entry {
+                    key: endpoint.key,
+                    service: endpoint.service,
+                    stats,
+                }
+            })
+            .collect();
+        drop(known);
+        *current = entries;
+    }
+
+    /// Returns the amount of endpoints in this [`Balance`] service,
+    /// including those that are currently ejected.
+    pub fn len(&self) -> usize {
+        self.shared.endpoints.read().len()
+    }
+
+    /// Returns `true` if this [`Balance`] service has no endpoints.
+    pub fn is_empty(&self) -> bool {
+        self.shared.endpoints.read().is_empty()
+    }
+
+    /// Returns the statistics of the endpoint with the given key, if known.
+    pub fn endpoint_stats(&self, key: &K) -> Option<Arc<EndpointStats>> {
+        self.shared
+            .endpoints
+            .read()
+            .iter()
+            .find(|entry| &entry.key == key)
+            .map(|entry| entry.stats.clone())
+    }
+
+    /// Spawn a task on the given executor which keeps updating the endpoints
+    /// of this [`Balance`] service using the given [`Discover`] implementation.
+    ///
+    /// The task ends once the discovery yields no more updates,
+    /// all clones of this [`Balance`] service are dropped,
+    /// or the executor's shutdown guard (if any) is cancelled.
+    /// Discovery errors are logged and otherwise ignored.
+    pub fn discover<D>(&self, mut discovery: D, executor: &Executor) -> tokio::task::JoinHandle<()>
+    where
+        D: Discover<Key = K, Service = S>,
+    {
+        let shared = Arc::downgrade(&self.shared);
+        let ejection = self.ejection;
+        let guard = executor.guard().cloned();
+
+        let task = async move {
+            loop {
+                let update = match &guard {
+                    Some(guard) => tokio::select! {
+                        update = discovery.next_endpoints() => update,
+                        _ = guard.cancelled() => {
+                            tracing::trace!("balance discovery: shutdown requested");
+                            return;
+                        }
+                    },
+                    None => discovery.next_endpoints().await,
+                };
+                let Some(shared) = Weak::upgrade(&shared) else {
+                    tracing::trace!("balance discovery: balance service dropped");
+                    return;
+                };
+                match update {
+                    Some(Ok(endpoints)) => {
+                        tracing::trace!(
+                            "balance discovery: update with {} endpoint(s)",
+                            endpoints.len()
+                        );
+                        Balance { shared, ejection }.set_endpoints(endpoints);
+                    }
+                    Some(Err(err)) => {
+                        let err = err.into();
+                        tracing::debug!("balance discovery: failed to discover endpoints: {err}");
+                    }
+                    None => {
+                        tracing::trace!("balance discovery: no more updates");
+                        return;
+                    }
+                }
+            }
+        };
+
+        executor.spawn_task(task.instrument(tracing::trace_span!("balance::discover")))
+    }
+}
+
+impl<K, S, P> Clone for Balance<K, S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            ejection: self.ejection,
+        }
+    }
+}
+
+impl<K: fmt::Debug, S, P: fmt::Debug> fmt::Debug for Balance<K, S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoints = self.shared.endpoints.read();
+        f.debug_struct("Balance")
+            .field("strategy", &self.shared.strategy)
+            .field(
+                "endpoints",
+                &endpoints.iter().map(|entry| &entry.key).collect::<Vec<_>>(),
+            )
+            .field("ejection", &self.ejection)
+            .finish()
+    }
+}
+
+impl<K, S, P, State, Request> Service<State, Request> for Balance<K, S, P>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+    S: Service<State, Request, Error: Into<BoxError>>,
+    P: Strategy,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let endpoints = self.shared.endpoints.read().clone();
+        if endpoints.is_empty() {
+            return Err(NoEndpoints.into());
+        }
+
+        let now = Instant::now();
+        let mut candidates: Vec<&Entry<K, S>> = endpoints
+            .iter()
+            .filter(|entry| !entry.stats.is_ejected(now))
+            .collect();
+        if candidates.is_empty() {
+            // better to try a failing endpoint than to not try at all
+            tracing::trace!("balance: all endpoints ejected, considering all of them");
+            candidates = endpoints.iter().collect();
+        }
+
+        let stats: Vec<&EndpointStats> = candidates.iter().map(|entry| &*entry.stats).collect();
+        let index = self.shared.strategy.pick(&stats);
+        let entry = candidates.get(index).copied().unwrap_or(candidates[0]);
+
+        let result = {
+            let _guard = entry.stats.start();
+            let start = Instant::now();
+            let result = entry.service.serve(ctx, req).await;
+            entry.stats.record_rtt(start.elapsed());
+            result
+        };
+
+        match result {
+            Ok(response) => {
+                entry.stats.record_success();
+                Ok(response)
+            }
+            Err(err) => {
+                let failures = entry.stats.record_failure();
+                let threshold = self.ejection.consecutive_failures;
+                if threshold > 0 && failures >= threshold {
+                    tracing::debug!(
+                        "balance: ejecting endpoint for {:?} after {failures} consecutive failures",
+                        self.ejection.duration,
+                    );
+                    entry.stats.eject(Instant::now() + self.ejection.duration);
+                }
+                Err(err.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn no_endpoints() {
+        let balance = Balance::<&str, crate::service::RejectService<()>, _>::new(RoundRobin::new());
+        let err = balance.serve(Context::default(), ()).await.unwrap_err();
+        assert!(err.downcast_ref::<NoEndpoints>().is_some());
+    }
+
+    #[tokio::test]
+    async fn set_endpoints_retains_stats() {
+        let echo =
+            |name: &'static str| service_fn(move || async move { Ok::<_, Infallible>(name) });
+        let balance = Balance::from_endpoints(
+            RoundRobin::new(),
+            vec![Endpoint::new("a", echo("a")), Endpoint::new("b", echo("b"))],
+        );
+        balance.serve(Context::default(), ()).await.unwrap();
+        let stats = balance.endpoint_stats(&"a").unwrap();
+        assert!(stats.rtt_estimate().is_some());
+
+        balance.set_endpoints(vec![
+            Endpoint::new("a", echo("a")).with_weight(3),
+            Endpoint::new("c", echo("c")),
+        ]);
+        assert_eq!(2, balance.len());
+        assert!(Arc::ptr_eq(&stats, &balance.endpoint_stats(&"a").unwrap()));
+        assert_eq!(3, stats.weight());
+        assert!(balance.endpoint_stats(&"b").is_none());
+    }
+
+    #[tokio::test]
+    async fn eject_failing_endpoint() {
+        let balance = Balance::from_endpoints(
+            RoundRobin::new(),
+            vec![
+                Endpoint::new(
+                    "bad",
+                    service_fn(async || Err::<&'static str, _>(BoxError::from("boom"))).boxed(),
+                ),
+                Endpoint::new(
+                    "good",
+                    service_fn(async || Ok::<_, BoxError>("good")).boxed(),
+                ),
+            ],
+        )
+        .with_ejection(2, Duration::from_secs(60));
+
+        let mut failures = 0;
+        for _ in 0..4 {
+            if balance.serve(Context::default(), ()).await.is_err() {
+                failures += 1;
+            }
+        }
+        assert_eq!(2, failures);
+
+        // the bad endpoint is ejected, so only the good one remains
+        for _ in 0..4 {
+            assert_eq!("good", balance.serve(Context::default(), ()).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn discover_updates_endpoints() {
+        let balance: Balance<&str, _, _> = Balance::new(RoundRobin::new());
+        let handle = balance.discover(
+            StaticDiscovery::new(vec![Endpoint::new(
+                "a",
+                service_fn(async || Ok::<_, Infallible>("a")),
+            )]),
+            &Executor::default(),
+        );
+        handle.await.unwrap();
+        assert_eq!("a", balance.serve(Context::default(), ()).await.unwrap());
+    }
+}
@@ -0,0 +1,126 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The time constant used to decay the round-trip time estimate of an endpoint.
+const RTT_DECAY: Duration = Duration::from_secs(10);
+
+/// Load and health statistics of a single endpoint of a [`Balance`],
+/// available to a [`Strategy`] to pick an endpoint.
+///
+/// Statistics are retained across discovery updates for as long as
+/// the key of the endpoint remains part of the endpoint set.
+///
+/// [`Balance`]: super::Balance
+/// [`Strategy`]: super::Strategy
+#[derive(Debug)]
+pub struct EndpointStats {
+    weight: AtomicU32,
+    in_flight: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    rtt: Mutex<Option<RttEstimate>>,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RttEstimate {
+    nanos: f64,
+    updated_at: Instant,
+}
+
+impl EndpointStats {
+    pub(super) fn new(weight: u32) -> Self {
+        Self {
+            weight: AtomicU32::new(weight),
+            in_flight: AtomicUsize::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            rtt: Mutex::new(None),
+            ejected_until: Mutex::new(None),
+        }
+    }
+
+    /// The (relative) weight of the endpoint.
+    pub fn weight(&self) -> u32 {
+        self.weight.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_weight(&self, weight: u32) {
+        self.weight.store(weight, Ordering::Relaxed);
+    }
+
+    /// The amount of requests currently in flight for the endpoint.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// The peak exponentially weighted moving average
+    /// of the round-trip time of the endpoint,
+    /// or `None` if no request has completed yet.
+    ///
+    /// Latency spikes are adopted immediately,
+    /// while lower latencies decay the estimate over time.
+    pub fn rtt_estimate(&self) -> Option<Duration> {
+        self.rtt
+            .lock()
+            .map(|rtt| Duration::from_nanos(rtt.nanos as u64))
+    }
+
+    /// The amount of consecutive failed requests for the endpoint.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn is_ejected(&self, now: Instant) -> bool {
+        matches!(*self.ejected_until.lock(), Some(until) if until > now)
+    }
+
+    pub(super) fn start(&self) -> InFlightGuard<'_> {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        InFlightGuard { stats: self }
+    }
+
+    pub(super) fn record_rtt(&self, rtt: Duration) {
+        let now = Instant::now();
+        let sample = rtt.as_nanos() as f64;
+        let mut estimate = self.rtt.lock();
+        let nanos = match *estimate {
+            Some(RttEstimate { nanos, .. }) if sample > nanos => sample,
+            Some(RttEstimate { nanos, updated_at }) => {
+                let elapsed = now.saturating_duration_since(updated_at).as_secs_f64();
+                let decay = (-elapsed / RTT_DECAY.as_secs_f64()).exp();
+                nanos.mul_add(decay, sample * (1.0 - decay))
+            }
+            None => sample,
+        };
+        *estimate = Some(RttEstimate {
+            nanos,
+            updated_at: now,
+        });
+    }
+
+    pub(super) fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a failure, returning the amount of consecutive failures.
+    pub(super) fn record_failure(&self) -> u32 {
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub(super) fn eject(&self, until: Instant) {
+        *self.ejected_until.lock() = Some(until);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Guard which tracks an in-flight request for an endpoint.
+pub(super) struct InFlightGuard<'a> {
+    stats: &'a EndpointStats,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.stats.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,255 @@
+use super::EndpointStats;
+use parking_lot::Mutex;
+use rama_utils::rng::{HasherRng, Rng};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// A [`Strategy`] picks the endpoint to be used by a [`Balance`] for a request.
+///
+/// [`Balance`]: super::Balance
+pub trait Strategy: Send + Sync + 'static {
+    /// Pick the index of the endpoint to use out of the given candidates.
+    ///
+    /// The candidates are guaranteed to be non-empty,
+    /// and the returned index is expected to be in bounds.
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize;
+}
+
+impl<S: Strategy> Strategy for std::sync::Arc<S> {
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize {
+        (**self).pick(candidates)
+    }
+}
+
+/// A [`Strategy`] which picks the endpoints one after the other.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl RoundRobin {
+    /// Create a new [`RoundRobin`] strategy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Strategy for RoundRobin {
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()
+    }
+}
+
+/// A [`Strategy`] which randomly picks an endpoint,
+/// with a probability proportional to its weight.
+///
+/// Endpoints with a weight of zero are only picked
+/// when all candidates have a weight of zero.
+pub struct Weighted {
+    rng: Mutex<HasherRng>,
+}
+
+impl Weighted {
+    /// Create a new [`Weighted`] strategy.
+    pub fn new() -> Self {
+        Self {
+            rng: Mutex::new(HasherRng::new()),
+        }
+    }
+}
+
+impl Default for Weighted {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Weighted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Weighted").finish()
+    }
+}
+
+impl Strategy for Weighted {
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize {
+        let total: u64 = candidates.iter().map(|c| c.weight() as u64).sum();
+        let mut rng = self.rng.lock();
+        if total == 0 {
+            return rng.next_range(0..candidates.len() as u64) as usize;
+        }
+        let mut point = rng.next_range(0..total);
+        for (index, candidate) in candidates.iter().enumerate() {
+            let weight = candidate.weight() as u64;
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        candidates.len() - 1
+    }
+}
+
+/// A [`Strategy`] which picks two random endpoints,
+/// and uses the one with the least requests in flight.
+///
+/// This is known as the "power of two choices",
+/// which performs nearly as well as picking the least loaded endpoint out of all of them,
+/// at a fraction of the cost and without herding all requests to a single endpoint.
+pub struct PowerOfTwoChoices {
+    rng: Mutex<HasherRng>,
+}
+
+impl PowerOfTwoChoices {
+    /// Create a new [`PowerOfTwoChoices`] strategy.
+    pub fn new() -> Self {
+        Self {
+            rng: Mutex::new(HasherRng::new()),
+        }
+    }
+}
+
+impl Default for PowerOfTwoChoices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for PowerOfTwoChoices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PowerOfTwoChoices").finish()
+    }
+}
+
+impl Strategy for PowerOfTwoChoices {
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize {
+        pick_two(&self.rng, candidates, |stats| stats.in_flight() as f64)
+    }
+}
+
+/// A [`Strategy`] which picks two random endpoints, and uses the one
+/// with the lowest peak EWMA round-trip time weighted by its requests in flight.
+///
+/// Endpoints for which no latency has been observed yet
+/// are assumed to have the configured default round-trip time.
+pub struct PeakEwma {
+    rng: Mutex<HasherRng>,
+    default_rtt: Duration,
+}
+
+impl PeakEwma {
+    /// Create a new [`PeakEwma`] strategy,
+    /// using the given round-trip time for endpoints without observed latency.
+    pub fn new(default_rtt: Duration) -> Self {
+        Self {
+            rng: Mutex::new(HasherRng::new()),
+            default_rtt,
+        }
+    }
+}
+
+impl Default for PeakEwma {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(30))
+    }
+}
+
+impl fmt::Debug for PeakEwma {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeakEwma")
+            .field("default_rtt", &self.default_rtt)
+            .finish()
+    }
+}
+
+impl Strategy for PeakEwma {
+    fn pick(&self, candidates: &[&EndpointStats]) -> usize {
+        pick_two(&self.rng, candidates, |stats| {
+            let rtt = stats.rtt_estimate().unwrap_or(self.default_rtt);
+            rtt.as_nanos() as f64 * (stats.in_flight() + 1) as f64
+        })
+    }
+}
+
+fn pick_two(
+    rng: &Mutex<HasherRng>,
+    candidates: &[&EndpointStats],
+    cost: impl Fn(&EndpointStats) -> f64,
+) -> usize {
+    let len = candidates.len() as u64;
+    if len < 2 {
+        return 0;
+    }
+    let (a, b) = {
+        let mut rng = rng.lock();
+        let a = rng.next_range(0..len);
+        // pick a distinct second index
+        let b = (a + 1 + rng.next_range(0..len - 1)) % len;
+        (a as usize, b as usize)
+    };
+    if cost(candidates[b]) < cost(candidates[a]) {
+        b
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(weights: &[u32]) -> Vec<EndpointStats> {
+        weights.iter().map(|w| EndpointStats::new(*w)).collect()
+    }
+
+    #[test]
+    fn round_robin() {
+        let stats = stats(&[1, 1, 1]);
+        let candidates: Vec<_> = stats.iter().collect();
+        let strategy = RoundRobin::new();
+        let picks: Vec<_> = (0..6).map(|_| strategy.pick(&candidates)).collect();
+        assert_eq!(vec![0, 1, 2, 0, 1, 2], picks);
+    }
+
+    #[test]
+    fn weighted() {
+        let stats = stats(&[0, 3, 1]);
+        let candidates: Vec<_> = stats.iter().collect();
+        let strategy = Weighted::new();
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            counts[strategy.pick(&candidates)] += 1;
+        }
+        assert_eq!(0, counts[0]);
+        assert!(counts[1] > counts[2]);
+    }
+
+    #[test]
+    fn power_of_two_choices() {
+        let stats = stats(&[1, 1]);
+        let _guards: Vec<_> = (0..3).map(|_| stats[0].start()).collect();
+        let candidates: Vec<_> = stats.iter().collect();
+        let strategy = PowerOfTwoChoices::new();
+        for _ in 0..10 {
+            assert_eq!(1, strategy.pick(&candidates));
+        }
+    }
+
+    #[test]
+    fn peak_ewma() {
+        let stats = stats(&[1, 1]);
+        stats[0].record_rtt(Duration::from_millis(100));
+        stats[1].record_rtt(Duration::from_millis(5));
+        let candidates: Vec<_> = stats.iter().collect();
+        let strategy = PeakEwma::default();
+        for _ in 0..10 {
+            assert_eq!(1, strategy.pick(&candidates));
+        }
+
+        // peaks are adopted immediately
+        stats[1].record_rtt(Duration::from_millis(500));
+        for _ in 0..10 {
+            assert_eq!(0, strategy.pick(&candidates));
+        }
+    }
+}
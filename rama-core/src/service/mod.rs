@@ -8,3 +8,7 @@ pub use svc::{BoxService, RejectError, RejectService, Service};
 
 pub mod handler;
 pub use handler::service_fn;
+
+pub mod balance;
+
+pub mod reload;
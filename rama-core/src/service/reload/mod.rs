@@ -0,0 +1,297 @@
+//! Service which can be swapped for a new one at runtime.
+//!
+//! Use [`reloadable`] to create a pair of:
+//!
+//! - [`Reloadable`]: the [`Service`] to be used in your stack instead of the inner service;
+//! - [`ReloadHandle`]: to be used to swap the inner service as many times as you wish.
+//!
+//! Each request is served by the inner service that was current at the time
+//! the request came in, such that in-flight work completes on the old service,
+//! while new requests (or connections) go to the new one. An old service is dropped
+//! as soon as the last request it was serving completes.
+//!
+//! Reloads can be triggered by any [`ReloadTrigger`], e.g. a [`FileTrigger`]
+//! to reload when a config file changes or (on unix) a [`SignalTrigger`] to reload on `SIGHUP`,
+//! using [`ReloadHandle::reload_on`]. The reload task is spawned on a (graceful) [`Executor`],
+//! such that it stops once shutdown is triggered.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Service, service::service_fn};
+//! use rama_core::service::reload::reloadable;
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (service, handle) = reloadable(service_fn(async || Ok::<_, Infallible>("v1")).boxed());
+//! assert_eq!("v1", service.serve(Context::default(), ()).await.unwrap());
+//!
+//! handle.set(service_fn(async || Ok::<_, Infallible>("v2")).boxed());
+//! assert_eq!("v2", service.serve(Context::default(), ()).await.unwrap());
+//! # }
+//! ```
+
+use crate::{
+    Context, Service,
+    error::BoxError,
+    rt::Executor,
+    telemetry::tracing::{self, Instrument},
+};
+use arc_swap::ArcSwap;
+use std::{fmt, sync::Arc};
+
+mod trigger;
+#[cfg(unix)]
+#[doc(inline)]
+pub use trigger::SignalTrigger;
+#[doc(inline)]
+pub use trigger::{FileTrigger, ReloadTrigger};
+
+/// Create a new [`Reloadable`] service and its linked [`ReloadHandle`],
+/// starting out with the given service.
+///
+/// See [the module docs](self) for more information.
+pub fn reloadable<S>(service: S) -> (Reloadable<S>, ReloadHandle<S>) {
+    let current = Arc::new(ArcSwap::from_pointee(service));
+    (
+        Reloadable {
+            current: current.clone(),
+        },
+        ReloadHandle { current },
+    )
+}
+
+/// A [`Service`] which serves requests using the current inner service,
+/// which can be swapped using the linked [`ReloadHandle`].
+///
+/// See [`reloadable`] for more information.
+pub struct Reloadable<S> {
+    current: Arc<ArcSwap<S>>,
+}
+
+impl<S> Reloadable<S> {
+    /// Get the current inner service.
+    pub fn current(&self) -> Arc<S> {
+        self.current.load_full()
+    }
+}
+
+impl<S> Clone for Reloadable<S> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Reloadable<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reloadable")
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for Reloadable<S>
+where
+    S: Service<State, Request>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        // keep the service alive for the duration of the request,
+        // even if it is swapped out in the meantime
+        let service = self.current.load_full();
+        service.serve(ctx, req).await
+    }
+}
+
+/// Handle used to swap the inner service of the linked [`Reloadable`] services.
+///
+/// See [`reloadable`] for more information.
+pub struct ReloadHandle<S> {
+    current: Arc<ArcSwap<S>>,
+}
+
+impl<S> ReloadHandle<S> {
+    /// Set the service to be used for future requests
+    /// made to the linked [`Reloadable`] services.
+    pub fn set(&self, service: S) {
+        self.current.store(Arc::new(service));
+    }
+
+    /// Set the service to be used for future requests
+    /// made to the linked [`Reloadable`] services,
+    /// returning the previous one.
+    ///
+    /// The returned service is shared with requests still in flight,
+    /// and can be used to wait for them to complete (e.g. using [`Arc::strong_count`]).
+    pub fn swap(&self, service: S) -> Arc<S> {
+        self.current.swap(Arc::new(service))
+    }
+
+    /// Get the current inner service.
+    pub fn current(&self) -> Arc<S> {
+        self.current.load_full()
+    }
+}
+
+impl<S: Send + Sync + 'static> ReloadHandle<S> {
+    /// Spawn a task on the given executor which reloads the service
+    /// using the given function each time the given [`ReloadTrigger`] triggers.
+    ///
+    /// Errors returned by the function are logged, keeping the current service in place.
+    /// The task ends once the trigger signals no more triggers will follow,
+    /// or the executor's shutdown guard (if any) is cancelled.
+    pub fn reload_on<T, F, Fut, E>(
+        self,
+        executor: &Executor,
+        mut trigger: T,
+        make_service: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        T: ReloadTrigger,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<S, E>> + Send + 'static,
+        E: Into<BoxError> + Send + 'static,
+    {
+        let guard = executor.guard().cloned();
+
+        let task = async move {
+            loop {
+                let triggered = match &guard {
+                    Some(guard) => tokio::select! {
+                        triggered = trigger.triggered() => triggered,
+                        _ = guard.cancelled() => {
+                            tracing::trace!("reload: shutdown requested");
+                            return;
+                        }
+                    },
+                    None => trigger.triggered().await,
+                };
+                if !triggered {
+                    tracing::trace!("reload: no more triggers");
+                    return;
+                }
+
+                match make_service().await {
+                    Ok(service) => {
+                        tracing::debug!("reload: service reloaded");
+                        self.set(service);
+                    }
+                    Err(err) => {
+                        let err = err.into();
+                        tracing::error!("reload: failed to create new service: {err}");
+                    }
+                }
+            }
+        };
+
+        executor.spawn_task(task.instrument(tracing::trace_span!("reload")))
+    }
+}
+
+impl<S> Clone for ReloadHandle<S> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for ReloadHandle<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadHandle")
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graceful::Shutdown;
+    use crate::service::service_fn;
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn in_flight_request_completes_on_old_service() {
+        let (service, handle) = reloadable(
+            service_fn(async || {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, Infallible>("old")
+            })
+            .boxed(),
+        );
+
+        let (in_flight, old) = tokio::join!(service.serve(Context::default(), ()), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let old = handle.swap(service_fn(async || Ok::<_, Infallible>("new")).boxed());
+            assert_eq!(2, Arc::strong_count(&old));
+            assert_eq!("new", service.serve(Context::default(), ()).await.unwrap());
+            old
+        });
+
+        assert_eq!("old", in_flight.unwrap());
+        assert_eq!(1, Arc::strong_count(&old));
+    }
+
+    #[tokio::test]
+    async fn reload_on_trigger() {
+        let (service, handle) = reloadable(0);
+        let (tx, rx) = tokio::sync::mpsc::channel::<()>(1);
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let task = handle.reload_on(&Executor::default(), rx, {
+            let counter = counter.clone();
+            move || {
+                let n = counter.fetch_add(1, Ordering::AcqRel) + 1;
+                async move {
+                    if n == 2 {
+                        Err(BoxError::from("failed reload"))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            }
+        });
+
+        tx.send(()).await.unwrap();
+        tx.send(()).await.unwrap();
+        drop(tx);
+        task.await.unwrap();
+
+        // the second reload failed, keeping the first one in place
+        assert_eq!(2, counter.load(Ordering::Acquire));
+        assert_eq!(1, *service.current());
+    }
+
+    #[tokio::test]
+    async fn reload_task_stops_on_shutdown() {
+        let shutdown = Shutdown::new(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        });
+        let (_service, handle) = reloadable(());
+        let (_tx, rx) = tokio::sync::mpsc::channel::<()>(1);
+
+        handle.reload_on(&Executor::graceful(shutdown.guard()), rx, || async {
+            Ok::<_, Infallible>(())
+        });
+
+        tokio::time::timeout(Duration::from_secs(1), shutdown.shutdown())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// A [`ReloadTrigger`] signals a [`ReloadHandle`] that its service has to be reloaded.
+///
+/// It is implemented for [`tokio::sync::mpsc::Receiver`] and
+/// [`tokio::sync::mpsc::UnboundedReceiver`] for manual triggering,
+/// while [`FileTrigger`] and (on unix) [`SignalTrigger`] can be used
+/// to reload on file changes or process signals (e.g. `SIGHUP`).
+///
+/// [`ReloadHandle`]: super::ReloadHandle
+pub trait ReloadTrigger: Send + 'static {
+    /// Wait for the next trigger.
+    ///
+    /// Returns `false` if no more triggers will follow.
+    fn triggered(&mut self) -> impl Future<Output = bool> + Send + '_;
+}
+
+impl<T: Send + 'static> ReloadTrigger for tokio::sync::mpsc::Receiver<T> {
+    async fn triggered(&mut self) -> bool {
+        self.recv().await.is_some()
+    }
+}
+
+impl<T: Send + 'static> ReloadTrigger for tokio::sync::mpsc::UnboundedReceiver<T> {
+    async fn triggered(&mut self) -> bool {
+        self.recv().await.is_some()
+    }
+}
+
+/// A [`ReloadTrigger`] which triggers each time the file at the given path is modified.
+///
+/// The file is polled for modifications at the configured interval (1 second by default).
+/// The initial state of the file does not trigger a reload,
+/// nor does the file being (temporarily) unavailable.
+pub struct FileTrigger {
+    path: PathBuf,
+    interval: Duration,
+    modified: Option<SystemTime>,
+    initialized: bool,
+}
+
+impl FileTrigger {
+    /// Create a new [`FileTrigger`] for the file at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            interval: Duration::from_secs(1),
+            modified: None,
+            initialized: false,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the interval at which the file is polled for modifications.
+        pub fn interval(mut self, interval: Duration) -> Self {
+            self.interval = interval;
+            self
+        }
+    }
+
+    async fn modified(&self) -> Option<SystemTime> {
+        tokio::fs::metadata(&self.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+impl fmt::Debug for FileTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileTrigger")
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl ReloadTrigger for FileTrigger {
+    async fn triggered(&mut self) -> bool {
+        if !self.initialized {
+            self.modified = self.modified().await;
+            self.initialized = true;
+        }
+        loop {
+            tokio::time::sleep(self.interval).await;
+            let modified = self.modified().await;
+            if modified.is_some() && modified != self.modified {
+                self.modified = modified;
+                return true;
+            }
+        }
+    }
+}
+
+/// A [`ReloadTrigger`] which triggers each time the process receives a unix signal,
+/// typically `SIGHUP`.
+#[cfg(unix)]
+pub struct SignalTrigger(tokio::signal::unix::Signal);
+
+#[cfg(unix)]
+impl SignalTrigger {
+    /// Create a new [`SignalTrigger`] for the given signal kind.
+    ///
+    /// Fails if the signal handler could not be registered.
+    pub fn new(kind: tokio::signal::unix::SignalKind) -> std::io::Result<Self> {
+        Ok(Self(tokio::signal::unix::signal(kind)?))
+    }
+
+    /// Create a new [`SignalTrigger`] for `SIGHUP`.
+    pub fn sighup() -> std::io::Result<Self> {
+        Self::new(tokio::signal::unix::SignalKind::hangup())
+    }
+}
+
+#[cfg(unix)]
+impl fmt::Debug for SignalTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalTrigger").finish()
+    }
+}
+
+#[cfg(unix)]
+impl ReloadTrigger for SignalTrigger {
+    async fn triggered(&mut self) -> bool {
+        self.0.recv().await.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn file_trigger() {
+        let dir = std::env::temp_dir().join(format!("rama-file-trigger-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.txt");
+        std::fs::write(&path, "a").unwrap();
+
+        let mut trigger = FileTrigger::new(&path).with_interval(Duration::from_millis(10));
+        let triggered = tokio::spawn(async move { trigger.triggered().await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), triggered)
+                .await
+                .unwrap()
+                .unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use crate::DnsResolver;
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    service::balance::{Discover, Endpoint},
+};
+use rama_net::address::Domain;
+use std::{fmt, net::SocketAddr, time::Duration};
+
+/// A [`Discover`] implementation which resolves a [`Domain`]
+/// into the endpoints to balance over, using a [`DnsResolver`].
+///
+/// The domain is re-resolved at the configured interval (30 seconds by default),
+/// yielding a new set of endpoints each time the resolved addresses change.
+/// Endpoints are keyed by their [`SocketAddr`], and their service is created
+/// using the given function.
+pub struct DnsDiscovery<R, F> {
+    resolver: R,
+    domain: Domain,
+    port: u16,
+    make_service: F,
+    interval: Duration,
+    addresses: Option<Vec<SocketAddr>>,
+}
+
+impl<R, F> DnsDiscovery<R, F> {
+    /// Create a new [`DnsDiscovery`] for the given domain and port,
+    /// using the given function to create a service per resolved address.
+    pub fn new(resolver: R, domain: Domain, port: u16, make_service: F) -> Self {
+        Self {
+            resolver,
+            domain,
+            port,
+            make_service,
+            interval: Duration::from_secs(30),
+            addresses: None,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the interval at which the domain is re-resolved.
+        pub fn interval(mut self, interval: Duration) -> Self {
+            self.interval = interval;
+            self
+        }
+    }
+}
+
+impl<R: fmt::Debug, F> fmt::Debug for DnsDiscovery<R, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsDiscovery")
+            .field("resolver", &self.resolver)
+            .field("domain", &self.domain)
+            .field("port", &self.port)
+            .field("interval", &self.interval)
+            .field("addresses", &self.addresses)
+            .finish()
+    }
+}
+
+impl<R, F, S> Discover for DnsDiscovery<R, F>
+where
+    R: DnsResolver,
+    F: Fn(SocketAddr) -> S + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+{
+    type Key = SocketAddr;
+    type Service = S;
+    type Error = OpaqueError;
+
+    async fn next_endpoints(
+        &mut self,
+    ) -> Option<Result<Vec<Endpoint<SocketAddr, S>>, Self::Error>> {
+        loop {
+            if self.addresses.is_some() {
+                tokio::time::sleep(self.interval).await;
+            }
+
+            let (ipv4, ipv6) = tokio::join!(
+                self.resolver.ipv4_lookup(self.domain.clone()),
+                self.resolver.ipv6_lookup(self.domain.clone()),
+            );
+            let mut addresses: Vec<SocketAddr> = match (ipv4, ipv6) {
+                (Err(err), Err(_)) => {
+                    self.addresses.get_or_insert_default();
+                    let err: BoxError = err.into();
+                    return Some(Err(OpaqueError::from_boxed(err)));
+                }
+                (ipv4, ipv6) => ipv4
+                    .into_iter()
+                    .flatten()
+                    .map(Into::into)
+                    .chain(ipv6.into_iter().flatten().map(Into::into))
+                    .map(|ip| SocketAddr::new(ip, self.port))
+                    .collect(),
+            };
+            addresses.sort_unstable();
+            addresses.dedup();
+
+            if addresses.is_empty() {
+                // keep the previous endpoints rather than wiping them all out
+                self.addresses.get_or_insert_default();
+                return Some(Err(OpaqueError::from_display(format!(
+                    "no addresses resolved for domain {}",
+                    self.domain
+                ))));
+            }
+
+            if self.addresses.as_ref() == Some(&addresses) {
+                continue;
+            }
+
+            let endpoints = addresses
+                .iter()
+                .map(|addr| Endpoint::new(*addr, (self.make_service)(*addr)))
+                .collect();
+            self.addresses = Some(addresses);
+            return Some(Ok(endpoints));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryDns;
+    use std::{
+        convert::Infallible,
+        net::{Ipv4Addr, Ipv6Addr},
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    #[tokio::test]
+    async fn dns_discovery() {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.com"),
+            vec![
+                Ipv6Addr::LOCALHOST.into(),
+                Ipv4Addr::new(127, 0, 0, 2).into(),
+            ],
+        );
+
+        let mut discovery = DnsDiscovery::new(
+            dns,
+            Domain::from_static("example.com"),
+            80,
+            |addr: SocketAddr| addr.to_string(),
+        );
+        let endpoints = discovery.next_endpoints().await.unwrap().unwrap();
+        let keys: Vec<_> = endpoints.iter().map(|e| e.key().to_string()).collect();
+        assert_eq!(vec!["127.0.0.2:80", "[::1]:80"], keys);
+        assert_eq!("127.0.0.2:80", endpoints[0].service());
+    }
+
+    #[tokio::test]
+    async fn dns_discovery_error() {
+        let mut discovery = DnsDiscovery::new(
+            InMemoryDns::new(),
+            Domain::from_static("example.com"),
+            80,
+            |_| (),
+        );
+        assert!(discovery.next_endpoints().await.unwrap().is_err());
+    }
+
+    #[derive(Debug)]
+    struct EmptyAfterFirstLookup(AtomicBool);
+
+    impl DnsResolver for EmptyAfterFirstLookup {
+        type Error = Infallible;
+
+        async fn ipv4_lookup(&self, _domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            Ok(if self.0.swap(true, Ordering::SeqCst) {
+                vec![]
+            } else {
+                vec![Ipv4Addr::new(127, 0, 0, 2)]
+            })
+        }
+
+        async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn dns_discovery_keeps_endpoints_on_empty_resolution() {
+        let mut discovery = DnsDiscovery::new(
+            EmptyAfterFirstLookup(AtomicBool::new(false)),
+            Domain::from_static("example.com"),
+            80,
+            |_| (),
+        )
+        .with_interval(Duration::from_millis(10));
+        assert_eq!(1, discovery.next_endpoints().await.unwrap().unwrap().len());
+        assert!(discovery.next_endpoints().await.unwrap().is_err());
+        assert_eq!(
+            Some(&vec![SocketAddr::from((Ipv4Addr::new(127, 0, 0, 2), 80))]),
+            discovery.addresses.as_ref()
+        );
+    }
+}
@@ -156,12 +156,55 @@ impl DnsResolver for InMemoryDns {
     }
 }
 
+impl DnsResolver for rama_net::test_utils::network::VirtualNetwork {
+    type Error = DomainNotMappedErr;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        let ips = self.resolve_ipv4(&domain);
+        (!ips.is_empty()).then_some(ips).ok_or(DomainNotMappedErr)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        let ips = self.resolve_ipv6(&domain);
+        (!ips.is_empty()).then_some(ips).ok_or(DomainNotMappedErr)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::{Ipv4Addr, Ipv6Addr};
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_virtual_network_dns() {
+        let network = rama_net::test_utils::network::VirtualNetwork::new();
+        network.insert_host(
+            Domain::from_static("example.com"),
+            Ipv4Addr::new(10, 0, 0, 1),
+        );
+
+        assert_eq!(
+            vec![Ipv4Addr::new(10, 0, 0, 1)],
+            network
+                .ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .unwrap()
+        );
+        assert!(
+            network
+                .ipv6_lookup(Domain::from_static("example.com"))
+                .await
+                .is_err()
+        );
+        assert!(
+            network
+                .ipv4_lookup(Domain::from_static("unknown.example"))
+                .await
+                .is_err()
+        );
+    }
+
     #[tokio::test]
     async fn test_dns_overwrite_deserialize() {
         let dns_overwrite: DnsOverwrite =
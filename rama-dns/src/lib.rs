@@ -132,6 +132,10 @@ pub use deny_all::{DenyAllDns, DnsDeniedError};
 
 pub mod chain;
 
+mod discovery;
+#[doc(inline)]
+pub use discovery::DnsDiscovery;
+
 mod variant;
 
 mod boxed;
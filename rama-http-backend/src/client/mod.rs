@@ -175,7 +175,7 @@ mod easy_connector {
     use rama_net::client::{
         EstablishedClientConnection,
         pool::{
-            FiFoReuseLruDropPool, PooledConnector,
+            FiFoReuseLruDropPool, MultiplexedPool, PooledConnector,
             http::{BasicHttpConId, BasicHttpConnIdentifier, HttpPooledConnectorConfig},
         },
     };
@@ -530,6 +530,11 @@ mod easy_connector {
         PoolStage,
     >;
 
+    type MultiplexedConnectionPoolBuilder<T, C> = EasyHttpWebClientBuilder<
+        PooledConnector<T, MultiplexedPool<C, BasicHttpConId>, BasicHttpConnIdentifier>,
+        PoolStage,
+    >;
+
     impl<T> EasyHttpWebClientBuilder<T, HttpStage> {
         /// Use the default connection pool for this [`super::EasyHttpWebClient`]
         ///
@@ -554,6 +559,23 @@ mod easy_connector {
             })
         }
 
+        /// Same as [`EasyHttpWebClientBuilder::with_connection_pool()`] but using a [`MultiplexedPool`]
+        ///
+        /// Negotiated HTTP/2 connections are shared between concurrent requests,
+        /// up to the max concurrent streams allowed by the server. New connections
+        /// are only created once all connections are saturated or going away.
+        pub fn with_multiplexed_connection_pool<C>(
+            self,
+            config: HttpPooledConnectorConfig,
+        ) -> Result<MultiplexedConnectionPoolBuilder<T, C>, OpaqueError> {
+            let connector = config.build_multiplexed_connector(self.connector)?;
+
+            Ok(EasyHttpWebClientBuilder {
+                connector,
+                _phantom: PhantomData,
+            })
+        }
+
         /// Configure this client to use the provided [`Pool`] and [`ReqToConnId`]
         ///
         /// Use `wait_for_pool_timeout` to limit how long we wait for the pool to give us a connection
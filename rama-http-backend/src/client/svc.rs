@@ -11,7 +11,7 @@ use rama_http_types::{
     dep::{http::uri::PathAndQuery, http_body},
     header::{CONNECTION, HOST, KEEP_ALIVE, PROXY_CONNECTION, TRANSFER_ENCODING, UPGRADE},
 };
-use rama_net::{address::ProxyAddress, http::RequestContext};
+use rama_net::{address::ProxyAddress, client::pool::MultiplexedConnection, http::RequestContext};
 use std::fmt;
 use tokio::sync::Mutex;
 
@@ -36,6 +36,24 @@ pub struct HttpClientService<Body, I = ()> {
     pub(super) http_req_inspector: I,
 }
 
+impl<Body, I> MultiplexedConnection for HttpClientService<Body, I> {
+    fn max_concurrent_streams(&self) -> Option<usize> {
+        match &self.sender {
+            SendRequest::Http1(_) => None,
+            SendRequest::Http2(sender) => Some(sender.current_max_send_streams()),
+        }
+    }
+
+    fn is_accepting_streams(&self) -> bool {
+        match &self.sender {
+            SendRequest::Http1(sender) => {
+                sender.try_lock().map_or(true, |sender| !sender.is_closed())
+            }
+            SendRequest::Http2(sender) => !sender.is_going_away(),
+        }
+    }
+}
+
 impl<State, BodyIn, BodyOut, I> Service<State, Request<BodyIn>> for HttpClientService<BodyOut, I>
 where
     State: Clone + Send + Sync + 'static,
@@ -97,6 +97,177 @@ mod tests {
         Ok(Response::new(Body::from("a random response body")))
     }
 
+    #[tokio::test]
+    async fn test_virtual_network_client_proxy_server_chain() {
+        use crate::client::{EasyHttpWebClient, HttpClientService};
+        use rama_http_types::BodyExtractExt;
+        use rama_net::{
+            address::{Domain, ProxyAddress},
+            client::EstablishedClientConnection,
+            http::RequestContext,
+            stream::SocketInfo,
+            test_utils::network::{VirtualConnector, VirtualNetwork},
+        };
+        use std::net::{IpAddr, Ipv4Addr};
+
+        type Client = EasyHttpWebClient<
+            (),
+            Body,
+            EstablishedClientConnection<HttpClientService<Body>, (), Request>,
+        >;
+
+        fn client(network: &VirtualNetwork, local_ip: Ipv4Addr) -> Client {
+            EasyHttpWebClient::builder()
+                .with_custom_transport_connector(
+                    VirtualConnector::new(network.clone()).with_local_ip(IpAddr::V4(local_ip)),
+                )
+                .without_tls_proxy_support()
+                .with_proxy_support()
+                .without_tls_support()
+                .build()
+        }
+
+        let network = VirtualNetwork::new();
+        network
+            .insert_host(
+                Domain::from_static("example.com"),
+                Ipv4Addr::new(10, 0, 0, 1),
+            )
+            .insert_host(
+                Domain::from_static("proxy.internal"),
+                Ipv4Addr::new(10, 0, 0, 2),
+            );
+
+        let server = network.bind(([10, 0, 0, 1], 80)).unwrap();
+        tokio::spawn(
+            server.serve(HttpServer::auto(Executor::default()).service(service_fn(
+                async |ctx: Context<()>, req: Request| {
+                    let peer_ip = ctx.get::<SocketInfo>().unwrap().peer_addr().ip();
+                    let client_ip = req.headers()["x-client-ip"].to_str().unwrap().to_owned();
+                    Ok::<_, Infallible>(Response::new(Body::from(format!(
+                        "hello {client_ip} via {peer_ip}"
+                    ))))
+                },
+            ))),
+        );
+
+        let proxy = network.bind(([10, 0, 0, 2], 8080)).unwrap();
+        let proxy_client = client(&network, Ipv4Addr::new(10, 0, 0, 2));
+        tokio::spawn(
+            proxy.serve(HttpServer::auto(Executor::default()).service(service_fn(
+                move |ctx: Context<()>, req: Request| {
+                    let proxy_client = proxy_client.clone();
+                    async move {
+                        let peer_ip = ctx.get::<SocketInfo>().unwrap().peer_addr().ip();
+                        let authority = RequestContext::try_from((&ctx, &req)).unwrap().authority;
+                        let (mut parts, body) = req.into_parts();
+                        parts.uri = format!("http://{authority}{}", parts.uri.path())
+                            .parse()
+                            .unwrap();
+                        parts
+                            .headers
+                            .insert("x-client-ip", peer_ip.to_string().parse().unwrap());
+                        Ok::<_, Infallible>(
+                            proxy_client
+                                .serve(Context::default(), Request::from_parts(parts, body))
+                                .await
+                                .unwrap(),
+                        )
+                    }
+                },
+            ))),
+        );
+
+        let mut ctx = Context::default();
+        ctx.insert(ProxyAddress::try_from("http://proxy.internal:8080").unwrap());
+        let resp = client(&network, Ipv4Addr::new(192, 168, 1, 2))
+            .serve(
+                ctx,
+                Request::builder()
+                    .uri("http://example.com/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            "hello 192.168.1.2 via 10.0.0.2",
+            resp.try_into_string().await.unwrap()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_multiplexed_pool_shares_h2_connection() {
+        use crate::client::EasyHttpWebClient;
+        use rama_net::{
+            stream::SocketInfo,
+            test_utils::network::{VirtualConnector, VirtualNetwork},
+        };
+        use std::{
+            collections::HashSet,
+            net::SocketAddr,
+            sync::{Arc, Mutex},
+        };
+
+        let network = VirtualNetwork::new();
+        let server = network.bind(([127, 0, 0, 1], 80)).unwrap();
+
+        let peers: Arc<Mutex<HashSet<SocketAddr>>> = Default::default();
+        let server_peers = peers.clone();
+        tokio::spawn(
+            server.serve(HttpServer::auto(Executor::default()).service(service_fn(
+                move |ctx: Context<()>, req: Request| {
+                    let peers = server_peers.clone();
+                    async move {
+                        peers
+                            .lock()
+                            .unwrap()
+                            .insert(*ctx.get::<SocketInfo>().unwrap().peer_addr());
+                        sleep(Duration::from_millis(50)).await;
+                        Ok::<_, Infallible>(Response::new(Body::from(format!(
+                            "{:?}",
+                            req.version()
+                        ))))
+                    }
+                },
+            ))),
+        );
+
+        let client = EasyHttpWebClient::builder()
+            .with_custom_transport_connector(VirtualConnector::new(network))
+            .without_tls_proxy_support()
+            .without_proxy_support()
+            .without_tls_support()
+            .with_multiplexed_connection_pool(Default::default())
+            .unwrap()
+            .build();
+
+        let requests: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    client
+                        .serve(
+                            Context::default(),
+                            Request::builder()
+                                .uri("http://127.0.0.1/")
+                                .version(Version::HTTP_2)
+                                .body(Body::empty())
+                                .unwrap(),
+                        )
+                        .await
+                        .unwrap()
+                        .status()
+                })
+            })
+            .collect();
+        for request in requests {
+            assert!(request.await.unwrap().is_success());
+        }
+
+        assert_eq!(1, peers.lock().unwrap().len());
+    }
+
     fn create_test_request(version: Version) -> Request {
         Request::builder()
             .uri("https://www.example.com")
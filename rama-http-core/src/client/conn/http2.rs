@@ -17,17 +17,20 @@ use tokio::io::{AsyncRead, AsyncWrite};
 
 use super::super::dispatch::{self, TrySendError};
 use crate::body::{Body, Incoming as IncomingBody};
+use crate::h2::client::ConnectionObserver;
 use crate::proto;
 
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::UnboundedSender<Request<B>, Response<IncomingBody>>,
+    observer: ConnectionObserver,
 }
 
 impl<B> Clone for SendRequest<B> {
     fn clone(&self) -> SendRequest<B> {
         SendRequest {
             dispatch: self.dispatch.clone(),
+            observer: self.observer.clone(),
         }
     }
 }
@@ -112,6 +115,19 @@ impl<B> SendRequest<B> {
     pub fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
+
+    /// Returns the max amount of concurrent streams that can be opened
+    /// on this connection, as limited by the `SETTINGS_MAX_CONCURRENT_STREAMS`
+    /// most recently received from the peer.
+    pub fn current_max_send_streams(&self) -> usize {
+        self.observer.current_max_send_streams()
+    }
+
+    /// Checks if the connection no longer accepts new streams,
+    /// e.g. because the peer sent a GOAWAY frame or the connection was closed.
+    pub fn is_going_away(&self) -> bool {
+        self.is_closed() || self.observer.is_going_away()
+    }
 }
 
 impl<B> SendRequest<B>
@@ -534,6 +550,7 @@ impl Builder {
             Ok((
                 SendRequest {
                     dispatch: tx.unbound(),
+                    observer: h2.connection_observer(),
                 },
                 Connection {
                     inner: (PhantomData, h2),
@@ -571,6 +571,39 @@ where
     pub fn current_max_recv_streams(&self) -> usize {
         self.inner.current_max_recv_streams()
     }
+
+    /// Returns a [`ConnectionObserver`] which can be used to inspect
+    /// the state of the connection without keeping it alive.
+    pub fn observer(&self) -> ConnectionObserver {
+        ConnectionObserver {
+            inner: self.inner.observer(),
+        }
+    }
+}
+
+/// Observes the state of an HTTP/2 client connection.
+///
+/// Unlike a [`SendRequest`] it does not keep the connection alive,
+/// and as such can be held on to by a connection pool for example.
+#[derive(Debug, Clone)]
+pub struct ConnectionObserver {
+    inner: proto::StreamsObserver,
+}
+
+impl ConnectionObserver {
+    /// Returns the current max send streams,
+    /// as limited by the `SETTINGS_MAX_CONCURRENT_STREAMS` of the peer.
+    ///
+    /// Returns `0` once the connection is gone.
+    pub fn current_max_send_streams(&self) -> usize {
+        self.inner.current_max_send_streams()
+    }
+
+    /// Returns `true` once the connection no longer accepts new streams,
+    /// because a GOAWAY frame was received, the connection failed or is gone.
+    pub fn is_going_away(&self) -> bool {
+        self.inner.is_going_away()
+    }
 }
 
 impl<B> fmt::Debug for SendRequest<B>
@@ -17,7 +17,7 @@ use rama_http_types::{HeaderMap, Request, Response};
 use std::task::{Context, Poll, Waker};
 use tokio::io::AsyncWrite;
 
-use std::sync::{Arc, Mutex, TryLockError};
+use std::sync::{Arc, Mutex, TryLockError, Weak};
 use std::{fmt, io};
 
 #[derive(Debug)]
@@ -53,6 +53,13 @@ pub(crate) struct DynStreams<'a, B> {
     peer: peer::Dyn,
 }
 
+/// Observes the connection level stream state,
+/// without keeping the connection alive.
+#[derive(Debug, Clone)]
+pub(crate) struct StreamsObserver {
+    inner: Weak<Mutex<Inner>>,
+}
+
 /// Reference to the stream state
 #[derive(Debug)]
 pub(crate) struct StreamRef<B> {
@@ -401,6 +408,30 @@ where
         let me = self.inner.lock().unwrap();
         me.counts.max_recv_streams()
     }
+
+    pub(crate) fn observer(&self) -> StreamsObserver {
+        StreamsObserver {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+}
+
+impl StreamsObserver {
+    pub(crate) fn current_max_send_streams(&self) -> usize {
+        self.inner
+            .upgrade()
+            .and_then(|inner| inner.lock().ok().map(|me| me.counts.max_send_streams()))
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` once a GOAWAY frame was received or the connection
+    /// failed otherwise, meaning no new streams can be opened.
+    pub(crate) fn is_going_away(&self) -> bool {
+        self.inner
+            .upgrade()
+            .and_then(|inner| inner.lock().ok().map(|me| me.actions.conn_error.is_some()))
+            .unwrap_or(true)
+    }
 }
 
 impl<B> DynStreams<'_, B> {
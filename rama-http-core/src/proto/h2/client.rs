@@ -28,7 +28,7 @@ use crate::client::dispatch::{Callback, SendWhen, TrySendError};
 use crate::ext::Protocol;
 use crate::h2::SendStream;
 use crate::h2::client::ResponseFuture;
-use crate::h2::client::{Builder, Connection, SendRequest};
+use crate::h2::client::{Builder, Connection, ConnectionObserver, SendRequest};
 use crate::headers;
 use crate::proto::Dispatched;
 use crate::proto::h2::UpgradedSendStream;
@@ -506,6 +506,10 @@ where
     pub(crate) fn is_extended_connect_protocol_enabled(&self) -> bool {
         self.h2_tx.is_extended_connect_protocol_enabled()
     }
+
+    pub(crate) fn connection_observer(&self) -> ConnectionObserver {
+        self.h2_tx.observer()
+    }
 }
 
 pin_project! {
@@ -244,7 +244,9 @@ impl Settings {
         });
     }
 
-    fn for_each<F: FnMut(Setting)>(&self, mut f: F) {
+    /// Call `f` for each [`Setting`] that has a value,
+    /// in the order in which they were received or are to be sent.
+    pub fn for_each<F: FnMut(Setting)>(&self, mut f: F) {
         let mut settings_order = self.config.setting_order.clone().unwrap_or_default();
         settings_order.extend_with_default();
 
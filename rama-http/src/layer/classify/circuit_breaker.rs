@@ -0,0 +1,75 @@
+//! [`FailureClassifier`] support for the http response classifiers,
+//! such that they can be used with the [`CircuitBreaker`] middleware.
+//!
+//! [`CircuitBreaker`]: rama_core::layer::circuit_breaker::CircuitBreaker
+
+use super::{
+    ClassifiedResponse, ClassifyResponse, GrpcErrorsAsFailures, ServerErrorsAsFailures,
+    StatusInRangeAsFailures,
+};
+use crate::Response;
+use rama_core::layer::circuit_breaker::FailureClassifier;
+
+/// Errors are always failures, responses are failures only if they can be
+/// classified as such without waiting for the end of the stream.
+fn is_failure<C, B, E>(classifier: &C, result: &Result<Response<B>, E>) -> bool
+where
+    C: ClassifyResponse + Clone,
+{
+    match result {
+        Ok(res) => matches!(
+            classifier.clone().classify_response(res),
+            ClassifiedResponse::Ready(Err(_))
+        ),
+        Err(_) => true,
+    }
+}
+
+macro_rules! impl_failure_classifier {
+    ($($classifier:ty),+ $(,)?) => {
+        $(
+            impl<B, E> FailureClassifier<Response<B>, E> for $classifier {
+                fn is_failure(&self, result: &Result<Response<B>, E>) -> bool {
+                    is_failure(self, result)
+                }
+            }
+        )+
+    };
+}
+
+impl_failure_classifier!(
+    ServerErrorsAsFailures,
+    StatusInRangeAsFailures,
+    GrpcErrorsAsFailures,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, StatusCode};
+    use rama_core::error::BoxError;
+
+    fn response(status: StatusCode) -> Result<Response, BoxError> {
+        Ok(Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .unwrap())
+    }
+
+    #[test]
+    fn server_errors_as_failures() {
+        let classifier = ServerErrorsAsFailures::new();
+        assert!(!classifier.is_failure(&response(StatusCode::OK)));
+        assert!(!classifier.is_failure(&response(StatusCode::NOT_FOUND)));
+        assert!(classifier.is_failure(&response(StatusCode::BAD_GATEWAY)));
+        assert!(classifier.is_failure(&Err::<Response, BoxError>("oops".into())));
+    }
+
+    #[test]
+    fn status_in_range_as_failures() {
+        let classifier = StatusInRangeAsFailures::new(400..=499);
+        assert!(!classifier.is_failure(&response(StatusCode::OK)));
+        assert!(classifier.is_failure(&response(StatusCode::NOT_FOUND)));
+        assert!(!classifier.is_failure(&response(StatusCode::BAD_GATEWAY)));
+    }
+}
@@ -3,6 +3,7 @@
 use crate::{HeaderMap, Request, Response, StatusCode};
 use std::{convert::Infallible, fmt, marker::PhantomData};
 
+mod circuit_breaker;
 pub(crate) mod grpc_errors_as_failures;
 mod map_failure_class;
 mod status_in_range_is_error;
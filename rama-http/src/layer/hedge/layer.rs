@@ -0,0 +1,88 @@
+use super::{Hedge, HedgeDelay, LatencyTracker};
+use crate::layer::retry::managed::Undefined;
+use rama_core::Layer;
+use std::fmt;
+
+/// A [`Layer`] that produces [`Hedge`] services.
+///
+/// All services produced by the same layer (or its clones)
+/// share the same observed latencies.
+///
+/// See [the module docs](super) for more information.
+pub struct HedgeLayer<C = Undefined> {
+    delay: HedgeDelay,
+    clone: C,
+    latencies: LatencyTracker,
+}
+
+impl<C: fmt::Debug> fmt::Debug for HedgeLayer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HedgeLayer")
+            .field("delay", &self.delay)
+            .field("clone", &self.clone)
+            .field("latencies", &self.latencies)
+            .finish()
+    }
+}
+
+impl<C: Clone> Clone for HedgeLayer<C> {
+    fn clone(&self) -> Self {
+        Self {
+            delay: self.delay.clone(),
+            clone: self.clone.clone(),
+            latencies: self.latencies.clone(),
+        }
+    }
+}
+
+impl HedgeLayer {
+    /// Creates a new [`HedgeLayer`] which hedges requests
+    /// according to the given [`HedgeDelay`].
+    pub fn new(delay: HedgeDelay) -> Self {
+        Self {
+            latencies: LatencyTracker::new(&delay),
+            delay,
+            clone: Undefined,
+        }
+    }
+}
+
+impl HedgeLayer<Undefined> {
+    /// Use a custom cloning function to determine if and how a request should be hedged.
+    ///
+    /// See [`CloneInput`] for more details.
+    ///
+    /// [`CloneInput`]: crate::layer::retry::managed::CloneInput
+    pub fn with_clone<C>(self, clone: C) -> HedgeLayer<C> {
+        HedgeLayer {
+            delay: self.delay,
+            clone,
+            latencies: self.latencies,
+        }
+    }
+}
+
+impl<C, S> Layer<S> for HedgeLayer<C>
+where
+    C: Clone,
+{
+    type Service = Hedge<S, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Hedge {
+            inner,
+            delay: self.delay.clone(),
+            clone: self.clone.clone(),
+            latencies: self.latencies.clone(),
+        }
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        Hedge {
+            inner,
+            delay: self.delay,
+            clone: self.clone,
+            latencies: self.latencies,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Middleware that hedges requests to reduce tail latency.
+//!
+//! When a request takes longer than a configured [`HedgeDelay`],
+//! a second (hedged) attempt of the same request is sent,
+//! and the response of whichever attempt finishes first is returned.
+//! The other attempt is cancelled by dropping it.
+//!
+//! The delay can either be fixed, or derived from a percentile
+//! of the latencies observed by the middleware, e.g. to hedge
+//! only the requests which are slower than 95% of recent requests.
+//!
+//! Requests are cloned using the same machinery as the [`Retry`] middleware,
+//! meaning the request body is buffered in a [`RetryBody`] and the
+//! cloning can be customised using a [`CloneInput`] implementation.
+//! By default only requests with an idempotent method are hedged.
+//!
+//! [`Retry`]: crate::layer::retry::Retry
+//! [`CloneInput`]: crate::layer::retry::managed::CloneInput
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer, Service, service::service_fn};
+//! use rama_http::layer::hedge::{HedgeDelay, HedgeLayer};
+//! use rama_http::layer::retry::RetryBody;
+//! use rama_http::{Body, Request, Response};
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = HedgeLayer::new(
+//!     // hedge requests slower than the p95 of recently observed latencies
+//!     HedgeDelay::percentile(95.0),
+//! ).into_layer(service_fn(async |_req: Request<RetryBody>| {
+//!     Ok::<_, Infallible>(Response::new(Body::empty()))
+//! }));
+//!
+//! let resp = client.serve(Context::default(), Request::new(Body::empty())).await.unwrap();
+//! # let _ = resp;
+//! # }
+//! ```
+
+use crate::dep::http_body::Body as HttpBody;
+use crate::dep::http_body_util::BodyExt;
+use crate::layer::retry::RetryBody;
+use crate::layer::retry::managed::CloneInput;
+use crate::{Method, Request};
+use parking_lot::Mutex;
+use rama_core::error::{BoxError, ErrorExt, OpaqueError};
+use rama_core::telemetry::tracing;
+use rama_core::{Context, Service};
+use rama_utils::latency::LatencyHistogram;
+use rama_utils::macros::{define_inner_service_accessors, generate_set_and_with};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+mod layer;
+#[doc(inline)]
+pub use layer::HedgeLayer;
+
+/// The delay after which a [`Hedge`] sends a second attempt of a request.
+#[derive(Debug, Clone)]
+pub struct HedgeDelay {
+    kind: HedgeDelayKind,
+    min_samples: u64,
+    window: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum HedgeDelayKind {
+    Fixed(Duration),
+    Percentile(f64),
+}
+
+impl HedgeDelay {
+    /// Hedge requests which are still pending after the given delay.
+    pub fn fixed(delay: Duration) -> Self {
+        Self {
+            kind: HedgeDelayKind::Fixed(delay),
+            min_samples: 0,
+            window: Duration::ZERO,
+        }
+    }
+
+    /// Hedge requests which are slower than the given percentile
+    /// (e.g. `95.0` for p95) of recently observed latencies.
+    ///
+    /// No requests are hedged until enough latencies are observed,
+    /// see [`HedgeDelay::with_min_samples`].
+    pub fn percentile(percentile: f64) -> Self {
+        Self {
+            kind: HedgeDelayKind::Percentile(percentile.clamp(0.0, 100.0)),
+            min_samples: 100,
+            window: Duration::from_secs(10),
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the minimum amount of latencies that have to be observed
+        /// before a percentile based delay is used.
+        ///
+        /// Defaults to `100`. Has no effect for a fixed delay.
+        pub fn min_samples(mut self, min_samples: u64) -> Self {
+            self.min_samples = min_samples;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the time window of observed latencies that a percentile based delay is computed for.
+        ///
+        /// Latencies are kept for at most two windows. Defaults to 10 seconds.
+        /// Has no effect for a fixed delay.
+        pub fn window(mut self, window: Duration) -> Self {
+            self.window = window;
+            self
+        }
+    }
+}
+
+/// Latencies observed by a [`Hedge`],
+/// used to compute percentile based delays.
+#[derive(Clone)]
+struct LatencyTracker(Option<Arc<Mutex<RotatingHistogram>>>);
+
+impl fmt::Debug for LatencyTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(histogram) => f
+                .debug_tuple("LatencyTracker")
+                .field(&*histogram.lock())
+                .finish(),
+            None => f.debug_tuple("LatencyTracker").finish(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RotatingHistogram {
+    current: LatencyHistogram,
+    previous: LatencyHistogram,
+    rotated_at: Instant,
+    window: Duration,
+}
+
+impl RotatingHistogram {
+    fn rotate(&mut self) {
+        let elapsed = self.rotated_at.elapsed();
+        if elapsed < self.window {
+            return;
+        }
+        if elapsed >= self.window * 2 {
+            self.previous.clear();
+            self.current.clear();
+        } else {
+            std::mem::swap(&mut self.previous, &mut self.current);
+            self.current.clear();
+        }
+        self.rotated_at = Instant::now();
+    }
+}
+
+impl LatencyTracker {
+    fn new(delay: &HedgeDelay) -> Self {
+        match delay.kind {
+            HedgeDelayKind::Fixed(_) => Self(None),
+            HedgeDelayKind::Percentile(_) => Self(Some(Arc::new(Mutex::new(RotatingHistogram {
+                current: LatencyHistogram::new(),
+                previous: LatencyHistogram::new(),
+                rotated_at: Instant::now(),
+                window: delay.window,
+            })))),
+        }
+    }
+
+    fn record(&self, latency: Duration) {
+        if let Some(histogram) = &self.0 {
+            let mut histogram = histogram.lock();
+            histogram.rotate();
+            histogram.current.record(latency);
+        }
+    }
+
+    fn delay(&self, delay: &HedgeDelay) -> Option<Duration> {
+        match delay.kind {
+            HedgeDelayKind::Fixed(delay) => Some(delay),
+            HedgeDelayKind::Percentile(percentile) => {
+                let mut histogram = self.0.as_ref()?.lock();
+                histogram.rotate();
+                [&histogram.previous, &histogram.current]
+                    .into_iter()
+                    .find(|h| !h.is_empty() && h.count() >= delay.min_samples)
+                    .and_then(|h| h.percentile(percentile))
+            }
+        }
+    }
+}
+
+/// Middleware that hedges requests to reduce tail latency.
+///
+/// Created using the [`HedgeLayer`].
+/// See [the module docs](self) for more information.
+pub struct Hedge<S, C> {
+    inner: S,
+    delay: HedgeDelay,
+    clone: C,
+    latencies: LatencyTracker,
+}
+
+impl<S, C> Hedge<S, C> {
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug, C: fmt::Debug> fmt::Debug for Hedge<S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hedge")
+            .field("inner", &self.inner)
+            .field("delay", &self.delay)
+            .field("clone", &self.clone)
+            .field("latencies", &self.latencies)
+            .finish()
+    }
+}
+
+impl<S: Clone, C: Clone> Clone for Hedge<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            delay: self.delay.clone(),
+            clone: self.clone.clone(),
+            latencies: self.latencies.clone(),
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+impl<S, C, State, Body> Service<State, Request<Body>> for Hedge<S, C>
+where
+    S: Service<State, Request<RetryBody>, Error: Into<BoxError>>,
+    C: CloneInput<State>,
+    State: Clone + Send + Sync + 'static,
+    Body: HttpBody<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        // consume body so we can clone the request if desired
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await.map_err(|err| {
+            OpaqueError::from_boxed(err.into()).context("hedge: collect request body")
+        })?;
+        let request = Request::from_parts(parts, RetryBody::new(body.to_bytes()));
+
+        let hedge = is_idempotent(request.method())
+            .then(|| self.latencies.delay(&self.delay))
+            .flatten()
+            .and_then(|delay| {
+                self.clone
+                    .clone_input(&ctx, &request)
+                    .map(|input| (delay, input))
+            });
+
+        let start = Instant::now();
+        let Some((delay, (hedge_ctx, hedge_request))) = hedge else {
+            let result = self.inner.serve(ctx, request).await;
+            self.latencies.record(start.elapsed());
+            return result.map_err(Into::into);
+        };
+
+        let mut primary = std::pin::pin!(self.inner.serve(ctx, request));
+        tokio::select! {
+            result = &mut primary => {
+                self.latencies.record(start.elapsed());
+                return result.map_err(Into::into);
+            }
+            _ = tokio::time::sleep(delay) => (),
+        }
+
+        tracing::trace!("hedge: request still pending after {delay:?}, sending hedged request");
+        let hedge_start = Instant::now();
+        let result = tokio::select! {
+            result = primary => {
+                self.latencies.record(start.elapsed());
+                result
+            }
+            result = self.inner.serve(hedge_ctx, hedge_request) => {
+                self.latencies.record(hedge_start.elapsed());
+                result
+            }
+        };
+        result.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, BodyExtractExt, Response};
+    use rama_core::{Layer, service::service_fn};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn request(method: Method) -> Request {
+        Request::builder()
+            .method(method)
+            .body(Body::from("hello"))
+            .unwrap()
+    }
+
+    /// Service for which the first call is slow, and all other calls are fast.
+    fn slow_first_service(
+        counter: Arc<AtomicUsize>,
+    ) -> impl Service<(), Request<RetryBody>, Response = Response, Error = Infallible> {
+        service_fn(move |req: Request<RetryBody>| {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+                let body = req.try_into_string().await.unwrap();
+                Ok(Response::new(Body::from(format!("{body}:{attempt}"))))
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_hedge_fixed_delay() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let service = HedgeLayer::new(HedgeDelay::fixed(Duration::from_millis(10)))
+            .into_layer(slow_first_service(counter.clone()));
+
+        let start = Instant::now();
+        let resp = service
+            .serve(Context::default(), request(Method::GET))
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!("hello:1", resp.try_into_string().await.unwrap());
+        assert_eq!(2, counter.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_non_idempotent_method() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let service = HedgeLayer::new(HedgeDelay::fixed(Duration::from_millis(10)))
+            .into_layer(slow_first_service(counter.clone()));
+
+        let resp = service
+            .serve(Context::default(), request(Method::POST))
+            .await
+            .unwrap();
+        assert_eq!("hello:0", resp.try_into_string().await.unwrap());
+        assert_eq!(1, counter.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_custom_clone() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let service = HedgeLayer::new(HedgeDelay::fixed(Duration::from_millis(10)))
+            .with_clone(|_: &Context<()>, _: &Request<RetryBody>| None)
+            .into_layer(slow_first_service(counter.clone()));
+
+        let resp = service
+            .serve(Context::default(), request(Method::GET))
+            .await
+            .unwrap();
+        assert_eq!("hello:0", resp.try_into_string().await.unwrap());
+        assert_eq!(1, counter.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_percentile_delay() {
+        let delay = HedgeDelay::percentile(50.0).with_min_samples(2);
+        let tracker = LatencyTracker::new(&delay);
+
+        assert_eq!(None, tracker.delay(&delay));
+        tracker.record(Duration::from_millis(10));
+        assert_eq!(None, tracker.delay(&delay));
+        tracker.record(Duration::from_millis(10));
+
+        let computed = tracker.delay(&delay).unwrap();
+        assert!(computed >= Duration::from_millis(10));
+        assert!(computed <= Duration::from_millis(12));
+    }
+}
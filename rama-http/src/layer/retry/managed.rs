@@ -7,6 +7,7 @@
 use super::{Policy, PolicyResult, RetryBody};
 use crate::{Request, Response};
 use rama_core::Context;
+use rama_core::layer::timeout::Deadline;
 use rama_core::telemetry::tracing;
 use rama_utils::backoff::Backoff;
 
@@ -26,12 +27,34 @@ pub struct DoNotRetry;
 /// [`DoNotRetry`] can be added to the [`Context`] of a [`Request`]
 /// to signal that the request should not be retried, regardless
 /// of the retry functionality defined.
+///
+/// A [`Deadline`] found in the [`Context`] is respected as well:
+/// no retry is attempted once it expired, including while waiting on the backoff.
 pub struct ManagedPolicy<B = Undefined, C = Undefined, R = Undefined> {
     backoff: B,
     clone: C,
     retry: R,
 }
 
+impl<B: Backoff, C, R> ManagedPolicy<B, C, R> {
+    async fn next_backoff(&self, deadline: Option<Deadline>) -> bool {
+        let Some(deadline) = deadline else {
+            return self.backoff.next_backoff().await;
+        };
+        if deadline.is_expired() {
+            tracing::debug!("deadline expired: do not retry");
+            return false;
+        }
+        deadline
+            .run(self.backoff.next_backoff())
+            .await
+            .unwrap_or_else(|_| {
+                tracing::debug!("deadline expired during retry backoff: do not retry");
+                false
+            })
+    }
+}
+
 impl<B, C, R, State, Response, Error> Policy<State, Response, Error> for ManagedPolicy<B, C, R>
 where
     B: Backoff,
@@ -53,7 +76,7 @@ where
         }
 
         let (ctx, result, retry) = self.retry.retry(ctx, result).await;
-        if retry && self.backoff.next_backoff().await {
+        if retry && self.next_backoff(ctx.get::<Deadline>().copied()).await {
             PolicyResult::Retry { ctx, req }
         } else {
             self.backoff.reset().await;
@@ -421,6 +444,40 @@ mod tests {
         assert_abort(ctx, req, Err(()), &policy).await;
     }
 
+    #[tokio::test]
+    async fn managed_policy_respects_deadline() {
+        let req = Request::builder()
+            .method("GET")
+            .uri("http://example.com")
+            .body(RetryBody::empty())
+            .unwrap();
+
+        let policy = ManagedPolicy::default().with_backoff(
+            ExponentialBackoff::new(
+                Duration::from_secs(5),
+                Duration::from_secs(10),
+                0.,
+                HasherRng::default,
+            )
+            .unwrap(),
+        );
+
+        // an expired deadline prevents any retry
+        let mut ctx = Context::default();
+        ctx.insert(Deadline::new(tokio::time::Instant::now()));
+        assert_abort(ctx, req.clone(), Err(()), &policy).await;
+
+        // a deadline expiring during the backoff aborts it
+        let mut ctx = Context::default();
+        ctx.insert(Deadline::after(Duration::from_millis(20)));
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            assert_abort(ctx, req, Err(()), &policy),
+        )
+        .await
+        .unwrap();
+    }
+
     #[tokio::test]
     async fn test_policy_custom_clone_fn() {
         let req = Request::builder()
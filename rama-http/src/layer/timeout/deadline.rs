@@ -0,0 +1,327 @@
+use crate::{HeaderName, HeaderValue, Request};
+use rama_core::{Context, Layer, Service, layer::timeout::Deadline};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, time::Duration};
+
+/// The `grpc-timeout` header, used by default to propagate a [`Deadline`].
+pub const GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+
+/// Layer that applies the [`PropagateDeadline`] middleware,
+/// which propagates the [`Deadline`] of a request to the upstream using a header.
+#[derive(Debug, Clone)]
+pub struct PropagateDeadlineLayer {
+    header_name: HeaderName,
+}
+
+impl PropagateDeadlineLayer {
+    /// Creates a new [`PropagateDeadlineLayer`] using the `grpc-timeout` header.
+    pub const fn new() -> Self {
+        Self {
+            header_name: GRPC_TIMEOUT,
+        }
+    }
+
+    /// Creates a new [`PropagateDeadlineLayer`] using the given header.
+    pub const fn with_header(header_name: HeaderName) -> Self {
+        Self { header_name }
+    }
+}
+
+impl Default for PropagateDeadlineLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for PropagateDeadlineLayer {
+    type Service = PropagateDeadline<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PropagateDeadline {
+            inner,
+            header_name: self.header_name.clone(),
+        }
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        PropagateDeadline {
+            inner,
+            header_name: self.header_name,
+        }
+    }
+}
+
+/// Middleware which propagates the [`Deadline`] found in the [`Context`]
+/// to the upstream, by setting the remaining time as a header on the outgoing request.
+///
+/// The value is encoded in the `grpc-timeout` format, e.g. `250m` for 250 milliseconds,
+/// which can be decoded again by the [`DeadlineFromHeader`] middleware.
+/// Requests without a [`Deadline`] are passed through untouched.
+#[derive(Clone)]
+pub struct PropagateDeadline<S> {
+    inner: S,
+    header_name: HeaderName,
+}
+
+impl<S> PropagateDeadline<S> {
+    /// Creates a new [`PropagateDeadline`] using the `grpc-timeout` header.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            header_name: GRPC_TIMEOUT,
+        }
+    }
+
+    /// Creates a new [`PropagateDeadline`] using the given header.
+    pub const fn with_header(inner: S, header_name: HeaderName) -> Self {
+        Self { inner, header_name }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for PropagateDeadline<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PropagateDeadline")
+            .field("inner", &self.inner)
+            .field("header_name", &self.header_name)
+            .finish()
+    }
+}
+
+impl<S, State, ReqBody> Service<State, Request<ReqBody>> for PropagateDeadline<S>
+where
+    S: Service<State, Request<ReqBody>>,
+    ReqBody: Send + 'static,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        mut req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        if let Some(deadline) = ctx.get::<Deadline>() {
+            req.headers_mut().insert(
+                self.header_name.clone(),
+                encode_timeout(deadline.remaining()),
+            );
+        }
+        self.inner.serve(ctx, req).await
+    }
+}
+
+/// Layer that applies the [`DeadlineFromHeader`] middleware,
+/// which sets the [`Deadline`] of a request based on a header.
+#[derive(Debug, Clone)]
+pub struct DeadlineFromHeaderLayer {
+    header_name: HeaderName,
+}
+
+impl DeadlineFromHeaderLayer {
+    /// Creates a new [`DeadlineFromHeaderLayer`] using the `grpc-timeout` header.
+    pub const fn new() -> Self {
+        Self {
+            header_name: GRPC_TIMEOUT,
+        }
+    }
+
+    /// Creates a new [`DeadlineFromHeaderLayer`] using the given header.
+    pub const fn with_header(header_name: HeaderName) -> Self {
+        Self { header_name }
+    }
+}
+
+impl Default for DeadlineFromHeaderLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for DeadlineFromHeaderLayer {
+    type Service = DeadlineFromHeader<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeadlineFromHeader {
+            inner,
+            header_name: self.header_name.clone(),
+        }
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        DeadlineFromHeader {
+            inner,
+            header_name: self.header_name,
+        }
+    }
+}
+
+/// Middleware which inserts a [`Deadline`] in the [`Context`]
+/// based on the timeout found in the header of the incoming request,
+/// as propagated by a downstream [`PropagateDeadline`] middleware.
+///
+/// An earlier [`Deadline`] already present in the [`Context`] takes precedence.
+/// Invalid header values are ignored.
+#[derive(Clone)]
+pub struct DeadlineFromHeader<S> {
+    inner: S,
+    header_name: HeaderName,
+}
+
+impl<S> DeadlineFromHeader<S> {
+    /// Creates a new [`DeadlineFromHeader`] using the `grpc-timeout` header.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            header_name: GRPC_TIMEOUT,
+        }
+    }
+
+    /// Creates a new [`DeadlineFromHeader`] using the given header.
+    pub const fn with_header(inner: S, header_name: HeaderName) -> Self {
+        Self { inner, header_name }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for DeadlineFromHeader<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadlineFromHeader")
+            .field("inner", &self.inner)
+            .field("header_name", &self.header_name)
+            .finish()
+    }
+}
+
+impl<S, State, ReqBody> Service<State, Request<ReqBody>> for DeadlineFromHeader<S>
+where
+    S: Service<State, Request<ReqBody>>,
+    ReqBody: Send + 'static,
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        if let Some(timeout) = req
+            .headers()
+            .get(&self.header_name)
+            .and_then(decode_timeout)
+        {
+            Deadline::insert_after(timeout, &mut ctx);
+        }
+        self.inner.serve(ctx, req).await
+    }
+}
+
+const MAX_TIMEOUT_VALUE: u128 = 99_999_999;
+
+/// Encode the timeout in the `grpc-timeout` format,
+/// using the most precise unit for which the value fits in 8 digits.
+fn encode_timeout(timeout: Duration) -> HeaderValue {
+    const UNITS: [(u128, char); 6] = [
+        (1, 'n'),
+        (1_000, 'u'),
+        (1_000_000, 'm'),
+        (1_000_000_000, 'S'),
+        (60 * 1_000_000_000, 'M'),
+        (60 * 60 * 1_000_000_000, 'H'),
+    ];
+
+    let nanos = timeout.as_nanos();
+    let (value, unit) = UNITS
+        .iter()
+        .map(|(nanos_per_unit, unit)| (nanos / nanos_per_unit, *unit))
+        .find(|(value, _)| *value <= MAX_TIMEOUT_VALUE)
+        .unwrap_or((MAX_TIMEOUT_VALUE, 'H'));
+
+    HeaderValue::try_from(format!("{value}{unit}")).expect("valid header value")
+}
+
+/// Decode a timeout in the `grpc-timeout` format.
+fn decode_timeout(value: &HeaderValue) -> Option<Duration> {
+    let value = value.to_str().ok()?;
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len() - 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "n" => Duration::from_nanos(value),
+        "u" => Duration::from_micros(value),
+        "m" => Duration::from_millis(value),
+        "S" => Duration::from_secs(value),
+        "M" => Duration::from_secs(value * 60),
+        "H" => Duration::from_secs(value * 60 * 60),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use rama_core::service::service_fn;
+    use std::{convert::Infallible, sync::Arc};
+
+    #[test]
+    fn encode_decode_timeout() {
+        for (timeout, expected) in [
+            (Duration::from_nanos(500), "500n"),
+            (Duration::from_millis(250), "250000u"),
+            (Duration::from_secs(30), "30000000u"),
+            (Duration::from_secs(300), "300000m"),
+            (Duration::from_secs(3 * 24 * 60 * 60), "259200S"),
+        ] {
+            let value = encode_timeout(timeout);
+            assert_eq!(expected, value);
+            assert_eq!(Some(timeout), decode_timeout(&value));
+        }
+
+        for value in ["", "m", "10", "10x", "-1S", "123456789S"] {
+            assert_eq!(None, decode_timeout(&HeaderValue::from_static(value)));
+        }
+    }
+
+    #[tokio::test]
+    async fn propagate_deadline_roundtrip() {
+        let server = Arc::new(DeadlineFromHeader::new(service_fn(
+            async |ctx: Context<()>, _: Request| {
+                let remaining = ctx.get::<Deadline>().map(Deadline::remaining);
+                Ok::<_, Infallible>(remaining)
+            },
+        )));
+        // the server does not share the client context,
+        // so the deadline can only be found via the header
+        let client = PropagateDeadline::new(service_fn(move |req: Request| {
+            let server = server.clone();
+            async move { server.serve(Context::default(), req).await }
+        }));
+
+        let remaining = client
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(None, remaining);
+
+        let mut ctx = Context::default();
+        ctx.insert(Deadline::after(Duration::from_secs(5)));
+        let remaining = client
+            .serve(ctx, Request::new(Body::empty()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(remaining <= Duration::from_secs(5));
+        assert!(remaining > Duration::from_secs(4));
+    }
+}
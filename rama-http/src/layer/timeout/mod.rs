@@ -13,6 +13,13 @@
 //! response. That means if your service's error type is [`Infallible`] it will still be
 //! [`Infallible`] after applying this middleware.
 //!
+//! # Deadlines
+//!
+//! The [`Timeout`] middleware inserts a [`Deadline`] in the [`Context`],
+//! respecting any earlier [`Deadline`] already present. Use [`PropagateDeadline`]
+//! to forward the remaining time to an upstream via a (`grpc-timeout` by default) header,
+//! and [`DeadlineFromHeader`] to pick it up again on the server side.
+//!
 //! # Example
 //!
 //! ```
@@ -40,11 +47,18 @@
 //! ```
 //!
 //! [`Infallible`]: std::convert::Infallible
+//! [`Deadline`]: rama_core::layer::timeout::Deadline
+//! [`Context`]: rama_core::Context
 
 mod body;
+mod deadline;
 mod service;
 
 pub use body::{TimeoutBody, TimeoutError};
+pub use deadline::{
+    DeadlineFromHeader, DeadlineFromHeaderLayer, GRPC_TIMEOUT, PropagateDeadline,
+    PropagateDeadlineLayer,
+};
 pub use service::{
     RequestBodyTimeout, RequestBodyTimeoutLayer, ResponseBodyTimeout, ResponseBodyTimeoutLayer,
     Timeout, TimeoutLayer,
@@ -1,6 +1,6 @@
 use super::TimeoutBody;
 use crate::{Request, Response, StatusCode};
-use rama_core::{Context, Layer, Service};
+use rama_core::{Context, Layer, Service, layer::timeout::Deadline};
 use rama_utils::macros::define_inner_service_accessors;
 use std::fmt;
 use std::time::Duration;
@@ -80,12 +80,17 @@ where
 
     async fn serve(
         &self,
-        ctx: Context<State>,
+        mut ctx: Context<State>,
         req: Request<ReqBody>,
     ) -> Result<Self::Response, Self::Error> {
+        // an outer deadline which expires earlier takes precedence
+        let Some(deadline) = Deadline::insert_after(self.timeout, &mut ctx) else {
+            return self.inner.serve(ctx, req).await;
+        };
+
         tokio::select! {
             res = self.inner.serve(ctx, req) => res,
-            _ = tokio::time::sleep(self.timeout) => {
+            _ = tokio::time::sleep_until(deadline.instant()) => {
                 let mut res = Response::new(ResBody::default());
                 *res.status_mut() = StatusCode::REQUEST_TIMEOUT;
                 Ok(res)
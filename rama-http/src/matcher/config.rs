@@ -0,0 +1,352 @@
+use super::{HttpMatcher, HttpMatcherKind, MethodMatcher, VersionMatcher, uri::dep::regex::Regex};
+use crate::{
+    Method,
+    header::{HeaderName, HeaderValue},
+};
+use rama_net::{address::Domain, stream::matcher::SocketMatcherConfig};
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, Error as _},
+};
+use std::{borrow::Cow, fmt};
+
+/// Declarative representation of an [`HttpMatcher`],
+/// which can be deserialized from any serde format (e.g. JSON, YAML or TOML).
+///
+/// Each rule is represented as a single-key map, where the key is the kind of rule, e.g. in JSON:
+///
+/// ```json
+/// {
+///     "all": [
+///         { "method": ["GET", "HEAD"] },
+///         { "subdomain": "example.com" },
+///         { "path": "/api/*" },
+///         { "not": { "header": { "name": "x-internal", "value": "1" } } },
+///         { "socket": { "not": "private_ip" } }
+///     ]
+/// }
+/// ```
+///
+/// All values are validated while deserializing, such that errors point
+/// to the offending rule in the input.
+///
+/// Use [`HttpMatcherConfig::into_matcher`] or the [`From`] implementation
+/// to turn it into an [`HttpMatcher`]. [`HttpMatcher`] can also be
+/// deserialized directly using this representation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum HttpMatcherConfig {
+    /// Match on one or more HTTP methods, e.g. `"GET"` or `["GET", "POST"]`.
+    Method(#[serde(deserialize_with = "deserialize_method")] MethodMatcher),
+    /// Match on the exact domain of the request.
+    Domain(Domain),
+    /// Match on the domain of the request or any of its subdomains.
+    Subdomain(Domain),
+    /// Match on the domain of the request or any of the subdomains of the given domains.
+    AnySubdomain(Vec<String>),
+    /// Match on one or more HTTP versions, e.g. `"HTTP/1.1"` or `["HTTP/2", "HTTP/3"]`.
+    Version(#[serde(deserialize_with = "deserialize_version")] VersionMatcher),
+    /// Match on the path of the request, see [`PathMatcher`] for the supported syntax.
+    ///
+    /// [`PathMatcher`]: super::PathMatcher
+    Path(String),
+    /// Match on the URI of the request using a regex pattern.
+    Uri(#[serde(deserialize_with = "deserialize_regex")] Regex),
+    /// Match if the header exists and has the exact given value.
+    Header(HeaderRule),
+    /// Match if the header exists.
+    HeaderExists(#[serde(deserialize_with = "deserialize_header_name")] HeaderName),
+    /// Match if the header exists and contains the given value.
+    HeaderContains(HeaderRule),
+    /// Match on the socket of the peer.
+    Socket(SocketMatcherConfig),
+    /// Match if all of the given rules match.
+    All(Vec<HttpMatcherConfig>),
+    /// Match if any of the given rules match.
+    Any(Vec<HttpMatcherConfig>),
+    /// Match if the given rule does not match.
+    Not(Box<HttpMatcherConfig>),
+}
+
+/// Header name and value used by the header rules of [`HttpMatcherConfig`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderRule {
+    /// Name of the header.
+    #[serde(deserialize_with = "deserialize_header_name")]
+    pub name: HeaderName,
+    /// Value of the header.
+    #[serde(deserialize_with = "deserialize_header_value")]
+    pub value: HeaderValue,
+}
+
+impl HttpMatcherConfig {
+    /// Compile this config into an [`HttpMatcher`].
+    pub fn into_matcher<State, Body>(self) -> HttpMatcher<State, Body> {
+        match self {
+            Self::Method(method) => HttpMatcher::method(method),
+            Self::Domain(domain) => HttpMatcher::domain(domain),
+            Self::Subdomain(domain) => HttpMatcher::subdomain(domain),
+            Self::AnySubdomain(domains) => HttpMatcher::any_subdomain(domains),
+            Self::Version(version) => HttpMatcher::version(version),
+            Self::Path(path) => HttpMatcher::path(path),
+            Self::Uri(re) => HttpMatcher {
+                kind: HttpMatcherKind::Uri(re.into()),
+                negate: false,
+            },
+            Self::Header(HeaderRule { name, value }) => HttpMatcher::header(name, value),
+            Self::HeaderExists(name) => HttpMatcher::header_exists(name),
+            Self::HeaderContains(HeaderRule { name, value }) => {
+                HttpMatcher::header_contains(name, value)
+            }
+            Self::Socket(socket) => HttpMatcher::socket(socket.into_matcher()),
+            Self::All(rules) => HttpMatcher {
+                kind: HttpMatcherKind::All(rules.into_iter().map(Self::into_matcher).collect()),
+                negate: false,
+            },
+            Self::Any(rules) => HttpMatcher {
+                kind: HttpMatcherKind::Any(rules.into_iter().map(Self::into_matcher).collect()),
+                negate: false,
+            },
+            Self::Not(rule) => rule.into_matcher().negate(),
+        }
+    }
+}
+
+impl<State, Body> From<HttpMatcherConfig> for HttpMatcher<State, Body> {
+    fn from(config: HttpMatcherConfig) -> Self {
+        config.into_matcher()
+    }
+}
+
+impl<'de, State, Body> Deserialize<'de> for HttpMatcher<State, Body> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        HttpMatcherConfig::deserialize(deserializer).map(HttpMatcherConfig::into_matcher)
+    }
+}
+
+fn deserialize_method<'de, D>(deserializer: D) -> Result<MethodMatcher, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(OneOrMany {
+        expecting: "an HTTP method or a list of HTTP methods",
+        parse: |s| {
+            let method = Method::from_bytes(s.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("invalid HTTP method {s:?}"))?;
+            MethodMatcher::try_from(&method).map_err(|err| err.to_string())
+        },
+        combine: MethodMatcher::or,
+    })
+}
+
+fn deserialize_version<'de, D>(deserializer: D) -> Result<VersionMatcher, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(OneOrMany {
+        expecting: "an HTTP version or a list of HTTP versions",
+        parse: |s| match s.to_ascii_uppercase().as_str() {
+            "HTTP/0.9" => Ok(VersionMatcher::HTTP_09),
+            "HTTP/1.0" => Ok(VersionMatcher::HTTP_10),
+            "HTTP/1.1" => Ok(VersionMatcher::HTTP_11),
+            "HTTP/2" | "HTTP/2.0" => Ok(VersionMatcher::HTTP_2),
+            "HTTP/3" | "HTTP/3.0" => Ok(VersionMatcher::HTTP_3),
+            _ => Err(format!(
+                "invalid HTTP version {s:?}: expected one of HTTP/0.9, HTTP/1.0, HTTP/1.1, HTTP/2 or HTTP/3"
+            )),
+        },
+        combine: VersionMatcher::or,
+    })
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(ParseStr {
+        expecting: "a regex pattern",
+        parse: |s| {
+            Regex::new(s).map_err(|err| {
+                // regex syntax errors are rendered over multiple lines,
+                // of which the last one describes the actual error
+                let err = err.to_string();
+                let reason = err
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+                    .trim_start_matches("error: ");
+                format!("invalid uri regex {s:?}: {reason}")
+            })
+        },
+    })
+}
+
+fn deserialize_header_name<'de, D>(deserializer: D) -> Result<HeaderName, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(ParseStr {
+        expecting: "a header name",
+        parse: |s| {
+            HeaderName::from_bytes(s.as_bytes()).map_err(|_| format!("invalid header name {s:?}"))
+        },
+    })
+}
+
+fn deserialize_header_value<'de, D>(deserializer: D) -> Result<HeaderValue, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(ParseStr {
+        expecting: "a header value",
+        parse: |s| HeaderValue::from_str(s).map_err(|_| format!("invalid header value {s:?}")),
+    })
+}
+
+/// Visitor which parses a string, such that parse errors
+/// are reported at the position of that string.
+struct ParseStr<T> {
+    expecting: &'static str,
+    parse: fn(&str) -> Result<T, String>,
+}
+
+impl<T> de::Visitor<'_> for ParseStr<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.parse)(v).map_err(E::custom)
+    }
+}
+
+/// Visitor which accepts either a single string or a list of strings,
+/// combining the parsed values into a single (bitset) matcher.
+struct OneOrMany<T> {
+    expecting: &'static str,
+    parse: fn(&str) -> Result<T, String>,
+    combine: fn(T, T) -> T,
+}
+
+impl<'de, T> de::Visitor<'de> for OneOrMany<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.parse)(v).map_err(E::custom)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut value: Option<T> = None;
+        while let Some(s) = seq.next_element::<Cow<'de, str>>()? {
+            let next = (self.parse)(&s).map_err(A::Error::custom)?;
+            value = Some(match value {
+                Some(value) => (self.combine)(value, next),
+                None => next,
+            });
+        }
+        value.ok_or_else(|| A::Error::invalid_length(0, &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, Request};
+    use rama_core::{Context, matcher::Matcher};
+
+    fn matches(matcher: &HttpMatcher<(), Body>, req: Request) -> bool {
+        matcher.matches(None, &Context::default(), &req)
+    }
+
+    #[test]
+    fn deserialize_http_matcher() {
+        let matcher: HttpMatcher<(), Body> = serde_json::from_str(
+            r#"{
+                "all": [
+                    { "method": ["get", "HEAD"] },
+                    { "subdomain": "example.com" },
+                    { "any": [{ "path": "/api/*" }, { "uri": "\\?debug=1$" }] },
+                    { "not": { "header": { "name": "x-internal", "value": "1" } } },
+                    { "version": "HTTP/1.1" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let req = || Request::builder().method("GET");
+        assert!(matches(
+            &matcher,
+            req()
+                .uri("http://www.example.com/api/foo")
+                .body(Body::empty())
+                .unwrap()
+        ));
+        assert!(matches(
+            &matcher,
+            req()
+                .uri("http://example.com/?debug=1")
+                .body(Body::empty())
+                .unwrap()
+        ));
+        assert!(!matches(
+            &matcher,
+            req()
+                .uri("http://example.org/api/foo")
+                .body(Body::empty())
+                .unwrap()
+        ));
+        assert!(!matches(
+            &matcher,
+            req()
+                .uri("http://example.com/api/foo")
+                .header("x-internal", "1")
+                .body(Body::empty())
+                .unwrap()
+        ));
+        assert!(!matches(
+            &matcher,
+            Request::post("http://example.com/api/foo")
+                .body(Body::empty())
+                .unwrap()
+        ));
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        for (input, expected) in [
+            (r#"{"method": "FETCH"}"#, "no `MethodMatcher` for `FETCH`"),
+            (r#"{"method": []}"#, "invalid length 0"),
+            (
+                r#"{"version": "HTTP/4"}"#,
+                "invalid HTTP version \"HTTP/4\"",
+            ),
+            (r#"{"uri": "(foo"}"#, "invalid uri regex \"(foo\""),
+            (
+                r#"{"any": [{"path": "/"}, {"header_exists": "x foo"}]}"#,
+                "invalid header name \"x foo\"",
+            ),
+            (r#"{"header": {"name": "x-foo"}}"#, "missing field `value`"),
+            (
+                r#"{"socket": {"ip": "not-an-ip"}}"#,
+                "invalid ip network \"not-an-ip\"",
+            ),
+            (r#"{"methods": "GET"}"#, "unknown variant `methods`"),
+        ] {
+            let err = serde_json::from_str::<HttpMatcherConfig>(input)
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(expected), "input: {input}; error: {err}");
+            assert!(err.contains("column"), "input: {input}; error: {err}");
+        }
+    }
+}
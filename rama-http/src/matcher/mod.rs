@@ -38,6 +38,10 @@ mod subdomain_trie;
 #[doc(inline)]
 pub use subdomain_trie::SubdomainTrieMatcher;
 
+mod config;
+#[doc(inline)]
+pub use config::{HeaderRule, HttpMatcherConfig};
+
 /// A matcher that is used to match an http [`Request`]
 pub struct HttpMatcher<State, Body> {
     kind: HttpMatcherKind<State, Body>,
@@ -3,7 +3,7 @@ use crate::stream::Socket;
 use parking_lot::Mutex;
 use rama_core::error::{BoxError, ErrorContext, OpaqueError};
 use rama_core::telemetry::tracing::trace;
-use rama_core::{Context, Layer, Service};
+use rama_core::{Context, Layer, Service, layer::timeout::Deadline};
 use rama_utils::macros::generate_set_and_with;
 use std::collections::VecDeque;
 use std::fmt::Debug;
@@ -14,7 +14,7 @@ use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use std::{future::Future, net::SocketAddr};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
 use tokio::time::timeout;
 
 /// [`PoolStorage`] implements the storage part of a connection pool. This storage
@@ -118,6 +118,10 @@ impl<C, ID> LeasedConnection<C, ID> {
         self.failed
             .store(true, std::sync::atomic::Ordering::Relaxed);
     }
+
+    fn is_marked_as_failed(&self) -> bool {
+        self.failed.load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 /// A connection which is stored in a pool.
@@ -488,6 +492,380 @@ impl<C, ID: Debug> Debug for FiFoReuseLruDropPool<C, ID> {
     }
 }
 
+/// A connection which is able to serve multiple requests concurrently,
+/// such as a negotiated HTTP/2 connection which multiplexes requests as streams.
+///
+/// Used by the [`MultiplexedPool`] to share a single pooled connection
+/// between concurrent leases.
+pub trait MultiplexedConnection {
+    /// The max amount of requests this connection can serve concurrently,
+    /// e.g. the `SETTINGS_MAX_CONCURRENT_STREAMS` of the peer for HTTP/2.
+    ///
+    /// Returns `None` in case the connection can only be used
+    /// by a single lease at a time (e.g. HTTP/1.1).
+    fn max_concurrent_streams(&self) -> Option<usize>;
+
+    /// Returns `false` once the connection no longer accepts new requests,
+    /// e.g. because the peer sent a GOAWAY frame or the connection was closed.
+    fn is_accepting_streams(&self) -> bool;
+}
+
+/// Connection pool which shares [`MultiplexedConnection`]s between concurrent requests.
+///
+/// Connections are leased from the wrapped [`FiFoReuseLruDropPool`] as usual,
+/// but multiplexed connections are handed out to concurrent requests
+/// until their [`MultiplexedConnection::max_concurrent_streams`] is reached.
+/// Once saturated, or no longer accepting new streams, a different or new
+/// connection is used instead. A shared connection is returned to the wrapped pool
+/// once its last lease is dropped, and as such still counts as a single
+/// active connection for the limits of the wrapped pool.
+///
+/// Connections which are not multiplexed are leased exclusively,
+/// exactly like [`FiFoReuseLruDropPool`] would.
+pub struct MultiplexedPool<C, ID> {
+    inner: FiFoReuseLruDropPool<C, ID>,
+    state: Arc<Mutex<MultiplexedState<C, ID>>>,
+}
+
+struct MultiplexedState<C, ID> {
+    shared: Vec<Weak<LeasedConnection<C, ID>>>,
+    /// Connections which are being created, awaited by other requests for the same ID,
+    /// as they can most likely share the connection once created.
+    pending: Vec<(ID, watch::Receiver<()>)>,
+    /// IDs for which the last leased connection was not multiplexed,
+    /// in which case requests do not wait on each other to connect.
+    exclusive: VecDeque<ID>,
+}
+
+/// Max amount of IDs remembered as not being multiplexed.
+const MULTIPLEXED_POOL_MAX_EXCLUSIVE_IDS: usize = 64;
+
+impl<C, ID> Default for MultiplexedState<C, ID> {
+    fn default() -> Self {
+        Self {
+            shared: Vec::new(),
+            pending: Vec::new(),
+            exclusive: VecDeque::new(),
+        }
+    }
+}
+
+impl<C, ID> MultiplexedPool<C, ID> {
+    /// Create a new [`MultiplexedPool`] which wraps the given [`FiFoReuseLruDropPool`].
+    pub fn new(pool: FiFoReuseLruDropPool<C, ID>) -> Self {
+        Self {
+            inner: pool,
+            state: Default::default(),
+        }
+    }
+}
+
+impl<C, ID> From<FiFoReuseLruDropPool<C, ID>> for MultiplexedPool<C, ID> {
+    fn from(pool: FiFoReuseLruDropPool<C, ID>) -> Self {
+        Self::new(pool)
+    }
+}
+
+impl<C, ID> Clone for MultiplexedPool<C, ID> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<C, ID: Debug> Debug for MultiplexedPool<C, ID> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("MultiplexedPool")
+            .field("inner", &self.inner)
+            .field("shared", &state.shared.len())
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+impl<C, ID> MultiplexedState<C, ID>
+where
+    C: MultiplexedConnection,
+    ID: PartialEq + Debug,
+{
+    fn try_share(&mut self, id: &ID) -> Option<MultiplexedLease<C, ID>> {
+        let mut found = None;
+        self.shared.retain(|weak| {
+            let Some(conn) = weak.upgrade() else {
+                return false;
+            };
+            if conn.is_marked_as_failed() {
+                trace!("multiplexed connection pool: connection marked as failed, removing it");
+                return false;
+            }
+            if !conn.is_accepting_streams() {
+                trace!(
+                    "multiplexed connection pool: connection no longer accepts streams, removing it"
+                );
+                conn.mark_as_failed();
+                return false;
+            }
+            if found.is_none() && &conn.pooled_conn.as_ref().expect("only None after drop").id == id
+            {
+                // one strong reference is the upgraded one we hold ourselves
+                let leases = Arc::strong_count(&conn) - 1;
+                if conn
+                    .max_concurrent_streams()
+                    .is_some_and(|max_streams| leases < max_streams)
+                {
+                    found = Some(conn);
+                }
+            }
+            true
+        });
+
+        found.map(|conn| {
+            trace!("multiplexed connection pool: sharing connection for given id {id:?}");
+            MultiplexedLease::Shared(conn)
+        })
+    }
+}
+
+impl<C, ID> MultiplexedPool<C, ID>
+where
+    C: MultiplexedConnection,
+    ID: Clone + PartialEq + Debug,
+{
+    /// Share an existing connection for the given ID, waiting for a connection
+    /// which is being created for it, if any. In case there is no connection to share
+    /// the returned [`PendingConnect`] (if any) is to be held while creating one.
+    async fn share_or_reserve(
+        &self,
+        id: &ID,
+    ) -> Result<MultiplexedLease<C, ID>, Option<PendingConnect<C, ID>>> {
+        loop {
+            let mut pending = {
+                let mut state = self.state.lock();
+                if let Some(conn) = state.try_share(id) {
+                    return Ok(conn);
+                }
+                match state
+                    .pending
+                    .iter()
+                    .find(|(pending_id, _)| pending_id == id)
+                {
+                    Some((_, pending)) => pending.clone(),
+                    None if state.exclusive.contains(id) => return Err(None),
+                    None => {
+                        let (done, pending) = watch::channel(());
+                        state.pending.push((id.clone(), pending));
+                        return Err(Some(PendingConnect {
+                            state: self.state.clone(),
+                            id: id.clone(),
+                            _done: done,
+                        }));
+                    }
+                }
+            };
+            trace!(
+                "multiplexed connection pool: waiting for pending connection (w/ id {id:?}) to be created"
+            );
+            // resolves with an error once the pending connect is dropped
+            let _ = pending.changed().await;
+        }
+    }
+
+    fn lease(&self, conn: LeasedConnection<C, ID>) -> MultiplexedLease<C, ID> {
+        let id = &conn.pooled_conn.as_ref().expect("only None after drop").id;
+        let mut state = self.state.lock();
+        if conn.max_concurrent_streams().is_some() {
+            state.exclusive.retain(|exclusive_id| exclusive_id != id);
+            let conn = Arc::new(conn);
+            state.shared.push(Arc::downgrade(&conn));
+            MultiplexedLease::Shared(conn)
+        } else {
+            if !state.exclusive.contains(id) {
+                if state.exclusive.len() >= MULTIPLEXED_POOL_MAX_EXCLUSIVE_IDS {
+                    state.exclusive.pop_front();
+                }
+                state.exclusive.push_back(id.clone());
+            }
+            MultiplexedLease::Exclusive(conn)
+        }
+    }
+}
+
+/// Connection which is being created by a [`MultiplexedPool`],
+/// on which other requests for the same ID wait until it is dropped.
+struct PendingConnect<C, ID: PartialEq> {
+    state: Arc<Mutex<MultiplexedState<C, ID>>>,
+    id: ID,
+    _done: watch::Sender<()>,
+}
+
+impl<C, ID: PartialEq> Drop for PendingConnect<C, ID> {
+    fn drop(&mut self) {
+        // waiters are woken up once the sender is dropped, right after this
+        self.state.lock().pending.retain(|(id, _)| id != &self.id);
+    }
+}
+
+/// [`Pool::CreatePermit`] of a [`MultiplexedPool`].
+pub struct MultiplexedCreatePermit<C, ID: PartialEq> {
+    permit: (ActiveSlot, PoolSlot),
+    pending: Option<PendingConnect<C, ID>>,
+}
+
+impl<C, ID: PartialEq + Debug> Debug for MultiplexedCreatePermit<C, ID> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiplexedCreatePermit")
+            .field("permit", &self.permit)
+            .field("pending", &self.pending.as_ref().map(|pending| &pending.id))
+            .finish()
+    }
+}
+
+impl<C, ID> Pool<C, ID> for MultiplexedPool<C, ID>
+where
+    C: MultiplexedConnection + Send + Sync + 'static,
+    ID: Clone + Send + Sync + PartialEq + Debug + 'static,
+{
+    type Connection = MultiplexedLease<C, ID>;
+    type CreatePermit = MultiplexedCreatePermit<C, ID>;
+
+    async fn get_conn(
+        &self,
+        id: &ID,
+    ) -> Result<ConnectionResult<Self::Connection, Self::CreatePermit>, OpaqueError> {
+        let pending = match self.share_or_reserve(id).await {
+            Ok(conn) => return Ok(ConnectionResult::Connection(conn)),
+            Err(pending) => pending,
+        };
+
+        loop {
+            match self.inner.get_conn(id).await? {
+                ConnectionResult::Connection(conn) => {
+                    if conn.is_accepting_streams() {
+                        return Ok(ConnectionResult::Connection(self.lease(conn)));
+                    }
+                    trace!(
+                        "multiplexed connection pool: dropping idle connection (w/ id {id:?}) which no longer accepts streams"
+                    );
+                    conn.mark_as_failed();
+                }
+                ConnectionResult::CreatePermit(permit) => {
+                    return Ok(ConnectionResult::CreatePermit(MultiplexedCreatePermit {
+                        permit,
+                        pending,
+                    }));
+                }
+            }
+        }
+    }
+
+    async fn create(&self, id: ID, conn: C, permit: Self::CreatePermit) -> Self::Connection {
+        let MultiplexedCreatePermit { permit, pending } = permit;
+        let conn = self.inner.create(id, conn, permit).await;
+        let conn = self.lease(conn);
+        // only now that the connection can be shared, waiters are released
+        drop(pending);
+        conn
+    }
+}
+
+/// [`MultiplexedLease`] is a connection that is temporarily leased from a [`MultiplexedPool`].
+///
+/// A multiplexed connection can be leased by multiple requests at once,
+/// while all other connections are leased exclusively as a [`LeasedConnection`].
+pub enum MultiplexedLease<C, ID> {
+    /// Connection which is leased exclusively by a single user.
+    Exclusive(LeasedConnection<C, ID>),
+    /// Multiplexed connection which is possibly shared with other leases.
+    Shared(Arc<LeasedConnection<C, ID>>),
+}
+
+impl<C, ID> MultiplexedLease<C, ID> {
+    /// Returns `true` if this is a lease of a multiplexed connection.
+    pub fn is_shared(&self) -> bool {
+        matches!(self, Self::Shared(_))
+    }
+}
+
+impl<C: Debug, ID: Debug> Debug for MultiplexedLease<C, ID> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exclusive(conn) => f.debug_tuple("Exclusive").field(conn).finish(),
+            Self::Shared(conn) => f.debug_tuple("Shared").field(conn).finish(),
+        }
+    }
+}
+
+impl<C, ID> Deref for MultiplexedLease<C, ID> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Exclusive(conn) => conn,
+            Self::Shared(conn) => conn,
+        }
+    }
+}
+
+impl<C, ID> AsRef<C> for MultiplexedLease<C, ID> {
+    fn as_ref(&self) -> &C {
+        self
+    }
+}
+
+impl<C, ID> Socket for MultiplexedLease<C, ID>
+where
+    ID: Send + Sync + 'static,
+    C: Socket,
+{
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.as_ref().local_addr()
+    }
+
+    fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.as_ref().peer_addr()
+    }
+}
+
+impl<State, Request, C, ID> Service<State, Request> for MultiplexedLease<C, ID>
+where
+    ID: Send + Sync + Debug + 'static,
+    C: Service<State, Request> + MultiplexedConnection + Sync,
+    Request: Send + 'static,
+    State: Send + Sync + 'static,
+{
+    type Response = C::Response;
+    type Error = C::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        match self {
+            Self::Exclusive(conn) => conn.serve(ctx, req).await,
+            Self::Shared(conn) => {
+                // served by the connection directly, as a failed request does not imply
+                // a broken connection for multiplexed connections, unlike what
+                // the service implementation of the leased connection assumes
+                let inner: &C = conn;
+                let result = inner.serve(ctx, req).await;
+                // only stop reusing it once it no longer accepts new streams,
+                // e.g. because of a GOAWAY or because the connection was closed
+                if result.is_err() && !conn.is_accepting_streams() {
+                    trace!(
+                        "multiplexed connection pool: detected error result for connection which no longer accepts streams, marking it as failed"
+                    );
+                    conn.mark_as_failed();
+                }
+                result
+            }
+        }
+    }
+}
+
 /// [`ReqToConnID`] is used to convert a `Request` to a connection ID. These IDs are
 /// not unique and multiple connections can have the same ID. IDs are used to filter
 /// which connections can be used for a specific Request in a way that is indepent of
@@ -532,6 +910,11 @@ impl<S, P, R> PooledConnector<S, P, R> {
         ///
         /// If no timeout is specified there will be no limit, this could be dangerous
         /// depending on how many users are waiting for a connection
+        ///
+        /// A [`Deadline`] found in the request [`Context`] further limits the time
+        /// we are willing to wait, regardless of whether or not a timeout is specified.
+        ///
+        /// [`Deadline`]: rama_core::layer::timeout::Deadline
         pub fn wait_for_pool_timeout(mut self, timeout: Option<Duration>) -> Self {
             self.wait_for_pool_timeout = timeout;
             self
@@ -571,7 +954,14 @@ where
                 }
             };
 
-            let pool_result = if let Some(duration) = self.wait_for_pool_timeout {
+            // the deadline of the request (if any) caps the time we are willing to wait
+            let wait_for_pool_timeout = match (self.wait_for_pool_timeout, ctx.get::<Deadline>()) {
+                (Some(duration), Some(deadline)) => Some(deadline.cap(duration)),
+                (None, Some(deadline)) => Some(deadline.remaining()),
+                (duration, None) => duration,
+            };
+
+            let pool_result = if let Some(duration) = wait_for_pool_timeout {
                 timeout(duration, pool.get_conn(&conn_id))
                     .await
                     .map_err(|err|{
@@ -651,7 +1041,7 @@ impl<S, P: Clone, R: Clone> Layer<S> for PooledConnectorLayer<P, R> {
 
 #[cfg(feature = "http")]
 pub mod http {
-    use super::{FiFoReuseLruDropPool, PooledConnector, ReqToConnID};
+    use super::{FiFoReuseLruDropPool, MultiplexedPool, PooledConnector, ReqToConnID};
     use crate::{Protocol, address::Authority, client::pool::OpaqueError, http::RequestContext};
     use rama_core::Context;
     use rama_http_types::Request;
@@ -726,6 +1116,24 @@ pub mod http {
             Ok(PooledConnector::new(inner, pool, BasicHttpConnIdentifier)
                 .maybe_with_wait_for_pool_timeout(self.wait_for_pool_timeout))
         }
+
+        /// Same as [`Self::build_connector`] but using a [`MultiplexedPool`],
+        /// such that negotiated HTTP/2 connections are shared between concurrent requests.
+        pub fn build_multiplexed_connector<C, S>(
+            self,
+            inner: S,
+        ) -> Result<
+            PooledConnector<S, MultiplexedPool<C, BasicHttpConId>, BasicHttpConnIdentifier>,
+            OpaqueError,
+        > {
+            let pool = FiFoReuseLruDropPool::new(self.max_active, self.max_total)?
+                .maybe_with_idle_timeout(self.idle_timeout);
+
+            Ok(
+                PooledConnector::new(inner, MultiplexedPool::new(pool), BasicHttpConnIdentifier)
+                    .maybe_with_wait_for_pool_timeout(self.wait_for_pool_timeout),
+            )
+        }
     }
 }
 
@@ -886,6 +1294,26 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_pool_wait_respects_deadline() {
+        let pool = FiFoReuseLruDropPool::new(1, 1).unwrap();
+        let svc = PooledConnector::new(TestService::default(), pool, StringRequestLengthID {});
+
+        let _conn1 = svc
+            .connect(Context::default(), String::from("a"))
+            .await
+            .unwrap();
+
+        // no wait timeout is configured, but the deadline still limits the wait
+        let mut ctx = Context::default();
+        ctx.insert(Deadline::after(Duration::from_millis(50)));
+        let conn2 =
+            tokio::time::timeout(Duration::from_secs(1), svc.connect(ctx, String::from("a")))
+                .await
+                .unwrap();
+        assert_err!(conn2);
+    }
+
     #[derive(Default)]
     struct TestConnector {
         pub created_connection: AtomicI16,
@@ -980,6 +1408,230 @@ mod tests {
         assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
     }
 
+    #[derive(Default)]
+    struct MultiplexConnector {
+        max_streams: Option<usize>,
+        connect_delay: Duration,
+        created_connection: AtomicI16,
+    }
+
+    impl<State, Request> Service<State, Request> for MultiplexConnector
+    where
+        State: Clone + Send + Sync + 'static,
+        Request: Send + 'static,
+    {
+        type Response = EstablishedClientConnection<MultiplexConn, State, Request>;
+        type Error = Infallible;
+
+        async fn serve(
+            &self,
+            ctx: Context<State>,
+            req: Request,
+        ) -> Result<Self::Response, Self::Error> {
+            tokio::time::sleep(self.connect_delay).await;
+            let conn = MultiplexConn {
+                id: self.created_connection.fetch_add(1, Ordering::Relaxed),
+                max_streams: self.max_streams,
+                going_away: AtomicBool::new(false),
+            };
+            Ok(EstablishedClientConnection { ctx, req, conn })
+        }
+    }
+
+    #[derive(Debug)]
+    struct MultiplexConn {
+        id: i16,
+        max_streams: Option<usize>,
+        going_away: AtomicBool,
+    }
+
+    impl MultiplexedConnection for MultiplexConn {
+        fn max_concurrent_streams(&self) -> Option<usize> {
+            self.max_streams
+        }
+
+        fn is_accepting_streams(&self) -> bool {
+            !self.going_away.load(Ordering::Relaxed)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_pool_shares_connections_up_to_max_streams() {
+        let pool = MultiplexedPool::new(FiFoReuseLruDropPool::new(5, 10).unwrap());
+        let svc = PooledConnector::new(
+            MultiplexConnector {
+                max_streams: Some(2),
+                ..Default::default()
+            },
+            pool,
+            StringRequestLengthID {},
+        );
+
+        let conn1 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        let conn2 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert!(conn1.conn.is_shared());
+        assert_eq!(conn1.conn.id, conn2.conn.id);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 1);
+
+        // saturated, so a new connection is created
+        let conn3 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert_ne!(conn1.conn.id, conn3.conn.id);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+
+        // different id never shares
+        let conn4 = svc
+            .connect(Context::default(), String::from("a"))
+            .await
+            .unwrap();
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 3);
+
+        drop((conn1, conn2, conn3, conn4));
+
+        // released connections are returned to the pool and reused
+        let conn = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert!(conn.conn.is_shared());
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_multiplexed_pool_concurrent_requests_share_pending_connection() {
+        let pool = MultiplexedPool::new(FiFoReuseLruDropPool::new(5, 10).unwrap());
+        let svc = Arc::new(PooledConnector::new(
+            MultiplexConnector {
+                max_streams: Some(100),
+                connect_delay: Duration::from_millis(50),
+                ..Default::default()
+            },
+            pool,
+            StringRequestLengthID {},
+        ));
+
+        let connects: Vec<_> = (0..10)
+            .map(|_| {
+                let svc = svc.clone();
+                tokio::spawn(async move {
+                    svc.connect(Context::default(), String::new())
+                        .await
+                        .unwrap()
+                        .conn
+                })
+            })
+            .collect();
+        let mut conns = Vec::new();
+        for connect in connects {
+            conns.push(connect.await.unwrap());
+        }
+
+        assert!(conns.iter().all(|conn| conn.is_shared() && conn.id == 0));
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_pool_stops_sharing_failed_connections() {
+        let pool = MultiplexedPool::new(FiFoReuseLruDropPool::new(5, 10).unwrap());
+        let svc = PooledConnector::new(
+            MultiplexConnector {
+                max_streams: Some(100),
+                ..Default::default()
+            },
+            pool,
+            StringRequestLengthID {},
+        );
+
+        let conn1 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        match &conn1.conn {
+            MultiplexedLease::Shared(conn) => conn.mark_as_failed(),
+            MultiplexedLease::Exclusive(_) => panic!("expected shared connection"),
+        }
+
+        let conn2 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert_ne!(conn1.conn.id, conn2.conn.id);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_pool_stops_sharing_going_away_connections() {
+        let pool = MultiplexedPool::new(FiFoReuseLruDropPool::new(5, 10).unwrap());
+        let svc = PooledConnector::new(
+            MultiplexConnector {
+                max_streams: Some(100),
+                ..Default::default()
+            },
+            pool,
+            StringRequestLengthID {},
+        );
+
+        let conn1 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        conn1.conn.going_away.store(true, Ordering::Relaxed);
+
+        let conn2 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert_ne!(conn1.conn.id, conn2.conn.id);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+
+        // going away connection is not returned to the pool
+        drop(conn1);
+        drop(conn2);
+        let conn3 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert_eq!(conn3.conn.id, 1);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_pool_leases_other_connections_exclusively() {
+        let pool = MultiplexedPool::new(FiFoReuseLruDropPool::new(5, 10).unwrap());
+        let svc = PooledConnector::new(
+            MultiplexConnector::default(),
+            pool,
+            StringRequestLengthID {},
+        );
+
+        let conn1 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        let conn2 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert!(!conn1.conn.is_shared());
+        assert_ne!(conn1.conn.id, conn2.conn.id);
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+
+        drop(conn1);
+        let _conn3 = svc
+            .connect(Context::default(), String::new())
+            .await
+            .unwrap();
+        assert_eq!(svc.inner.created_connection.load(Ordering::Relaxed), 2);
+    }
+
     #[tokio::test]
     async fn drop_idle_connections() {
         let pool = FiFoReuseLruDropPool::new(5, 10)
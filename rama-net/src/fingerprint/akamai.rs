@@ -0,0 +1,354 @@
+//! Passive HTTP/2 fingerprint as described by Akamai.
+//!
+//! See the original white paper for more information:
+//! <https://www.blackhat.com/docs/eu-17/materials/eu-17-Shuster-Passive-Fingerprinting-Of-HTTP2-Clients-wp.pdf>.
+
+use std::{fmt, io};
+
+use rama_http_types::{
+    Request,
+    proto::h2::{
+        PseudoHeader, PseudoHeaderOrder,
+        frame::{EarlyFrame, EarlyFrameCapture, Priority, Setting, StreamId},
+    },
+};
+
+#[derive(Debug, Clone)]
+/// Data which can be hashed using [`Self::hash`],
+/// and which is displayed in the Akamai HTTP/2 fingerprint format:
+///
+/// ```text
+/// SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER
+/// ```
+///
+/// Computed using [`Http2Fingerprint::compute`].
+pub struct Http2Fingerprint {
+    settings: Vec<Setting>,
+    window_update: Option<u32>,
+    priorities: Vec<Priority>,
+    pseudo_headers: Vec<PseudoHeader>,
+}
+
+impl Http2Fingerprint {
+    /// Compute the [`Http2Fingerprint`] for an incoming h2 request.
+    ///
+    /// This requires the [`EarlyFrameCapture`] and [`PseudoHeaderOrder`]
+    /// to be available in the request extensions, which is the case
+    /// for requests received by the rama h2 server.
+    pub fn compute<B>(req: &Request<B>) -> Result<Self, Http2FingerprintComputeError> {
+        let ext = req.extensions();
+        let early_frames = ext
+            .get::<EarlyFrameCapture>()
+            .ok_or(Http2FingerprintComputeError::MissingEarlyFrames)?;
+        let pseudo_headers = ext
+            .get::<PseudoHeaderOrder>()
+            .ok_or(Http2FingerprintComputeError::MissingPseudoHeaders)?;
+        Self::compute_from_early_frames(early_frames.as_slice(), pseudo_headers)
+    }
+
+    /// Compute the [`Http2Fingerprint`] from the early frames
+    /// and pseudo header order of an h2 connection.
+    ///
+    /// In case your source is a [`Request`] you can use [`Self::compute`] instead.
+    pub fn compute_from_early_frames(
+        early_frames: &[EarlyFrame],
+        pseudo_headers: &PseudoHeaderOrder,
+    ) -> Result<Self, Http2FingerprintComputeError> {
+        let mut settings = None;
+        let mut window_update = None;
+        let mut priorities = Vec::new();
+
+        for frame in early_frames {
+            match frame {
+                EarlyFrame::Settings(frame) => {
+                    if settings.is_none() && !frame.is_ack() {
+                        let mut v = Vec::new();
+                        frame.for_each(|setting| v.push(setting));
+                        settings = Some(v);
+                    }
+                }
+                EarlyFrame::WindowUpdate(frame) => {
+                    if window_update.is_none() && frame.stream_id == StreamId::zero() {
+                        window_update = Some(frame.size_increment);
+                    }
+                }
+                EarlyFrame::Priority(frame) => priorities.push(frame.clone()),
+            }
+        }
+
+        Ok(Self {
+            settings: settings.ok_or(Http2FingerprintComputeError::MissingSettings)?,
+            window_update,
+            priorities,
+            pseudo_headers: pseudo_headers.iter().collect(),
+        })
+    }
+
+    #[inline]
+    /// compute the md5 hash of this [`Http2Fingerprint`] as a String.
+    pub fn hash(&self) -> String {
+        format!("{self:x}")
+    }
+
+    fn hash_to(&self, w: &mut impl fmt::Write, lower: bool) -> fmt::Result {
+        let mut ctx = md5::Context::new();
+        let _ = self.write_to_io(&mut ctx).inspect_err(|err| {
+            if cfg!(debug_assertions) {
+                panic!("md5 ingest failed: {err:?}");
+            }
+        });
+        let digest = ctx.compute();
+        if lower {
+            write!(w, "{digest:x}",)?;
+        } else {
+            write!(w, "{digest:X}",)?;
+        }
+        Ok(())
+    }
+}
+
+fn pseudo_header_str(header: PseudoHeader) -> &'static str {
+    match header {
+        PseudoHeader::Method => "m",
+        PseudoHeader::Authority => "a",
+        PseudoHeader::Scheme => "s",
+        PseudoHeader::Path => "p",
+        // not part of the original format,
+        // so written in full to avoid ambiguity
+        PseudoHeader::Protocol => "protocol",
+        PseudoHeader::Status => "status",
+    }
+}
+
+macro_rules! impl_write_to {
+    ($w:ident, $this:ident) => {{
+        let mut sep = "";
+        for setting in &$this.settings {
+            write!($w, "{sep}{}:{}", u16::from(setting.id), setting.value)?;
+            sep = ";";
+        }
+
+        match $this.window_update {
+            Some(increment) => write!($w, "|{increment}|")?,
+            None => write!($w, "|00|")?,
+        }
+
+        if $this.priorities.is_empty() {
+            write!($w, "0")?;
+        }
+        let mut sep = "";
+        for priority in &$this.priorities {
+            write!(
+                $w,
+                "{sep}{}:{}:{}:{}",
+                u32::from(priority.stream_id),
+                u8::from(priority.dependency.is_exclusive),
+                u32::from(priority.dependency.dependency_id),
+                // weight is stored in the range [0, 255]
+                u16::from(priority.dependency.weight) + 1,
+            )?;
+            sep = ",";
+        }
+
+        write!($w, "|")?;
+        let mut sep = "";
+        for header in &$this.pseudo_headers {
+            write!($w, "{sep}{}", pseudo_header_str(*header))?;
+            sep = ",";
+        }
+
+        Ok(())
+    }};
+}
+
+impl Http2Fingerprint {
+    fn write_to_io(&self, w: &mut impl io::Write) -> io::Result<()> {
+        impl_write_to!(w, self)
+    }
+
+    fn write_to_fmt(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        impl_write_to!(w, self)
+    }
+}
+
+impl fmt::Display for Http2Fingerprint {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to_fmt(f)
+    }
+}
+
+impl fmt::LowerHex for Http2Fingerprint {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.hash_to(f, true)
+    }
+}
+
+impl fmt::UpperHex for Http2Fingerprint {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.hash_to(f, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// error identifying a failure in [`Http2Fingerprint::compute`]
+pub enum Http2FingerprintComputeError {
+    /// missing [`EarlyFrameCapture`]
+    MissingEarlyFrames,
+    /// missing (non-ack) settings frame
+    MissingSettings,
+    /// missing [`PseudoHeaderOrder`]
+    MissingPseudoHeaders,
+}
+
+impl fmt::Display for Http2FingerprintComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Http2FingerprintComputeError::MissingEarlyFrames => {
+                write!(f, "Http2 Fingerprint Compute Error: missing early frames")
+            }
+            Http2FingerprintComputeError::MissingSettings => {
+                write!(f, "Http2 Fingerprint Compute Error: missing settings frame")
+            }
+            Http2FingerprintComputeError::MissingPseudoHeaders => {
+                write!(f, "Http2 Fingerprint Compute Error: missing pseudo headers")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Http2FingerprintComputeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::proto::h2::frame::{
+        EarlyFrameStreamContext, SettingId, SettingOrder, Settings, StreamDependency, WindowUpdate,
+    };
+
+    fn settings(values: &[(SettingId, u32)]) -> Settings {
+        let mut settings = Settings::default();
+        let mut order = SettingOrder::default();
+        for (id, value) in values.iter().copied() {
+            order.push(id);
+            match id {
+                SettingId::HeaderTableSize => settings.set_header_table_size(Some(value)),
+                SettingId::EnablePush => settings.set_enable_push(value == 1),
+                SettingId::MaxConcurrentStreams => settings.set_max_concurrent_streams(Some(value)),
+                SettingId::InitialWindowSize => settings.set_initial_window_size(Some(value)),
+                SettingId::MaxFrameSize => settings.set_max_frame_size(Some(value)),
+                SettingId::MaxHeaderListSize => settings.set_max_header_list_size(Some(value)),
+                _ => unreachable!(),
+            }
+        }
+        settings.set_setting_order(Some(order));
+        settings
+    }
+
+    #[test]
+    fn test_http2_fingerprint_chrome() {
+        let early_frames = [
+            EarlyFrame::Settings(settings(&[
+                (SettingId::HeaderTableSize, 65536),
+                (SettingId::EnablePush, 0),
+                (SettingId::InitialWindowSize, 6291456),
+                (SettingId::MaxHeaderListSize, 262144),
+            ])),
+            EarlyFrame::WindowUpdate(WindowUpdate::new(StreamId::zero(), 15663105)),
+            EarlyFrame::Settings(Settings::ack()),
+        ];
+        let pseudo_headers: PseudoHeaderOrder = [
+            PseudoHeader::Method,
+            PseudoHeader::Authority,
+            PseudoHeader::Scheme,
+            PseudoHeader::Path,
+        ]
+        .into_iter()
+        .collect();
+
+        let fp =
+            Http2Fingerprint::compute_from_early_frames(&early_frames, &pseudo_headers).unwrap();
+        assert_eq!(
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p",
+            fp.to_string()
+        );
+        assert_eq!("52d84b11737d980aef856699f885ca86", fp.hash());
+    }
+
+    #[test]
+    fn test_http2_fingerprint_priorities() {
+        let early_frames = [
+            EarlyFrame::Settings(settings(&[
+                (SettingId::HeaderTableSize, 65536),
+                (SettingId::InitialWindowSize, 131072),
+                (SettingId::MaxFrameSize, 16384),
+            ])),
+            EarlyFrame::WindowUpdate(WindowUpdate::new(StreamId::zero(), 12517377)),
+            EarlyFrame::Priority(Priority::new(
+                StreamId::from(3),
+                StreamDependency::new(StreamId::zero(), 200, false),
+            )),
+            EarlyFrame::Priority(Priority::new(
+                StreamId::from(9),
+                StreamDependency::new(StreamId::from(7), 0, true),
+            )),
+        ];
+        let pseudo_headers: PseudoHeaderOrder = [
+            PseudoHeader::Method,
+            PseudoHeader::Path,
+            PseudoHeader::Authority,
+            PseudoHeader::Scheme,
+        ]
+        .into_iter()
+        .collect();
+
+        let fp =
+            Http2Fingerprint::compute_from_early_frames(&early_frames, &pseudo_headers).unwrap();
+        assert_eq!(
+            "1:65536;4:131072;5:16384|12517377|3:0:0:201,9:1:7:1|m,p,a,s",
+            fp.to_string()
+        );
+    }
+
+    #[test]
+    fn test_http2_fingerprint_compute_from_request() {
+        let mut ctx = EarlyFrameStreamContext::new_recorder();
+        ctx.record_settings_frame(&settings(&[(SettingId::MaxConcurrentStreams, 100)]));
+
+        let mut req = Request::new(());
+        assert!(matches!(
+            Http2Fingerprint::compute(&req),
+            Err(Http2FingerprintComputeError::MissingEarlyFrames)
+        ));
+
+        req.extensions_mut().insert(ctx.freeze_recorder().unwrap());
+        assert!(matches!(
+            Http2Fingerprint::compute(&req),
+            Err(Http2FingerprintComputeError::MissingPseudoHeaders)
+        ));
+
+        req.extensions_mut().insert(
+            [
+                PseudoHeader::Method,
+                PseudoHeader::Scheme,
+                PseudoHeader::Path,
+                PseudoHeader::Authority,
+            ]
+            .into_iter()
+            .collect::<PseudoHeaderOrder>(),
+        );
+        let fp = Http2Fingerprint::compute(&req).unwrap();
+        assert_eq!("3:100|00|0|m,s,p,a", fp.to_string());
+    }
+
+    #[test]
+    fn test_http2_fingerprint_missing_settings() {
+        let early_frames = [EarlyFrame::Settings(Settings::ack())];
+        assert!(matches!(
+            Http2Fingerprint::compute_from_early_frames(&early_frames, &PseudoHeaderOrder::new()),
+            Err(Http2FingerprintComputeError::MissingSettings)
+        ));
+    }
+}
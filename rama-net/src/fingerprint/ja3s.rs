@@ -0,0 +1,212 @@
+use rama_core::context::Extensions;
+use std::{fmt, io};
+
+use crate::tls::{CipherSuite, ExtensionId, ProtocolVersion, server::ServerHello};
+
+#[derive(Debug, Clone)]
+/// Data which can be hashed using [`Self::hash`],
+/// and which is also displayed as a "ja3s" hash.
+///
+/// Computed using [`Ja3S::compute`].
+pub struct Ja3S {
+    version: ProtocolVersion,
+    cipher_suite: CipherSuite,
+    extensions: Vec<ExtensionId>,
+}
+
+impl Ja3S {
+    /// Compute the [`Ja3S`] (hash).
+    ///
+    /// As specified by <https://github.com/salesforce/ja3>.
+    pub fn compute(ext: &Extensions) -> Result<Self, Ja3SComputeError> {
+        let server_hello = ext
+            .get::<ServerHello>()
+            .ok_or(Ja3SComputeError::MissingServerHello)?;
+        Ok(Self::compute_from_server_hello(server_hello))
+    }
+
+    /// Compute the [`Ja3S`] (hash) from a reference to a [`ServerHello`].
+    ///
+    /// In case your source is [`Extensions`] you can use [`Self::compute`] instead.
+    pub fn compute_from_server_hello(server_hello: &ServerHello) -> Self {
+        Self {
+            version: server_hello.protocol_version(),
+            cipher_suite: server_hello.cipher_suite(),
+            extensions: server_hello
+                .extensions()
+                .iter()
+                .map(|ext| ext.id())
+                .collect(),
+        }
+    }
+
+    #[inline]
+    /// compute the "ja3s" hash from this [`Ja3S`] data structure as a String.
+    pub fn hash(&self) -> String {
+        format!("{self:x}")
+    }
+
+    /// compute the "ja3s" hash from this [`Ja3S`] data structure into the writer.
+    fn hash_to(&self, w: &mut impl fmt::Write, lower: bool) -> fmt::Result {
+        let mut ctx = md5::Context::new();
+        let _ = self.write_to_io(&mut ctx).inspect_err(|err| {
+            if cfg!(debug_assertions) {
+                panic!("md5 ingest failed: {err:?}");
+            }
+        });
+        let digest = ctx.compute();
+        if lower {
+            write!(w, "{digest:x}",)?;
+        } else {
+            write!(w, "{digest:X}",)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! impl_write_to {
+    ($w:ident, $this:ident) => {{
+        write!(
+            $w,
+            "{},{},",
+            u16::from($this.version),
+            u16::from($this.cipher_suite)
+        )?;
+
+        let mut sep = "";
+        for ext in &$this.extensions {
+            write!($w, "{sep}{}", u16::from(*ext))?;
+            sep = "-";
+        }
+
+        Ok(())
+    }};
+}
+
+impl Ja3S {
+    fn write_to_io(&self, w: &mut impl io::Write) -> io::Result<()> {
+        impl_write_to!(w, self)
+    }
+
+    fn write_to_fmt(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        impl_write_to!(w, self)
+    }
+}
+
+impl fmt::Display for Ja3S {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to_fmt(f)
+    }
+}
+
+impl fmt::LowerHex for Ja3S {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.hash_to(f, true)?;
+        Ok(())
+    }
+}
+
+impl fmt::UpperHex for Ja3S {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.hash_to(f, false)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// error identifying a failure in [`Ja3S::compute`]
+pub enum Ja3SComputeError {
+    /// missing [`ServerHello`]
+    MissingServerHello,
+}
+
+impl fmt::Display for Ja3SComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ja3SComputeError::MissingServerHello => {
+                write!(f, "Ja3S Compute Error: missing server hello")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ja3SComputeError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::tls::server::parse_server_hello;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestCase {
+        server_hello: Vec<u8>,
+        description: &'static str,
+        expected_ja3s_str: &'static str,
+        expected_ja3s_hash: &'static str,
+    }
+
+    #[test]
+    fn test_ja3s_compute() {
+        let test_cases = [
+            TestCase {
+                server_hello: vec![
+                    0x03, 0x01, 0x5f, 0x0c, 0x42, 0x38, 0x91, 0x0e, 0xd2, 0x4d, 0x6a, 0x21, 0x3f,
+                    0xb8, 0x6c, 0x44, 0x06, 0x9a, 0x0b, 0x1e, 0x7d, 0x95, 0x1a, 0xcd, 0x3e, 0x55,
+                    0x20, 0xb8, 0x43, 0x5e, 0xe1, 0xfb, 0x3c, 0x62, 0x00, 0x00, 0x2f, 0x00, 0x00,
+                    0x20, 0xff, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, 0x00,
+                    0x02, 0x01, 0x00, 0x00, 0x23, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x10,
+                    0x00, 0x05, 0x00, 0x03, 0x02, 0x68, 0x32,
+                ],
+                description: "tls 1.0",
+                expected_ja3s_str: "769,47,65281-0-11-35-5-16",
+                expected_ja3s_hash: "836ce314215654b5b1f85f97c73e506f",
+            },
+            TestCase {
+                server_hello: vec![
+                    0x03, 0x03, 0x8e, 0x4b, 0x06, 0x52, 0x3a, 0xc1, 0x0b, 0x7d, 0x7f, 0x2c, 0x9e,
+                    0x61, 0xd4, 0x08, 0x35, 0x26, 0x97, 0xf0, 0x1c, 0x55, 0xb3, 0x6a, 0x48, 0x21,
+                    0x0c, 0xde, 0x93, 0x7a, 0x15, 0xe2, 0x46, 0x80, 0x00, 0x13, 0x01, 0x00, 0x00,
+                    0x2e, 0x00, 0x2b, 0x00, 0x02, 0x03, 0x04, 0x00, 0x33, 0x00, 0x24, 0x00, 0x1d,
+                    0x00, 0x20, 0x4b, 0x13, 0x9e, 0x62, 0x07, 0xd1, 0x84, 0x3f, 0xa6, 0x2d, 0x59,
+                    0xc4, 0x70, 0x1e, 0x88, 0xb5, 0x36, 0xfb, 0x0a, 0x91, 0x67, 0x2c, 0xe3, 0x18,
+                    0x5d, 0xa0, 0x4f, 0x76, 0xc9, 0x02, 0xbe, 0x3b,
+                ],
+                description: "tls 1.3",
+                expected_ja3s_str: "771,4865,43-51",
+                expected_ja3s_hash: "f4febc55ea12b31ae17cfb7e614afda8",
+            },
+        ];
+        for test_case in test_cases {
+            let mut ext = Extensions::new();
+            ext.insert(parse_server_hello(&test_case.server_hello).expect(test_case.description));
+
+            let ja3s = Ja3S::compute(&ext).expect(test_case.description);
+
+            assert_eq!(
+                test_case.expected_ja3s_str,
+                format!("{ja3s}"),
+                "description: {}",
+                test_case.description,
+            );
+
+            assert_eq!(
+                test_case.expected_ja3s_hash,
+                ja3s.hash(),
+                "description: {}",
+                test_case.description,
+            );
+        }
+    }
+
+    #[test]
+    fn test_ja3s_compute_missing_server_hello() {
+        assert!(matches!(
+            Ja3S::compute(&Extensions::new()),
+            Err(Ja3SComputeError::MissingServerHello)
+        ));
+    }
+}
@@ -9,3 +9,19 @@ mod tls;
 
 #[cfg(feature = "tls")]
 pub use tls::{Ja4, Ja4ComputeError};
+
+#[cfg(feature = "tls")]
+mod server;
+
+#[cfg(feature = "tls")]
+pub use server::{Ja4S, Ja4SComputeError};
+
+#[cfg(feature = "tls")]
+mod x509;
+
+#[cfg(feature = "tls")]
+pub use x509::{Ja4X, Ja4XComputeError};
+
+mod tcp;
+
+pub use tcp::{Ja4T, Ja4TComputeError};
@@ -0,0 +1,198 @@
+use itertools::Itertools as _;
+use std::fmt;
+
+use rama_core::context::Extensions;
+
+use super::tls::{TlsVersion, TransportProtocol, hash12};
+use crate::tls::{ApplicationProtocol, CipherSuite, ExtensionId, server::ServerHello};
+
+#[derive(Clone)]
+/// Input data for a "ja4s" hash.
+///
+/// Computed using [`Ja4S::compute`].
+pub struct Ja4S {
+    protocol: TransportProtocol,
+    version: TlsVersion,
+    alpn: Option<ApplicationProtocol>,
+    cipher_suite: CipherSuite,
+    extensions: Vec<ExtensionId>,
+}
+
+impl Ja4S {
+    /// Compute the [`Ja4S`] (hash).
+    ///
+    /// As specified by <https://blog.foxio.io/ja4%2B-network-fingerprinting>
+    /// and reference implementations found at <https://github.com/FoxIO-LLC/ja4>.
+    pub fn compute(ext: &Extensions) -> Result<Self, Ja4SComputeError> {
+        let server_hello = ext
+            .get::<ServerHello>()
+            .ok_or(Ja4SComputeError::MissingServerHello)?;
+        Self::compute_from_server_hello(server_hello)
+    }
+
+    /// Compute the [`Ja4S`] (hash) from a reference to a [`ServerHello`].
+    ///
+    /// In case your source is [`Extensions`] you can use [`Self::compute`] instead.
+    pub fn compute_from_server_hello(server_hello: &ServerHello) -> Result<Self, Ja4SComputeError> {
+        let version: TlsVersion = server_hello
+            .negotiated_version()
+            .try_into()
+            .map_err(|_| Ja4SComputeError::InvalidTlsVersion)?;
+
+        let extensions: Vec<_> = server_hello
+            .extensions()
+            .iter()
+            .map(|ext| ext.id())
+            .collect();
+        let protocol = if extensions.contains(&ExtensionId::QUIC_TRANSPORT_PARAMETERS) {
+            TransportProtocol::Quic
+        } else {
+            TransportProtocol::Tcp
+        };
+
+        Ok(Self {
+            protocol,
+            version,
+            alpn: server_hello.ext_alpn().cloned(),
+            cipher_suite: server_hello.cipher_suite(),
+            extensions,
+        })
+    }
+
+    /// Format the [`Ja4S`] in its raw (human readable) form,
+    /// with the extensions listed rather than hashed.
+    ///
+    /// Equivalent to the [`Debug`](fmt::Debug) output,
+    /// while the [`Display`](fmt::Display) output is the hashed fingerprint.
+    #[inline]
+    pub fn to_human_string(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn fmt_as(&self, f: &mut fmt::Formatter<'_>, hash_chunks: bool) -> fmt::Result {
+        let protocol = self.protocol;
+        let version = self.version;
+        let nr_exts = 99.min(self.extensions.len());
+        let mut alpn_it = self
+            .alpn
+            .as_ref()
+            .and_then(|alpn| std::str::from_utf8(alpn.as_bytes()).ok())
+            .map(|s| s.chars())
+            .into_iter()
+            .flatten();
+        let alpn_0 = alpn_it.next().unwrap_or('0');
+        let alpn_1 = alpn_it.last().unwrap_or('0');
+
+        // JA4S_a (AKA first chunk)
+        write!(f, "{protocol}{version}{nr_exts:02}{alpn_0}{alpn_1}")?;
+
+        // JA4S_b (AKA selected Cipher Suite)
+        write!(f, "_{:04x}", self.cipher_suite)?;
+
+        // JA4S_c (AKA Exts, in original order)
+        let extensions = self.extensions.iter().map(|e| format!("{e:04x}")).join(",");
+
+        if hash_chunks {
+            write!(f, "_{}", hash12(extensions))
+        } else {
+            write!(f, "_{extensions}")
+        }
+    }
+}
+
+impl fmt::Display for Ja4S {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as(f, true)
+    }
+}
+
+impl fmt::Debug for Ja4S {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as(f, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// error identifying a failure in [`Ja4S::compute`]
+pub enum Ja4SComputeError {
+    /// missing [`ServerHello`]
+    MissingServerHello,
+    /// invalid tls version
+    InvalidTlsVersion,
+}
+
+impl fmt::Display for Ja4SComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ja4SComputeError::MissingServerHello => {
+                write!(f, "Ja4S Compute Error: missing server hello")
+            }
+            Ja4SComputeError::InvalidTlsVersion => {
+                write!(f, "Ja4S Compute Error: invalid tls version")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ja4SComputeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{CompressionAlgorithm, ProtocolVersion, server::ServerHelloExtension};
+
+    #[test]
+    fn test_ja4s_compute_tls13() {
+        let server_hello = ServerHello::new(
+            ProtocolVersion::TLSv1_2,
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CompressionAlgorithm::Null,
+            vec![
+                ServerHelloExtension::SupportedVersions(ProtocolVersion::TLSv1_3),
+                ServerHelloExtension::Opaque {
+                    id: ExtensionId::KEY_SHARE,
+                    data: vec![],
+                },
+            ],
+        );
+
+        let mut ext = Extensions::new();
+        ext.insert(server_hello);
+
+        let ja4s = Ja4S::compute(&ext).unwrap();
+        assert_eq!("t130200_1301_002b,0033", format!("{ja4s:?}"));
+        assert_eq!("t130200_1301_a56c5b993250", format!("{ja4s}"));
+    }
+
+    #[test]
+    fn test_ja4s_compute_tls12_alpn() {
+        let server_hello = ServerHello::new(
+            ProtocolVersion::TLSv1_2,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            CompressionAlgorithm::Null,
+            vec![
+                ServerHelloExtension::Opaque {
+                    id: ExtensionId::RENEGOTIATION_INFO,
+                    data: vec![0],
+                },
+                ServerHelloExtension::ApplicationLayerProtocolNegotiation(
+                    ApplicationProtocol::HTTP_2,
+                ),
+            ],
+        );
+
+        let ja4s = Ja4S::compute_from_server_hello(&server_hello).unwrap();
+        assert_eq!("t1202h2_c02f_ff01,0010", ja4s.to_human_string());
+        assert_eq!("t1202h2_c02f_87b1562aab70", ja4s.to_string());
+    }
+
+    #[test]
+    fn test_ja4s_compute_missing_server_hello() {
+        assert!(matches!(
+            Ja4S::compute(&Extensions::new()),
+            Err(Ja4SComputeError::MissingServerHello)
+        ));
+    }
+}
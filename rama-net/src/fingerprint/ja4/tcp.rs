@@ -0,0 +1,134 @@
+use std::fmt;
+
+use rama_core::context::Extensions;
+
+use crate::stream::TcpSyn;
+
+#[derive(Debug, Clone)]
+/// Input data for a "ja4t" fingerprint,
+/// the passive TCP fingerprint of a client.
+///
+/// Unlike most other Ja4+ fingerprints it is not hashed,
+/// and is displayed as `{window}_{options}_{mss}_{window_scale}`.
+///
+/// Computed using [`Ja4T::compute`].
+pub struct Ja4T {
+    window_size: u16,
+    options: Vec<u8>,
+    mss: Option<u16>,
+    window_scale: Option<u8>,
+}
+
+impl Ja4T {
+    /// Compute the [`Ja4T`] fingerprint.
+    ///
+    /// As specified by <https://blog.foxio.io/ja4t-tcp-fingerprinting>
+    /// and reference implementations found at <https://github.com/FoxIO-LLC/ja4>.
+    pub fn compute(ext: &Extensions) -> Result<Self, Ja4TComputeError> {
+        let syn = ext.get::<TcpSyn>().ok_or(Ja4TComputeError::MissingTcpSyn)?;
+        Ok(Self::compute_from_tcp_syn(syn))
+    }
+
+    /// Compute the [`Ja4T`] fingerprint from a reference to a [`TcpSyn`].
+    ///
+    /// In case your source is [`Extensions`] you can use [`Self::compute`] instead.
+    pub fn compute_from_tcp_syn(syn: &TcpSyn) -> Self {
+        Self {
+            window_size: syn.window_size(),
+            options: syn.options().to_vec(),
+            mss: syn.mss(),
+            window_scale: syn.window_scale(),
+        }
+    }
+}
+
+impl fmt::Display for Ja4T {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_", self.window_size)?;
+
+        if self.options.is_empty() {
+            write!(f, "00")?;
+        }
+        let mut sep = "";
+        for kind in &self.options {
+            write!(f, "{sep}{kind}")?;
+            sep = "-";
+        }
+
+        match self.mss {
+            Some(mss) => write!(f, "_{mss}")?,
+            None => write!(f, "_00")?,
+        }
+        match self.window_scale {
+            Some(scale) => write!(f, "_{scale}"),
+            None => write!(f, "_00"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// error identifying a failure in [`Ja4T::compute`]
+pub enum Ja4TComputeError {
+    /// missing [`TcpSyn`]
+    MissingTcpSyn,
+}
+
+impl fmt::Display for Ja4TComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ja4TComputeError::MissingTcpSyn => {
+                write!(f, "Ja4T Compute Error: missing tcp syn")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ja4TComputeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp_syn(window: u16, options: &[u8]) -> TcpSyn {
+        let data_offset = 20 + options.len();
+        let mut packet = vec![
+            0x45, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x80, 0x06, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+        ];
+        packet.extend_from_slice(&[0xc3, 0x50, 0x01, 0xbb, 0, 0, 0, 1, 0, 0, 0, 0]);
+        packet.push(((data_offset / 4) as u8) << 4);
+        packet.push(0x02);
+        packet.extend_from_slice(&window.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet.extend_from_slice(options);
+        TcpSyn::parse(&packet).unwrap()
+    }
+
+    #[test]
+    fn test_ja4t_compute() {
+        let mut ext = Extensions::new();
+        ext.insert(tcp_syn(
+            64240,
+            &[
+                0x02, 0x04, 0x05, 0xb4, 0x01, 0x03, 0x03, 0x08, 0x01, 0x01, 0x04, 0x02,
+            ],
+        ));
+
+        let ja4t = Ja4T::compute(&ext).unwrap();
+        assert_eq!("64240_2-1-3-1-1-4_1460_8", ja4t.to_string());
+    }
+
+    #[test]
+    fn test_ja4t_compute_no_options() {
+        let ja4t = Ja4T::compute_from_tcp_syn(&tcp_syn(1024, &[]));
+        assert_eq!("1024_00_00_00", ja4t.to_string());
+    }
+
+    #[test]
+    fn test_ja4t_compute_missing_tcp_syn() {
+        assert!(matches!(
+            Ja4T::compute(&Extensions::new()),
+            Err(Ja4TComputeError::MissingTcpSyn)
+        ));
+    }
+}
@@ -226,7 +226,7 @@ impl fmt::Debug for Ja4 {
     }
 }
 
-fn hash12(s: impl AsRef<str>) -> Cow<'static, str> {
+pub(super) fn hash12(s: impl AsRef<str>) -> Cow<'static, str> {
     use sha2::{Digest as _, Sha256};
 
     let s = s.as_ref();
@@ -239,7 +239,7 @@ fn hash12(s: impl AsRef<str>) -> Cow<'static, str> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-enum TransportProtocol {
+pub(super) enum TransportProtocol {
     Tcp,
     Quic,
 }
@@ -255,7 +255,7 @@ impl fmt::Display for TransportProtocol {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-enum TlsVersion {
+pub(super) enum TlsVersion {
     Tls1_0,
     Tls1_1,
     Tls1_2,
@@ -0,0 +1,305 @@
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as ENGINE;
+use itertools::Itertools as _;
+use std::fmt;
+
+use rama_core::context::Extensions;
+
+use super::tls::hash12;
+use crate::tls::{
+    DataEncoding,
+    client::NegotiatedTlsParameters,
+    der::{
+        TAG_OID, TAG_SEQUENCE, TAG_SET, TAG_X509_EXTENSIONS, TAG_X509_VERSION, expect_der, read_der,
+    },
+};
+
+#[derive(Clone)]
+/// Input data for a "ja4x" hash,
+/// which fingerprints how a X.509 certificate was generated.
+///
+/// Computed using [`Ja4X::compute`].
+pub struct Ja4X {
+    issuer_rdns: Vec<String>,
+    subject_rdns: Vec<String>,
+    extensions: Vec<String>,
+}
+
+impl Ja4X {
+    /// Compute the [`Ja4X`] (hash) for each certificate
+    /// in the peer certificate chain, starting with the leaf certificate.
+    ///
+    /// The peer certificate chain is only available in the [`NegotiatedTlsParameters`]
+    /// if the tls connector or acceptor was configured to store it.
+    ///
+    /// As specified by <https://blog.foxio.io/ja4%2B-network-fingerprinting>
+    /// and reference implementations found at <https://github.com/FoxIO-LLC/ja4>.
+    pub fn compute(ext: &Extensions) -> Result<Vec<Self>, Ja4XComputeError> {
+        let chain = ext
+            .get::<NegotiatedTlsParameters>()
+            .and_then(|params| params.peer_certificate_chain.as_ref())
+            .ok_or(Ja4XComputeError::MissingCertificateChain)?;
+        Self::compute_from_certificate_chain(chain)
+    }
+
+    /// Compute the [`Ja4X`] (hash) for each certificate in the given chain.
+    ///
+    /// In case your source is [`Extensions`] you can use [`Self::compute`] instead.
+    pub fn compute_from_certificate_chain(
+        chain: &DataEncoding,
+    ) -> Result<Vec<Self>, Ja4XComputeError> {
+        match chain {
+            DataEncoding::Der(der) => Ok(vec![Self::compute_from_der(der)?]),
+            DataEncoding::DerStack(stack) => stack
+                .iter()
+                .map(|der| Self::compute_from_der(der))
+                .collect(),
+            DataEncoding::Pem(pem) => pem_certificates(pem.as_str())?
+                .iter()
+                .map(|der| Self::compute_from_der(der))
+                .collect(),
+        }
+    }
+
+    /// Compute the [`Ja4X`] (hash) from a single DER encoded certificate.
+    pub fn compute_from_der(der: &[u8]) -> Result<Self, Ja4XComputeError> {
+        parse_certificate(der).ok_or(Ja4XComputeError::InvalidCertificate)
+    }
+
+    /// Format the [`Ja4X`] in its raw (human readable) form,
+    /// with the issuer RDNs, subject RDNs and extensions listed rather than hashed.
+    ///
+    /// Equivalent to the [`Debug`](fmt::Debug) output,
+    /// while the [`Display`](fmt::Display) output is the hashed fingerprint.
+    #[inline]
+    pub fn to_human_string(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn fmt_as(&self, f: &mut fmt::Formatter<'_>, hash_chunks: bool) -> fmt::Result {
+        let issuer_rdns = self.issuer_rdns.iter().join(",");
+        let subject_rdns = self.subject_rdns.iter().join(",");
+        let extensions = self.extensions.iter().join(",");
+
+        if hash_chunks {
+            write!(
+                f,
+                "{}_{}_{}",
+                hash12(issuer_rdns),
+                hash12(subject_rdns),
+                hash12(extensions),
+            )
+        } else {
+            write!(f, "{issuer_rdns}_{subject_rdns}_{extensions}")
+        }
+    }
+}
+
+impl fmt::Display for Ja4X {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as(f, true)
+    }
+}
+
+impl fmt::Debug for Ja4X {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as(f, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// error identifying a failure in [`Ja4X::compute`]
+pub enum Ja4XComputeError {
+    /// missing peer certificate chain
+    MissingCertificateChain,
+    /// invalid (DER or PEM encoded) certificate
+    InvalidCertificate,
+}
+
+impl fmt::Display for Ja4XComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ja4XComputeError::MissingCertificateChain => {
+                write!(f, "Ja4X Compute Error: missing peer certificate chain")
+            }
+            Ja4XComputeError::InvalidCertificate => {
+                write!(f, "Ja4X Compute Error: invalid certificate")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ja4XComputeError {}
+
+fn pem_certificates(pem: &str) -> Result<Vec<Vec<u8>>, Ja4XComputeError> {
+    let mut certificates = Vec::new();
+    let mut lines = pem.lines().map(str::trim);
+    while lines.any(|line| line == "-----BEGIN CERTIFICATE-----") {
+        let b64: String = lines
+            .by_ref()
+            .take_while(|line| *line != "-----END CERTIFICATE-----")
+            .collect();
+        let der = ENGINE
+            .decode(b64)
+            .map_err(|_| Ja4XComputeError::InvalidCertificate)?;
+        certificates.push(der);
+    }
+    if certificates.is_empty() {
+        return Err(Ja4XComputeError::InvalidCertificate);
+    }
+    Ok(certificates)
+}
+
+// Certificate ::= SEQUENCE {
+//     tbsCertificate       TBSCertificate,
+//     signatureAlgorithm   AlgorithmIdentifier,
+//     signatureValue       BIT STRING }
+//
+// TBSCertificate ::= SEQUENCE {
+//     version         [0]  EXPLICIT Version DEFAULT v1,
+//     serialNumber         CertificateSerialNumber,
+//     signature            AlgorithmIdentifier,
+//     issuer               Name,
+//     validity             Validity,
+//     subject              Name,
+//     subjectPublicKeyInfo SubjectPublicKeyInfo,
+//     issuerUniqueID  [1]  IMPLICIT UniqueIdentifier OPTIONAL,
+//     subjectUniqueID [2]  IMPLICIT UniqueIdentifier OPTIONAL,
+//     extensions      [3]  EXPLICIT Extensions OPTIONAL }
+fn parse_certificate(der: &[u8]) -> Option<Ja4X> {
+    let (certificate, _) = expect_der(der, TAG_SEQUENCE)?;
+    let (tbs, _) = expect_der(certificate, TAG_SEQUENCE)?;
+
+    let (tag, _, mut i) = read_der(tbs)?;
+    if tag == TAG_X509_VERSION {
+        // serial number
+        (_, _, i) = read_der(i)?;
+    }
+    // signature
+    let (_, i) = expect_der(i, TAG_SEQUENCE)?;
+    let (issuer, i) = expect_der(i, TAG_SEQUENCE)?;
+    // validity
+    let (_, i) = expect_der(i, TAG_SEQUENCE)?;
+    let (subject, i) = expect_der(i, TAG_SEQUENCE)?;
+    // subject public key info
+    let (_, mut i) = expect_der(i, TAG_SEQUENCE)?;
+
+    let mut extensions = Vec::new();
+    while !i.is_empty() {
+        let (tag, value, rem) = read_der(i)?;
+        if tag == TAG_X509_EXTENSIONS {
+            // Extension ::= SEQUENCE {
+            //     extnID      OBJECT IDENTIFIER,
+            //     critical    BOOLEAN DEFAULT FALSE,
+            //     extnValue   OCTET STRING }
+            let (mut exts, _) = expect_der(value, TAG_SEQUENCE)?;
+            while !exts.is_empty() {
+                let (ext, rem) = expect_der(exts, TAG_SEQUENCE)?;
+                let (oid, _) = expect_der(ext, TAG_OID)?;
+                extensions.push(hex::encode(oid));
+                exts = rem;
+            }
+        }
+        i = rem;
+    }
+
+    Some(Ja4X {
+        issuer_rdns: parse_name(issuer)?,
+        subject_rdns: parse_name(subject)?,
+        extensions,
+    })
+}
+
+// Name ::= SEQUENCE OF RelativeDistinguishedName
+// RelativeDistinguishedName ::= SET SIZE (1..MAX) OF AttributeTypeAndValue
+// AttributeTypeAndValue ::= SEQUENCE {
+//     type     OBJECT IDENTIFIER,
+//     value    ANY -- DEFINED BY type }
+fn parse_name(mut i: &[u8]) -> Option<Vec<String>> {
+    let mut oids = Vec::new();
+    while !i.is_empty() {
+        let (mut rdn, rem) = expect_der(i, TAG_SET)?;
+        while !rdn.is_empty() {
+            let (attr, rem) = expect_der(rdn, TAG_SEQUENCE)?;
+            let (oid, _) = expect_der(attr, TAG_OID)?;
+            oids.push(hex::encode(oid));
+            rdn = rem;
+        }
+        i = rem;
+    }
+    Some(oids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::ProtocolVersion;
+    use rama_utils::str::NonEmptyString;
+
+    const CERT_PEM: &str = r#"-----BEGIN CERTIFICATE-----
+MIIBuTCCAV+gAwIBAgIUex8HlsVPHxu1USPeeLesPG8y1aUwCgYIKoZIzj0EAwIw
+MjELMAkGA1UEBhMCQkUxDTALBgNVBAoMBFJhbWExFDASBgNVBAMMC2V4YW1wbGUu
+Y29tMB4XDTI2MTAxNzAxMDgwOVoXDTM2MTAxNDAxMDgwOVowMjELMAkGA1UEBhMC
+QkUxDTALBgNVBAoMBFJhbWExFDASBgNVBAMMC2V4YW1wbGUuY29tMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAEEShN/q3hqWoOWEOZG8srmkZe1tEIdvRvf+2NUQFv
+NXFNSVVAkbZPNER7n07aUkQ6uQTeku7FoDjwEV2NSafFzKNTMFEwHQYDVR0OBBYE
+FAqTJuRFPYcAizBRGhG0uzBlcbHwMB8GA1UdIwQYMBaAFAqTJuRFPYcAizBRGhG0
+uzBlcbHwMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSAAwRQIhAJw10STu
+QL1LI7rBq0F0NAh3PcI4Y3CH6dfXfv6qyJZ2AiBIpvtT2fa/NCYBW8R2XVq4uxgM
+gN974yuBms4JBeg2SA==
+-----END CERTIFICATE-----
+"#;
+
+    #[test]
+    fn test_ja4x_compute() {
+        let pem: NonEmptyString = format!("{CERT_PEM}{CERT_PEM}").try_into().unwrap();
+
+        let mut ext = Extensions::new();
+        ext.insert(NegotiatedTlsParameters {
+            protocol_version: ProtocolVersion::TLSv1_3,
+            application_layer_protocol: None,
+            peer_certificate_chain: Some(DataEncoding::Pem(pem)),
+        });
+
+        let ja4x = Ja4X::compute(&ext).unwrap();
+        assert_eq!(2, ja4x.len());
+        for ja4x in ja4x {
+            assert_eq!(
+                "550406,55040a,550403_550406,55040a,550403_551d0e,551d23,551d13",
+                ja4x.to_human_string()
+            );
+            assert_eq!("a373a9f83c6b_a373a9f83c6b_795797892f9c", ja4x.to_string());
+        }
+    }
+
+    #[test]
+    fn test_ja4x_compute_from_der() {
+        let der = pem_certificates(CERT_PEM).unwrap().pop().unwrap();
+        let ja4x = Ja4X::compute_from_der(&der).unwrap();
+        assert_eq!("a373a9f83c6b_a373a9f83c6b_795797892f9c", ja4x.to_string());
+
+        assert!(Ja4X::compute_from_der(&der[..der.len() / 2]).is_err());
+        assert!(Ja4X::compute_from_der(&[]).is_err());
+    }
+
+    #[test]
+    fn test_ja4x_compute_missing_certificate_chain() {
+        let mut ext = Extensions::new();
+        assert!(matches!(
+            Ja4X::compute(&ext),
+            Err(Ja4XComputeError::MissingCertificateChain)
+        ));
+
+        ext.insert(NegotiatedTlsParameters {
+            protocol_version: ProtocolVersion::TLSv1_3,
+            application_layer_protocol: None,
+            peer_certificate_chain: None,
+        });
+        assert!(matches!(
+            Ja4X::compute(&ext),
+            Err(Ja4XComputeError::MissingCertificateChain)
+        ));
+    }
+}
@@ -1,13 +1,20 @@
 //! fingerprint implementations for the network surface
 
-#[cfg(any(feature = "tls", feature = "http"))]
 mod ja4;
 
+pub use ja4::{Ja4T, Ja4TComputeError};
+
 #[cfg(feature = "http")]
 pub use ja4::{Ja4H, Ja4HComputeError};
 
 #[cfg(feature = "tls")]
-pub use ja4::{Ja4, Ja4ComputeError};
+pub use ja4::{Ja4, Ja4ComputeError, Ja4S, Ja4SComputeError, Ja4X, Ja4XComputeError};
+
+#[cfg(feature = "http")]
+mod akamai;
+
+#[cfg(feature = "http")]
+pub use akamai::{Http2Fingerprint, Http2FingerprintComputeError};
 
 #[cfg(feature = "tls")]
 mod peet;
@@ -21,6 +28,12 @@ mod ja3;
 #[cfg(feature = "tls")]
 pub use ja3::{Ja3, Ja3ComputeError};
 
+#[cfg(feature = "tls")]
+mod ja3s;
+
+#[cfg(feature = "tls")]
+pub use ja3s::{Ja3S, Ja3SComputeError};
+
 #[cfg(feature = "tls")]
 mod tls_utils {
     use private::ClientHelloProviderPriv;
@@ -0,0 +1,469 @@
+use std::{collections::BTreeMap, fmt, net::IpAddr, path::Path, sync::Arc};
+
+use maxminddb::Reader;
+use rama_core::{
+    Context, Layer, Service,
+    error::{ErrorContext, OpaqueError},
+    telemetry::tracing,
+};
+use rama_utils::macros::{define_inner_service_accessors, generate_set_and_with};
+use serde::Deserialize;
+
+use super::IpGeoInfo;
+use crate::{asn::Asn, forwarded::Forwarded, stream::SocketInfo};
+
+#[derive(Clone, Default)]
+/// Local database of [`IpGeoInfo`], backed by one or multiple
+/// MaxMind DB (`.mmdb`) files, e.g. the (GeoLite2) Country, City and ASN databases.
+///
+/// The information found for an IP address in all databases is merged,
+/// where databases added first take precedence.
+pub struct GeoIpDb {
+    readers: Vec<Arc<Reader<Vec<u8>>>>,
+}
+
+impl fmt::Debug for GeoIpDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoIpDb")
+            .field(
+                "databases",
+                &self
+                    .readers
+                    .iter()
+                    .map(|reader| reader.metadata.database_type.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl GeoIpDb {
+    /// Create a new empty [`GeoIpDb`].
+    ///
+    /// Add databases using [`GeoIpDb::try_with_database_file`]
+    /// or [`GeoIpDb::try_with_database_bytes`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    generate_set_and_with! {
+        /// Add the MaxMind DB (`.mmdb`) file found at the given path,
+        /// which is read fully into memory.
+        pub fn database_file(mut self, path: impl AsRef<Path>) -> Result<Self, OpaqueError> {
+            let path = path.as_ref();
+            let reader = Reader::open_readfile(path)
+                .with_context(|| format!("open mmdb file {}", path.display()))?;
+            self.readers.push(Arc::new(reader));
+            Ok(self)
+        }
+    }
+
+    generate_set_and_with! {
+        /// Add the MaxMind DB from the given (`.mmdb`) file content.
+        pub fn database_bytes(mut self, bytes: Vec<u8>) -> Result<Self, OpaqueError> {
+            let reader = Reader::from_source(bytes).context("read mmdb bytes")?;
+            self.readers.push(Arc::new(reader));
+            Ok(self)
+        }
+    }
+
+    /// Lookup the [`IpGeoInfo`] of the given IP address,
+    /// returning `None` in case it is not found in any of the databases.
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<IpGeoInfo>, OpaqueError> {
+        let ip = ip.to_canonical();
+        let mut info = IpGeoInfo::default();
+        for reader in &self.readers {
+            if ip.is_ipv6() && reader.metadata.ip_version == 4 {
+                continue;
+            }
+            if let Some(record) = reader
+                .lookup::<MmdbRecord<'_>>(ip)
+                .context("lookup ip in mmdb")?
+            {
+                info.merge(record.into());
+            }
+        }
+        Ok((!info.is_empty()).then_some(info))
+    }
+}
+
+/// Subset of the fields of the records found in the
+/// MaxMind GeoIP2 / GeoLite2 (and compatible) databases.
+#[derive(Debug, Deserialize)]
+struct MmdbRecord<'a> {
+    #[serde(borrow)]
+    continent: Option<MmdbCode<'a>>,
+    #[serde(borrow)]
+    country: Option<MmdbIsoCode<'a>>,
+    #[serde(borrow)]
+    registered_country: Option<MmdbIsoCode<'a>>,
+    #[serde(borrow)]
+    subdivisions: Option<Vec<MmdbIsoCode<'a>>>,
+    #[serde(borrow)]
+    city: Option<MmdbNames<'a>>,
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MmdbCode<'a> {
+    code: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MmdbIsoCode<'a> {
+    iso_code: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MmdbNames<'a> {
+    #[serde(borrow)]
+    names: Option<BTreeMap<&'a str, &'a str>>,
+}
+
+impl From<MmdbRecord<'_>> for IpGeoInfo {
+    fn from(record: MmdbRecord<'_>) -> Self {
+        Self {
+            continent: record.continent.and_then(|c| c.code).map(ToOwned::to_owned),
+            country: record
+                .country
+                .and_then(|c| c.iso_code)
+                .or_else(|| record.registered_country.and_then(|c| c.iso_code))
+                .map(ToOwned::to_owned),
+            state: record
+                .subdivisions
+                .and_then(|s| s.into_iter().next())
+                .and_then(|s| s.iso_code)
+                .map(ToOwned::to_owned),
+            city: record
+                .city
+                .and_then(|c| c.names)
+                .and_then(|names| names.get("en").map(|name| (*name).to_owned())),
+            asn: record
+                .autonomous_system_number
+                .and_then(|n| Asn::try_from(n).ok()),
+            as_organization: record.autonomous_system_organization.map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// A [`Service`] which looks up the [`IpGeoInfo`] of the client IP address
+/// in a [`GeoIpDb`] and inserts it into the [`Context`], if found.
+///
+/// The client IP address is the peer address found in the [`SocketInfo`],
+/// or optionally the client IP address found in the [`Forwarded`] information.
+pub struct GeoIpService<S> {
+    inner: S,
+    db: GeoIpDb,
+    forwarded_client_ip: bool,
+}
+
+impl<S: fmt::Debug> fmt::Debug for GeoIpService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoIpService")
+            .field("inner", &self.inner)
+            .field("db", &self.db)
+            .field("forwarded_client_ip", &self.forwarded_client_ip)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for GeoIpService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            db: self.db.clone(),
+            forwarded_client_ip: self.forwarded_client_ip,
+        }
+    }
+}
+
+impl<S> GeoIpService<S> {
+    /// Create a new [`GeoIpService`].
+    pub fn new(inner: S, db: GeoIpDb) -> Self {
+        Self {
+            inner,
+            db,
+            forwarded_client_ip: false,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Lookup the client IP address found in the [`Forwarded`] context extension
+        /// instead of the peer address, in case it is available.
+        ///
+        /// Only enable this in case the [`Forwarded`] information can be trusted,
+        /// e.g. because it is added by a trusted reverse proxy.
+        pub fn forwarded_client_ip(mut self, forwarded_client_ip: bool) -> Self {
+            self.forwarded_client_ip = forwarded_client_ip;
+            self
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<State, Request, S> Service<State, Request> for GeoIpService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+    S: Service<State, Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let ip = self
+            .forwarded_client_ip
+            .then(|| ctx.get::<Forwarded>().and_then(Forwarded::client_ip))
+            .flatten()
+            .or_else(|| ctx.get::<SocketInfo>().map(|info| info.peer_addr().ip()));
+
+        if let Some(ip) = ip {
+            match self.db.lookup(ip) {
+                Ok(Some(info)) => {
+                    tracing::trace!(%ip, "geo ip info found: {info:?}");
+                    ctx.insert(info);
+                }
+                Ok(None) => tracing::trace!(%ip, "no geo ip info found"),
+                Err(err) => tracing::debug!(%ip, "failed to lookup geo ip info: {err}"),
+            }
+        }
+
+        self.inner.serve(ctx, req)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`Layer`] which produces a [`GeoIpService`].
+pub struct GeoIpLayer {
+    db: GeoIpDb,
+    forwarded_client_ip: bool,
+}
+
+impl GeoIpLayer {
+    /// Create a new [`GeoIpLayer`].
+    pub fn new(db: GeoIpDb) -> Self {
+        Self {
+            db,
+            forwarded_client_ip: false,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Lookup the client IP address found in the [`Forwarded`] context extension
+        /// instead of the peer address, in case it is available.
+        ///
+        /// Only enable this in case the [`Forwarded`] information can be trusted,
+        /// e.g. because it is added by a trusted reverse proxy.
+        pub fn forwarded_client_ip(mut self, forwarded_client_ip: bool) -> Self {
+            self.forwarded_client_ip = forwarded_client_ip;
+            self
+        }
+    }
+}
+
+impl<S> Layer<S> for GeoIpLayer {
+    type Service = GeoIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GeoIpService {
+            inner,
+            db: self.db.clone(),
+            forwarded_client_ip: self.forwarded_client_ip,
+        }
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        GeoIpService {
+            inner,
+            db: self.db,
+            forwarded_client_ip: self.forwarded_client_ip,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forwarded::ForwardedElement;
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+
+    /// Minimal MaxMind DB encoder, only supporting the types used in these tests.
+    enum Value {
+        Str(&'static str),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        Map(Vec<(&'static str, Value)>),
+        Array(Vec<Value>),
+    }
+
+    fn encode(value: &Value, out: &mut Vec<u8>) {
+        fn control(out: &mut Vec<u8>, kind: u8, size: usize) {
+            let (size, extra) = match size {
+                0..29 => (size as u8, None),
+                29..285 => (29, Some((size - 29) as u8)),
+                _ => panic!("test encoder does not support values of size {size}"),
+            };
+            if kind <= 7 {
+                out.push((kind << 5) | size);
+            } else {
+                out.extend([size, kind - 7]);
+            }
+            out.extend(extra);
+        }
+
+        match value {
+            Value::Str(s) => {
+                control(out, 2, s.len());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Value::U16(n) => {
+                control(out, 5, 2);
+                out.extend(n.to_be_bytes());
+            }
+            Value::U32(n) => {
+                control(out, 6, 4);
+                out.extend(n.to_be_bytes());
+            }
+            Value::U64(n) => {
+                control(out, 9, 8);
+                out.extend(n.to_be_bytes());
+            }
+            Value::Map(entries) => {
+                control(out, 7, entries.len());
+                for (key, value) in entries {
+                    encode(&Value::Str(key), out);
+                    encode(value, out);
+                }
+            }
+            Value::Array(values) => {
+                control(out, 11, values.len());
+                for value in values {
+                    encode(value, out);
+                }
+            }
+        }
+    }
+
+    /// Create an IPv4 database where `0.0.0.0/1` maps to the given record.
+    fn mmdb(record: Value) -> Vec<u8> {
+        // single node, left record points to the data, right record is empty
+        let mut db = vec![0, 0, 17, 0, 0, 1];
+        db.extend([0; 16]);
+        encode(&record, &mut db);
+        db.extend(b"\xab\xcd\xefMaxMind.com");
+        encode(
+            &Value::Map(vec![
+                ("binary_format_major_version", Value::U16(2)),
+                ("binary_format_minor_version", Value::U16(0)),
+                ("build_epoch", Value::U64(0)),
+                ("database_type", Value::Str("Test")),
+                ("description", Value::Map(vec![])),
+                ("ip_version", Value::U16(4)),
+                ("languages", Value::Array(vec![Value::Str("en")])),
+                ("node_count", Value::U32(1)),
+                ("record_size", Value::U16(24)),
+            ]),
+            &mut db,
+        );
+        db
+    }
+
+    fn test_db() -> GeoIpDb {
+        let city = mmdb(Value::Map(vec![
+            ("continent", Value::Map(vec![("code", Value::Str("EU"))])),
+            ("country", Value::Map(vec![("iso_code", Value::Str("BE"))])),
+            (
+                "subdivisions",
+                Value::Array(vec![Value::Map(vec![("iso_code", Value::Str("VOV"))])]),
+            ),
+            (
+                "city",
+                Value::Map(vec![(
+                    "names",
+                    Value::Map(vec![
+                        ("de", Value::Str("Gent")),
+                        ("en", Value::Str("Ghent")),
+                    ]),
+                )]),
+            ),
+        ]));
+        let asn = mmdb(Value::Map(vec![
+            ("autonomous_system_number", Value::U32(13335)),
+            (
+                "autonomous_system_organization",
+                Value::Str("CLOUDFLARENET"),
+            ),
+        ]));
+
+        GeoIpDb::new()
+            .try_with_database_bytes(city)
+            .unwrap()
+            .try_with_database_bytes(asn)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_geo_ip_db_lookup() {
+        let db = test_db();
+
+        let info = db.lookup([1, 2, 3, 4].into()).unwrap().unwrap();
+        assert_eq!(
+            IpGeoInfo {
+                continent: Some("EU".to_owned()),
+                country: Some("BE".to_owned()),
+                state: Some("VOV".to_owned()),
+                city: Some("Ghent".to_owned()),
+                asn: Some(Asn::from_static(13335)),
+                as_organization: Some("CLOUDFLARENET".to_owned()),
+            },
+            info
+        );
+
+        assert_eq!(
+            Some(info),
+            db.lookup("::ffff:1.2.3.4".parse().unwrap()).unwrap()
+        );
+        assert!(db.lookup([200, 0, 0, 1].into()).unwrap().is_none());
+        assert!(db.lookup("2001:db8::1".parse().unwrap()).unwrap().is_none());
+
+        assert!(
+            GeoIpDb::new()
+                .try_with_database_bytes(b"foo".to_vec())
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_geo_ip_layer() {
+        let svc =
+            GeoIpLayer::new(test_db()).into_layer(service_fn(async |ctx: Context<()>, ()| {
+                Ok::<_, Infallible>(ctx.get::<IpGeoInfo>().and_then(|info| info.country.clone()))
+            }));
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([1, 2, 3, 4], 8080).into()));
+        assert_eq!(Some("BE".to_owned()), svc.serve(ctx, ()).await.unwrap());
+
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(None, ([200, 0, 0, 1], 8080).into()));
+        ctx.insert(Forwarded::new(ForwardedElement::forwarded_for(
+            IpAddr::from([1, 2, 3, 4]),
+        )));
+        assert_eq!(None, svc.serve(ctx.clone(), ()).await.unwrap());
+
+        let svc = GeoIpLayer::new(test_db())
+            .with_forwarded_client_ip(true)
+            .into_layer(service_fn(async |ctx: Context<()>, ()| {
+                Ok::<_, Infallible>(ctx.get::<IpGeoInfo>().and_then(|info| info.country.clone()))
+            }));
+        assert_eq!(Some("BE".to_owned()), svc.serve(ctx, ()).await.unwrap());
+    }
+}
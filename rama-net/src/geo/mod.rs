@@ -0,0 +1,66 @@
+//! Geographical and network information of IP addresses,
+//! such as the country and autonomous system (AS) an IP address belongs to.
+//!
+//! See [`IpGeoInfo`] for more information. With the `mmdb` feature enabled
+//! it can be looked up from local MaxMind DB (`.mmdb`) databases,
+//! see `GeoIpDb` and `GeoIpLayer`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::asn::Asn;
+
+#[cfg(feature = "mmdb")]
+mod mmdb;
+#[cfg(feature = "mmdb")]
+#[doc(inline)]
+pub use mmdb::{GeoIpDb, GeoIpLayer, GeoIpService};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Geographical and network information of an IP address.
+///
+/// Inserted as a [`Context`] extension by the `GeoIpLayer`
+/// for the IP address of the client, such that it can be used
+/// for routing, (proxy) filtering or logging.
+///
+/// All information is optional, as it depends on the source it was looked up from.
+///
+/// [`Context`]: rama_core::Context
+pub struct IpGeoInfo {
+    /// Continent code, e.g. `EU`.
+    pub continent: Option<String>,
+    /// Country (ISO 3166-1 alpha-2) code, e.g. `BE`.
+    pub country: Option<String>,
+    /// State or province (ISO 3166-2 subdivision) code, e.g. `VOV`.
+    pub state: Option<String>,
+    /// City name (in English), e.g. `Ghent`.
+    pub city: Option<String>,
+    /// Autonomous System Number (ASN) of the network.
+    pub asn: Option<Asn>,
+    /// Organization the autonomous system is registered to.
+    pub as_organization: Option<String>,
+}
+
+impl IpGeoInfo {
+    /// Returns `true` in case no information is known.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Merge the `other` info into this info,
+    /// filling in the information which is not yet known.
+    pub fn merge(&mut self, other: Self) -> &mut Self {
+        fn merge_field<T>(field: &mut Option<T>, other: Option<T>) {
+            if field.is_none() {
+                *field = other;
+            }
+        }
+
+        merge_field(&mut self.continent, other.continent);
+        merge_field(&mut self.country, other.country);
+        merge_field(&mut self.state, other.state);
+        merge_field(&mut self.city, other.city);
+        merge_field(&mut self.asn, other.asn);
+        merge_field(&mut self.as_organization, other.as_organization);
+        self
+    }
+}
@@ -21,6 +21,7 @@ pub mod asn;
 pub mod client;
 pub mod conn;
 pub mod forwarded;
+pub mod geo;
 pub mod mode;
 pub mod proxy;
 pub mod stream;
@@ -40,7 +41,9 @@ pub mod http;
 #[cfg(feature = "tls")]
 pub mod tls;
 
-#[cfg(any(feature = "tls", feature = "http"))]
+#[cfg(feature = "quic")]
+pub mod quic;
+
 pub mod fingerprint;
 
 pub mod socket;
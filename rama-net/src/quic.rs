@@ -0,0 +1,560 @@
+//! QUIC support, without terminating QUIC connections.
+//!
+//! The QUIC Initial packets sent by a client are protected
+//! using keys derived from the (public) destination connection id,
+//! as specified in <https://www.rfc-editor.org/rfc/rfc9001#section-5.2>.
+//! This allows a passive observer, e.g. a UDP proxy, to decrypt them and retrieve the
+//! TLS [`ClientHello`], so it can be used for (SNI) routing and fingerprinting.
+//!
+//! Use [`parse_quic_client_hello`] in case the [`ClientHello`] fits
+//! in a single datagram, or [`QuicClientHelloParser`] to reassemble it
+//! from multiple datagrams.
+
+use aws_lc_rs::{
+    aead::{self, quic::HeaderProtectionKey},
+    hkdf,
+};
+use rama_core::error::OpaqueError;
+use std::collections::BTreeMap;
+
+use crate::tls::client::{ClientHello, parse_client_hello};
+
+/// Parse the [`ClientHello`] from the QUIC Initial packet(s) within a single UDP datagram.
+///
+/// Returns `None` in case the datagram contains valid Initial packets,
+/// but not the complete [`ClientHello`], in which case you can
+/// use [`QuicClientHelloParser`] to reassemble it from multiple datagrams.
+pub fn parse_quic_client_hello(datagram: &[u8]) -> Result<Option<ClientHello>, OpaqueError> {
+    QuicClientHelloParser::new().push_datagram(datagram)
+}
+
+#[derive(Debug, Clone, Default)]
+/// Parser which decrypts the Initial packets of a QUIC client
+/// and reassembles the [`ClientHello`] from their CRYPTO frames,
+/// which might be spread over multiple packets and datagrams.
+///
+/// Both QUIC version 1 (RFC 9000) and version 2 (RFC 9369) are supported.
+pub struct QuicClientHelloParser {
+    chunks: BTreeMap<u64, Vec<u8>>,
+    buffered: usize,
+}
+
+/// Maximum amount of CRYPTO data buffered while reassembling the [`ClientHello`].
+const MAX_CRYPTO_BUFFER_SIZE: usize = 64 * 1024;
+
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+
+impl QuicClientHelloParser {
+    /// Create a new [`QuicClientHelloParser`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push a UDP datagram received from the QUIC client into this parser.
+    ///
+    /// Returns the [`ClientHello`] once it is completely received,
+    /// and `None` in case more datagrams are required.
+    pub fn push_datagram(&mut self, datagram: &[u8]) -> Result<Option<ClientHello>, OpaqueError> {
+        let mut rest = datagram;
+        // coalesced packets are all long header packets,
+        // a short header packet or padding is always the last one
+        while rest.first().is_some_and(|b| b & 0x80 != 0) {
+            let (packet_len, payload) = decrypt_client_initial_packet(rest)?;
+            if let Some(payload) = payload {
+                self.push_frames(&payload)?;
+            }
+            rest = &rest[packet_len..];
+        }
+        self.try_client_hello()
+    }
+
+    fn push_frames(&mut self, mut payload: &[u8]) -> Result<(), OpaqueError> {
+        while !payload.is_empty() {
+            match read_varint(&mut payload)? {
+                // PADDING, PING
+                0x00 | 0x01 => (),
+                // ACK
+                frame_type @ (0x02 | 0x03) => {
+                    read_varint(&mut payload)?; // largest acknowledged
+                    read_varint(&mut payload)?; // ack delay
+                    let range_count = read_varint(&mut payload)?;
+                    read_varint(&mut payload)?; // first ack range
+                    for _ in 0..range_count {
+                        read_varint(&mut payload)?; // gap
+                        read_varint(&mut payload)?; // ack range length
+                    }
+                    if frame_type == 0x03 {
+                        for _ in 0..3 {
+                            read_varint(&mut payload)?; // ECN counts
+                        }
+                    }
+                }
+                // CRYPTO
+                0x06 => {
+                    let offset = read_varint(&mut payload)?;
+                    let len = read_varint(&mut payload)? as usize;
+                    let data = read_bytes(&mut payload, len)?;
+                    self.push_crypto_data(offset, data)?;
+                }
+                // CONNECTION_CLOSE
+                0x1c => {
+                    read_varint(&mut payload)?; // error code
+                    read_varint(&mut payload)?; // frame type
+                    let len = read_varint(&mut payload)? as usize;
+                    read_bytes(&mut payload, len)?; // reason phrase
+                }
+                frame_type => {
+                    return Err(OpaqueError::from_display(format!(
+                        "quic: unexpected frame type in initial packet: {frame_type:#x}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push_crypto_data(&mut self, offset: u64, data: &[u8]) -> Result<(), OpaqueError> {
+        if self.buffered + data.len() > MAX_CRYPTO_BUFFER_SIZE {
+            return Err(OpaqueError::from_display(
+                "quic: crypto data exceeds maximum buffer size",
+            ));
+        }
+        let chunk = self.chunks.entry(offset).or_default();
+        if data.len() > chunk.len() {
+            self.buffered += data.len() - chunk.len();
+            *chunk = data.to_vec();
+        }
+        Ok(())
+    }
+
+    fn try_client_hello(&self) -> Result<Option<ClientHello>, OpaqueError> {
+        let mut data = Vec::new();
+        for (offset, chunk) in &self.chunks {
+            let offset = *offset as usize;
+            if offset > data.len() {
+                break;
+            }
+            let skip = data.len() - offset;
+            if skip < chunk.len() {
+                data.extend_from_slice(&chunk[skip..]);
+            }
+        }
+
+        if data.len() < 4 {
+            return Ok(None);
+        }
+        if data[0] != HANDSHAKE_TYPE_CLIENT_HELLO {
+            return Err(OpaqueError::from_display(
+                "quic: initial crypto data does not start with a client hello",
+            ));
+        }
+        let len = u32::from_be_bytes([0, data[1], data[2], data[3]]) as usize;
+        if data.len() < 4 + len {
+            return Ok(None);
+        }
+        parse_client_hello(&data[4..4 + len]).map(Some)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuicVersion {
+    V1,
+    V2,
+}
+
+impl QuicVersion {
+    fn from_u32(version: u32) -> Option<Self> {
+        match version {
+            0x0000_0001 => Some(Self::V1),
+            0x6b33_43cf => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    fn initial_salt(self) -> &'static [u8] {
+        match self {
+            Self::V1 => &[
+                0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8,
+                0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+            ],
+            Self::V2 => &[
+                0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93, 0x81, 0xbe, 0x6e, 0x26,
+                0x9d, 0xcb, 0xf9, 0xbd, 0x2e, 0xd9,
+            ],
+        }
+    }
+
+    fn labels(self) -> (&'static [u8], &'static [u8], &'static [u8]) {
+        match self {
+            Self::V1 => (b"quic key", b"quic iv", b"quic hp"),
+            Self::V2 => (b"quicv2 key", b"quicv2 iv", b"quicv2 hp"),
+        }
+    }
+
+    fn initial_packet_type(self) -> u8 {
+        match self {
+            Self::V1 => 0b00,
+            Self::V2 => 0b01,
+        }
+    }
+
+    fn retry_packet_type(self) -> u8 {
+        match self {
+            Self::V1 => 0b11,
+            Self::V2 => 0b00,
+        }
+    }
+}
+
+/// The keys used to protect the Initial packets sent by the client.
+struct ClientInitialKeys {
+    key: aead::LessSafeKey,
+    iv: [u8; aead::NONCE_LEN],
+    hp: HeaderProtectionKey,
+}
+
+impl ClientInitialKeys {
+    fn new(version: QuicVersion, dcid: &[u8]) -> Result<Self, OpaqueError> {
+        let initial_secret =
+            hkdf::Salt::new(hkdf::HKDF_SHA256, version.initial_salt()).extract(dcid);
+        let client_secret: hkdf::Prk =
+            hkdf_expand_label(&initial_secret, b"client in", hkdf::HKDF_SHA256, |okm| {
+                Ok(okm.into())
+            })?;
+
+        let (key_label, iv_label, hp_label) = version.labels();
+
+        let mut key = [0; 16];
+        fill_expand_label(&client_secret, key_label, &mut key)?;
+        let mut iv = [0; aead::NONCE_LEN];
+        fill_expand_label(&client_secret, iv_label, &mut iv)?;
+        let mut hp = [0; 16];
+        fill_expand_label(&client_secret, hp_label, &mut hp)?;
+
+        Ok(Self {
+            key: aead::LessSafeKey::new(
+                aead::UnboundKey::new(&aead::AES_128_GCM, &key)
+                    .map_err(|_| OpaqueError::from_display("quic: create initial aead key"))?,
+            ),
+            iv,
+            hp: HeaderProtectionKey::new(&aead::quic::AES_128, &hp).map_err(|_| {
+                OpaqueError::from_display("quic: create initial header protection key")
+            })?,
+        })
+    }
+
+    fn nonce(&self, packet_number: u64) -> aead::Nonce {
+        let mut nonce = self.iv;
+        for (n, b) in nonce.iter_mut().rev().zip(packet_number.to_le_bytes()) {
+            *n ^= b;
+        }
+        aead::Nonce::assume_unique_for_key(nonce)
+    }
+}
+
+struct OutputLen(usize);
+
+impl hkdf::KeyType for OutputLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// HKDF-Expand-Label as defined in <https://www.rfc-editor.org/rfc/rfc8446#section-7.1>,
+/// with an empty context.
+fn hkdf_expand_label<L: hkdf::KeyType, T>(
+    prk: &hkdf::Prk,
+    label: &[u8],
+    len: L,
+    f: impl FnOnce(hkdf::Okm<'_, L>) -> Result<T, aws_lc_rs::error::Unspecified>,
+) -> Result<T, OpaqueError> {
+    let out_len = (len.len() as u16).to_be_bytes();
+    let label_len = [(6 + label.len()) as u8];
+    let info: [&[u8]; 5] = [&out_len, &label_len, b"tls13 ", label, &[0]];
+    prk.expand(&info, len)
+        .and_then(f)
+        .map_err(|_| OpaqueError::from_display("quic: hkdf expand label"))
+}
+
+fn fill_expand_label(prk: &hkdf::Prk, label: &[u8], out: &mut [u8]) -> Result<(), OpaqueError> {
+    hkdf_expand_label(prk, label, OutputLen(out.len()), |okm| okm.fill(out))
+}
+
+/// Decrypt the long header packet at the start of the given input.
+///
+/// Returns the length of the packet and, in case it is an Initial packet, its decrypted payload.
+fn decrypt_client_initial_packet(packet: &[u8]) -> Result<(usize, Option<Vec<u8>>), OpaqueError> {
+    let mut i = &packet[1..];
+
+    let version = u32::from_be_bytes(
+        read_bytes(&mut i, 4)?
+            .try_into()
+            .expect("4 bytes read for version"),
+    );
+    let version = QuicVersion::from_u32(version).ok_or_else(|| {
+        OpaqueError::from_display(format!("quic: unsupported version: {version:#x}"))
+    })?;
+
+    let dcid_len = read_bytes(&mut i, 1)?[0] as usize;
+    if dcid_len > 20 {
+        return Err(OpaqueError::from_display(
+            "quic: invalid destination connection id length",
+        ));
+    }
+    let dcid = read_bytes(&mut i, dcid_len)?;
+    let scid_len = read_bytes(&mut i, 1)?[0] as usize;
+    if scid_len > 20 {
+        return Err(OpaqueError::from_display(
+            "quic: invalid source connection id length",
+        ));
+    }
+    read_bytes(&mut i, scid_len)?;
+
+    let packet_type = (packet[0] >> 4) & 0b11;
+    if packet_type == version.retry_packet_type() {
+        // retry packets have no length and are never coalesced
+        return Ok((packet.len(), None));
+    }
+
+    let is_initial = packet_type == version.initial_packet_type();
+    if is_initial {
+        let token_len = read_varint(&mut i)? as usize;
+        read_bytes(&mut i, token_len)?;
+    }
+    let length = read_varint(&mut i)? as usize;
+    let pn_offset = packet.len() - i.len();
+    let end = pn_offset + length;
+    if end > packet.len() {
+        return Err(OpaqueError::from_display(
+            "quic: truncated long header packet",
+        ));
+    }
+    if !is_initial {
+        return Ok((end, None));
+    }
+
+    // packet number (max 4 bytes) + sample (16 bytes)
+    if length < 20 {
+        return Err(OpaqueError::from_display("quic: initial packet too short"));
+    }
+
+    let keys = ClientInitialKeys::new(version, dcid)?;
+    let mask = keys
+        .hp
+        .new_mask(&packet[pn_offset + 4..pn_offset + 20])
+        .map_err(|_| OpaqueError::from_display("quic: compute header protection mask"))?;
+
+    let mut buf = packet[..end].to_vec();
+    buf[0] ^= mask[0] & 0x0f;
+    let pn_len = (buf[0] & 0b11) as usize + 1;
+    let mut packet_number = 0u64;
+    for (n, m) in buf[pn_offset..pn_offset + pn_len]
+        .iter_mut()
+        .zip(&mask[1..])
+    {
+        *n ^= m;
+        packet_number = (packet_number << 8) | u64::from(*n);
+    }
+
+    let (header, payload) = buf.split_at_mut(pn_offset + pn_len);
+    let plaintext = keys
+        .key
+        .open_in_place(
+            keys.nonce(packet_number),
+            aead::Aad::from(&header[..]),
+            payload,
+        )
+        .map_err(|_| OpaqueError::from_display("quic: decrypt initial packet"))?;
+
+    Ok((end, Some(plaintext.to_vec())))
+}
+
+fn read_bytes<'a>(i: &mut &'a [u8], n: usize) -> Result<&'a [u8], OpaqueError> {
+    if i.len() < n {
+        return Err(OpaqueError::from_display("quic: unexpected end of input"));
+    }
+    let (bytes, rest) = i.split_at(n);
+    *i = rest;
+    Ok(bytes)
+}
+
+/// Read a variable-length integer as defined in <https://www.rfc-editor.org/rfc/rfc9000#section-16>.
+fn read_varint(i: &mut &[u8]) -> Result<u64, OpaqueError> {
+    let first = read_bytes(i, 1)?[0];
+    let len = 1 << (first >> 6);
+    let mut value = u64::from(first & 0x3f);
+    for b in read_bytes(i, len - 1)? {
+        value = (value << 8) | u64::from(*b);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::ExtensionId;
+
+    const DCID: &[u8] = &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+    #[test]
+    fn test_client_initial_keys_rfc9001() {
+        // test vectors from <https://www.rfc-editor.org/rfc/rfc9001#appendix-A.1>
+        let keys = ClientInitialKeys::new(QuicVersion::V1, DCID).unwrap();
+        assert_eq!(
+            [
+                0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c
+            ],
+            keys.iv
+        );
+        let mask = keys
+            .hp
+            .new_mask(&[
+                0xd1, 0xb1, 0xc9, 0x8d, 0xd7, 0x68, 0x9f, 0xb8, 0xec, 0x11, 0xd2, 0x42, 0xb1, 0x23,
+                0xdc, 0x9b,
+            ])
+            .unwrap();
+        assert_eq!([0x43, 0x7b, 0x9a, 0xec, 0x36], mask);
+    }
+
+    fn client_hello_handshake() -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0xaa; 32]); // random
+        body.push(0x00); // session id
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // cipher suites
+        body.extend_from_slice(&[0x01, 0x00]); // compression
+
+        let mut extensions = vec![
+            0x00, 0x00, 0x00, 0x10, 0x00, 0x0e, 0x00, 0x00, 0x0b, // server name
+        ];
+        extensions.extend_from_slice(b"example.com");
+        extensions.extend_from_slice(&[0x00, 0x39, 0x00, 0x04, 0x01, 0x02, 0x40, 0x64]); // quic transport params
+        extensions.extend_from_slice(&[0x00, 0x15, 0x00, 0xc8]); // padding
+        extensions.extend_from_slice(&[0; 200]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut msg = vec![HANDSHAKE_TYPE_CLIENT_HELLO];
+        msg.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        msg.extend_from_slice(&body);
+        msg
+    }
+
+    fn crypto_frame(offset: u64, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x06];
+        frame.extend_from_slice(&(0x4000 | offset as u16).to_be_bytes());
+        frame.extend_from_slice(&(0x4000 | data.len() as u16).to_be_bytes());
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    fn client_initial_packet(version: QuicVersion, packet_number: u8, frames: &[u8]) -> Vec<u8> {
+        let keys = ClientInitialKeys::new(version, DCID).unwrap();
+
+        let mut payload = frames.to_vec();
+        payload.resize(payload.len().max(64), 0); // padding
+
+        let (version_bytes, first) = match version {
+            QuicVersion::V1 => ([0x00, 0x00, 0x00, 0x01], 0xc0),
+            QuicVersion::V2 => ([0x6b, 0x33, 0x43, 0xcf], 0xd0),
+        };
+        let mut packet = vec![first]; // 1 byte packet number
+        packet.extend_from_slice(&version_bytes);
+        packet.push(DCID.len() as u8);
+        packet.extend_from_slice(DCID);
+        packet.extend_from_slice(&[0x00, 0x00]); // scid, token
+        let length = 1 + payload.len() + 16;
+        packet.extend_from_slice(&(0x4000 | length as u16).to_be_bytes());
+        let pn_offset = packet.len();
+        packet.push(packet_number);
+
+        let tag = keys
+            .key
+            .seal_in_place_separate_tag(
+                keys.nonce(u64::from(packet_number)),
+                aead::Aad::from(&packet[..]),
+                &mut payload,
+            )
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(tag.as_ref());
+
+        let mask = keys
+            .hp
+            .new_mask(&packet[pn_offset + 4..pn_offset + 20])
+            .unwrap();
+        packet[0] ^= mask[0] & 0x0f;
+        packet[pn_offset] ^= mask[1];
+        packet
+    }
+
+    #[test]
+    fn test_parse_quic_client_hello_single_datagram() {
+        for version in [QuicVersion::V1, QuicVersion::V2] {
+            let packet =
+                client_initial_packet(version, 0, &crypto_frame(0, &client_hello_handshake()));
+
+            let hello = parse_quic_client_hello(&packet).unwrap().unwrap();
+            assert_eq!(
+                Some(&crate::address::Domain::from_static("example.com")),
+                hello.ext_server_name()
+            );
+            assert!(
+                hello
+                    .extensions()
+                    .iter()
+                    .any(|ext| ext.id() == ExtensionId::QUIC_TRANSPORT_PARAMETERS)
+            );
+
+            let ja4 = crate::fingerprint::Ja4::compute_from_client_hello(&hello, None).unwrap();
+            assert!(ja4.to_string().starts_with("q"));
+        }
+    }
+
+    #[test]
+    fn test_parse_quic_client_hello_multiple_datagrams() {
+        let handshake = client_hello_handshake();
+        let (a, b) = handshake.split_at(100);
+
+        let mut parser = QuicClientHelloParser::new();
+        // second part arrives first, together with a ping
+        let mut frames = vec![0x01];
+        frames.extend(crypto_frame(100, b));
+        let second = client_initial_packet(QuicVersion::V1, 1, &frames);
+        assert!(parser.push_datagram(&second).unwrap().is_none());
+
+        let first = client_initial_packet(QuicVersion::V1, 0, &crypto_frame(0, a));
+        let hello = parser.push_datagram(&first).unwrap().unwrap();
+        assert_eq!(
+            Some(&crate::address::Domain::from_static("example.com")),
+            hello.ext_server_name()
+        );
+
+        // incomplete client hello within a single datagram
+        assert!(parse_quic_client_hello(&first).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_quic_client_hello_errors() {
+        let mut packet = client_initial_packet(
+            QuicVersion::V1,
+            0,
+            &crypto_frame(0, &client_hello_handshake()),
+        );
+
+        // unknown version
+        let mut unknown = packet.clone();
+        unknown[4] = 0x02;
+        assert!(parse_quic_client_hello(&unknown).is_err());
+
+        // truncated
+        assert!(parse_quic_client_hello(&packet[..packet.len() - 1]).is_err());
+
+        // tampered payload
+        let last = packet.len() - 1;
+        packet[last] ^= 0xff;
+        assert!(parse_quic_client_hello(&packet).is_err());
+
+        // not a long header packet
+        assert!(parse_quic_client_hello(&[0x40, 0x00]).unwrap().is_none());
+    }
+}
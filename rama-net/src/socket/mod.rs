@@ -12,6 +12,9 @@ pub mod opts;
 #[doc(inline)]
 pub use opts::SocketOptions;
 
+#[cfg(target_os = "linux")]
+pub mod syn;
+
 mod svc;
 #[doc(inline)]
 pub use svc::SocketService;
@@ -807,6 +807,16 @@ pub struct SocketOptions {
     /// [`set_freebind`]: SocketOptions::freebind
     pub freebind_ipv6: Option<bool>,
 
+    #[cfg(target_os = "linux")]
+    /// Set value for the `TCP_SAVE_SYN` option on this [`Socket`].
+    ///
+    /// If enabled on a listening [`Socket`], the kernel saves the headers
+    /// of the SYN packet of each accepted connection, which can be retrieved
+    /// once per connection using [`tcp_saved_syn`].
+    ///
+    /// [`tcp_saved_syn`]: crate::socket::syn::tcp_saved_syn
+    pub tcp_save_syn: Option<bool>,
+
     #[cfg(target_os = "linux")]
     /// Set value for the `SO_INCOMING_CPU` option on this [`Socket`].
     ///
@@ -1099,6 +1109,11 @@ impl SocketOptions {
             }
         }
 
+        #[cfg(target_os = "linux")]
+        if let Some(save_syn) = self.tcp_save_syn {
+            super::syn::set_tcp_save_syn(&socket, save_syn)?;
+        }
+
         #[cfg(target_os = "linux")]
         if let Some(cpu) = self.cpu_affinity {
             socket.set_cpu_affinity(cpu)?;
@@ -0,0 +1,118 @@
+//! Access to the SYN packet saved by the Linux kernel for accepted TCP connections,
+//! using the `TCP_SAVE_SYN` and `TCP_SAVED_SYN` socket options.
+
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd},
+};
+
+/// Set the value of the `TCP_SAVE_SYN` option on a (listening) TCP socket.
+///
+/// When enabled the kernel keeps the headers of the SYN packet
+/// of each accepted connection, which can be retrieved once using [`tcp_saved_syn`].
+pub fn set_tcp_save_syn(socket: &impl AsFd, save_syn: bool) -> io::Result<()> {
+    let value: libc::c_int = save_syn.into();
+    // SAFETY: the fd is valid for the lifetime of the borrow,
+    // and the value pointer and length match the c_int option type
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_fd().as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_SAVE_SYN,
+            (&value as *const libc::c_int).cast(),
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Get the value of the `TCP_SAVE_SYN` option of a TCP socket.
+///
+/// See [`set_tcp_save_syn`] for more information.
+pub fn tcp_save_syn(socket: &impl AsFd) -> io::Result<bool> {
+    let mut value: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: the fd is valid for the lifetime of the borrow,
+    // and the value pointer and length match the c_int option type
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_fd().as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_SAVE_SYN,
+            (&mut value as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value != 0)
+}
+
+/// Initial buffer size used to retrieve the saved SYN,
+/// enough for the maximum IPv4 and TCP header sizes.
+const SAVED_SYN_BUFFER_SIZE: usize = 256;
+
+/// Take the SYN packet headers (IP and TCP) saved by the kernel
+/// for an accepted TCP connection, using the `TCP_SAVED_SYN` option.
+///
+/// This requires `TCP_SAVE_SYN` to be enabled on the listening socket,
+/// see [`set_tcp_save_syn`]. The kernel only returns the saved SYN once,
+/// so consecutive calls return `None`.
+pub fn tcp_saved_syn(socket: &impl AsFd) -> io::Result<Option<Vec<u8>>> {
+    let mut buf = vec![0u8; SAVED_SYN_BUFFER_SIZE];
+    loop {
+        let mut len = buf.len() as libc::socklen_t;
+        // SAFETY: the fd is valid for the lifetime of the borrow,
+        // and the buffer is valid for writes of up to `len` bytes
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_fd().as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_SAVED_SYN,
+                buf.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if ret == -1 {
+            let err = io::Error::last_os_error();
+            // the kernel reports the required size in case the buffer is too small
+            if err.raw_os_error() == Some(libc::EINVAL) && len as usize > buf.len() {
+                buf.resize(len as usize, 0);
+                continue;
+            }
+            return Err(err);
+        }
+        if len == 0 {
+            return Ok(None);
+        }
+        buf.truncate(len as usize);
+        return Ok(Some(buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcp_saved_syn() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(!tcp_save_syn(&listener).unwrap());
+        set_tcp_save_syn(&listener, true).unwrap();
+        assert!(tcp_save_syn(&listener).unwrap());
+
+        let _client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let syn = tcp_saved_syn(&stream).unwrap().unwrap();
+        // IPv4 header without options, followed by at least a TCP header
+        assert_eq!(0x45, syn[0]);
+        assert!(syn.len() >= 40);
+
+        assert!(tcp_saved_syn(&stream).unwrap().is_none());
+    }
+}
@@ -9,6 +9,14 @@ pub use tracker::{
     OutgoingBytesTrackerLayer, OutgoingBytesTrackerService,
 };
 
+mod throttle;
+#[doc(inline)]
+pub use throttle::{
+    BandwidthBuckets, BandwidthLimit, BandwidthThrottleHandle, IncomingBandwidthThrottleLayer,
+    IncomingBandwidthThrottleService, KeyedTokenBuckets, OutgoingBandwidthThrottleLayer,
+    OutgoingBandwidthThrottleService, ThrottledStream, TokenBucket,
+};
+
 #[cfg(feature = "http")]
 pub mod http;
 
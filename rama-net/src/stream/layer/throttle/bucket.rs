@@ -0,0 +1,202 @@
+use parking_lot::Mutex;
+use std::{collections::HashMap, fmt, hash::Hash, num::NonZeroU64, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A bandwidth limit, expressed as a sustained rate in bytes per second,
+/// and a burst size in bytes which can be transferred at once after being idle.
+pub struct BandwidthLimit {
+    bytes_per_second: NonZeroU64,
+    burst: NonZeroU64,
+}
+
+impl BandwidthLimit {
+    /// Create a new [`BandwidthLimit`] for the given rate in bytes per second,
+    /// with a burst size equal to the amount of bytes allowed in one second.
+    pub const fn new(bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            bytes_per_second,
+            burst: bytes_per_second,
+        }
+    }
+
+    /// Set the burst size of this [`BandwidthLimit`],
+    /// the amount of bytes which can be transferred at once after being idle.
+    pub const fn with_burst(mut self, burst: NonZeroU64) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// The sustained rate in bytes per second.
+    pub const fn bytes_per_second(&self) -> NonZeroU64 {
+        self.bytes_per_second
+    }
+
+    /// The burst size in bytes.
+    pub const fn burst(&self) -> NonZeroU64 {
+        self.burst
+    }
+}
+
+#[derive(Clone)]
+/// A token bucket which enforces a [`BandwidthLimit`].
+///
+/// Cloning a [`TokenBucket`] shares the underlying bucket, such that it can be used to enforce
+/// a single [`BandwidthLimit`] across multiple streams, e.g. for all streams of a user.
+pub struct TokenBucket {
+    limit: BandwidthLimit,
+    state: Arc<Mutex<TokenBucketState>>,
+}
+
+#[derive(Debug)]
+struct TokenBucketState {
+    // can be negative as tokens are consumed after the fact
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl fmt::Debug for TokenBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenBucket")
+            .field("limit", &self.limit)
+            .field("state", &self.state.lock())
+            .finish()
+    }
+}
+
+impl TokenBucket {
+    /// Create a new (full) [`TokenBucket`] for the given [`BandwidthLimit`].
+    pub fn new(limit: BandwidthLimit) -> Self {
+        Self {
+            limit,
+            state: Arc::new(Mutex::new(TokenBucketState {
+                tokens: limit.burst.get() as f64,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// The [`BandwidthLimit`] enforced by this [`TokenBucket`].
+    pub fn limit(&self) -> BandwidthLimit {
+        self.limit
+    }
+
+    /// Returns the amount of bytes which can be transferred right now,
+    /// or the duration to wait until a reasonable amount of bytes can be transferred.
+    pub(super) fn available(&self, now: Instant) -> Result<usize, Duration> {
+        let mut state = self.state.lock();
+        self.refill(&mut state, now);
+        if state.tokens >= 1.0 {
+            return Ok(state.tokens as usize);
+        }
+
+        // wait for (at most) 50ms worth of tokens, to avoid tiny reads and writes
+        let rate = self.limit.bytes_per_second.get() as f64;
+        let wanted = (rate / 20.0).clamp(1.0, self.limit.burst.get() as f64);
+        Err(Duration::from_secs_f64((wanted - state.tokens) / rate))
+    }
+
+    /// Consume the given amount of bytes from this [`TokenBucket`].
+    pub(super) fn consume(&self, n: usize) {
+        self.state.lock().tokens -= n as f64;
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let mut state = self.state.lock();
+        self.refill(&mut state, now);
+        state.tokens >= self.limit.burst.get() as f64
+    }
+
+    fn refill(&self, state: &mut TokenBucketState, now: Instant) {
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.last_refill = now;
+        state.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(self.limit.bytes_per_second.get() as f64, state.tokens)
+            .min(self.limit.burst.get() as f64);
+    }
+}
+
+/// A collection of [`TokenBucket`]s, one per key, all enforcing the same [`BandwidthLimit`].
+///
+/// Useful to enforce a bandwidth limit per user or proxy id, shared across all their streams.
+/// Buckets which are no longer in use and are full are cleaned up automatically.
+pub struct KeyedTokenBuckets<K> {
+    limit: BandwidthLimit,
+    buckets: Arc<Mutex<HashMap<K, TokenBucket>>>,
+}
+
+impl<K> Clone for KeyedTokenBuckets<K> {
+    fn clone(&self) -> Self {
+        Self {
+            limit: self.limit,
+            buckets: self.buckets.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for KeyedTokenBuckets<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedTokenBuckets")
+            .field("limit", &self.limit)
+            .field("buckets", &self.buckets.lock())
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq> KeyedTokenBuckets<K> {
+    /// Create a new [`KeyedTokenBuckets`] where each bucket enforces the given [`BandwidthLimit`].
+    pub fn new(limit: BandwidthLimit) -> Self {
+        Self {
+            limit,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Get the [`TokenBucket`] for the given key, creating it if it does not exist yet.
+    pub fn bucket(&self, key: K) -> TokenBucket {
+        let mut buckets = self.buckets.lock();
+        if let Some(bucket) = buckets.get(&key) {
+            return bucket.clone();
+        }
+
+        // cleanup buckets no longer in use, keeping those which are still being refilled,
+        // as dropping those would reset their limit
+        let now = Instant::now();
+        buckets.retain(|_, bucket| Arc::strong_count(&bucket.state) > 1 || !bucket.is_full(now));
+
+        let bucket = TokenBucket::new(self.limit);
+        buckets.insert(key, bucket.clone());
+        bucket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket_and_keyed_buckets() {
+        let limit = BandwidthLimit::new(NonZeroU64::new(100).unwrap())
+            .with_burst(NonZeroU64::new(50).unwrap());
+
+        let buckets = KeyedTokenBuckets::new(limit);
+        let a = buckets.bucket("a");
+        assert_eq!(Ok(50), a.available(Instant::now()));
+
+        // same key shares the bucket
+        buckets.bucket("a").consume(50);
+        // wait for 50ms worth of tokens
+        let wait = a.available(Instant::now()).unwrap_err();
+        assert!(wait.abs_diff(Duration::from_millis(50)) < Duration::from_micros(1));
+        assert_eq!(Ok(50), buckets.bucket("b").available(Instant::now()));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(Ok(50), a.available(Instant::now()));
+
+        // unused and full buckets are cleaned up
+        drop(a);
+        buckets.bucket("c");
+        assert_eq!(1, buckets.buckets.lock().len());
+    }
+}
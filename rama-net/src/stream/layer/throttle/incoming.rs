@@ -0,0 +1,208 @@
+use super::{BandwidthBuckets, BandwidthLimit, ThrottledStream};
+use crate::stream::Stream;
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::{define_inner_service_accessors, generate_set_and_with};
+use std::fmt;
+
+/// A [`Service`] that wraps a [`Service`]'s input IO [`Stream`] with a bandwidth throttle.
+///
+/// Each stream gets its own per-connection [`BandwidthLimit`]s (if configured),
+/// on top of any [`BandwidthBuckets`] found in the [`Context`].
+/// A [`BandwidthThrottleHandle`] is inserted in the [`Context`],
+/// such that shared buckets can be attached later on (e.g. once the user is known).
+///
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+/// [`BandwidthThrottleHandle`]: super::BandwidthThrottleHandle
+pub struct IncomingBandwidthThrottleService<S> {
+    inner: S,
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for IncomingBandwidthThrottleService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingBandwidthThrottleService")
+            .field("inner", &self.inner)
+            .field("read_limit", &self.read_limit)
+            .field("write_limit", &self.write_limit)
+            .finish()
+    }
+}
+
+impl<S> IncomingBandwidthThrottleService<S> {
+    /// Create a new [`IncomingBandwidthThrottleService`],
+    /// without any per-connection limits.
+    ///
+    /// See [`IncomingBandwidthThrottleService`] for more information.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            read_limit: None,
+            write_limit: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for reading from the stream.
+        pub fn read_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.read_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for writing to the stream.
+        pub fn write_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.write_limit = limit;
+            self
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S> Clone for IncomingBandwidthThrottleService<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            read_limit: self.read_limit,
+            write_limit: self.write_limit,
+        }
+    }
+}
+
+pub(super) fn throttle_stream<State, IO>(
+    ctx: &mut Context<State>,
+    stream: IO,
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+) -> ThrottledStream<IO> {
+    let mut stream = ThrottledStream::new(stream);
+    if let Some(limit) = read_limit {
+        stream = stream.with_read_limit(limit);
+    }
+    if let Some(limit) = write_limit {
+        stream = stream.with_write_limit(limit);
+    }
+    let handle = stream.handle();
+    if let Some(buckets) = ctx.get::<BandwidthBuckets>() {
+        handle.add_buckets(buckets);
+    }
+    ctx.insert(handle);
+    stream
+}
+
+impl<State, S, IO> Service<State, IO> for IncomingBandwidthThrottleService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, ThrottledStream<IO>>,
+    IO: Stream,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        mut ctx: Context<State>,
+        stream: IO,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let stream = throttle_stream(&mut ctx, stream, self.read_limit, self.write_limit);
+        self.inner.serve(ctx, stream)
+    }
+}
+
+/// A [`Layer`] that wraps a [`Service`]'s input IO [`Stream`] with a bandwidth throttle.
+///
+/// See [`IncomingBandwidthThrottleService`] for more information.
+///
+/// [`Layer`]: rama_core::Layer
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+#[derive(Debug, Clone, Default)]
+pub struct IncomingBandwidthThrottleLayer {
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+}
+
+impl IncomingBandwidthThrottleLayer {
+    /// Create a new [`IncomingBandwidthThrottleLayer`],
+    /// without any per-connection limits.
+    pub const fn new() -> Self {
+        Self {
+            read_limit: None,
+            write_limit: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for reading from the stream.
+        pub fn read_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.read_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for writing to the stream.
+        pub fn write_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.write_limit = limit;
+            self
+        }
+    }
+}
+
+impl<S> Layer<S> for IncomingBandwidthThrottleLayer {
+    type Service = IncomingBandwidthThrottleService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IncomingBandwidthThrottleService {
+            inner,
+            read_limit: self.read_limit,
+            write_limit: self.write_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::{BandwidthThrottleHandle, TokenBucket};
+    use rama_core::service::service_fn;
+    use std::{convert::Infallible, num::NonZeroU64, time::Duration};
+    use tokio::{io::AsyncReadExt, time::Instant};
+    use tokio_test::io::Builder;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_incoming_throttle_with_shared_bucket() {
+        let per_connection = BandwidthLimit::new(NonZeroU64::new(1000).unwrap());
+        let shared = TokenBucket::new(BandwidthLimit::new(NonZeroU64::new(100).unwrap()));
+
+        let svc = IncomingBandwidthThrottleLayer::new()
+            .with_read_limit(per_connection)
+            .into_layer(service_fn(
+                async |ctx: Context<()>, mut stream: ThrottledStream<tokio_test::io::Mock>| {
+                    assert!(ctx.contains::<BandwidthThrottleHandle>());
+                    let start = Instant::now();
+                    let mut buf = vec![0; 300];
+                    stream.read_exact(&mut buf).await.unwrap();
+                    Ok::<_, Infallible>(start.elapsed())
+                },
+            ));
+
+        let mut ctx = Context::default();
+        ctx.insert(BandwidthBuckets::new().with_read(shared));
+        let elapsed = svc
+            .serve(ctx, Builder::new().read(&[1; 300]).build())
+            .await
+            .unwrap();
+
+        // limited by the shared bucket: 100 bytes burst, 200 bytes at 100 bytes per second
+        assert!(elapsed >= Duration::from_secs(2), "elapsed: {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(3), "elapsed: {elapsed:?}");
+    }
+}
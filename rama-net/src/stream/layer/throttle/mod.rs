@@ -0,0 +1,24 @@
+//! Bandwidth throttling of [`Stream`]s, using token buckets.
+//!
+//! Limits can be applied per connection, using [`IncomingBandwidthThrottleLayer`]
+//! for accepted streams and [`OutgoingBandwidthThrottleLayer`] for established connections,
+//! as well as shared across connections (e.g. per user or proxy id) using
+//! [`KeyedTokenBuckets`] combined with [`BandwidthBuckets`] or a [`BandwidthThrottleHandle`].
+//!
+//! [`Stream`]: crate::stream::Stream
+
+mod bucket;
+#[doc(inline)]
+pub use bucket::{BandwidthLimit, KeyedTokenBuckets, TokenBucket};
+
+mod stream;
+#[doc(inline)]
+pub use stream::{BandwidthBuckets, BandwidthThrottleHandle, ThrottledStream};
+
+mod incoming;
+#[doc(inline)]
+pub use incoming::{IncomingBandwidthThrottleLayer, IncomingBandwidthThrottleService};
+
+mod outgoing;
+#[doc(inline)]
+pub use outgoing::{OutgoingBandwidthThrottleLayer, OutgoingBandwidthThrottleService};
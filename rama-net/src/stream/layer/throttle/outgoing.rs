@@ -0,0 +1,152 @@
+use super::{BandwidthLimit, ThrottledStream, incoming::throttle_stream};
+use crate::{
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+};
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::{define_inner_service_accessors, generate_set_and_with};
+use std::fmt;
+
+/// A [`Service`] that wraps a [`Service`]'s output IO [`Stream`] with a bandwidth throttle.
+///
+/// Each established connection gets its own per-connection [`BandwidthLimit`]s (if configured),
+/// on top of any [`BandwidthBuckets`] found in the [`Context`].
+/// A [`BandwidthThrottleHandle`] is inserted in the [`Context`] of the established connection.
+///
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+/// [`BandwidthBuckets`]: super::BandwidthBuckets
+/// [`BandwidthThrottleHandle`]: super::BandwidthThrottleHandle
+pub struct OutgoingBandwidthThrottleService<S> {
+    inner: S,
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for OutgoingBandwidthThrottleService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutgoingBandwidthThrottleService")
+            .field("inner", &self.inner)
+            .field("read_limit", &self.read_limit)
+            .field("write_limit", &self.write_limit)
+            .finish()
+    }
+}
+
+impl<S> OutgoingBandwidthThrottleService<S> {
+    /// Create a new [`OutgoingBandwidthThrottleService`],
+    /// without any per-connection limits.
+    ///
+    /// See [`OutgoingBandwidthThrottleService`] for more information.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            read_limit: None,
+            write_limit: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for reading from the connection.
+        pub fn read_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.read_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for writing to the connection.
+        pub fn write_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.write_limit = limit;
+            self
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S> Clone for OutgoingBandwidthThrottleService<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            read_limit: self.read_limit,
+            write_limit: self.write_limit,
+        }
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for OutgoingBandwidthThrottleService<S>
+where
+    S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Send + 'static>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = EstablishedClientConnection<ThrottledStream<S::Connection>, State, Request>;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let EstablishedClientConnection { mut ctx, req, conn } =
+            self.inner.connect(ctx, req).await?;
+        let conn = throttle_stream(&mut ctx, conn, self.read_limit, self.write_limit);
+        Ok(EstablishedClientConnection { ctx, req, conn })
+    }
+}
+
+/// A [`Layer`] that wraps a [`Service`]'s output IO [`Stream`] with a bandwidth throttle.
+///
+/// See [`OutgoingBandwidthThrottleService`] for more information.
+///
+/// [`Layer`]: rama_core::Layer
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+#[derive(Debug, Clone, Default)]
+pub struct OutgoingBandwidthThrottleLayer {
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+}
+
+impl OutgoingBandwidthThrottleLayer {
+    /// Create a new [`OutgoingBandwidthThrottleLayer`],
+    /// without any per-connection limits.
+    pub const fn new() -> Self {
+        Self {
+            read_limit: None,
+            write_limit: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for reading from the connection.
+        pub fn read_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.read_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the per-connection [`BandwidthLimit`] for writing to the connection.
+        pub fn write_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.write_limit = limit;
+            self
+        }
+    }
+}
+
+impl<S> Layer<S> for OutgoingBandwidthThrottleLayer {
+    type Service = OutgoingBandwidthThrottleService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OutgoingBandwidthThrottleService {
+            inner,
+            read_limit: self.read_limit,
+            write_limit: self.write_limit,
+        }
+    }
+}
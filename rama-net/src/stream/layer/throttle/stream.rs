@@ -0,0 +1,366 @@
+//! Provides [`ThrottledStream`] which wraps a [`AsyncRead`] and/or [`AsyncWrite`]
+//! in order to limit the read and/or write throughput using [`TokenBucket`]s.
+//!
+//! Use [`ThrottledStream::handle`] to get a [`BandwidthThrottleHandle`], which
+//! can be used to attach shared [`TokenBucket`]s at a later stage, e.g. once
+//! the user of a proxy connection is authenticated.
+//!
+//! [`AsyncRead`]: crate::stream::AsyncRead
+//! [`AsyncWrite`]: crate::stream::AsyncWrite
+
+use super::{BandwidthLimit, TokenBucket};
+use crate::stream::poll_read_limited;
+use parking_lot::Mutex;
+use pin_project_lite::pin_project;
+use rama_utils::macros::generate_set_and_with;
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+pin_project! {
+    /// A wrapper around a [`AsyncRead`] and/or [`AsyncWrite`] that limits
+    /// the read and/or write throughput using [`TokenBucket`]s.
+    ///
+    /// A stream without any [`TokenBucket`] for a direction is not limited in that direction.
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub struct ThrottledStream<S> {
+        buckets: Arc<ThrottleBuckets>,
+        read_sleep: Option<Pin<Box<Sleep>>>,
+        write_sleep: Option<Pin<Box<Sleep>>>,
+        #[pin]
+        stream: S,
+    }
+}
+
+#[derive(Debug, Default)]
+struct ThrottleBuckets {
+    read: Mutex<Vec<TokenBucket>>,
+    write: Mutex<Vec<TokenBucket>>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for ThrottledStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThrottledStream")
+            .field("buckets", &self.buckets)
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl<S> ThrottledStream<S> {
+    /// Create a new [`ThrottledStream`] that wraps the
+    /// given [`AsyncRead`] and/or [`AsyncWrite`],
+    /// without any limits applied yet.
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn new(stream: S) -> Self {
+        Self {
+            buckets: Default::default(),
+            read_sleep: None,
+            write_sleep: None,
+            stream,
+        }
+    }
+
+    /// Limit the read throughput of this stream to the given [`BandwidthLimit`].
+    pub fn with_read_limit(self, limit: BandwidthLimit) -> Self {
+        self.with_read_bucket(TokenBucket::new(limit))
+    }
+
+    /// Limit the write throughput of this stream to the given [`BandwidthLimit`].
+    pub fn with_write_limit(self, limit: BandwidthLimit) -> Self {
+        self.with_write_bucket(TokenBucket::new(limit))
+    }
+
+    /// Limit the read throughput of this stream using the given (possibly shared) [`TokenBucket`].
+    pub fn with_read_bucket(self, bucket: TokenBucket) -> Self {
+        self.buckets.read.lock().push(bucket);
+        self
+    }
+
+    /// Limit the write throughput of this stream using the given (possibly shared) [`TokenBucket`].
+    pub fn with_write_bucket(self, bucket: TokenBucket) -> Self {
+        self.buckets.write.lock().push(bucket);
+        self
+    }
+
+    /// Get a [`BandwidthThrottleHandle`] that can be used to attach
+    /// additional [`TokenBucket`]s even though the stream is consumed by a
+    /// protocol consumer in a later stage.
+    pub fn handle(&self) -> BandwidthThrottleHandle {
+        BandwidthThrottleHandle {
+            buckets: self.buckets.clone(),
+        }
+    }
+
+    /// Get the inner [`AsyncRead`] and/or [`AsyncWrite`] stream,
+    /// dropping the throttling of this stream.
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Poll until bytes can be transferred according to all given buckets,
+/// returning `None` in case there are no buckets to respect.
+fn poll_available(
+    buckets: &Mutex<Vec<TokenBucket>>,
+    sleep: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<usize>> {
+    loop {
+        if let Some(sleep) = sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+        }
+        *sleep = None;
+
+        let buckets = buckets.lock();
+        if buckets.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        let now = Instant::now();
+        let mut available = usize::MAX;
+        let mut wait = Duration::ZERO;
+        for bucket in buckets.iter() {
+            match bucket.available(now) {
+                Ok(n) => available = available.min(n),
+                Err(duration) => wait = wait.max(duration),
+            }
+        }
+
+        if wait.is_zero() {
+            return Poll::Ready(Some(available));
+        }
+        *sleep = Some(Box::pin(tokio::time::sleep_until(now + wait)));
+    }
+}
+
+fn consume(buckets: &Mutex<Vec<TokenBucket>>, n: usize) {
+    if n == 0 {
+        return;
+    }
+    for bucket in buckets.lock().iter() {
+        bucket.consume(n);
+    }
+}
+
+impl<S> AsyncRead for ThrottledStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        if buf.remaining() == 0 {
+            return this.stream.poll_read(cx, buf);
+        }
+
+        let Some(available) = ready!(poll_available(&this.buckets.read, this.read_sleep, cx))
+        else {
+            return this.stream.poll_read(cx, buf);
+        };
+
+        let n = ready!(poll_read_limited(this.stream, cx, buf, available))?;
+        consume(&this.buckets.read, n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for ThrottledStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.project();
+        if buf.is_empty() {
+            return this.stream.poll_write(cx, buf);
+        }
+
+        let Some(available) = ready!(poll_available(&this.buckets.write, this.write_sleep, cx))
+        else {
+            return this.stream.poll_write(cx, buf);
+        };
+
+        let n = ready!(this.stream.poll_write(cx, &buf[..available.min(buf.len())]))?;
+        consume(&this.buckets.write, n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().stream.poll_shutdown(cx)
+    }
+}
+
+/// A handle to a [`ThrottledStream`] that can be used to attach additional
+/// [`TokenBucket`]s even though the stream is consumed by a protocol consumer.
+///
+/// Inserted in the [`Context`] by the bandwidth throttle layers.
+///
+/// [`Context`]: rama_core::Context
+#[derive(Debug, Clone)]
+pub struct BandwidthThrottleHandle {
+    buckets: Arc<ThrottleBuckets>,
+}
+
+impl BandwidthThrottleHandle {
+    /// Limit the read throughput of the stream using the given (possibly shared) [`TokenBucket`].
+    pub fn add_read_bucket(&self, bucket: TokenBucket) {
+        self.buckets.read.lock().push(bucket);
+    }
+
+    /// Limit the write throughput of the stream using the given (possibly shared) [`TokenBucket`].
+    pub fn add_write_bucket(&self, bucket: TokenBucket) {
+        self.buckets.write.lock().push(bucket);
+    }
+
+    /// Limit the throughput of the stream using the given [`BandwidthBuckets`].
+    pub fn add_buckets(&self, buckets: &BandwidthBuckets) {
+        if let Some(bucket) = buckets.read.clone() {
+            self.add_read_bucket(bucket);
+        }
+        if let Some(bucket) = buckets.write.clone() {
+            self.add_write_bucket(bucket);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// (Shared) [`TokenBucket`]s to limit the read and/or write throughput of a stream.
+///
+/// When found in the [`Context`] by a bandwidth throttle layer, these buckets are
+/// attached to the stream, on top of the per-connection limits of that layer.
+/// This allows to enforce a [`BandwidthLimit`] for all streams of a user or proxy id,
+/// e.g. using [`KeyedTokenBuckets`].
+///
+/// [`Context`]: rama_core::Context
+/// [`KeyedTokenBuckets`]: super::KeyedTokenBuckets
+pub struct BandwidthBuckets {
+    read: Option<TokenBucket>,
+    write: Option<TokenBucket>,
+}
+
+impl BandwidthBuckets {
+    /// Create a new empty [`BandwidthBuckets`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    generate_set_and_with! {
+        /// Set the [`TokenBucket`] used to limit the read throughput.
+        pub fn read(mut self, bucket: Option<TokenBucket>) -> Self {
+            self.read = bucket;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`TokenBucket`] used to limit the write throughput.
+        pub fn write(mut self, bucket: Option<TokenBucket>) -> Self {
+            self.write = bucket;
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::num::NonZeroU64;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_test::io::Builder;
+
+    fn limit(bytes_per_second: u64) -> BandwidthLimit {
+        BandwidthLimit::new(NonZeroU64::new(bytes_per_second).unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_read() {
+        let stream = Builder::new().read(&[1; 400]).build();
+        let mut stream = ThrottledStream::new(stream).with_read_limit(limit(100));
+
+        let start = Instant::now();
+        let mut buf = vec![0; 400];
+        stream.read_exact(&mut buf).await.unwrap();
+
+        // 100 bytes burst, followed by 300 bytes at 100 bytes per second
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(3), "elapsed: {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(4), "elapsed: {elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_write() {
+        let stream = Builder::new().write(&[1; 250]).build();
+        let mut stream = ThrottledStream::new(stream).with_write_limit(limit(50));
+
+        let start = Instant::now();
+        stream.write_all(&[1; 250]).await.unwrap();
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(4), "elapsed: {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "elapsed: {elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unthrottled_direction() {
+        let stream = Builder::new().read(&[1; 1000]).write(&[1; 1000]).build();
+        let mut stream = ThrottledStream::new(stream).with_read_limit(limit(1000));
+
+        let start = Instant::now();
+        let mut buf = vec![0; 1000];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&buf).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_bucket_via_handle() {
+        let bucket = TokenBucket::new(limit(100));
+
+        let a = ThrottledStream::new(Builder::new().write(&[1; 200]).build());
+        a.handle().add_write_bucket(bucket.clone());
+        let b = ThrottledStream::new(Builder::new().write(&[1; 200]).build());
+        b.handle()
+            .add_buckets(&BandwidthBuckets::new().with_write(bucket));
+
+        async fn write(mut stream: ThrottledStream<tokio_test::io::Mock>) -> io::Result<()> {
+            stream.write_all(&[1; 200]).await
+        }
+
+        let start = Instant::now();
+        let (ra, rb) = tokio::join!(write(a), write(b));
+        ra.unwrap();
+        rb.unwrap();
+
+        // 400 bytes in total: 100 bytes burst, followed by 300 bytes at 100 bytes per second
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(3), "elapsed: {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(4), "elapsed: {elapsed:?}");
+    }
+}
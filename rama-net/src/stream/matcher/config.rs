@@ -0,0 +1,248 @@
+use super::{SocketMatcher, SocketMatcherKind};
+use crate::stream::dep::ipnet::IpNet;
+use serde::{Deserialize, Deserializer, de};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Declarative representation of a [`SocketMatcher`],
+/// which can be deserialized from any serde format (e.g. JSON, YAML or TOML).
+///
+/// Each rule is represented as a single-key map (or a plain string for rules without a value),
+/// where the key is the kind of rule, e.g. in JSON:
+///
+/// ```json
+/// {
+///     "any": [
+///         "loopback",
+///         { "ip": "10.0.0.0/8" },
+///         { "all": [{ "port": 443 }, { "not": "private_ip" }] }
+///     ]
+/// }
+/// ```
+///
+/// The `optional_` variants of a rule also match when the peer address
+/// of the socket could not be determined.
+///
+/// Use [`SocketMatcherConfig::into_matcher`] or the [`From`] implementation
+/// to turn it into a [`SocketMatcher`]. [`SocketMatcher`] can also be
+/// deserialized directly using this representation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SocketMatcherConfig {
+    /// Match on the exact [`SocketAddr`] of the peer.
+    SocketAddr(SocketAddr),
+    /// Match on the exact [`SocketAddr`] of the peer, or if it is unknown.
+    OptionalSocketAddr(SocketAddr),
+    /// Match if the peer IP is contained in the IP network,
+    /// given in CIDR notation or as a single IP address.
+    Ip(#[serde(deserialize_with = "deserialize_ip_net")] IpNet),
+    /// Match if the peer IP is contained in the IP network, or if it is unknown.
+    OptionalIp(#[serde(deserialize_with = "deserialize_ip_net")] IpNet),
+    /// Match if the peer IP is contained in any of the IP networks,
+    /// efficient for large lists of networks.
+    IpSet(#[serde(deserialize_with = "deserialize_ip_nets")] Vec<IpNet>),
+    /// Match if the peer IP is contained in any of the IP networks, or if it is unknown.
+    OptionalIpSet(#[serde(deserialize_with = "deserialize_ip_nets")] Vec<IpNet>),
+    /// Match on the port of the peer.
+    Port(u16),
+    /// Match on the port of the peer, or if it is unknown.
+    OptionalPort(u16),
+    /// Match if the peer IP is a loopback address.
+    Loopback,
+    /// Match if the peer IP is a loopback address, or if it is unknown.
+    OptionalLoopback,
+    /// Match if the peer IP is a private address.
+    PrivateIp,
+    /// Match if the peer IP is a private address, or if it is unknown.
+    OptionalPrivateIp,
+    /// Match if all of the given rules match.
+    All(Vec<SocketMatcherConfig>),
+    /// Match if any of the given rules match.
+    Any(Vec<SocketMatcherConfig>),
+    /// Match if the given rule does not match.
+    Not(Box<SocketMatcherConfig>),
+}
+
+impl SocketMatcherConfig {
+    /// Compile this config into a [`SocketMatcher`].
+    pub fn into_matcher<State, Socket>(self) -> SocketMatcher<State, Socket> {
+        match self {
+            Self::SocketAddr(addr) => SocketMatcher::socket_addr(addr),
+            Self::OptionalSocketAddr(addr) => SocketMatcher::optional_socket_addr(addr),
+            Self::Ip(net) => SocketMatcher::ip_net(net),
+            Self::OptionalIp(net) => SocketMatcher::optional_ip_net(net),
+            Self::IpSet(nets) => SocketMatcher::ip_net_set(nets),
+            Self::OptionalIpSet(nets) => SocketMatcher::optional_ip_net_set(nets),
+            Self::Port(port) => SocketMatcher::port(port),
+            Self::OptionalPort(port) => SocketMatcher::optional_port(port),
+            Self::Loopback => SocketMatcher::loopback(),
+            Self::OptionalLoopback => SocketMatcher::optional_loopback(),
+            Self::PrivateIp => SocketMatcher::private_ip_net(),
+            Self::OptionalPrivateIp => SocketMatcher::optional_private_ip_net(),
+            Self::All(rules) => SocketMatcher {
+                kind: SocketMatcherKind::All(rules.into_iter().map(Self::into_matcher).collect()),
+                negate: false,
+            },
+            Self::Any(rules) => SocketMatcher {
+                kind: SocketMatcherKind::Any(rules.into_iter().map(Self::into_matcher).collect()),
+                negate: false,
+            },
+            Self::Not(rule) => rule.into_matcher().negate(),
+        }
+    }
+}
+
+impl<State, Socket> From<SocketMatcherConfig> for SocketMatcher<State, Socket> {
+    fn from(config: SocketMatcherConfig) -> Self {
+        config.into_matcher()
+    }
+}
+
+impl<'de, State, Socket> Deserialize<'de> for SocketMatcher<State, Socket> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        SocketMatcherConfig::deserialize(deserializer).map(SocketMatcherConfig::into_matcher)
+    }
+}
+
+fn deserialize_ip_net<'de, D>(deserializer: D) -> Result<IpNet, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(IpNetVisitor)
+}
+
+fn deserialize_ip_nets<'de, D>(deserializer: D) -> Result<Vec<IpNet>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct IpNetItem(IpNet);
+
+    impl<'de> Deserialize<'de> for IpNetItem {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserialize_ip_net(deserializer).map(Self)
+        }
+    }
+
+    let nets = Vec::<IpNetItem>::deserialize(deserializer)?;
+    Ok(nets.into_iter().map(|IpNetItem(net)| net).collect())
+}
+
+/// Visitor used to parse an [`IpNet`] from a string,
+/// such that parse errors are reported at the position of that string.
+struct IpNetVisitor;
+
+impl de::Visitor<'_> for IpNetVisitor {
+    type Value = IpNet;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an ip address or a network in CIDR notation")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse::<IpNet>()
+            .or_else(|_| v.parse::<IpAddr>().map(IpNet::from))
+            .map_err(|_| {
+                E::custom(format!(
+                    "invalid ip network {v:?}: expected an ip address or a network in CIDR notation (e.g. \"10.0.0.0/8\")"
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::{Context, matcher::Matcher};
+
+    struct FakeSocket(SocketAddr);
+
+    impl crate::stream::Socket for FakeSocket {
+        fn local_addr(&self) -> std::io::Result<SocketAddr> {
+            Err(std::io::Error::from(std::io::ErrorKind::AddrNotAvailable))
+        }
+
+        fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+            Ok(self.0)
+        }
+    }
+
+    fn matches(matcher: &SocketMatcher<(), FakeSocket>, addr: &str) -> bool {
+        let socket = FakeSocket(addr.parse().unwrap());
+        matcher.matches(None, &Context::default(), &socket)
+    }
+
+    #[test]
+    fn deserialize_socket_matcher() {
+        let matcher: SocketMatcher<(), FakeSocket> = serde_json::from_str(
+            r#"{
+                "any": [
+                    "loopback",
+                    { "ip": "10.0.0.0/8" },
+                    { "all": [{ "port": 8443 }, { "not": "private_ip" }] }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches(&matcher, "127.0.0.1:80"));
+        assert!(matches(&matcher, "10.1.2.3:80"));
+        assert!(matches(&matcher, "1.1.1.1:8443"));
+        assert!(!matches(&matcher, "192.168.0.1:8443"));
+        assert!(!matches(&matcher, "1.1.1.1:443"));
+    }
+
+    #[test]
+    fn deserialize_ip_set() {
+        let matcher: SocketMatcher<(), FakeSocket> = serde_json::from_str(
+            r#"{ "not": { "ip_set": ["10.0.0.0/8", "192.168.1.0/24", "::1"] } }"#,
+        )
+        .unwrap();
+
+        assert!(!matches(&matcher, "10.1.2.3:80"));
+        assert!(!matches(&matcher, "192.168.1.10:80"));
+        assert!(!matches(&matcher, "[::1]:80"));
+        assert!(matches(&matcher, "192.168.2.10:80"));
+        assert!(matches(&matcher, "1.1.1.1:80"));
+
+        let err = serde_json::from_str::<SocketMatcherConfig>(r#"{"ip_set": ["::1", "foo"]}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("invalid ip network \"foo\""), "{err}");
+    }
+
+    #[test]
+    fn deserialize_single_ip() {
+        let config: SocketMatcherConfig = serde_json::from_str(r#"{"ip": "::1"}"#).unwrap();
+        assert_eq!(SocketMatcherConfig::Ip("::1/128".parse().unwrap()), config);
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let err = serde_json::from_str::<SocketMatcherConfig>(
+            r#"{"any": ["loopback", {"ip": "10.0.0/8"}]}"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("invalid ip network \"10.0.0/8\""), "{err}");
+        assert!(err.contains("column"), "{err}");
+
+        let err = serde_json::from_str::<SocketMatcherConfig>(r#"{"optional_ip": "foo"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("invalid ip network \"foo\""), "{err}");
+        assert!(err.contains("column"), "{err}");
+
+        let err = serde_json::from_str::<SocketMatcherConfig>(r#"{"prot": 80}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown variant `prot`"), "{err}");
+    }
+}
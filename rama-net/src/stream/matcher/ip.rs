@@ -3,9 +3,11 @@
 pub use crate::stream::dep::ipnet::{IpNet, Ipv4Net, Ipv6Net};
 
 use rama_core::{Context, context::Extensions};
+use rama_utils::macros::generate_set_and_with;
+use std::{net::IpAddr, sync::Arc};
 
 #[cfg(feature = "http")]
-use crate::stream::SocketInfo;
+use crate::{forwarded::Forwarded, stream::SocketInfo};
 #[cfg(feature = "http")]
 use rama_http_types::Request;
 
@@ -75,6 +77,298 @@ where
     }
 }
 
+#[derive(Debug, Clone)]
+/// Matcher based on whether or not any of a (potentially large) set of [`IpNet`]s
+/// contains the [`SocketAddr`] of the peer.
+///
+/// Unlike combining many [`IpNetMatcher`]s, the networks are stored in an [`IpNetTrie`],
+/// such that matching is independent of the amount of networks,
+/// making it suitable for large allow or deny lists.
+///
+/// [`SocketAddr`]: std::net::SocketAddr
+pub struct IpNetSetMatcher {
+    nets: Arc<IpNetTrie<()>>,
+    optional: bool,
+    forwarded_client_ip: bool,
+}
+
+impl IpNetSetMatcher {
+    /// create a new IP network set matcher to match on any of the given IP networks.
+    ///
+    /// This matcher will not match in case socket address could not be found,
+    /// if you want to match in case socket address could not be found,
+    /// use the [`IpNetSetMatcher::optional`] constructor..
+    pub fn new<I>(nets: I) -> Self
+    where
+        I: IntoIterator<Item: IntoIpNet>,
+    {
+        Self {
+            nets: Arc::new(nets.into_iter().map(private::Sealed::into_ip_net).collect()),
+            optional: false,
+            forwarded_client_ip: false,
+        }
+    }
+
+    /// create a new IP network set matcher to match on any of the given IP networks.
+    ///
+    /// This matcher will match in case socket address could not be found.
+    /// Use the [`IpNetSetMatcher::new`] constructor if you want do not want
+    /// to match in case socket address could not be found.
+    pub fn optional<I>(nets: I) -> Self
+    where
+        I: IntoIterator<Item: IntoIpNet>,
+    {
+        Self {
+            optional: true,
+            ..Self::new(nets)
+        }
+    }
+
+    generate_set_and_with! {
+        /// Match http requests on the client IP found in the [`Forwarded`] context extension
+        /// instead of the peer address, in case it is available.
+        ///
+        /// Only enable this in case the [`Forwarded`] information can be trusted,
+        /// e.g. because it is added by a trusted reverse proxy.
+        ///
+        /// [`Forwarded`]: crate::forwarded::Forwarded
+        pub fn forwarded_client_ip(mut self, forwarded_client_ip: bool) -> Self {
+            self.forwarded_client_ip = forwarded_client_ip;
+            self
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl<State, Body> rama_core::matcher::Matcher<State, Request<Body>> for IpNetSetMatcher {
+    fn matches(
+        &self,
+        _ext: Option<&mut Extensions>,
+        ctx: &Context<State>,
+        _req: &Request<Body>,
+    ) -> bool {
+        self.forwarded_client_ip
+            .then(|| ctx.get::<Forwarded>().and_then(Forwarded::client_ip))
+            .flatten()
+            .or_else(|| ctx.get::<SocketInfo>().map(|info| info.peer_addr().ip()))
+            .map(|ip| self.nets.contains(ip))
+            .unwrap_or(self.optional)
+    }
+}
+
+impl<State, Socket> rama_core::matcher::Matcher<State, Socket> for IpNetSetMatcher
+where
+    Socket: crate::stream::Socket,
+{
+    fn matches(
+        &self,
+        _ext: Option<&mut Extensions>,
+        _ctx: &Context<State>,
+        stream: &Socket,
+    ) -> bool {
+        stream
+            .peer_addr()
+            .map(|addr| self.nets.contains(addr.ip()))
+            .unwrap_or(self.optional)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A prefix trie (binary radix tree) of [`IpNet`]s, each associated with a value.
+///
+/// Lookups return the value of the longest (most specific) network
+/// containing the IP address, in a time independent of the amount of networks.
+///
+/// IPv4-mapped IPv6 addresses are looked up as their IPv4 counterpart,
+/// and IPv4-mapped IPv6 networks (within `::ffff:0:0/96`) are stored as such.
+pub struct IpNetTrie<T> {
+    nodes: Vec<IpNetTrieNode<T>>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct IpNetTrieNode<T> {
+    // index 0 is the ipv4 root node, which is never a child,
+    // and therefore used to indicate the absence of a child
+    children: [usize; 2],
+    entry: Option<(IpNet, T)>,
+}
+
+impl<T> Default for IpNetTrieNode<T> {
+    fn default() -> Self {
+        Self {
+            children: [0; 2],
+            entry: None,
+        }
+    }
+}
+
+const IP_NET_TRIE_ROOT_V4: usize = 0;
+const IP_NET_TRIE_ROOT_V6: usize = 1;
+
+impl<T> Default for IpNetTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IpNetTrie<T> {
+    /// Create a new empty [`IpNetTrie`].
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![IpNetTrieNode::default(), IpNetTrieNode::default()],
+            len: 0,
+        }
+    }
+
+    /// Returns the amount of networks stored in the [`IpNetTrie`].
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no networks are stored in the [`IpNetTrie`].
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert the network with its value in the [`IpNetTrie`],
+    /// returning the previous value in case the network was already present.
+    ///
+    /// Host bits of the network are ignored, e.g. `10.1.2.3/8` is stored as `10.0.0.0/8`,
+    /// and IPv4-mapped IPv6 networks are stored as IPv4 networks,
+    /// e.g. `::ffff:10.0.0.0/104` is stored as `10.0.0.0/8`.
+    pub fn insert(&mut self, net: impl IntoIpNet, value: T) -> Option<T> {
+        let net = ip_net_to_canonical(net.into_ip_net().trunc());
+        let (mut node, bits) = ip_net_trie_key(net.addr());
+        for i in 0..net.prefix_len() as u32 {
+            let bit = ip_net_trie_bit(bits, i);
+            node = match self.nodes[node].children[bit] {
+                0 => {
+                    self.nodes.push(IpNetTrieNode::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children[bit] = child;
+                    child
+                }
+                child => child,
+            };
+        }
+
+        let previous = self.nodes[node].entry.replace((net, value));
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous.map(|(_, value)| value)
+    }
+
+    /// Returns the longest network containing the given IP address,
+    /// together with its value.
+    pub fn longest_match(&self, ip: impl Into<IpAddr>) -> Option<(&IpNet, &T)> {
+        let (mut node, bits) = ip_net_trie_key(ip.into().to_canonical());
+        let max_len = if node == IP_NET_TRIE_ROOT_V4 { 32 } else { 128 };
+
+        // IPv6 networks shorter than the IPv4-mapped prefix can contain IPv4 addresses,
+        // any IPv4 network is more specific than those
+        let mut result = if node == IP_NET_TRIE_ROOT_V4 {
+            self.ipv4_mapped_supernet()
+        } else {
+            None
+        };
+        if let Some(entry) = self.nodes[node].entry.as_ref() {
+            result = Some(entry);
+        }
+        for i in 0..max_len {
+            node = match self.nodes[node].children[ip_net_trie_bit(bits, i)] {
+                0 => break,
+                child => child,
+            };
+            if let Some(entry) = self.nodes[node].entry.as_ref() {
+                result = Some(entry);
+            }
+        }
+        result.map(|(net, value)| (net, value))
+    }
+
+    /// Returns the longest IPv6 network containing all IPv4-mapped IPv6 addresses.
+    fn ipv4_mapped_supernet(&self) -> Option<&(IpNet, T)> {
+        // ::ffff:0:0
+        let bits: u128 = 0xffff << 32;
+        let mut node = IP_NET_TRIE_ROOT_V6;
+        let mut result = self.nodes[node].entry.as_ref();
+        for i in 0..IPV4_MAPPED_PREFIX_LEN as u32 {
+            node = match self.nodes[node].children[ip_net_trie_bit(bits, i)] {
+                0 => break,
+                child => child,
+            };
+            if let Some(entry) = self.nodes[node].entry.as_ref() {
+                result = Some(entry);
+            }
+        }
+        result
+    }
+
+    /// Returns the value of the longest network containing the given IP address.
+    pub fn get(&self, ip: impl Into<IpAddr>) -> Option<&T> {
+        self.longest_match(ip).map(|(_, value)| value)
+    }
+
+    /// Returns `true` if any of the networks contains the given IP address.
+    pub fn contains(&self, ip: impl Into<IpAddr>) -> bool {
+        self.longest_match(ip).is_some()
+    }
+}
+
+const IPV4_MAPPED_PREFIX_LEN: u8 = 96;
+
+/// Converts an IPv4-mapped IPv6 network into its IPv4 counterpart.
+fn ip_net_to_canonical(net: IpNet) -> IpNet {
+    match net {
+        IpNet::V6(v6) if v6.prefix_len() >= IPV4_MAPPED_PREFIX_LEN => {
+            match v6.addr().to_ipv4_mapped() {
+                Some(addr) => IpNet::V4(
+                    Ipv4Net::new(addr, v6.prefix_len() - IPV4_MAPPED_PREFIX_LEN)
+                        .expect("prefix length of at most 32"),
+                ),
+                None => net,
+            }
+        }
+        net => net,
+    }
+}
+
+/// Returns the root node and the (left-aligned) bits of the IP address.
+fn ip_net_trie_key(ip: IpAddr) -> (usize, u128) {
+    match ip {
+        IpAddr::V4(ip) => (IP_NET_TRIE_ROOT_V4, (u32::from(ip) as u128) << 96),
+        IpAddr::V6(ip) => (IP_NET_TRIE_ROOT_V6, u128::from(ip)),
+    }
+}
+
+fn ip_net_trie_bit(bits: u128, i: u32) -> usize {
+    ((bits >> (127 - i)) & 1) as usize
+}
+
+impl<N: IntoIpNet, T> FromIterator<(N, T)> for IpNetTrie<T> {
+    fn from_iter<I: IntoIterator<Item = (N, T)>>(iter: I) -> Self {
+        let mut trie = Self::new();
+        trie.extend(iter);
+        trie
+    }
+}
+
+impl<N: IntoIpNet, T> Extend<(N, T)> for IpNetTrie<T> {
+    fn extend<I: IntoIterator<Item = (N, T)>>(&mut self, iter: I) {
+        for (net, value) in iter {
+            self.insert(net, value);
+        }
+    }
+}
+
+impl FromIterator<IpNet> for IpNetTrie<()> {
+    fn from_iter<I: IntoIterator<Item = IpNet>>(iter: I) -> Self {
+        iter.into_iter().map(|net| (net, ())).collect()
+    }
+}
+
 /// utility trait to consume a tpe into an [`IpNet`]
 pub trait IntoIpNet: private::Sealed {}
 
@@ -357,4 +651,156 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_ip_net_trie_longest_match() {
+        let mut trie: IpNetTrie<&'static str> = [
+            ("10.0.0.0/8".parse::<IpNet>().unwrap(), "a"),
+            ("10.1.0.0/16".parse().unwrap(), "b"),
+            ("10.1.2.3/32".parse().unwrap(), "c"),
+            ("fd00::/16".parse().unwrap(), "d"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(4, trie.len());
+
+        assert_eq!(Some(&"a"), trie.get([10, 2, 0, 1]));
+        assert_eq!(Some(&"b"), trie.get([10, 1, 2, 4]));
+        assert_eq!(Some(&"c"), trie.get([10, 1, 2, 3]));
+        assert_eq!(None, trie.get([11, 0, 0, 1]));
+        assert_eq!(
+            Some(&"c"),
+            trie.get("::ffff:10.1.2.3".parse::<IpAddr>().unwrap())
+        );
+        assert_eq!(Some(&"d"), trie.get("fd00::1".parse::<IpAddr>().unwrap()));
+        assert_eq!(None, trie.get("fd01::1".parse::<IpAddr>().unwrap()));
+
+        let (net, _) = trie.longest_match([10, 1, 9, 9]).unwrap();
+        assert_eq!("10.1.0.0/16", net.to_string());
+
+        // host bits are ignored and existing values are replaced
+        assert_eq!(
+            Some("b"),
+            trie.insert("10.1.9.9/16".parse::<IpNet>().unwrap(), "e")
+        );
+        assert_eq!(4, trie.len());
+        assert_eq!(Some(&"e"), trie.get([10, 1, 9, 9]));
+
+        // default routes
+        assert_eq!(
+            None,
+            trie.insert("0.0.0.0/0".parse::<IpNet>().unwrap(), "f")
+        );
+        assert_eq!(Some(&"f"), trie.get([11, 0, 0, 1]));
+        assert_eq!(None, trie.get("fd01::1".parse::<IpAddr>().unwrap()));
+    }
+
+    #[test]
+    fn test_ip_net_trie_ipv4_mapped_networks() {
+        let mut trie: IpNetTrie<&'static str> = [
+            ("::ffff:10.0.0.0/104".parse::<IpNet>().unwrap(), "a"),
+            ("10.1.0.0/16".parse().unwrap(), "b"),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(Some(&"a"), trie.get([10, 2, 0, 1]));
+        assert_eq!(
+            Some(&"a"),
+            trie.get("::ffff:10.2.0.1".parse::<IpAddr>().unwrap())
+        );
+        assert_eq!(Some(&"b"), trie.get([10, 1, 0, 1]));
+        let (net, _) = trie.longest_match([10, 2, 0, 1]).unwrap();
+        assert_eq!("10.0.0.0/8", net.to_string());
+
+        // the same network, once mapped
+        assert_eq!(
+            Some("b"),
+            trie.insert("::ffff:10.1.0.0/112".parse::<IpNet>().unwrap(), "c")
+        );
+        assert_eq!(2, trie.len());
+
+        // the whole IPv4-mapped range is the IPv4 default route
+        trie.insert("::ffff:0:0/96".parse::<IpNet>().unwrap(), "d");
+        let (net, _) = trie.longest_match([11, 0, 0, 1]).unwrap();
+        assert_eq!("0.0.0.0/0", net.to_string());
+        assert_eq!(None, trie.get("fd00::1".parse::<IpAddr>().unwrap()));
+
+        // IPv6 networks containing the IPv4-mapped range contain IPv4 addresses
+        let trie: IpNetTrie<&'static str> = [
+            ("::/0".parse::<IpNet>().unwrap(), "e"),
+            ("10.0.0.0/8".parse().unwrap(), "f"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(Some(&"e"), trie.get([11, 0, 0, 1]));
+        assert_eq!(Some(&"f"), trie.get([10, 0, 0, 1]));
+        assert_eq!(Some(&"e"), trie.get("fd00::1".parse::<IpAddr>().unwrap()));
+    }
+
+    #[test]
+    fn test_ip_net_set_matcher_socket_trait() {
+        struct FakeSocket(Option<SocketAddr>);
+
+        impl crate::stream::Socket for FakeSocket {
+            fn local_addr(&self) -> std::io::Result<SocketAddr> {
+                Err(std::io::Error::from(std::io::ErrorKind::AddrNotAvailable))
+            }
+
+            fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+                self.0
+                    .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::AddrNotAvailable))
+            }
+        }
+
+        let nets: Vec<IpNet> = (0..=255u8)
+            .map(|i| IpNet::new([10, i, 0, 0].into(), 24).unwrap())
+            .chain([SUBNET_IPV6.parse().unwrap()])
+            .collect();
+        let matcher = IpNetSetMatcher::new(nets.clone());
+        let ctx = Context::default();
+
+        for case in SUBNET_IPV6_VALID_CASES {
+            let socket = FakeSocket(Some(socket_addr_from_case(case)));
+            assert!(matcher.matches(None, &ctx, &socket), "{case}");
+        }
+        for case in SUBNET_IPV6_INVALID_CASES {
+            let socket = FakeSocket(Some(socket_addr_from_case(case)));
+            assert!(!matcher.matches(None, &ctx, &socket), "{case}");
+        }
+        assert!(matcher.matches(None, &ctx, &FakeSocket(Some(([10, 42, 0, 1], 80).into()))));
+        assert!(!matcher.matches(None, &ctx, &FakeSocket(Some(([10, 42, 1, 1], 80).into()))));
+        assert!(!matcher.matches(None, &ctx, &FakeSocket(None)));
+
+        let matcher = IpNetSetMatcher::optional(nets);
+        assert!(matcher.matches(None, &ctx, &FakeSocket(None)));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_ip_net_set_matcher_http_forwarded() {
+        use crate::forwarded::ForwardedElement;
+
+        let matcher = IpNetSetMatcher::new([[10, 0, 0, 1], [10, 0, 0, 2]]);
+        let req = Request::builder().uri("/").body(()).unwrap();
+
+        let mut ctx = Context::default();
+        assert!(!matcher.matches(None, &ctx, &req));
+
+        ctx.insert(SocketInfo::new(None, ([10, 0, 0, 1], 8080).into()));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        ctx.insert(Forwarded::new(ForwardedElement::forwarded_for(
+            IpAddr::from([1, 1, 1, 1]),
+        )));
+        assert!(matcher.matches(None, &ctx, &req));
+
+        let matcher = matcher.with_forwarded_client_ip(true);
+        assert!(!matcher.matches(None, &ctx, &req));
+
+        ctx.insert(Forwarded::new(ForwardedElement::forwarded_for(
+            IpAddr::from([10, 0, 0, 2]),
+        )));
+        assert!(matcher.matches(None, &ctx, &req));
+    }
 }
@@ -24,7 +24,11 @@ pub use loopback::LoopbackMatcher;
 
 pub mod ip;
 #[doc(inline)]
-pub use ip::IpNetMatcher;
+pub use ip::{IpNetMatcher, IpNetSetMatcher};
+
+mod config;
+#[doc(inline)]
+pub use config::SocketMatcherConfig;
 
 use rama_core::{Context, context::Extensions, matcher::IteratorMatcherExt};
 use std::{fmt, sync::Arc};
@@ -78,6 +82,12 @@ enum SocketMatcherKind<State, Socket> {
     /// [`IpNet`]: ipnet::IpNet
     /// [`SocketAddr`]: std::net::SocketAddr
     IpNet(IpNetMatcher),
+    /// [`IpNetSetMatcher`], a matcher to match on whether or not
+    /// any of a set of [`IpNet`]s contains the [`SocketAddr`] of the peer.
+    ///
+    /// [`IpNet`]: ipnet::IpNet
+    /// [`SocketAddr`]: std::net::SocketAddr
+    IpNetSet(IpNetSetMatcher),
     /// zero or more matchers that all need to match in order for the matcher to return `true`.
     All(Vec<SocketMatcher<State, Socket>>),
     /// `true` if no matchers are defined, or any of the defined matcher match.
@@ -94,6 +104,7 @@ impl<State, Socket> Clone for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => Self::PrivateIpNet(matcher.clone()),
             Self::Port(matcher) => Self::Port(matcher.clone()),
             Self::IpNet(matcher) => Self::IpNet(matcher.clone()),
+            Self::IpNetSet(matcher) => Self::IpNetSet(matcher.clone()),
             Self::All(matcher) => Self::All(matcher.clone()),
             Self::Any(matcher) => Self::Any(matcher.clone()),
             Self::Custom(matcher) => Self::Custom(matcher.clone()),
@@ -109,6 +120,7 @@ impl<State, Socket> fmt::Debug for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => f.debug_tuple("PrivateIpNet").field(matcher).finish(),
             Self::Port(matcher) => f.debug_tuple("Port").field(matcher).finish(),
             Self::IpNet(matcher) => f.debug_tuple("IpNet").field(matcher).finish(),
+            Self::IpNetSet(matcher) => f.debug_tuple("IpNetSet").field(matcher).finish(),
             Self::All(matcher) => f.debug_tuple("All").field(matcher).finish(),
             Self::Any(matcher) => f.debug_tuple("Any").field(matcher).finish(),
             Self::Custom(_) => f.debug_tuple("Custom").finish(),
@@ -315,6 +327,78 @@ impl<State, Socket> SocketMatcher<State, Socket> {
         self.or(Self::optional_ip_net(ip_net))
     }
 
+    /// create a new IP network set matcher to match on any of the given IP networks.
+    ///
+    /// See [`IpNetSetMatcher::new`] for more information.
+    pub fn ip_net_set<I>(ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        Self::ip_net_set_matcher(IpNetSetMatcher::new(ip_nets))
+    }
+
+    /// Create a new optional IP network set matcher to match on any of the given IP networks,
+    /// this matcher will match in case socket address could not be found.
+    ///
+    /// See [`IpNetSetMatcher::optional`] for more information.
+    pub fn optional_ip_net_set<I>(ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        Self::ip_net_set_matcher(IpNetSetMatcher::optional(ip_nets))
+    }
+
+    /// create a new IP network set matcher from an existing [`IpNetSetMatcher`],
+    /// e.g. to match http requests on the forwarded client IP.
+    ///
+    /// See [`IpNetSetMatcher`] for more information.
+    pub fn ip_net_set_matcher(matcher: IpNetSetMatcher) -> Self {
+        Self {
+            kind: SocketMatcherKind::IpNetSet(matcher),
+            negate: false,
+        }
+    }
+
+    /// Add a new IP network set matcher to the existing [`SocketMatcher`] to also match on any of the given IP networks.
+    ///
+    /// See [`IpNetSetMatcher::new`] for more information.
+    pub fn and_ip_net_set<I>(self, ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        self.and(Self::ip_net_set(ip_nets))
+    }
+
+    /// Add a new optional IP network set matcher to the existing [`SocketMatcher`] to also match on any of the given IP networks.
+    ///
+    /// See [`IpNetSetMatcher::optional`] for more information.
+    pub fn and_optional_ip_net_set<I>(self, ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        self.and(Self::optional_ip_net_set(ip_nets))
+    }
+
+    /// Add a new IP network set matcher to the existing [`SocketMatcher`] as an alternative matcher to match on any of the given IP networks.
+    ///
+    /// See [`IpNetSetMatcher::new`] for more information.
+    pub fn or_ip_net_set<I>(self, ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        self.or(Self::ip_net_set(ip_nets))
+    }
+
+    /// Add a new optional IP network set matcher to the existing [`SocketMatcher`] as an alternative matcher to match on any of the given IP networks.
+    ///
+    /// See [`IpNetSetMatcher::optional`] for more information.
+    pub fn or_optional_ip_net_set<I>(self, ip_nets: I) -> Self
+    where
+        I: IntoIterator<Item: ip::IntoIpNet>,
+    {
+        self.or(Self::optional_ip_net_set(ip_nets))
+    }
+
     /// create a new local IP network matcher to match on whether or not the peer address is a private address.
     ///
     /// See [`PrivateIpNetMatcher::new`] for more information.
@@ -450,6 +534,7 @@ where
         match self {
             SocketMatcherKind::SocketAddress(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::IpNet(matcher) => matcher.matches(ext, ctx, req),
+            SocketMatcherKind::IpNetSet(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::Loopback(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::PrivateIpNet(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, req),
@@ -487,6 +572,7 @@ where
         match self {
             SocketMatcherKind::SocketAddress(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::IpNet(matcher) => matcher.matches(ext, ctx, stream),
+            SocketMatcherKind::IpNetSet(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::Loopback(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::PrivateIpNet(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, stream),
@@ -8,6 +8,7 @@ pub mod layer;
 pub mod service;
 
 mod read;
+pub(crate) use read::poll_read_limited;
 #[doc(inline)]
 pub use read::{ChainReader, HeapReader, StackReader};
 
@@ -25,6 +26,10 @@ mod socket;
 #[doc(inline)]
 pub use socket::{ClientSocketInfo, Socket, SocketInfo};
 
+mod tcp_syn;
+#[doc(inline)]
+pub use tcp_syn::TcpSyn;
+
 pub mod dep {
     //! Dependencies for rama stream modules.
     //!
@@ -340,6 +340,24 @@ where
     }
 }
 
+/// Read from the given reader into at most `limit` bytes of the unfilled part of `buf`,
+/// returning the amount of bytes read.
+///
+/// The (limited) unfilled part is initialized first, such that no unsafe code
+/// is required to advance `buf` with the bytes read into it.
+pub(crate) fn poll_read_limited<R: AsyncRead + ?Sized>(
+    reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+    limit: usize,
+) -> Poll<io::Result<usize>> {
+    let mut limited_buf = ReadBuf::new(buf.initialize_unfilled_to(limit.min(buf.remaining())));
+    let result = ready!(reader.poll_read(cx, &mut limited_buf));
+    let n = limited_buf.filled().len();
+    buf.advance(n);
+    Poll::Ready(result.map(|()| n))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
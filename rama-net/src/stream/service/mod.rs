@@ -5,3 +5,10 @@
 mod echo;
 #[doc(inline)]
 pub use echo::EchoService;
+
+mod peek;
+#[doc(inline)]
+pub use peek::{
+    PROTOCOL_PEEK_LEN, ProtocolPeekRouter, ProtocolPeekStream, StreamProtocol,
+    UnknownProtocolRejectError,
+};
@@ -0,0 +1,527 @@
+use std::{fmt, time::Duration};
+
+use rama_core::{
+    Context, Service,
+    error::{BoxError, ErrorContext},
+    layer::MapErr,
+    service::{BoxService, RejectService},
+    telemetry::tracing,
+};
+use rama_utils::macros::generate_set_and_with;
+use tokio::io::AsyncReadExt;
+
+use crate::stream::{PeekStream, StackReader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The protocol of a stream, as detected by the [`ProtocolPeekRouter`]
+/// based on the first bytes sent by the client.
+///
+/// It is inserted in the [`Context`] by the [`ProtocolPeekRouter`]
+/// prior to serving the stream.
+pub enum StreamProtocol {
+    /// TLS (record layer handshake)
+    Tls,
+    /// HTTP/1.x (a known request method)
+    Http1,
+    /// HTTP/2 with prior knowledge (connection preface)
+    H2,
+    /// SOCKS4 (or SOCKS4a)
+    Socks4,
+    /// SOCKS5
+    Socks5,
+    /// HaProxy PROXY protocol version 1 (text)
+    HaProxyV1,
+    /// HaProxy PROXY protocol version 2 (binary)
+    HaProxyV2,
+    /// SSH (protocol version exchange)
+    Ssh,
+    /// Unknown protocol, including streams which did
+    /// not send enough data prior to EOF or the peek timeout.
+    Unknown,
+}
+
+impl StreamProtocol {
+    /// Detect the [`StreamProtocol`] from the given (first) bytes of a stream.
+    ///
+    /// Returns `None` in case more data is required to make a decision,
+    /// which will never be the case for [`PROTOCOL_PEEK_LEN`] bytes or more.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let mut partial = false;
+        for (protocol, matcher) in PROTOCOL_MATCHERS {
+            match matcher(data) {
+                PrefixMatch::Match => return Some(*protocol),
+                PrefixMatch::Partial => partial = true,
+                PrefixMatch::NoMatch => (),
+            }
+        }
+        (!partial || data.len() >= PROTOCOL_PEEK_LEN).then_some(Self::Unknown)
+    }
+}
+
+impl fmt::Display for StreamProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tls => "tls",
+            Self::Http1 => "http/1",
+            Self::H2 => "h2",
+            Self::Socks4 => "socks4",
+            Self::Socks5 => "socks5",
+            Self::HaProxyV1 => "haproxy/v1",
+            Self::HaProxyV2 => "haproxy/v2",
+            Self::Ssh => "ssh",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrefixMatch {
+    Match,
+    Partial,
+    NoMatch,
+}
+
+fn match_prefix(data: &[u8], prefix: &[u8]) -> PrefixMatch {
+    if data.len() >= prefix.len() {
+        if data.starts_with(prefix) {
+            PrefixMatch::Match
+        } else {
+            PrefixMatch::NoMatch
+        }
+    } else if prefix.starts_with(data) {
+        PrefixMatch::Partial
+    } else {
+        PrefixMatch::NoMatch
+    }
+}
+
+fn match_tls(data: &[u8]) -> PrefixMatch {
+    match data {
+        [0x16, 0x03, 0x00..=0x04, ..] => PrefixMatch::Match,
+        [] | [0x16] | [0x16, 0x03] => PrefixMatch::Partial,
+        _ => PrefixMatch::NoMatch,
+    }
+}
+
+fn match_http1(data: &[u8]) -> PrefixMatch {
+    const HTTP_METHODS: &[&[u8]] = &[
+        b"GET ",
+        b"POST ",
+        b"PUT ",
+        b"DELETE ",
+        b"HEAD ",
+        b"OPTIONS ",
+        b"CONNECT ",
+        b"TRACE ",
+        b"PATCH ",
+    ];
+    HTTP_METHODS
+        .iter()
+        .map(|method| match_prefix(data, method))
+        .min_by_key(|m| match m {
+            PrefixMatch::Match => 0,
+            PrefixMatch::Partial => 1,
+            PrefixMatch::NoMatch => 2,
+        })
+        .unwrap_or(PrefixMatch::NoMatch)
+}
+
+fn match_h2(data: &[u8]) -> PrefixMatch {
+    match_prefix(data, b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+}
+
+fn match_socks4(data: &[u8]) -> PrefixMatch {
+    // version followed by the CONNECT or BIND command
+    match data {
+        [0x04, 0x01 | 0x02, ..] => PrefixMatch::Match,
+        [] | [0x04] => PrefixMatch::Partial,
+        _ => PrefixMatch::NoMatch,
+    }
+}
+
+fn match_socks5(data: &[u8]) -> PrefixMatch {
+    // version followed by a non-zero amount of auth methods
+    match data {
+        [0x05, 0x01..=0xff, ..] => PrefixMatch::Match,
+        [] | [0x05] => PrefixMatch::Partial,
+        _ => PrefixMatch::NoMatch,
+    }
+}
+
+fn match_haproxy_v1(data: &[u8]) -> PrefixMatch {
+    match_prefix(data, b"PROXY ")
+}
+
+fn match_haproxy_v2(data: &[u8]) -> PrefixMatch {
+    match_prefix(data, b"\r\n\r\n\0\r\nQUIT\n")
+}
+
+fn match_ssh(data: &[u8]) -> PrefixMatch {
+    match_prefix(data, b"SSH-")
+}
+
+type PrefixMatcher = fn(&[u8]) -> PrefixMatch;
+
+const PROTOCOL_MATCHERS: &[(StreamProtocol, PrefixMatcher)] = &[
+    (StreamProtocol::Tls, match_tls),
+    (StreamProtocol::H2, match_h2),
+    (StreamProtocol::Http1, match_http1),
+    (StreamProtocol::HaProxyV2, match_haproxy_v2),
+    (StreamProtocol::HaProxyV1, match_haproxy_v1),
+    (StreamProtocol::Socks5, match_socks5),
+    (StreamProtocol::Socks4, match_socks4),
+    (StreamProtocol::Ssh, match_ssh),
+];
+
+/// Maximum amount of bytes peeked by the [`ProtocolPeekRouter`],
+/// which is the length of the HTTP/2 connection preface.
+pub const PROTOCOL_PEEK_LEN: usize = 24;
+
+/// [`PeekStream`] alias used by [`ProtocolPeekRouter`].
+pub type ProtocolPeekStream<S> = PeekStream<StackReader<PROTOCOL_PEEK_LEN>, S>;
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "connection with unrouted protocol is rejected"]
+    pub struct UnknownProtocolRejectError;
+}
+
+/// A [`Service`] router that peeks the first bytes of a stream once,
+/// detects its [`StreamProtocol`] and dispatches it to the service
+/// registered for that protocol.
+///
+/// This is useful for single-port gateways, e.g. a proxy which accepts
+/// socks5, HTTP and TLS traffic on the same port. Streams of a protocol
+/// without a registered service are served by the fallback service,
+/// which by default rejects them using [`RejectService`].
+///
+/// The peeked bytes are not consumed: services receive
+/// a [`ProtocolPeekStream`] which replays them first. The detected
+/// [`StreamProtocol`] is inserted in the [`Context`] prior to serving the stream.
+///
+/// Protocol specific routers such as the `TlsPeekRouter`
+/// can still be used in case only a single protocol has to be detected.
+pub struct ProtocolPeekRouter<State, S, Response> {
+    services: Vec<(StreamProtocol, ProtocolPeekService<State, S, Response>)>,
+    fallback: ProtocolPeekService<State, S, Response>,
+    peek_timeout: Option<Duration>,
+}
+
+type ProtocolPeekService<State, S, Response> =
+    BoxService<State, ProtocolPeekStream<S>, Response, BoxError>;
+
+impl<State, S, Response> ProtocolPeekRouter<State, S, Response>
+where
+    State: Clone + Send + Sync + 'static,
+    S: crate::stream::Stream + Unpin,
+    Response: Send + 'static,
+{
+    /// Create a new [`ProtocolPeekRouter`],
+    /// which rejects all streams until services are registered.
+    pub fn new() -> Self {
+        Self {
+            services: Vec::new(),
+            fallback: MapErr::new(
+                RejectService::<Response, _>::new(UnknownProtocolRejectError),
+                Into::<BoxError>::into,
+            )
+            .boxed(),
+            peek_timeout: None,
+        }
+    }
+
+    /// Register the [`Service`] used to serve streams of the given [`StreamProtocol`],
+    /// replacing the service previously registered for it (if any).
+    pub fn with_service<T>(mut self, protocol: StreamProtocol, service: T) -> Self
+    where
+        T: Service<State, ProtocolPeekStream<S>, Response = Response, Error: Into<BoxError>>,
+    {
+        self.set_service(protocol, service);
+        self
+    }
+
+    /// Register the [`Service`] used to serve streams of the given [`StreamProtocol`],
+    /// replacing the service previously registered for it (if any).
+    pub fn set_service<T>(&mut self, protocol: StreamProtocol, service: T) -> &mut Self
+    where
+        T: Service<State, ProtocolPeekStream<S>, Response = Response, Error: Into<BoxError>>,
+    {
+        let service = MapErr::new(service, Into::<BoxError>::into).boxed();
+        match self.services.iter_mut().find(|(p, _)| *p == protocol) {
+            Some((_, svc)) => *svc = service,
+            None => self.services.push((protocol, service)),
+        }
+        self
+    }
+
+    /// Set the fallback [`Service`], used to serve streams of a [`StreamProtocol`]
+    /// without registered service, including [`StreamProtocol::Unknown`].
+    pub fn with_fallback<T>(mut self, fallback: T) -> Self
+    where
+        T: Service<State, ProtocolPeekStream<S>, Response = Response, Error: Into<BoxError>>,
+    {
+        self.set_fallback(fallback);
+        self
+    }
+
+    /// Set the fallback [`Service`], used to serve streams of a [`StreamProtocol`]
+    /// without registered service, including [`StreamProtocol::Unknown`].
+    pub fn set_fallback<T>(&mut self, fallback: T) -> &mut Self
+    where
+        T: Service<State, ProtocolPeekStream<S>, Response = Response, Error: Into<BoxError>>,
+    {
+        self.fallback = MapErr::new(fallback, Into::<BoxError>::into).boxed();
+        self
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum duration to wait for the client to send enough
+        /// data to detect its protocol, by default there is no timeout.
+        ///
+        /// Streams which timed out are detected as [`StreamProtocol::Unknown`],
+        /// and are thus served by the fallback service.
+        pub fn peek_timeout(mut self, timeout: Option<Duration>) -> Self {
+            self.peek_timeout = timeout;
+            self
+        }
+    }
+}
+
+impl<State, S, Response> Default for ProtocolPeekRouter<State, S, Response>
+where
+    State: Clone + Send + Sync + 'static,
+    S: crate::stream::Stream + Unpin,
+    Response: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State, S, Response> Clone for ProtocolPeekRouter<State, S, Response> {
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+            fallback: self.fallback.clone(),
+            peek_timeout: self.peek_timeout,
+        }
+    }
+}
+
+impl<State, S, Response> fmt::Debug for ProtocolPeekRouter<State, S, Response> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolPeekRouter")
+            .field(
+                "services",
+                &self.services.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+            )
+            .field("peek_timeout", &self.peek_timeout)
+            .finish()
+    }
+}
+
+impl<State, S, Response> Service<State, S> for ProtocolPeekRouter<State, S, Response>
+where
+    State: Clone + Send + Sync + 'static,
+    S: crate::stream::Stream + Unpin,
+    Response: Send + 'static,
+{
+    type Response = Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut stream: S,
+    ) -> Result<Self::Response, Self::Error> {
+        let mut peek_buf = [0u8; PROTOCOL_PEEK_LEN];
+        let mut n = 0;
+
+        let peek = async {
+            loop {
+                if let Some(protocol) = StreamProtocol::detect(&peek_buf[..n]) {
+                    return Ok::<_, BoxError>(protocol);
+                }
+                let m = stream
+                    .read(&mut peek_buf[n..])
+                    .await
+                    .context("try to read protocol prefix")?;
+                if m == 0 {
+                    return Ok(StreamProtocol::Unknown);
+                }
+                n += m;
+            }
+        };
+        let protocol = match self.peek_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, peek).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    tracing::debug!("protocol peek timed out after {timeout:?}");
+                    StreamProtocol::Unknown
+                }
+            },
+            None => peek.await?,
+        };
+        tracing::trace!(%protocol, "protocol prefix read (read: {n})");
+
+        let offset = PROTOCOL_PEEK_LEN - n;
+        if offset > 0 {
+            peek_buf.copy_within(..n, offset);
+        }
+
+        let mut peek = StackReader::new(peek_buf);
+        peek.skip(offset);
+
+        let stream = PeekStream::new(peek, stream);
+        ctx.insert(protocol);
+
+        match self.services.iter().find(|(p, _)| *p == protocol) {
+            Some((_, service)) => service.serve(ctx, stream).await,
+            None => self.fallback.serve(ctx, stream).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn test_stream_protocol_detect() {
+        let cases: &[(&[u8], Option<StreamProtocol>)] = &[
+            (b"", None),
+            (b"\x16", None),
+            (b"\x16\x03\x01\x02\x00", Some(StreamProtocol::Tls)),
+            (b"\x16\x05", Some(StreamProtocol::Unknown)),
+            (b"GE", None),
+            (b"GET / HTTP/1.1\r\n", Some(StreamProtocol::Http1)),
+            (b"CONNECT example.com:443", Some(StreamProtocol::Http1)),
+            (b"PRI * HTTP/2.0\r\n", None),
+            (
+                b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n",
+                Some(StreamProtocol::H2),
+            ),
+            (b"PRI * HTTP/1.1\r\n", Some(StreamProtocol::Unknown)),
+            (b"P", None),
+            (
+                b"PROXY TCP4 127.0.0.1 127.0.0.1 1 2\r\n",
+                Some(StreamProtocol::HaProxyV1),
+            ),
+            (b"\r\n\r\n\0\r\nQUIT\n\x21", Some(StreamProtocol::HaProxyV2)),
+            (b"\x05\x01\x00", Some(StreamProtocol::Socks5)),
+            (b"\x05\x00", Some(StreamProtocol::Unknown)),
+            (b"\x04\x01\x00\x50", Some(StreamProtocol::Socks4)),
+            (b"SSH-2.0-OpenSSH_9.6\r\n", Some(StreamProtocol::Ssh)),
+            (b"foo", Some(StreamProtocol::Unknown)),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(
+                *expected,
+                StreamProtocol::detect(data),
+                "data: {:?}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    async fn echo_protocol(
+        ctx: Context<()>,
+        mut stream: ProtocolPeekStream<std::io::Cursor<Vec<u8>>>,
+    ) -> Result<(StreamProtocol, Vec<u8>), Infallible> {
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await.unwrap();
+        Ok((*ctx.get::<StreamProtocol>().unwrap(), data))
+    }
+
+    #[tokio::test]
+    async fn test_protocol_peek_router() {
+        let router = ProtocolPeekRouter::new()
+            .with_service(StreamProtocol::Tls, service_fn(echo_protocol))
+            .with_service(StreamProtocol::Http1, service_fn(echo_protocol))
+            .with_service(StreamProtocol::Socks5, service_fn(echo_protocol));
+
+        for (content, protocol) in [
+            (&b"\x16\x03\x03\x00\x2afoo"[..], StreamProtocol::Tls),
+            (
+                b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n",
+                StreamProtocol::Http1,
+            ),
+            (b"\x05\x01\x00", StreamProtocol::Socks5),
+        ] {
+            let (detected, data) = router
+                .serve(Context::default(), std::io::Cursor::new(content.to_vec()))
+                .await
+                .unwrap();
+            assert_eq!(protocol, detected);
+            assert_eq!(content, &data[..]);
+        }
+
+        // no service registered for ssh and unknown protocols
+        for content in [&b"SSH-2.0-OpenSSH_9.6\r\n"[..], b"", b"foo"] {
+            assert!(
+                router
+                    .serve(Context::default(), std::io::Cursor::new(content.to_vec()))
+                    .await
+                    .is_err()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_protocol_peek_router_fallback() {
+        let router = ProtocolPeekRouter::new()
+            .with_service(StreamProtocol::Tls, service_fn(echo_protocol))
+            .with_fallback(service_fn(echo_protocol));
+
+        for (content, protocol) in [
+            (&b""[..], StreamProtocol::Unknown),
+            (b"foobarbazbananas", StreamProtocol::Unknown),
+            (
+                b"PROXY TCP4 127.0.0.1 127.0.0.1 1 2\r\n",
+                StreamProtocol::HaProxyV1,
+            ),
+            (b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\nfoo", StreamProtocol::H2),
+        ] {
+            let (detected, data) = router
+                .serve(Context::default(), std::io::Cursor::new(content.to_vec()))
+                .await
+                .unwrap();
+            assert_eq!(protocol, detected);
+            assert_eq!(content, &data[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_protocol_peek_router_timeout() {
+        async fn service(
+            ctx: Context<()>,
+            mut stream: ProtocolPeekStream<tokio::io::DuplexStream>,
+        ) -> Result<(StreamProtocol, Vec<u8>), Infallible> {
+            let mut data = Vec::new();
+            stream.read_to_end(&mut data).await.unwrap();
+            Ok((*ctx.get::<StreamProtocol>().unwrap(), data))
+        }
+
+        let router = ProtocolPeekRouter::new()
+            .with_service(StreamProtocol::H2, service_fn(service))
+            .with_fallback(service_fn(service))
+            .with_peek_timeout(Duration::from_millis(50));
+
+        let (client, server) = tokio::io::duplex(64);
+        let client = tokio::spawn(async move {
+            let mut client = client;
+            tokio::io::AsyncWriteExt::write_all(&mut client, b"PRI * HTTP/2.0")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        });
+
+        let (detected, data) = router.serve(Context::default(), server).await.unwrap();
+        assert_eq!(StreamProtocol::Unknown, detected);
+        assert_eq!(b"PRI * HTTP/2.0", &data[..]);
+        client.await.unwrap();
+    }
+}
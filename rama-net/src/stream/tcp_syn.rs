@@ -0,0 +1,219 @@
+use rama_core::error::OpaqueError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// The relevant headers of the TCP SYN packet which initiated a connection,
+/// as received by the server.
+///
+/// On Linux servers this is made available by the TCP listener
+/// as an extension for each accepted connection in case saving the SYN is enabled.
+/// It is the input used by passive TCP fingerprints such as [`Ja4T`].
+///
+/// [`Ja4T`]: crate::fingerprint::Ja4T
+pub struct TcpSyn {
+    ip_version: u8,
+    ttl: u8,
+    dont_fragment: bool,
+    window_size: u16,
+    options: Vec<u8>,
+    mss: Option<u16>,
+    window_scale: Option<u8>,
+}
+
+const TCP_OPTION_EOL: u8 = 0;
+const TCP_OPTION_NOP: u8 = 1;
+const TCP_OPTION_MSS: u8 = 2;
+const TCP_OPTION_WINDOW_SCALE: u8 = 3;
+
+const IP_PROTOCOL_TCP: u8 = 6;
+
+impl TcpSyn {
+    /// Parse a [`TcpSyn`] from the raw IP and TCP headers of a SYN packet,
+    /// e.g. as returned by the Linux `TCP_SAVED_SYN` socket option.
+    ///
+    /// IPv6 extension headers are not supported.
+    pub fn parse(packet: &[u8]) -> Result<Self, OpaqueError> {
+        let version = packet
+            .first()
+            .map(|b| b >> 4)
+            .ok_or_else(|| OpaqueError::from_display("parse tcp syn: empty packet"))?;
+
+        let (ttl, dont_fragment, tcp) = match version {
+            4 => {
+                let ihl = ((packet[0] & 0x0f) as usize) * 4;
+                if ihl < 20 || packet.len() < ihl {
+                    return Err(OpaqueError::from_display(
+                        "parse tcp syn: invalid ipv4 header length",
+                    ));
+                }
+                if packet[9] != IP_PROTOCOL_TCP {
+                    return Err(OpaqueError::from_display(
+                        "parse tcp syn: ipv4 packet is not tcp",
+                    ));
+                }
+                (packet[8], packet[6] & 0x40 != 0, &packet[ihl..])
+            }
+            6 => {
+                if packet.len() < 40 {
+                    return Err(OpaqueError::from_display(
+                        "parse tcp syn: invalid ipv6 header length",
+                    ));
+                }
+                if packet[6] != IP_PROTOCOL_TCP {
+                    return Err(OpaqueError::from_display(
+                        "parse tcp syn: ipv6 packet is not tcp (or uses extension headers)",
+                    ));
+                }
+                (packet[7], false, &packet[40..])
+            }
+            _ => {
+                return Err(OpaqueError::from_display(
+                    "parse tcp syn: unknown ip version",
+                ));
+            }
+        };
+
+        if tcp.len() < 20 {
+            return Err(OpaqueError::from_display(
+                "parse tcp syn: tcp header too short",
+            ));
+        }
+        let window_size = u16::from_be_bytes([tcp[14], tcp[15]]);
+        let data_offset = ((tcp[12] >> 4) as usize) * 4;
+        if data_offset < 20 || tcp.len() < data_offset {
+            return Err(OpaqueError::from_display(
+                "parse tcp syn: invalid tcp data offset",
+            ));
+        }
+
+        let mut options = Vec::new();
+        let mut mss = None;
+        let mut window_scale = None;
+
+        let mut i = &tcp[20..data_offset];
+        while let Some(&kind) = i.first() {
+            options.push(kind);
+            match kind {
+                TCP_OPTION_EOL => break,
+                TCP_OPTION_NOP => i = &i[1..],
+                _ => {
+                    let len = i.get(1).copied().unwrap_or_default() as usize;
+                    if len < 2 || i.len() < len {
+                        return Err(OpaqueError::from_display(
+                            "parse tcp syn: invalid tcp option length",
+                        ));
+                    }
+                    let data = &i[2..len];
+                    match (kind, data) {
+                        (TCP_OPTION_MSS, &[a, b]) => mss = Some(u16::from_be_bytes([a, b])),
+                        (TCP_OPTION_WINDOW_SCALE, &[scale]) => window_scale = Some(scale),
+                        _ => (),
+                    }
+                    i = &i[len..];
+                }
+            }
+        }
+
+        Ok(Self {
+            ip_version: version,
+            ttl,
+            dont_fragment,
+            window_size,
+            options,
+            mss,
+            window_scale,
+        })
+    }
+
+    /// The IP version (`4` or `6`) of the SYN packet.
+    pub fn ip_version(&self) -> u8 {
+        self.ip_version
+    }
+
+    /// The (remaining) time to live of the SYN packet,
+    /// which is the hop limit in case of IPv6.
+    pub fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// Whether the "don't fragment" flag is set (IPv4 only).
+    pub fn dont_fragment(&self) -> bool {
+        self.dont_fragment
+    }
+
+    /// The TCP window size advertised in the SYN packet.
+    pub fn window_size(&self) -> u16 {
+        self.window_size
+    }
+
+    /// The kinds of the TCP options, in the order they appear in the SYN packet.
+    pub fn options(&self) -> &[u8] {
+        &self.options
+    }
+
+    /// The maximum segment size (MSS) TCP option value, if present.
+    pub fn mss(&self) -> Option<u16> {
+        self.mss
+    }
+
+    /// The window scale TCP option value, if present.
+    pub fn window_scale(&self) -> Option<u8> {
+        self.window_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP_SYN_LINUX: &[u8] = &[
+        // ipv4
+        0x45, 0x00, 0x00, 0x3c, 0x1c, 0x46, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00, 0x7f, 0x00, 0x00,
+        0x01, 0x7f, 0x00, 0x00, 0x01, // tcp
+        0xd4, 0x31, 0x1f, 0x90, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xa0, 0x02, 0xfa,
+        0xf0, 0x00, 0x00, 0x00, 0x00, // tcp options
+        0x02, 0x04, 0x05, 0xb4, 0x04, 0x02, 0x08, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x03, 0x03, 0x07,
+    ];
+
+    #[test]
+    fn test_parse_tcp_syn_ipv4() {
+        let syn = TcpSyn::parse(TCP_SYN_LINUX).unwrap();
+        assert_eq!(4, syn.ip_version());
+        assert_eq!(64, syn.ttl());
+        assert!(syn.dont_fragment());
+        assert_eq!(64240, syn.window_size());
+        assert_eq!(&[2, 4, 8, 1, 3], syn.options());
+        assert_eq!(Some(1460), syn.mss());
+        assert_eq!(Some(7), syn.window_scale());
+    }
+
+    #[test]
+    fn test_parse_tcp_syn_ipv6() {
+        let mut packet = vec![0x60, 0, 0, 0, 0, 24, IP_PROTOCOL_TCP, 57];
+        packet.extend_from_slice(&[0; 32]);
+        packet.extend_from_slice(&[
+            0xd4, 0x31, 0x01, 0xbb, 0, 0, 0, 1, 0, 0, 0, 0, 0x60, 0x02, 0xff, 0xff, 0, 0, 0, 0,
+        ]);
+        packet.extend_from_slice(&[0x02, 0x04, 0x05, 0xa0]);
+
+        let syn = TcpSyn::parse(&packet).unwrap();
+        assert_eq!(6, syn.ip_version());
+        assert_eq!(57, syn.ttl());
+        assert_eq!(65535, syn.window_size());
+        assert_eq!(&[2], syn.options());
+        assert_eq!(Some(1440), syn.mss());
+        assert_eq!(None, syn.window_scale());
+    }
+
+    #[test]
+    fn test_parse_tcp_syn_errors() {
+        assert!(TcpSyn::parse(&[]).is_err());
+        assert!(TcpSyn::parse(&TCP_SYN_LINUX[..30]).is_err());
+        assert!(TcpSyn::parse(&TCP_SYN_LINUX[..TCP_SYN_LINUX.len() - 1]).is_err());
+
+        let mut udp = TCP_SYN_LINUX.to_vec();
+        udp[9] = 17;
+        assert!(TcpSyn::parse(&udp).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use super::{FaultProfile, FaultRng, FaultyStream};
+use crate::{
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+};
+use rama_core::{
+    Context, Layer, Service,
+    error::{BoxError, OpaqueError},
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Shared state to give each stream its own (reproducible) fault schedule.
+#[derive(Debug, Clone)]
+struct FaultScheduler {
+    profile: FaultProfile,
+    connections: Arc<AtomicU64>,
+}
+
+/// Salt used to derive the seed of the connect fault from the connection seed.
+const CONNECT_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl FaultScheduler {
+    fn new(profile: FaultProfile) -> Self {
+        Self {
+            profile,
+            connections: Default::default(),
+        }
+    }
+
+    /// The [`FaultProfile`] for the next connection,
+    /// with a seed derived from the profile seed and connection index.
+    fn next_profile(&self) -> FaultProfile {
+        let index = self.connections.fetch_add(1, Ordering::Relaxed);
+        let seed = FaultRng::new(self.profile.seed ^ index).next_u64();
+        self.profile.clone().with_seed(seed)
+    }
+}
+
+/// A [`Service`] that wraps a [`Service`]'s input IO [`Stream`] in a [`FaultyStream`].
+///
+/// Each stream gets its own fault schedule, derived from the seed of
+/// the [`FaultProfile`] and the order in which streams are accepted.
+///
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+pub struct IncomingFaultInjectionService<S> {
+    inner: S,
+    scheduler: FaultScheduler,
+}
+
+impl<S: fmt::Debug> fmt::Debug for IncomingFaultInjectionService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingFaultInjectionService")
+            .field("inner", &self.inner)
+            .field("scheduler", &self.scheduler)
+            .finish()
+    }
+}
+
+impl<S> IncomingFaultInjectionService<S> {
+    /// Create a new [`IncomingFaultInjectionService`].
+    ///
+    /// See [`IncomingFaultInjectionService`] for more information.
+    pub fn new(inner: S, profile: FaultProfile) -> Self {
+        Self {
+            inner,
+            scheduler: FaultScheduler::new(profile),
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S> Clone for IncomingFaultInjectionService<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            scheduler: self.scheduler.clone(),
+        }
+    }
+}
+
+impl<State, S, IO> Service<State, IO> for IncomingFaultInjectionService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, FaultyStream<IO>>,
+    IO: Stream,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn serve(
+        &self,
+        ctx: Context<State>,
+        stream: IO,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let stream = FaultyStream::new(stream, self.scheduler.next_profile());
+        self.inner.serve(ctx, stream)
+    }
+}
+
+/// A [`Layer`] that wraps a [`Service`]'s input IO [`Stream`] in a [`FaultyStream`].
+///
+/// See [`IncomingFaultInjectionService`] for more information.
+///
+/// [`Layer`]: rama_core::Layer
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+#[derive(Debug, Clone)]
+pub struct IncomingFaultInjectionLayer {
+    profile: FaultProfile,
+}
+
+impl IncomingFaultInjectionLayer {
+    /// Create a new [`IncomingFaultInjectionLayer`] for the given [`FaultProfile`].
+    pub const fn new(profile: FaultProfile) -> Self {
+        Self { profile }
+    }
+}
+
+impl<S> Layer<S> for IncomingFaultInjectionLayer {
+    type Service = IncomingFaultInjectionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IncomingFaultInjectionService::new(inner, self.profile.clone())
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        IncomingFaultInjectionService::new(inner, self.profile)
+    }
+}
+
+/// A [`Service`] that wraps the IO [`Stream`] established by a connector in a [`FaultyStream`],
+/// e.g. to simulate a bad network for a `TcpConnector`.
+///
+/// Each connection gets its own fault schedule, derived from the seed of
+/// the [`FaultProfile`] and the order in which connections are established.
+/// Connection attempts fail according to [`FaultProfile::with_connect_failure_probability`].
+///
+/// [`Service`]: rama_core::Service
+/// [`Stream`]: crate::stream::Stream
+pub struct OutgoingFaultInjectionService<S> {
+    inner: S,
+    scheduler: FaultScheduler,
+}
+
+impl<S: fmt::Debug> fmt::Debug for OutgoingFaultInjectionService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutgoingFaultInjectionService")
+            .field("inner", &self.inner)
+            .field("scheduler", &self.scheduler)
+            .finish()
+    }
+}
+
+impl<S> OutgoingFaultInjectionService<S> {
+    /// Create a new [`OutgoingFaultInjectionService`].
+    ///
+    /// See [`OutgoingFaultInjectionService`] for more information.
+    pub fn new(inner: S, profile: FaultProfile) -> Self {
+        Self {
+            inner,
+            scheduler: FaultScheduler::new(profile),
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S> Clone for OutgoingFaultInjectionService<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            scheduler: self.scheduler.clone(),
+        }
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for OutgoingFaultInjectionService<S>
+where
+    S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Send + 'static>,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = EstablishedClientConnection<FaultyStream<S::Connection>, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let profile = self.scheduler.next_profile();
+        // derived separately, as the profile seed also drives the faults of the stream
+        let connect_seed = profile.seed ^ CONNECT_SEED_SALT;
+        if FaultRng::new(connect_seed).chance(profile.connect_failure_probability) {
+            return Err(OpaqueError::from_display("connect failed (injected fault)").into());
+        }
+
+        let EstablishedClientConnection { ctx, req, conn } =
+            self.inner.connect(ctx, req).await.map_err(Into::into)?;
+        let conn = FaultyStream::new(conn, profile);
+        Ok(EstablishedClientConnection { ctx, req, conn })
+    }
+}
+
+/// A [`Layer`] that wraps the IO [`Stream`] established by a connector in a [`FaultyStream`].
+///
+/// See [`OutgoingFaultInjectionService`] for more information.
+///
+/// [`Layer`]: rama_core::Layer
+/// [`Stream`]: crate::stream::Stream
+#[derive(Debug, Clone)]
+pub struct OutgoingFaultInjectionLayer {
+    profile: FaultProfile,
+}
+
+impl OutgoingFaultInjectionLayer {
+    /// Create a new [`OutgoingFaultInjectionLayer`] for the given [`FaultProfile`].
+    pub const fn new(profile: FaultProfile) -> Self {
+        Self { profile }
+    }
+}
+
+impl<S> Layer<S> for OutgoingFaultInjectionLayer {
+    type Service = OutgoingFaultInjectionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OutgoingFaultInjectionService::new(inner, self.profile.clone())
+    }
+
+    fn into_layer(self, inner: S) -> Self::Service {
+        OutgoingFaultInjectionService::new(inner, self.profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rama_core::service::service_fn;
+    use std::{convert::Infallible, io};
+    use tokio::io::AsyncReadExt;
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_outgoing_fault_injection() {
+        use crate::test_utils::client::{MockConnectorService, MockSocket};
+        use tokio::io::AsyncWriteExt;
+
+        let connector = OutgoingFaultInjectionLayer::new(
+            FaultProfile::new()
+                .with_seed(1)
+                .with_connect_failure_probability(0.5)
+                .with_truncate_probability(1.0),
+        )
+        .into_layer(MockConnectorService::new(|| {
+            service_fn(async |_, mut stream: MockSocket| {
+                let mut buf = [0; 64];
+                loop {
+                    let n = stream.read(&mut buf).await?;
+                    if n == 0 {
+                        return Ok::<_, io::Error>(());
+                    }
+                    stream.write_all(&buf[..n]).await?;
+                }
+            })
+        }));
+
+        let mut results = Vec::new();
+        for _ in 0..16 {
+            results.push(connector.serve(Context::default(), ()).await);
+        }
+        assert!(results.iter().any(Result::is_err));
+
+        let mut conn = results.into_iter().find_map(Result::ok).unwrap().conn;
+        // writes are truncated, but all data is eventually echoed back
+        conn.write_all(b"hello world").await.unwrap();
+        let mut buf = [0; 11];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"hello world", &buf);
+    }
+
+    #[tokio::test]
+    async fn test_incoming_fault_injection() {
+        let svc = IncomingFaultInjectionLayer::new(FaultProfile::new().with_reset_probability(1.0))
+            .into_layer(service_fn(
+                async |_, mut stream: FaultyStream<tokio::io::DuplexStream>| {
+                    Ok::<_, Infallible>(stream.read_u8().await.unwrap_err().kind())
+                },
+            ));
+
+        let (_client, server) = tokio::io::duplex(64);
+        let kind = svc.serve(Context::default(), server).await.unwrap();
+        assert_eq!(io::ErrorKind::ConnectionReset, kind);
+    }
+}
@@ -0,0 +1,167 @@
+//! Network fault injection, to test how clients, servers and proxies
+//! behave on bad networks in a reproducible manner.
+//!
+//! A [`FaultProfile`] describes the faults to inject (latency, jitter, throughput caps,
+//! connection resets, half-closes and truncated reads/writes), which are applied
+//! according to a schedule derived from its seed. The same seed (and the same sequence of
+//! IO operations) results in the same faults.
+//!
+//! Use [`FaultyStream`] to wrap a single stream, [`IncomingFaultInjectionLayer`]
+//! to wrap accepted streams of a server, or [`OutgoingFaultInjectionLayer`] to wrap
+//! the connections established by a connector, such as the `TcpConnector` of `rama-tcp`.
+
+use crate::stream::layer::BandwidthLimit;
+use rama_utils::macros::generate_set_and_with;
+use std::time::Duration;
+
+mod stream;
+#[doc(inline)]
+pub use stream::FaultyStream;
+
+mod layer;
+#[doc(inline)]
+pub use layer::{
+    IncomingFaultInjectionLayer, IncomingFaultInjectionService, OutgoingFaultInjectionLayer,
+    OutgoingFaultInjectionService,
+};
+
+#[derive(Debug, Clone, Default)]
+/// The faults to inject into a stream (or connector), see the [module docs](self).
+///
+/// Probabilities are expressed in the range `[0, 1]`,
+/// and (except for connect failures) are rolled for each IO operation.
+/// By default no faults are injected.
+pub struct FaultProfile {
+    seed: u64,
+    latency: Duration,
+    jitter: Duration,
+    read_limit: Option<BandwidthLimit>,
+    write_limit: Option<BandwidthLimit>,
+    reset_probability: f64,
+    half_close_probability: f64,
+    truncate_probability: f64,
+    connect_failure_probability: f64,
+}
+
+impl FaultProfile {
+    /// Create a new [`FaultProfile`] which does not inject any faults yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    generate_set_and_with! {
+        /// Set the seed from which the fault schedule is derived.
+        pub fn seed(mut self, seed: u64) -> Self {
+            self.seed = seed;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the latency added to each IO operation.
+        pub fn latency(mut self, latency: Duration) -> Self {
+            self.latency = latency;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the maximum random latency added on top of the fixed latency
+        /// to each IO operation.
+        pub fn jitter(mut self, jitter: Duration) -> Self {
+            self.jitter = jitter;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`BandwidthLimit`] to cap the read throughput.
+        pub fn read_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.read_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`BandwidthLimit`] to cap the write throughput.
+        pub fn write_limit(mut self, limit: Option<BandwidthLimit>) -> Self {
+            self.write_limit = limit;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the probability of an IO operation resetting the connection,
+        /// failing it and all following operations with [`ConnectionReset`].
+        ///
+        /// [`ConnectionReset`]: std::io::ErrorKind::ConnectionReset
+        pub fn reset_probability(mut self, probability: f64) -> Self {
+            self.reset_probability = probability.clamp(0.0, 1.0);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the probability of an IO operation half-closing the stream in its direction.
+        ///
+        /// A half-closed read direction returns EOF, while a half-closed
+        /// write direction fails with [`BrokenPipe`].
+        ///
+        /// [`BrokenPipe`]: std::io::ErrorKind::BrokenPipe
+        pub fn half_close_probability(mut self, probability: f64) -> Self {
+            self.half_close_probability = probability.clamp(0.0, 1.0);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the probability of an IO operation only reading or writing
+        /// a random part of the requested bytes.
+        pub fn truncate_probability(mut self, probability: f64) -> Self {
+            self.truncate_probability = probability.clamp(0.0, 1.0);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the probability of a connection attempt failing,
+        /// only used by [`OutgoingFaultInjectionService`].
+        pub fn connect_failure_probability(mut self, probability: f64) -> Self {
+            self.connect_failure_probability = probability.clamp(0.0, 1.0);
+            self
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A small deterministic PRNG (splitmix64),
+/// such that fault schedules are stable across platforms and dependency versions.
+struct FaultRng(u64);
+
+impl FaultRng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random float in the range `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        // always roll, such that the schedule does not depend on the probabilities
+        self.next_f64() < probability
+    }
+
+    fn delay(&mut self, latency: Duration, jitter: Duration) -> Duration {
+        latency + jitter.mul_f64(self.next_f64())
+    }
+}
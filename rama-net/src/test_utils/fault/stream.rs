@@ -0,0 +1,343 @@
+use super::{FaultProfile, FaultRng};
+use crate::stream::{layer::ThrottledStream, poll_read_limited};
+use pin_project_lite::pin_project;
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::Sleep,
+};
+
+pin_project! {
+    /// A wrapper around a [`AsyncRead`] and/or [`AsyncWrite`] that injects
+    /// the faults described by a [`FaultProfile`].
+    ///
+    /// Read and write operations each follow their own schedule,
+    /// derived from the seed of the [`FaultProfile`].
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub struct FaultyStream<S> {
+        profile: FaultProfile,
+        reset: bool,
+        read: FaultDirection,
+        write: FaultDirection,
+        #[pin]
+        stream: ThrottledStream<S>,
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for FaultyStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultyStream")
+            .field("profile", &self.profile)
+            .field("reset", &self.reset)
+            .field("read", &self.read)
+            .field("write", &self.write)
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+struct FaultDirection {
+    rng: FaultRng,
+    closed: bool,
+    op: Option<PendingOp>,
+}
+
+impl fmt::Debug for FaultDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultDirection")
+            .field("rng", &self.rng)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+struct PendingOp {
+    delay: Option<Pin<Box<Sleep>>>,
+    truncate: Option<f64>,
+}
+
+enum OpFault {
+    Reset,
+    HalfClose,
+    Proceed { truncate: Option<f64> },
+}
+
+impl FaultDirection {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: FaultRng::new(seed),
+            closed: false,
+            op: None,
+        }
+    }
+
+    fn poll_op(
+        &mut self,
+        profile: &FaultProfile,
+        reset: &mut bool,
+        cx: &mut Context<'_>,
+    ) -> Poll<OpFault> {
+        if *reset {
+            return Poll::Ready(OpFault::Reset);
+        }
+        if self.closed {
+            return Poll::Ready(OpFault::HalfClose);
+        }
+
+        let op = match &mut self.op {
+            Some(op) => op,
+            op @ None => {
+                let rng = &mut self.rng;
+                let do_reset = rng.chance(profile.reset_probability);
+                let do_half_close = rng.chance(profile.half_close_probability);
+                let delay = rng.delay(profile.latency, profile.jitter);
+                let fraction = rng.next_f64();
+                let do_truncate = rng.chance(profile.truncate_probability);
+
+                if do_reset {
+                    *reset = true;
+                    return Poll::Ready(OpFault::Reset);
+                }
+                if do_half_close {
+                    self.closed = true;
+                    return Poll::Ready(OpFault::HalfClose);
+                }
+
+                op.insert(PendingOp {
+                    delay: (!delay.is_zero()).then(|| Box::pin(tokio::time::sleep(delay))),
+                    truncate: do_truncate.then_some(fraction),
+                })
+            }
+        };
+
+        if let Some(delay) = op.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            op.delay = None;
+        }
+        Poll::Ready(OpFault::Proceed {
+            truncate: op.truncate,
+        })
+    }
+}
+
+fn truncated_len(len: usize, truncate: Option<f64>) -> usize {
+    match truncate {
+        Some(fraction) => ((len as f64 * fraction) as usize).clamp(1, len),
+        None => len,
+    }
+}
+
+fn reset_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionReset,
+        "connection reset (injected fault)",
+    )
+}
+
+impl<S> FaultyStream<S> {
+    /// Create a new [`FaultyStream`] that wraps the given [`AsyncRead`] and/or [`AsyncWrite`],
+    /// injecting the faults described by the given [`FaultProfile`].
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn new(stream: S, profile: FaultProfile) -> Self {
+        let mut stream = ThrottledStream::new(stream);
+        if let Some(limit) = profile.read_limit {
+            stream = stream.with_read_limit(limit);
+        }
+        if let Some(limit) = profile.write_limit {
+            stream = stream.with_write_limit(limit);
+        }
+        Self {
+            read: FaultDirection::new(profile.seed),
+            write: FaultDirection::new(!profile.seed),
+            profile,
+            reset: false,
+            stream,
+        }
+    }
+
+    /// Returns true if the connection was reset by an injected fault.
+    pub fn is_reset(&self) -> bool {
+        self.reset
+    }
+
+    /// Get the inner [`AsyncRead`] and/or [`AsyncWrite`] stream,
+    /// no longer injecting any faults.
+    ///
+    /// [`AsyncRead`]: crate::stream::AsyncRead
+    /// [`AsyncWrite`]: crate::stream::AsyncWrite
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+}
+
+impl<S> AsyncRead for FaultyStream<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        if buf.remaining() == 0 {
+            return this.stream.poll_read(cx, buf);
+        }
+
+        let truncate = match ready!(this.read.poll_op(this.profile, this.reset, cx)) {
+            OpFault::Reset => return Poll::Ready(Err(reset_error())),
+            OpFault::HalfClose => return Poll::Ready(Ok(())),
+            OpFault::Proceed { truncate } => truncate,
+        };
+
+        let limit = truncated_len(buf.remaining(), truncate);
+        let result = ready!(poll_read_limited(this.stream, cx, buf, limit));
+        this.read.op = None;
+
+        Poll::Ready(result.map(|_| ()))
+    }
+}
+
+impl<S> AsyncWrite for FaultyStream<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.project();
+        if buf.is_empty() {
+            return this.stream.poll_write(cx, buf);
+        }
+
+        let truncate = match ready!(this.write.poll_op(this.profile, this.reset, cx)) {
+            OpFault::Reset => return Poll::Ready(Err(reset_error())),
+            OpFault::HalfClose => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "write side closed (injected fault)",
+                )));
+            }
+            OpFault::Proceed { truncate } => truncate,
+        };
+
+        let result = ready!(
+            this.stream
+                .poll_write(cx, &buf[..truncated_len(buf.len(), truncate)])
+        );
+        this.write.op = None;
+        Poll::Ready(result)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        if self.reset {
+            return Poll::Ready(Err(reset_error()));
+        }
+        self.project().stream.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        if self.reset {
+            return Poll::Ready(Err(reset_error()));
+        }
+        self.project().stream.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stream::layer::BandwidthLimit;
+    use std::{num::NonZeroU64, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt, duplex},
+        time::Instant,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_and_bandwidth() {
+        let (client, mut server) = duplex(1024);
+        let mut client = FaultyStream::new(
+            client,
+            FaultProfile::new()
+                .with_latency(Duration::from_millis(100))
+                .with_write_limit(BandwidthLimit::new(NonZeroU64::new(100).unwrap())),
+        );
+
+        let start = Instant::now();
+        client.write_all(&[1; 300]).await.unwrap();
+        let mut buf = [0; 300];
+        server.read_exact(&mut buf).await.unwrap();
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(2), "elapsed: {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(3), "elapsed: {elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let (client, _server) = duplex(1024);
+        let mut client = FaultyStream::new(client, FaultProfile::new().with_reset_probability(1.0));
+
+        let err = client.write_all(b"hello").await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        assert!(client.is_reset());
+
+        let err = client.read_u8().await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    }
+
+    #[tokio::test]
+    async fn test_half_close() {
+        let (client, mut server) = duplex(1024);
+        server.write_all(b"hello").await.unwrap();
+        let mut client =
+            FaultyStream::new(client, FaultProfile::new().with_half_close_probability(1.0));
+
+        let mut buf = Vec::new();
+        assert_eq!(0, client.read_to_end(&mut buf).await.unwrap());
+        let err = client.write_all(b"hello").await.unwrap_err();
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+    }
+
+    #[tokio::test]
+    async fn test_truncate_is_reproducible() {
+        async fn read_sizes(seed: u64) -> Vec<usize> {
+            let (client, mut server) = duplex(1024);
+            server.write_all(&[1; 1000]).await.unwrap();
+            drop(server);
+
+            let mut client = FaultyStream::new(
+                client,
+                FaultProfile::new()
+                    .with_seed(seed)
+                    .with_truncate_probability(0.5),
+            );
+            let mut sizes = Vec::new();
+            let mut buf = [0; 100];
+            loop {
+                match client.read(&mut buf).await.unwrap() {
+                    0 => break sizes,
+                    n => sizes.push(n),
+                }
+            }
+        }
+
+        let sizes = read_sizes(42).await;
+        assert_eq!(1000, sizes.iter().sum::<usize>());
+        assert!(sizes.iter().any(|n| *n < 100));
+        assert_eq!(sizes, read_sizes(42).await);
+        assert_ne!(sizes, read_sizes(7).await);
+    }
+}
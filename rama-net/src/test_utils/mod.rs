@@ -1 +1,5 @@
 pub mod client;
+
+pub mod fault;
+
+pub mod network;
@@ -0,0 +1,217 @@
+use super::{VirtualNetwork, VirtualStream};
+use crate::{
+    address::{Authority, Host, ProxyAddress},
+    client::EstablishedClientConnection,
+    stream::{ClientSocketInfo, Socket, SocketInfo},
+    transport::{TransportProtocol, TryRefIntoTransportContext},
+};
+use rama_core::{
+    Context, Service,
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+#[derive(Debug, Clone)]
+/// A connector which establishes [`VirtualStream`]s within a [`VirtualNetwork`].
+///
+/// It behaves like the `TcpConnector` of `rama-tcp`: it connects to the
+/// [`ProxyAddress`] if one is found in the [`Context`] and otherwise to the
+/// authority of the transport context of the request. Domains are resolved
+/// using the host records of the [`VirtualNetwork`].
+pub struct VirtualConnector {
+    network: VirtualNetwork,
+    local_ip: Option<IpAddr>,
+}
+
+impl VirtualConnector {
+    /// Create a new [`VirtualConnector`] for the given [`VirtualNetwork`].
+    pub fn new(network: VirtualNetwork) -> Self {
+        Self {
+            network,
+            local_ip: None,
+        }
+    }
+
+    rama_utils::macros::generate_set_and_with! {
+        /// Set the local IP used for established connections.
+        ///
+        /// By default the loopback address matching the IP version of the target is used.
+        pub fn local_ip(mut self, ip: Option<IpAddr>) -> Self {
+            self.local_ip = ip;
+            self
+        }
+    }
+
+    fn connect_authority(&self, authority: &Authority) -> Result<VirtualStream, OpaqueError> {
+        let ips = match authority.host() {
+            Host::Address(ip) => vec![*ip],
+            Host::Name(domain) => self.network.resolve(domain),
+        };
+        if ips.is_empty() {
+            return Err(OpaqueError::from_display(format!(
+                "virtual connector: failed to resolve {authority}"
+            )));
+        }
+
+        let mut last_err = None;
+        for ip in ips {
+            let local_ip = self.local_ip.unwrap_or(match ip {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+            match self
+                .network
+                .connect(local_ip, SocketAddr::new(ip, authority.port()))
+            {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(OpaqueError::from_std(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "no addresses to connect to",
+            )
+        })))
+    }
+}
+
+impl<State, Request> Service<State, Request> for VirtualConnector
+where
+    State: Clone + Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State> + Send + 'static,
+    Request::Error: Into<BoxError> + Send + Sync + 'static,
+{
+    type Response = EstablishedClientConnection<VirtualStream, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let conn = if let Some(proxy) = ctx.get::<ProxyAddress>() {
+            self.connect_authority(&proxy.authority)
+                .context("virtual connector: connect to proxy")?
+        } else {
+            let transport_ctx = ctx
+                .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+                .map_err(|err| {
+                    OpaqueError::from_boxed(err.into())
+                        .context("virtual connector: compute transport context to get authority")
+                })?;
+
+            if transport_ctx.protocol == TransportProtocol::Udp {
+                return Err(OpaqueError::from_display(
+                    "virtual connector cannot establish a UDP transport",
+                )
+                .into());
+            }
+
+            self.connect_authority(&transport_ctx.authority.clone())
+                .context("virtual connector: connect to server")?
+        };
+
+        ctx.insert(ClientSocketInfo(SocketInfo::new(
+            conn.local_addr().ok(),
+            conn.peer_addr()?,
+        )));
+        Ok(EstablishedClientConnection { ctx, req, conn })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{address::Domain, client::ConnectorService};
+    use rama_core::service::service_fn;
+    use rama_http_types::Request;
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn echo_peer_addr_server(network: &VirtualNetwork, addr: SocketAddr) {
+        let listener = network.bind(addr).unwrap();
+        tokio::spawn(listener.serve(service_fn(
+            async |ctx: Context<()>, mut stream: VirtualStream| {
+                let info = ctx.get::<SocketInfo>().unwrap();
+                stream
+                    .write_all(info.peer_addr().to_string().as_bytes())
+                    .await
+                    .unwrap();
+                Ok::<_, Infallible>(())
+            },
+        )));
+    }
+
+    fn request(uri: &'static str) -> Request<()> {
+        Request::builder().uri(uri).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_virtual_connector_resolves_domain() {
+        let network = VirtualNetwork::new();
+        network.insert_host(
+            Domain::from_static("example.com"),
+            Ipv4Addr::new(10, 0, 0, 1),
+        );
+        echo_peer_addr_server(&network, ([10, 0, 0, 1], 80).into());
+
+        let local_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+        let connector = VirtualConnector::new(network).with_local_ip(local_ip);
+        let EstablishedClientConnection { ctx, mut conn, .. } = connector
+            .connect(Context::default(), request("http://example.com"))
+            .await
+            .unwrap();
+
+        let info = ctx.get::<ClientSocketInfo>().unwrap();
+        let local_addr = *info.local_addr().unwrap();
+        assert_eq!(local_ip, local_addr.ip());
+        assert_eq!(
+            "10.0.0.1:80".parse::<SocketAddr>().unwrap(),
+            *info.peer_addr()
+        );
+
+        let mut peer_addr = String::new();
+        conn.read_to_string(&mut peer_addr).await.unwrap();
+        assert_eq!(local_addr.to_string(), peer_addr);
+    }
+
+    #[tokio::test]
+    async fn test_virtual_connector_proxy_address() {
+        let network = VirtualNetwork::new();
+        echo_peer_addr_server(&network, ([0, 0, 0, 0], 3128).into());
+
+        let mut ctx = Context::default();
+        ctx.insert(ProxyAddress::try_from("http://10.1.1.1:3128").unwrap());
+        let EstablishedClientConnection { ctx, .. } = VirtualConnector::new(network)
+            .connect(ctx, request("https://example.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            "10.1.1.1:3128".parse::<SocketAddr>().unwrap(),
+            *ctx.get::<ClientSocketInfo>().unwrap().peer_addr()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_virtual_connector_errors() {
+        let network = VirtualNetwork::new();
+        let connector = VirtualConnector::new(network.clone());
+        assert!(
+            connector
+                .connect(Context::default(), request("http://unknown.example"))
+                .await
+                .is_err()
+        );
+        assert!(
+            connector
+                .connect(Context::default(), request("http://127.0.0.1:8080"))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use super::{VirtualNetwork, VirtualStream};
+use crate::{address::SocketAddress, stream::SocketInfo};
+use rama_core::{Context, Service, graceful::ShutdownGuard, rt::Executor, telemetry::tracing};
+use std::{fmt, io, net::SocketAddr, pin::pin, sync::Arc};
+
+/// A listener bound to an address of a [`VirtualNetwork`],
+/// accepting [`VirtualStream`]s.
+///
+/// Offers the same API as the `TcpListener` of `rama-tcp`,
+/// such that servers can be tested without binding real sockets.
+/// The address is released once the listener is dropped.
+pub struct VirtualListener<State = ()> {
+    binding: Binding,
+    incoming: flume::Receiver<(VirtualStream, SocketAddr)>,
+    state: State,
+}
+
+impl<State: fmt::Debug> fmt::Debug for VirtualListener<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VirtualListener")
+            .field("addr", &self.binding.addr)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl VirtualListener<()> {
+    pub(super) fn new(
+        network: VirtualNetwork,
+        addr: SocketAddr,
+        incoming: flume::Receiver<(VirtualStream, SocketAddr)>,
+    ) -> Self {
+        Self {
+            binding: Binding { network, addr },
+            incoming,
+            state: (),
+        }
+    }
+
+    /// Define the listener's state.
+    pub fn with_state<State>(self, state: State) -> VirtualListener<State> {
+        VirtualListener {
+            binding: self.binding,
+            incoming: self.incoming,
+            state,
+        }
+    }
+}
+
+/// Releases the bound address of the listener once dropped.
+struct Binding {
+    network: VirtualNetwork,
+    addr: SocketAddr,
+}
+
+impl Drop for Binding {
+    fn drop(&mut self) {
+        self.network.unbind(&self.addr);
+    }
+}
+
+impl<State> VirtualListener<State> {
+    /// Returns the virtual address that this listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.binding.addr
+    }
+
+    /// Gets a reference to the listener's state.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Gets an exclusive reference to the listener's state.
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    /// Accept a single connection from this listener.
+    pub async fn accept(&self) -> io::Result<(VirtualStream, SocketAddress)> {
+        let (stream, addr) = self.incoming.recv_async().await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotConnected,
+                "virtual network listener closed",
+            )
+        })?;
+        Ok((stream, addr.into()))
+    }
+}
+
+impl<State> VirtualListener<State>
+where
+    State: Clone + Send + Sync + 'static,
+{
+    /// Serve connections from this listener with the given service.
+    ///
+    /// Each accepted stream is served in its own task,
+    /// with its [`SocketInfo`] inserted in the [`Context`].
+    pub async fn serve<S>(self, service: S)
+    where
+        S: Service<State, VirtualStream>,
+    {
+        let ctx = Context::new(self.state.clone(), Executor::new());
+        let service = Arc::new(service);
+
+        while let Ok((stream, peer_addr)) = self.incoming.recv_async().await {
+            tokio::spawn(serve_stream(
+                ctx.clone(),
+                self.binding.addr,
+                peer_addr,
+                service.clone(),
+                stream,
+            ));
+        }
+    }
+
+    /// Serve gracefully connections from this listener with the given service.
+    ///
+    /// This method does the same as [`Self::serve`] but it
+    /// will respect the given [`ShutdownGuard`], and also pass
+    /// it to the service.
+    pub async fn serve_graceful<S>(self, guard: ShutdownGuard, service: S)
+    where
+        S: Service<State, VirtualStream>,
+    {
+        let ctx = Context::new(self.state.clone(), Executor::graceful(guard.clone()));
+        let service = Arc::new(service);
+        let mut cancelled_fut = pin!(guard.cancelled());
+
+        loop {
+            tokio::select! {
+                _ = cancelled_fut.as_mut() => {
+                    tracing::trace!("signal received: initiate graceful shutdown");
+                    break;
+                }
+                result = self.incoming.recv_async() => {
+                    let Ok((stream, peer_addr)) = result else {
+                        break;
+                    };
+                    let service = service.clone();
+                    let ctx = ctx.clone();
+                    guard.spawn_task(serve_stream(
+                        ctx,
+                        self.binding.addr,
+                        peer_addr,
+                        service,
+                        stream,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+async fn serve_stream<State, S>(
+    mut ctx: Context<State>,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    service: Arc<S>,
+    stream: VirtualStream,
+) where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, VirtualStream>,
+{
+    ctx.insert(SocketInfo::new(Some(local_addr), peer_addr));
+    let _ = service.serve(ctx, stream).await;
+}
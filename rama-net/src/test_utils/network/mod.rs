@@ -0,0 +1,295 @@
+//! An in-process virtual network, to run client, proxy and server stacks
+//! end-to-end in tests, without binding real sockets.
+//!
+//! A [`VirtualNetwork`] has addressable [`VirtualListener`]s, bound with [`VirtualNetwork::bind`],
+//! which can be connected to using a [`VirtualConnector`] (a [`ConnectorService`] which can
+//! be used in place of a `TcpConnector`, requires the `http` feature) or [`VirtualNetwork::connect`].
+//! Domains are resolved using the in-memory host records of the network,
+//! which can also be used as a `DnsResolver` (implemented in `rama-dns`).
+//!
+//! The established [`VirtualStream`]s are in-memory byte streams which expose
+//! their virtual addresses as a regular [`Socket`].
+//!
+//! [`ConnectorService`]: crate::client::ConnectorService
+//! [`Socket`]: crate::stream::Socket
+
+use crate::address::Domain;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+mod stream;
+#[doc(inline)]
+pub use stream::VirtualStream;
+
+mod listener;
+#[doc(inline)]
+pub use listener::VirtualListener;
+
+#[cfg(feature = "http")]
+mod connector;
+#[cfg(feature = "http")]
+#[doc(inline)]
+pub use connector::VirtualConnector;
+
+const EPHEMERAL_PORT_START: u16 = 49152;
+const DEFAULT_MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Clone)]
+/// An in-process virtual network, see the [module docs](self) for more information.
+///
+/// Cloning a [`VirtualNetwork`] gives a handle to the same network.
+pub struct VirtualNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+struct NetworkState {
+    listeners: HashMap<SocketAddr, flume::Sender<(VirtualStream, SocketAddr)>>,
+    hosts: HashMap<Domain, Vec<IpAddr>>,
+    next_port: u16,
+    max_buffer_size: usize,
+}
+
+impl fmt::Debug for VirtualNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("VirtualNetwork")
+            .field("listeners", &state.listeners.keys().collect::<Vec<_>>())
+            .field("hosts", &state.hosts)
+            .field("max_buffer_size", &state.max_buffer_size)
+            .finish()
+    }
+}
+
+impl Default for VirtualNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualNetwork {
+    /// Create a new empty [`VirtualNetwork`].
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NetworkState {
+                listeners: HashMap::new(),
+                hosts: HashMap::new(),
+                next_port: EPHEMERAL_PORT_START,
+                max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            })),
+        }
+    }
+
+    /// Set the maximum amount of bytes buffered in each direction of a [`VirtualStream`].
+    pub fn with_max_buffer_size(self, size: usize) -> Self {
+        self.state.lock().max_buffer_size = size;
+        self
+    }
+
+    /// Add the given address to the host records of the network, used to resolve the domain.
+    pub fn insert_host(&self, domain: Domain, addr: impl Into<IpAddr>) -> &Self {
+        self.state
+            .lock()
+            .hosts
+            .entry(domain)
+            .or_default()
+            .push(addr.into());
+        self
+    }
+
+    /// Remove the given domain from the host records of the network.
+    pub fn remove_host(&self, domain: &Domain) -> &Self {
+        self.state.lock().hosts.remove(domain);
+        self
+    }
+
+    /// Resolve the domain using the host records of the network.
+    pub fn resolve(&self, domain: &Domain) -> Vec<IpAddr> {
+        self.state
+            .lock()
+            .hosts
+            .get(domain)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Resolve the IPv4 addresses of the domain using the host records of the network.
+    pub fn resolve_ipv4(&self, domain: &Domain) -> Vec<Ipv4Addr> {
+        self.resolve(domain)
+            .into_iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .collect()
+    }
+
+    /// Resolve the IPv6 addresses of the domain using the host records of the network.
+    pub fn resolve_ipv6(&self, domain: &Domain) -> Vec<Ipv6Addr> {
+        self.resolve(domain)
+            .into_iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(_) => None,
+                IpAddr::V6(ip) => Some(ip),
+            })
+            .collect()
+    }
+
+    /// Bind a [`VirtualListener`] to the given address.
+    ///
+    /// Binding with a port number of 0 assigns an unused port to the listener,
+    /// which can be queried using [`VirtualListener::local_addr`].
+    /// Binding to an unspecified address (e.g. `0.0.0.0`) accepts connections
+    /// for any address of that IP version.
+    pub fn bind(&self, addr: impl Into<SocketAddr>) -> io::Result<VirtualListener> {
+        let mut addr = addr.into();
+        let mut state = self.state.lock();
+        if addr.port() == 0 {
+            addr.set_port(state.ephemeral_port(addr.ip()));
+        } else if state.listeners.contains_key(&addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("virtual address {addr} already in use"),
+            ));
+        }
+
+        let (tx, rx) = flume::unbounded();
+        state.listeners.insert(addr, tx);
+        Ok(VirtualListener::new(self.clone(), addr, rx))
+    }
+
+    /// Connect to the [`VirtualListener`] bound to the given address,
+    /// using a local address with the given IP and an unused port.
+    pub fn connect(&self, local_ip: IpAddr, addr: SocketAddr) -> io::Result<VirtualStream> {
+        let mut state = self.state.lock();
+
+        let unspecified_ip = match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let listener = state
+            .listeners
+            .get(&addr)
+            .or_else(|| {
+                state
+                    .listeners
+                    .get(&SocketAddr::new(unspecified_ip, addr.port()))
+            })
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("no virtual listener bound to {addr}"),
+                )
+            })?;
+
+        let local_addr = SocketAddr::new(local_ip, state.ephemeral_port(local_ip));
+        let (client, server) = tokio::io::duplex(state.max_buffer_size);
+        drop(state);
+
+        listener
+            .send((VirtualStream::new(server, addr, local_addr), local_addr))
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("virtual listener bound to {addr} is closed"),
+                )
+            })?;
+        Ok(VirtualStream::new(client, local_addr, addr))
+    }
+
+    fn unbind(&self, addr: &SocketAddr) {
+        self.state.lock().listeners.remove(addr);
+    }
+}
+
+impl NetworkState {
+    fn ephemeral_port(&mut self, ip: IpAddr) -> u16 {
+        loop {
+            let port = self.next_port;
+            self.next_port = self
+                .next_port
+                .checked_add(1)
+                .unwrap_or(EPHEMERAL_PORT_START);
+            if !self.listeners.contains_key(&SocketAddr::new(ip, port)) {
+                return port;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_virtual_network_connect_and_accept() {
+        let network = VirtualNetwork::new();
+        let listener = network.bind(([0, 0, 0, 0], 80)).unwrap();
+
+        let mut client = network
+            .connect(
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
+                ([10, 0, 0, 1], 80).into(),
+            )
+            .unwrap();
+        let (mut server, peer_addr) = listener.accept().await.unwrap();
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
+            peer_addr.ip_addr()
+        );
+        assert_eq!(
+            "10.0.0.1:80".parse::<SocketAddr>().unwrap(),
+            crate::stream::Socket::local_addr(&server).unwrap()
+        );
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"ping", &buf);
+    }
+
+    #[test]
+    fn test_virtual_network_resolve() {
+        let network = VirtualNetwork::new();
+        let domain = Domain::from_static("example.com");
+        network
+            .insert_host(domain.clone(), Ipv4Addr::new(10, 0, 0, 1))
+            .insert_host(domain.clone(), Ipv6Addr::LOCALHOST);
+        assert_eq!(
+            vec![Ipv4Addr::new(10, 0, 0, 1)],
+            network.resolve_ipv4(&domain)
+        );
+        assert_eq!(vec![Ipv6Addr::LOCALHOST], network.resolve_ipv6(&domain));
+
+        network.remove_host(&domain);
+        assert!(network.resolve(&domain).is_empty());
+    }
+
+    #[test]
+    fn test_virtual_network_bind_errors() {
+        let network = VirtualNetwork::new();
+        let listener = network.bind(([127, 0, 0, 1], 8080)).unwrap();
+        assert_eq!(
+            io::ErrorKind::AddrInUse,
+            network.bind(([127, 0, 0, 1], 8080)).unwrap_err().kind()
+        );
+        let ephemeral = network.bind(([127, 0, 0, 1], 0)).unwrap();
+        assert_ne!(0, ephemeral.local_addr().port());
+
+        drop(listener);
+        let err = network
+            .connect(
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                ([127, 0, 0, 1], 8080).into(),
+            )
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+    }
+}
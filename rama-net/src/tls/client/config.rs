@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use rama_core::{Context, combinators::Either3};
 
-use super::{ClientHelloExtension, merge_client_hello_lists};
+use super::{
+    CertificatePin, ClientHelloExtension, DynamicCertVerifier, DynamicVerifier,
+    merge_client_hello_lists,
+};
 use crate::tls::{CipherSuite, CompressionAlgorithm, DataEncoding, KeyLogIntent, ProtocolVersion};
 
 #[derive(Debug, Clone, Default)]
@@ -272,8 +275,10 @@ pub struct ClientAuthData {
     pub cert_chain: DataEncoding,
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 /// Mode of server verification by a (tls) client
+///
+/// As some modes carry data this type is [`Clone`] but not [`Copy`].
 pub enum ServerVerifyMode {
     #[default]
     /// Use the default verification approach as defined
@@ -281,6 +286,30 @@ pub enum ServerVerifyMode {
     Auto,
     /// Explicitly disable server verification (if possible)
     Disable,
+    /// Verify the server certificate chain using the given root certificates
+    /// as trust anchors, instead of the default root store of the used (tls) client
+    TrustAnchors(DataEncoding),
+    /// Only trust the server if its end-entity certificate matches any of the given pins
+    ///
+    /// The certificate chain is not verified against any root certificates,
+    /// nor is the server name checked, making this suitable for self-signed certificates.
+    /// The (tls) client does still verify that the server owns the key of the pinned certificate.
+    Pinned(Vec<CertificatePin>),
+    /// Verify the server certificate chain using a custom (async) verifier
+    ///
+    /// The rustls client can only run async verifiers once the handshake is complete,
+    /// and therefore refuses to combine this mode with client authentication,
+    /// as the client certificate would otherwise be sent to an unverified server.
+    Dynamic(DynamicVerifier),
+}
+
+impl<T> From<T> for ServerVerifyMode
+where
+    T: DynamicCertVerifier,
+{
+    fn from(verifier: T) -> Self {
+        Self::Dynamic(DynamicVerifier::new(verifier))
+    }
 }
 
 impl From<super::ClientHello> for ClientConfig {
@@ -18,6 +18,14 @@ pub use parser::{
     parse_client_hello,
 };
 
+mod recorder;
+#[doc(inline)]
+pub use recorder::ServerHelloRecorder;
+
+mod verify;
+#[doc(inline)]
+pub use verify::{CertificatePin, DynamicCertVerifier, DynamicVerifier, verify_certificate_pins};
+
 mod config;
 #[doc(inline)]
 pub use config::{
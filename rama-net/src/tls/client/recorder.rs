@@ -0,0 +1,353 @@
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::tls::server::{ServerHello, parse_server_hello};
+
+pin_project! {
+    /// A stream which records the [`ServerHello`] received
+    /// from the server while establishing a TLS connection,
+    /// to be used by TLS implementations which do not expose it themselves.
+    ///
+    /// It does so by inspecting the (plaintext) handshake records read from the inner stream,
+    /// until a [`ServerHello`] is found or it is clear that none will be found.
+    /// From that point on it is a zero-cost passthrough to the inner stream.
+    ///
+    /// A HelloRetryRequest is skipped, as it is not the actual [`ServerHello`]
+    /// that decides the parameters of the connection.
+    pub struct ServerHelloRecorder<S> {
+        #[pin]
+        inner: S,
+        state: RecorderState,
+    }
+}
+
+enum RecorderState {
+    Recording {
+        record_buf: Vec<u8>,
+        handshake_buf: Vec<u8>,
+    },
+    Done(Option<ServerHello>),
+}
+
+const CONTENT_TYPE_CHANGE_CIPHER_SPEC: u8 = 0x14;
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_TYPE_SERVER_HELLO: u8 = 0x02;
+
+/// Random value used in a ServerHello to indicate it is a HelloRetryRequest,
+/// as defined in <https://www.rfc-editor.org/rfc/rfc8446#section-4.1.3>.
+const HELLO_RETRY_REQUEST_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+/// Maximum amount of handshake bytes buffered while looking for a [`ServerHello`],
+/// which is plenty given the server hello is the first message sent by the server.
+const MAX_HANDSHAKE_BUFFER_SIZE: usize = 16 * 1024;
+
+impl<S> ServerHelloRecorder<S> {
+    /// Create a new [`ServerHelloRecorder`] for the given inner stream.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            state: RecorderState::Recording {
+                record_buf: Vec::new(),
+                handshake_buf: Vec::new(),
+            },
+        }
+    }
+
+    /// Return the [`ServerHello`] recorded, if any.
+    ///
+    /// This is only available once the handshake has progressed
+    /// past the point where the server sent its hello.
+    pub fn server_hello(&self) -> Option<&ServerHello> {
+        match &self.state {
+            RecorderState::Done(server_hello) => server_hello.as_ref(),
+            RecorderState::Recording { .. } => None,
+        }
+    }
+
+    /// Take the [`ServerHello`] recorded, if any,
+    /// and stop recording in case it was still in progress.
+    ///
+    /// Use this once the handshake is complete, such that no data
+    /// is buffered anymore for a [`ServerHello`] which will never come.
+    /// See [`Self::server_hello`] for more information.
+    pub fn take_server_hello(&mut self) -> Option<ServerHello> {
+        match std::mem::replace(&mut self.state, RecorderState::Done(None)) {
+            RecorderState::Done(server_hello) => server_hello,
+            RecorderState::Recording { .. } => None,
+        }
+    }
+
+    /// Get a reference to the inner stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consume this [`ServerHelloRecorder`] and return the inner stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for ServerHelloRecorder<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerHelloRecorder")
+            .field("inner", &self.inner)
+            .field("server_hello", &self.server_hello())
+            .finish()
+    }
+}
+
+impl RecorderState {
+    fn record(&mut self, data: &[u8]) {
+        let RecorderState::Recording {
+            record_buf,
+            handshake_buf,
+        } = self
+        else {
+            return;
+        };
+
+        record_buf.extend_from_slice(data);
+
+        let mut offset = 0;
+        let result = loop {
+            let record = &record_buf[offset..];
+            if record.len() < 5 {
+                break None;
+            }
+            let content_type = record[0];
+            let length = u16::from_be_bytes([record[3], record[4]]) as usize;
+            if record.len() < 5 + length {
+                break None;
+            }
+            let payload = &record[5..5 + length];
+            offset += 5 + length;
+
+            match content_type {
+                CONTENT_TYPE_HANDSHAKE => {
+                    handshake_buf.extend_from_slice(payload);
+                    if let Some(state) = find_server_hello(handshake_buf) {
+                        break Some(state);
+                    }
+                    if handshake_buf.len() > MAX_HANDSHAKE_BUFFER_SIZE {
+                        break Some(RecorderState::Done(None));
+                    }
+                }
+                // (middlebox) compatibility records can be ignored
+                CONTENT_TYPE_CHANGE_CIPHER_SPEC => (),
+                // any other record (e.g. an alert) means no server hello is coming
+                _ => break Some(RecorderState::Done(None)),
+            }
+        };
+
+        match result {
+            Some(state) => *self = state,
+            None => {
+                record_buf.drain(..offset);
+            }
+        }
+    }
+}
+
+/// Try to find a [`ServerHello`] in the buffered handshake messages,
+/// draining the messages consumed.
+///
+/// Returns `None` when more data is required,
+/// and the final [`RecorderState`] otherwise.
+fn find_server_hello(handshake_buf: &mut Vec<u8>) -> Option<RecorderState> {
+    loop {
+        if handshake_buf.len() < 4 {
+            return None;
+        }
+        let msg_type = handshake_buf[0];
+        let length =
+            u32::from_be_bytes([0, handshake_buf[1], handshake_buf[2], handshake_buf[3]]) as usize;
+        if handshake_buf.len() < 4 + length {
+            return None;
+        }
+        if msg_type != HANDSHAKE_TYPE_SERVER_HELLO {
+            return Some(RecorderState::Done(None));
+        }
+
+        let msg = &handshake_buf[4..4 + length];
+        if msg.get(2..34) == Some(&HELLO_RETRY_REQUEST_RANDOM[..]) {
+            handshake_buf.drain(..4 + length);
+            continue;
+        }
+        return Some(RecorderState::Done(parse_server_hello(msg).ok()));
+    }
+}
+
+impl<S> AsyncRead for ServerHelloRecorder<S>
+where
+    S: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let me = self.project();
+
+        let filled = buf.filled().len();
+        ready!(me.inner.poll_read(cx, buf))?;
+
+        if let RecorderState::Recording { .. } = me.state {
+            let data = &buf.filled()[filled..];
+            if data.is_empty() {
+                *me.state = RecorderState::Done(None);
+            } else {
+                me.state.record(data);
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for ServerHelloRecorder<S>
+where
+    S: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{CipherSuite, ProtocolVersion};
+    use tokio::io::AsyncReadExt;
+
+    fn server_hello_msg(random: [u8; 32], cipher_suite: [u8; 2]) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&random);
+        body.push(0x00); // session id
+        body.extend_from_slice(&cipher_suite);
+        body.push(0x00); // compression
+        body.extend_from_slice(&[0x00, 0x06, 0x00, 0x2b, 0x00, 0x02, 0x03, 0x04]);
+
+        let mut msg = vec![HANDSHAKE_TYPE_SERVER_HELLO, 0x00, 0x00, body.len() as u8];
+        msg.extend_from_slice(&body);
+        msg
+    }
+
+    fn record(content_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut record = vec![content_type, 0x03, 0x03];
+        record.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        record.extend_from_slice(payload);
+        record
+    }
+
+    #[tokio::test]
+    async fn test_record_server_hello_split_over_records() {
+        let msg = server_hello_msg([0xaa; 32], [0x13, 0x02]);
+        let (a, b) = msg.split_at(10);
+
+        let mut data = record(CONTENT_TYPE_HANDSHAKE, a);
+        data.extend(record(CONTENT_TYPE_HANDSHAKE, b));
+        data.extend(record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[0x01]));
+        data.extend(record(0x17, &[0xff; 64]));
+
+        let mut stream =
+            ServerHelloRecorder::new(tokio_test::io::Builder::new().read(&data).build());
+        let mut output = vec![0; data.len()];
+        stream.read_exact(&mut output).await.unwrap();
+        assert_eq!(data, output);
+
+        let hello = stream.server_hello().unwrap();
+        assert_eq!(CipherSuite::TLS13_AES_256_GCM_SHA384, hello.cipher_suite());
+        assert_eq!(Some(ProtocolVersion::TLSv1_3), hello.selected_version());
+    }
+
+    #[tokio::test]
+    async fn test_record_server_hello_skips_hello_retry_request() {
+        let mut data = record(
+            CONTENT_TYPE_HANDSHAKE,
+            &server_hello_msg(HELLO_RETRY_REQUEST_RANDOM, [0x13, 0x01]),
+        );
+        data.extend(record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[0x01]));
+        data.extend(record(
+            CONTENT_TYPE_HANDSHAKE,
+            &server_hello_msg([0xaa; 32], [0x13, 0x03]),
+        ));
+
+        let mut stream =
+            ServerHelloRecorder::new(tokio_test::io::Builder::new().read(&data).build());
+        let mut output = vec![0; data.len()];
+        stream.read_exact(&mut output).await.unwrap();
+
+        let hello = stream.take_server_hello().unwrap();
+        assert_eq!(
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+            hello.cipher_suite()
+        );
+        assert!(stream.server_hello().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_record_no_server_hello() {
+        let data = record(0x15, &[0x02, 0x28]);
+
+        let mut stream =
+            ServerHelloRecorder::new(tokio_test::io::Builder::new().read(&data).build());
+        let mut output = vec![0; data.len()];
+        stream.read_exact(&mut output).await.unwrap();
+
+        assert!(stream.server_hello().is_none());
+        assert!(matches!(stream.state, RecorderState::Done(None)));
+    }
+
+    #[tokio::test]
+    async fn test_take_server_hello_stops_recording() {
+        let data = record(CONTENT_TYPE_HANDSHAKE, &[HANDSHAKE_TYPE_SERVER_HELLO, 0x00]);
+
+        let mut stream =
+            ServerHelloRecorder::new(tokio_test::io::Builder::new().read(&data).build());
+        let mut output = vec![0; data.len()];
+        stream.read_exact(&mut output).await.unwrap();
+        assert!(matches!(stream.state, RecorderState::Recording { .. }));
+
+        assert!(stream.take_server_hello().is_none());
+        assert!(matches!(stream.state, RecorderState::Done(None)));
+    }
+}
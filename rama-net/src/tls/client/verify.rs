@@ -0,0 +1,234 @@
+use crate::address::Host;
+use crate::tls::der::{TAG_SEQUENCE, read_der, x509_tbs_certificate};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as ENGINE;
+use rama_core::error::{ErrorContext, OpaqueError};
+use sha2::{Digest as _, Sha256};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A pin of a server certificate, used by [`ServerVerifyMode::Pinned`].
+///
+/// [`ServerVerifyMode::Pinned`]: super::ServerVerifyMode::Pinned
+pub enum CertificatePin {
+    /// SHA-256 digest of the DER-encoded SubjectPublicKeyInfo (SPKI) of the certificate,
+    /// which remains valid as long as the key of the server is not rotated.
+    SpkiSha256([u8; 32]),
+    /// SHA-256 digest of the DER-encoded certificate.
+    CertSha256([u8; 32]),
+}
+
+impl CertificatePin {
+    /// Compute the [`CertificatePin::SpkiSha256`] pin for the given DER-encoded certificate.
+    pub fn spki_sha256_from_der(cert: &[u8]) -> Result<Self, OpaqueError> {
+        let spki = extract_spki(cert).context("extract spki from DER certificate")?;
+        Ok(Self::SpkiSha256(Sha256::digest(spki).into()))
+    }
+
+    /// Compute the [`CertificatePin::CertSha256`] pin for the given DER-encoded certificate.
+    pub fn cert_sha256_from_der(cert: &[u8]) -> Self {
+        Self::CertSha256(Sha256::digest(cert).into())
+    }
+
+    /// Parse a [`CertificatePin::SpkiSha256`] pin from its base64 representation,
+    /// as produced by `openssl x509 -pubkey | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64`.
+    pub fn try_from_spki_sha256_base64(s: &str) -> Result<Self, OpaqueError> {
+        let digest = ENGINE
+            .decode(s.trim())
+            .context("decode base64 spki sha256 pin")?;
+        Ok(Self::SpkiSha256(digest.try_into().map_err(|_| {
+            OpaqueError::from_display("spki sha256 pin has to be 32 bytes")
+        })?))
+    }
+
+    /// Returns `true` if the given DER-encoded certificate matches this pin.
+    pub fn matches(&self, cert: &[u8]) -> bool {
+        match self {
+            Self::SpkiSha256(digest) => {
+                extract_spki(cert).is_some_and(|spki| Sha256::digest(spki).as_slice() == digest)
+            }
+            Self::CertSha256(digest) => Sha256::digest(cert).as_slice() == digest,
+        }
+    }
+}
+
+/// Verify that the given DER-encoded end-entity certificate matches any of the given pins.
+pub fn verify_certificate_pins(pins: &[CertificatePin], cert: &[u8]) -> Result<(), OpaqueError> {
+    if pins.iter().any(|pin| pin.matches(cert)) {
+        Ok(())
+    } else {
+        Err(OpaqueError::from_display(
+            "server certificate does not match any of the pins",
+        ))
+    }
+}
+
+/// Extract the DER-encoded SubjectPublicKeyInfo from a DER-encoded X.509 certificate.
+fn extract_spki(cert: &[u8]) -> Option<&[u8]> {
+    let mut tbs = x509_tbs_certificate(cert)?;
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        tbs = read_der(tbs)?.2;
+    }
+
+    let (tag, _, rest) = read_der(tbs)?;
+    (tag == TAG_SEQUENCE).then(|| &tbs[..tbs.len() - rest.len()])
+}
+
+#[derive(Clone)]
+/// Dynamic verifier which internally contains the dyn verifier,
+/// used by [`ServerVerifyMode::Dynamic`].
+///
+/// [`ServerVerifyMode::Dynamic`]: super::ServerVerifyMode::Dynamic
+pub struct DynamicVerifier {
+    /// Verifier not public in case we want to migrate away from dyn approach to alternative (eg channels)
+    verifier: Arc<dyn DynDynamicCertVerifier + Send + Sync>,
+}
+
+impl DynamicVerifier {
+    /// Create a new [`DynamicVerifier`] for the given [`DynamicCertVerifier`].
+    pub fn new<T: DynamicCertVerifier>(verifier: T) -> Self {
+        Self {
+            verifier: Arc::new(verifier),
+        }
+    }
+
+    /// Verify the DER-encoded certificate chain presented by the server,
+    /// starting with the end-entity certificate.
+    ///
+    /// The `server_name` is the SNI value sent by the (tls) client,
+    /// which is `None` in case the server is connected to by ip address.
+    pub async fn verify_cert(
+        &self,
+        cert_chain: Vec<Vec<u8>>,
+        server_name: Option<Host>,
+    ) -> Result<(), OpaqueError> {
+        self.verifier.verify_cert(cert_chain, server_name).await
+    }
+}
+
+impl PartialEq for DynamicVerifier {
+    /// Two [`DynamicVerifier`]s are equal if they share the same verifier.
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.verifier, &other.verifier)
+    }
+}
+
+impl Eq for DynamicVerifier {}
+
+impl Hash for DynamicVerifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.verifier).cast::<()>().hash(state);
+    }
+}
+
+impl fmt::Debug for DynamicVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicVerifier").finish()
+    }
+}
+
+/// Trait that needs to be implemented by verifiers to support
+/// verifying the server certificate chain in a custom (async) manner.
+///
+/// The verifier fully replaces the default verification of the (tls) client,
+/// the handshake signatures are still verified by the (tls) client itself.
+///
+/// Depending on the (tls) client the verifier is either run during the handshake
+/// or right after it completed, but always before any application data is exchanged.
+pub trait DynamicCertVerifier: Send + Sync + 'static {
+    /// Verify the DER-encoded certificate chain presented by the server,
+    /// starting with the end-entity certificate.
+    fn verify_cert(
+        &self,
+        cert_chain: Vec<Vec<u8>>,
+        server_name: Option<Host>,
+    ) -> impl Future<Output = Result<(), OpaqueError>> + Send + '_;
+}
+
+/// Internal trait to support dynamic dispatch of trait with async fn.
+/// See trait [`rama_core::service::svc::DynService`] for more info about this pattern.
+trait DynDynamicCertVerifier {
+    fn verify_cert(
+        &self,
+        cert_chain: Vec<Vec<u8>>,
+        server_name: Option<Host>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), OpaqueError>> + Send + '_>>;
+}
+
+impl<T> DynDynamicCertVerifier for T
+where
+    T: DynamicCertVerifier,
+{
+    fn verify_cert(
+        &self,
+        cert_chain: Vec<Vec<u8>>,
+        server_name: Option<Host>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), OpaqueError>> + Send + '_>> {
+        Box::pin(self.verify_cert(cert_chain, server_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal certificate structure: version, serial, sig alg, issuer, validity, subject and spki.
+    fn test_cert(spki: &[u8]) -> Vec<u8> {
+        let mut tbs = vec![0xa0, 0x03, 0x02, 0x01, 0x02];
+        for _ in 0..5 {
+            tbs.extend_from_slice(&[0x30, 0x00]);
+        }
+        tbs.extend_from_slice(spki);
+        let mut cert = vec![0x30, (tbs.len() + 2) as u8, 0x30, tbs.len() as u8];
+        cert.extend_from_slice(&tbs);
+        cert
+    }
+
+    #[test]
+    fn test_extract_spki() {
+        let spki = [0x30, 0x03, 0x02, 0x01, 0x07];
+        let cert = test_cert(&spki);
+
+        assert_eq!(Some(&spki[..]), extract_spki(&cert));
+        assert_eq!(None, extract_spki(&cert[..cert.len() - 1]));
+        assert_eq!(None, extract_spki(&[]));
+    }
+
+    #[test]
+    fn test_certificate_pins() {
+        let cert = test_cert(&[0x30, 0x03, 0x02, 0x01, 0x07]);
+        let rotated_cert = test_cert(&[0x30, 0x03, 0x02, 0x01, 0x08]);
+
+        let cert_pin = CertificatePin::cert_sha256_from_der(&cert);
+        assert!(cert_pin.matches(&cert));
+        assert!(!cert_pin.matches(&rotated_cert));
+
+        let spki_pin = CertificatePin::spki_sha256_from_der(&cert).unwrap();
+        assert!(spki_pin.matches(&cert));
+        assert!(!spki_pin.matches(&rotated_cert));
+        assert!(CertificatePin::spki_sha256_from_der(&cert[1..]).is_err());
+
+        let other_pin = CertificatePin::CertSha256([0; 32]);
+        assert!(verify_certificate_pins(&[other_pin.clone(), spki_pin], &cert).is_ok());
+        assert!(verify_certificate_pins(&[other_pin], &cert).is_err());
+        assert!(verify_certificate_pins(&[], &cert).is_err());
+    }
+
+    #[test]
+    fn test_spki_sha256_base64() {
+        let pin = CertificatePin::try_from_spki_sha256_base64(
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+        )
+        .unwrap();
+        assert_eq!(CertificatePin::SpkiSha256(Sha256::digest([]).into()), pin);
+
+        assert!(CertificatePin::try_from_spki_sha256_base64("AAAA").is_err());
+        assert!(CertificatePin::try_from_spki_sha256_base64("not base64!").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Minimal DER reader, used to extract the few fields
+//! of X.509 certificates and private keys rama needs to know about,
+//! e.g. to fingerprint, cache or issue certificates.
+//!
+//! It is not a general purpose ASN.1 parser: only (low tag number)
+//! DER elements are supported, as used by X.509 certificates and private keys.
+
+use std::time::{Duration, SystemTime};
+
+/// DER tag of a `BOOLEAN`.
+pub const TAG_BOOLEAN: u8 = 0x01;
+/// DER tag of an `INTEGER`.
+pub const TAG_INTEGER: u8 = 0x02;
+/// DER tag of a `BIT STRING`.
+pub const TAG_BIT_STRING: u8 = 0x03;
+/// DER tag of an `OCTET STRING`.
+pub const TAG_OCTET_STRING: u8 = 0x04;
+/// DER tag of an `OBJECT IDENTIFIER`.
+pub const TAG_OID: u8 = 0x06;
+/// DER tag of a `UTF8String`.
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+/// DER tag of a `PrintableString`.
+pub const TAG_PRINTABLE_STRING: u8 = 0x13;
+/// DER tag of an `IA5String`.
+pub const TAG_IA5_STRING: u8 = 0x16;
+/// DER tag of a `SEQUENCE` (or `SEQUENCE OF`).
+pub const TAG_SEQUENCE: u8 = 0x30;
+/// DER tag of a `SET` (or `SET OF`).
+pub const TAG_SET: u8 = 0x31;
+/// DER tag of the explicit version (`[0]`) of an X.509 TBSCertificate.
+pub const TAG_X509_VERSION: u8 = 0xa0;
+/// DER tag of the explicit extensions (`[3]`) of an X.509 TBSCertificate.
+pub const TAG_X509_EXTENSIONS: u8 = 0xa3;
+
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+
+/// Read a single DER element, returning its tag, content and the remaining input.
+///
+/// Returns `None` for truncated input and high tag numbers, which X.509 does not use.
+pub fn read_der(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    if tag & 0x1f == 0x1f {
+        return None;
+    }
+    let (&len, mut input) = input.split_first()?;
+
+    let len = if len & 0x80 == 0 {
+        len as usize
+    } else {
+        let n = (len & 0x7f) as usize;
+        if n == 0 || n > size_of::<usize>() || input.len() < n {
+            return None;
+        }
+        let (len_bytes, rest) = input.split_at(n);
+        input = rest;
+        len_bytes
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | *b as usize)
+    };
+
+    (input.len() >= len).then(|| (tag, &input[..len], &input[len..]))
+}
+
+/// Read a single DER element with the expected tag, returning its content and the remaining input.
+pub fn expect_der(input: &[u8], expected_tag: u8) -> Option<(&[u8], &[u8])> {
+    let (tag, content, rest) = read_der(input)?;
+    (tag == expected_tag).then_some((content, rest))
+}
+
+/// Decode the content of a DER-encoded object identifier into its arcs.
+pub fn decode_oid(oid: &[u8]) -> Option<Vec<u64>> {
+    let (&first, rest) = oid.split_first()?;
+    let mut arcs = vec![u64::from(first / 40), u64::from(first % 40)];
+    let mut arc: u64 = 0;
+    for &b in rest {
+        arc = arc.checked_mul(128)? | u64::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    (arc == 0).then_some(arcs)
+}
+
+/// Read the content of the TBSCertificate of a DER-encoded X.509 certificate,
+/// starting at its serial number (the optional version is skipped).
+pub fn x509_tbs_certificate(cert: &[u8]) -> Option<&[u8]> {
+    let (cert, _) = expect_der(cert, TAG_SEQUENCE)?;
+    let (tbs, _) = expect_der(cert, TAG_SEQUENCE)?;
+
+    if tbs.first() == Some(&TAG_X509_VERSION) {
+        Some(read_der(tbs)?.2)
+    } else {
+        Some(tbs)
+    }
+}
+
+/// Read the validity period (`notBefore`, `notAfter`) of a DER-encoded X.509 certificate.
+pub fn x509_validity(cert: &[u8]) -> Option<(SystemTime, SystemTime)> {
+    let mut tbs = x509_tbs_certificate(cert)?;
+    // serial number, signature algorithm and issuer
+    for _ in 0..3 {
+        tbs = read_der(tbs)?.2;
+    }
+
+    let (validity, _) = expect_der(tbs, TAG_SEQUENCE)?;
+    let (tag, not_before, validity) = read_der(validity)?;
+    let not_before = parse_time(tag, not_before)?;
+    let (tag, not_after, _) = read_der(validity)?;
+    Some((not_before, parse_time(tag, not_after)?))
+}
+
+/// Parse a (UTC) `UTCTime` or `GeneralizedTime` as used in X.509 certificates (RFC 5280 §4.1.2.5).
+fn parse_time(tag: u8, content: &[u8]) -> Option<SystemTime> {
+    let (year, rest) = match tag {
+        TAG_UTC_TIME if content.len() == 13 => {
+            let year = parse_digits(&content[..2])?;
+            (
+                if year >= 50 { 1900 + year } else { 2000 + year },
+                &content[2..],
+            )
+        }
+        TAG_GENERALIZED_TIME if content.len() == 15 => {
+            (parse_digits(&content[..4])?, &content[4..])
+        }
+        _ => return None,
+    };
+    if rest[10] != b'Z' {
+        return None;
+    }
+
+    let month = parse_digits(&rest[0..2])?;
+    let day = parse_digits(&rest[2..4])?;
+    let hour = parse_digits(&rest[4..6])?;
+    let minute = parse_digits(&rest[6..8])?;
+    let second = parse_digits(&rest[8..10])?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let seconds =
+        days_from_civil(year, month, day) * 86400 + i64::from(hour * 3600 + minute * 60 + second);
+    // certificates which expired before the unix epoch are expired all the same
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds.try_into().unwrap_or_default()))
+}
+
+fn parse_digits(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0, |n, d| {
+        d.is_ascii_digit().then(|| n * 10 + u32::from(d - b'0'))
+    })
+}
+
+/// Days since the unix epoch of the given (proleptic Gregorian) date.
+fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_oid() {
+        // subjectKeyIdentifier, commonName and ecdsa-with-SHA256
+        assert_eq!(decode_oid(&[0x55, 0x1d, 0x0e]), Some(vec![2, 5, 29, 14]));
+        assert_eq!(decode_oid(&[0x55, 0x04, 0x03]), Some(vec![2, 5, 4, 3]));
+        assert_eq!(
+            decode_oid(&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]),
+            Some(vec![1, 2, 840, 10045, 4, 3, 2])
+        );
+        // truncated arc
+        assert_eq!(decode_oid(&[0x2a, 0x86]), None);
+        assert_eq!(decode_oid(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        for (tag, input, expected) in [
+            (TAG_UTC_TIME, &b"700101000000Z"[..], Some(0)),
+            (TAG_UTC_TIME, b"491231235959Z", Some(2524607999)),
+            (TAG_UTC_TIME, b"000229120000Z", Some(951825600)),
+            (TAG_GENERALIZED_TIME, b"20500101000000Z", Some(2524608000)),
+            (TAG_GENERALIZED_TIME, b"19500101000000Z", Some(0)),
+            (TAG_UTC_TIME, b"700101000000+0100", None),
+            (TAG_UTC_TIME, b"701301000000Z", None),
+            (TAG_GENERALIZED_TIME, b"700101000000Z", None),
+            (TAG_INTEGER, b"700101000000Z", None),
+        ] {
+            assert_eq!(
+                parse_time(tag, input),
+                expected.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+                "{}",
+                String::from_utf8_lossy(input),
+            );
+        }
+    }
+}
@@ -10,6 +10,7 @@ pub use enums::{
 };
 
 pub mod client;
+pub mod der;
 pub mod keylog;
 pub mod server;
 
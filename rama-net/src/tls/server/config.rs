@@ -1,10 +1,15 @@
+use super::cache::{pem_block_der, pem_blocks};
+use super::{DiskCertCache, DynamicCertCache, ReloadableServerAuth};
 use crate::{
     address::Host,
-    tls::{ApplicationProtocol, DataEncoding, KeyLogIntent, ProtocolVersion, client::ClientHello},
+    tls::{
+        ApplicationProtocol, DataEncoding, KeyLogIntent, ProtocolVersion, client::ClientHello,
+        der::x509_validity,
+    },
 };
 use rama_core::error::OpaqueError;
 use serde::{Deserialize, Serialize};
-use std::{num::NonZeroU64, pin::Pin, sync::Arc};
+use std::{borrow::Cow, fmt, num::NonZeroU64, pin::Pin, str::FromStr, sync::Arc, time::SystemTime};
 
 #[derive(Debug, Clone)]
 /// Common API to configure a TLS Server
@@ -56,6 +61,8 @@ pub enum ServerAuth {
     SelfSigned(SelfSignedData),
     /// Single data provided by the configurator
     Single(ServerAuthData),
+    /// Single data loaded from files, which can be reloaded without restarting the server
+    Reloadable(ReloadableServerAuth),
     /// Issuer which provides certs on the fly
     CertIssuer(ServerCertIssuerData),
 }
@@ -77,7 +84,13 @@ pub struct ServerCertIssuerData {
 #[derive(Debug, Clone)]
 /// Cache kind that will be used to cache results of certificate issuers
 pub enum CacheKind {
+    /// In-memory cache, bounded by the given max amount of certificates
     MemCache { max_size: NonZeroU64 },
+    /// Persistent cache which stores the certificates as PEM files in a directory
+    Disk(DiskCertCache),
+    /// Custom cache provided by the configurator
+    Custom(DynamicCertCache),
+    /// Do not cache issued certificates
     Disabled,
 }
 
@@ -130,6 +143,62 @@ pub struct SelfSignedData {
     pub subject_alternative_names: Option<Vec<String>>,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// Algorithm used to generate the key pair of a certificate,
+/// e.g. by a certificate authority (CA) issuer of a tls implementation.
+pub enum KeyAlgorithm {
+    /// RSA with a 2048 bit modulus.
+    Rsa2048,
+    /// RSA with a 4096 bit modulus.
+    Rsa4096,
+    #[default]
+    /// ECDSA using the NIST P-256 curve.
+    EcdsaP256,
+    /// ECDSA using the NIST P-384 curve.
+    EcdsaP384,
+    /// EdDSA using Curve25519.
+    ///
+    /// Only supported for leaf certificates,
+    /// and not by all clients (e.g. most browsers do not support it).
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    /// Returns the [`KeyAlgorithm`] as a string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rsa2048 => "rsa2048",
+            Self::Rsa4096 => "rsa4096",
+            Self::EcdsaP256 => "ecdsa-p256",
+            Self::EcdsaP384 => "ecdsa-p384",
+            Self::Ed25519 => "ed25519",
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Rsa2048,
+            Self::Rsa4096,
+            Self::EcdsaP256,
+            Self::EcdsaP384,
+            Self::Ed25519,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(s.trim()))
+        .ok_or_else(|| OpaqueError::from_display(format!("unknown key algorithm: {s}")))
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Raw private key and certificate data to facilitate server authentication.
 pub struct ServerAuthData {
@@ -142,6 +211,23 @@ pub struct ServerAuthData {
     pub ocsp: Option<Vec<u8>>,
 }
 
+impl ServerAuthData {
+    /// Validity period (`notBefore`, `notAfter`) of the leaf (first) certificate
+    /// of the cert chain, `None` in case it could not be parsed.
+    pub fn leaf_cert_validity(&self) -> Option<(SystemTime, SystemTime)> {
+        let der = match &self.cert_chain {
+            DataEncoding::Der(der) => Cow::Borrowed(der.as_slice()),
+            DataEncoding::DerStack(der_list) => Cow::Borrowed(der_list.first()?.as_slice()),
+            DataEncoding::Pem(pem) => {
+                let (_, block) =
+                    pem_blocks(pem.as_str()).find(|(label, _)| *label == "CERTIFICATE")?;
+                Cow::Owned(pem_block_der(block)?)
+            }
+        };
+        x509_validity(&der)
+    }
+}
+
 #[derive(Clone)]
 /// Dynamic issuer which internally contains the dyn issuer
 pub struct DynamicIssuer {
@@ -216,3 +302,23 @@ pub enum ClientVerifyMode {
     /// PEM-encoded certificate chain containing the acceptable client certificates
     ClientAuth(DataEncoding),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_algorithm_from_str() {
+        for algorithm in [
+            KeyAlgorithm::Rsa2048,
+            KeyAlgorithm::Rsa4096,
+            KeyAlgorithm::EcdsaP256,
+            KeyAlgorithm::EcdsaP384,
+            KeyAlgorithm::Ed25519,
+        ] {
+            assert_eq!(algorithm, algorithm.to_string().parse().unwrap());
+        }
+        assert_eq!(KeyAlgorithm::EcdsaP384, " ECDSA-P384 ".parse().unwrap());
+        assert!("dsa".parse::<KeyAlgorithm>().is_err());
+    }
+}
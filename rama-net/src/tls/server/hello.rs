@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tls::{
+    ApplicationProtocol, CipherSuite, ExtensionId, ProtocolVersion, enums::CompressionAlgorithm,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+/// The ServerHello is sent by the server in response to a [`ClientHello`],
+/// and contains the parameters selected by the server for the connection.
+///
+/// Contrary to the [`ClientHello`] it does not contain a list of options
+/// but the single [`CipherSuite`] and [`CompressionAlgorithm`] picked by the server,
+/// next to the extensions the server responds with.
+///
+/// For Rama we only focus on the parts which
+/// a user might want to inspect, e.g. to fingerprint the server.
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+pub struct ServerHello {
+    pub(super) protocol_version: ProtocolVersion,
+    pub(super) cipher_suite: CipherSuite,
+    pub(super) compression_algorithm: CompressionAlgorithm,
+    pub(super) extensions: Vec<ServerHelloExtension>,
+}
+
+impl ServerHello {
+    /// Create a new [`ServerHello`] from the parameters selected by the server
+    /// and the extensions it responds with.
+    pub fn new(
+        protocol_version: ProtocolVersion,
+        cipher_suite: CipherSuite,
+        compression_algorithm: CompressionAlgorithm,
+        extensions: Vec<ServerHelloExtension>,
+    ) -> Self {
+        Self {
+            protocol_version,
+            cipher_suite,
+            compression_algorithm,
+            extensions,
+        }
+    }
+
+    /// Return the (legacy) [`ProtocolVersion`] defined in this [`ServerHello`].
+    ///
+    /// Since TLS 1.3 this version is frozen at [`ProtocolVersion::TLSv1_2`],
+    /// use [`Self::selected_version`] to get the actual negotiated version.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Return the [`CipherSuite`] selected by the server in this [`ServerHello`].
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Return the [`CompressionAlgorithm`] selected by the server in this [`ServerHello`].
+    pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+        self.compression_algorithm
+    }
+
+    /// Return all [`ServerHelloExtension`]s defined in this [`ServerHello`].
+    pub fn extensions(&self) -> &[ServerHelloExtension] {
+        &self.extensions[..]
+    }
+
+    /// Return the application layer protocol selected by the server
+    /// if it is set in the [`ServerHelloExtension`] defined in this [`ServerHello`].
+    ///
+    /// See [`ServerHelloExtension::ApplicationLayerProtocolNegotiation`] for more information (ALPN).
+    pub fn ext_alpn(&self) -> Option<&ApplicationProtocol> {
+        for ext in &self.extensions {
+            if let ServerHelloExtension::ApplicationLayerProtocolNegotiation(alpn) = ext {
+                return Some(alpn);
+            }
+        }
+        None
+    }
+
+    /// Return the TLS version selected by the server
+    /// if it is set in the [`ServerHelloExtension`] defined in this [`ServerHello`].
+    ///
+    /// See [`ServerHelloExtension::SupportedVersions`] for more information about this version.
+    pub fn selected_version(&self) -> Option<ProtocolVersion> {
+        for ext in &self.extensions {
+            if let ServerHelloExtension::SupportedVersions(version) = ext {
+                return Some(*version);
+            }
+        }
+        None
+    }
+
+    /// Return the [`ProtocolVersion`] negotiated by the server,
+    /// which is the [`Self::selected_version`] if defined,
+    /// and the (legacy) [`Self::protocol_version`] otherwise.
+    pub fn negotiated_version(&self) -> ProtocolVersion {
+        self.selected_version().unwrap_or(self.protocol_version)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+/// Extensions that can be set in a [`ServerHello`] message by a TLS server.
+///
+/// A server is only allowed to respond with extensions
+/// which were also offered by the client in its [`ClientHello`].
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+pub enum ServerHelloExtension {
+    /// Application Layer Protocol Negotiation, often referred to as ALPN.
+    ///
+    /// Used to indicate the application layer protocol selected by the server
+    /// from the protocols offered by the client.
+    ///
+    /// # Reference
+    ///
+    /// - <https://www.iana.org/go/rfc7301>
+    ApplicationLayerProtocolNegotiation(ApplicationProtocol),
+    /// used by the server to indicate which version of TLS it selected
+    ///
+    /// # Reference
+    ///
+    /// - <https://www.iana.org/go/rfc8446>
+    SupportedVersions(ProtocolVersion),
+    /// Any extension not supported by Rama,
+    /// as it is still to be done or considered out of scope.
+    Opaque {
+        /// extension id
+        id: ExtensionId,
+        /// extension data
+        data: Vec<u8>,
+    },
+}
+
+impl ServerHelloExtension {
+    /// returns the [`ExtensionId`] which identifies this [`ServerHelloExtension`].
+    pub fn id(&self) -> ExtensionId {
+        match self {
+            ServerHelloExtension::ApplicationLayerProtocolNegotiation(_) => {
+                ExtensionId::APPLICATION_LAYER_PROTOCOL_NEGOTIATION
+            }
+            ServerHelloExtension::SupportedVersions(_) => ExtensionId::SUPPORTED_VERSIONS,
+            ServerHelloExtension::Opaque { id, .. } => *id,
+        }
+    }
+}
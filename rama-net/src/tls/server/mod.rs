@@ -3,10 +3,25 @@
 mod config;
 #[doc(inline)]
 pub use config::{
-    CacheKind, ClientVerifyMode, DynamicCertIssuer, DynamicIssuer, SelfSignedData, ServerAuth,
-    ServerAuthData, ServerCertIssuerData, ServerCertIssuerKind, ServerConfig,
+    CacheKind, ClientVerifyMode, DynamicCertIssuer, DynamicIssuer, KeyAlgorithm, SelfSignedData,
+    ServerAuth, ServerAuthData, ServerCertIssuerData, ServerCertIssuerKind, ServerConfig,
 };
 
+mod cache;
+#[doc(inline)]
+pub use cache::{DiskCertCache, DynamicCertCache, IssuedCertCache, ServerCertCache};
+
+mod reload;
+#[doc(inline)]
+pub use reload::{ReloadableServerAuth, ServerAuthFiles};
+
+mod hello;
+#[doc(inline)]
+pub use hello::{ServerHello, ServerHelloExtension};
+
+mod parser;
+pub use parser::parse_server_hello;
+
 mod peek;
 #[doc(inline)]
 pub use peek::{NoTlsRejectError, TlsPeekRouter, TlsPeekStream};
@@ -0,0 +1,172 @@
+use super::{ServerHello, ServerHelloExtension};
+use crate::tls::{
+    ApplicationProtocol, CipherSuite, ExtensionId, ProtocolVersion, enums::CompressionAlgorithm,
+};
+use nom::{
+    IResult, Parser,
+    bytes::streaming::take,
+    combinator::{all_consuming, complete, cond, map, opt, verify},
+    multi::length_data,
+    number::streaming::{be_u8, be_u16},
+};
+use rama_core::error::OpaqueError;
+
+/// Parse a [`ServerHello`] from the raw "wire" bytes.
+///
+/// The input is expected to be the ServerHello handshake message body,
+/// so without the record and handshake header bytes in front of it.
+///
+/// This function is not infallible, it can return an error if the input is not a valid
+/// TLS ServerHello message or if there is unexpected trailing data.
+pub fn parse_server_hello(i: &[u8]) -> Result<ServerHello, OpaqueError> {
+    match parse_server_hello_inner(i) {
+        Err(err) => Err(OpaqueError::from_display(format!(
+            "parse server hello handshake message: {err:?}"
+        ))),
+        Ok((i, hello)) => {
+            if i.is_empty() {
+                Ok(hello)
+            } else {
+                Err(OpaqueError::from_display(
+                    "parse server hello handshake message: unexpected trailer content",
+                ))
+            }
+        }
+    }
+}
+
+fn parse_server_hello_inner(i: &[u8]) -> IResult<&[u8], ServerHello> {
+    let (i, version) = be_u16(i)?;
+    let (i, _random) = take(32usize)(i)?;
+    let (i, sidlen) = verify(be_u8, |&n| n <= 32).parse(i)?;
+    let (i, _sid) = cond(sidlen > 0, take(sidlen as usize)).parse(i)?;
+    let (i, cipher_suite) = be_u16(i)?;
+    let (i, compression_algorithm) = be_u8(i)?;
+    let (i, opt_ext) = opt(complete(length_data(be_u16))).parse(i)?;
+
+    let mut extensions = vec![];
+    if let Some(mut i) = opt_ext {
+        while !i.is_empty() {
+            let (new_i, sh_ext) = parse_tls_server_hello_extension(i)?;
+            extensions.push(sh_ext);
+            i = new_i;
+        }
+    }
+
+    Ok((
+        i,
+        ServerHello {
+            protocol_version: version.into(),
+            cipher_suite: CipherSuite::from(cipher_suite),
+            compression_algorithm: CompressionAlgorithm::from(compression_algorithm),
+            extensions,
+        },
+    ))
+}
+
+fn parse_tls_server_hello_extension(i: &[u8]) -> IResult<&[u8], ServerHelloExtension> {
+    let (i, ext_type) = be_u16(i)?;
+    let id = ExtensionId::from(ext_type);
+    let (i, ext_data) = length_data(be_u16).parse(i)?;
+
+    let ext = match id {
+        // struct {
+        //     ProtocolName protocol_name_list<2..2^16-1>
+        // } ProtocolNameList;
+        //
+        // with the server selecting exactly one protocol
+        ExtensionId::APPLICATION_LAYER_PROTOCOL_NEGOTIATION => {
+            let (_, alpn) = all_consuming(map(
+                verify(length_data(be_u16), |list: &[u8]| {
+                    list.first().map(|n| *n as usize + 1) == Some(list.len())
+                }),
+                |list: &[u8]| ApplicationProtocol::from(&list[1..]),
+            ))
+            .parse(ext_data)?;
+            ServerHelloExtension::ApplicationLayerProtocolNegotiation(alpn)
+        }
+        // ProtocolVersion selected_version;
+        ExtensionId::SUPPORTED_VERSIONS => {
+            let (_, version) = all_consuming(be_u16).parse(ext_data)?;
+            ServerHelloExtension::SupportedVersions(ProtocolVersion::from(version))
+        }
+        _ => ServerHelloExtension::Opaque {
+            id,
+            data: ext_data.to_vec(),
+        },
+    };
+    Ok((i, ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_hello_tls13() {
+        let mut data = vec![0x03, 0x03];
+        data.extend_from_slice(&[0xaa; 32]); // random
+        data.push(0x20); // session id
+        data.extend_from_slice(&[0xbb; 32]);
+        data.extend_from_slice(&[0x13, 0x01]); // cipher suite
+        data.push(0x00); // compression
+        data.extend_from_slice(&[0x00, 0x2e]); // extensions length
+        data.extend_from_slice(&[0x00, 0x2b, 0x00, 0x02, 0x03, 0x04]); // supported versions
+        data.extend_from_slice(&[0x00, 0x33, 0x00, 0x24, 0x00, 0x1d, 0x00, 0x20]); // key share
+        data.extend_from_slice(&[0xcc; 32]);
+
+        let hello = parse_server_hello(&data).unwrap();
+        assert_eq!(ProtocolVersion::TLSv1_2, hello.protocol_version());
+        assert_eq!(CipherSuite::TLS13_AES_128_GCM_SHA256, hello.cipher_suite());
+        assert_eq!(CompressionAlgorithm::Null, hello.compression_algorithm());
+        assert_eq!(
+            vec![ExtensionId::SUPPORTED_VERSIONS, ExtensionId::KEY_SHARE],
+            hello
+                .extensions()
+                .iter()
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(Some(ProtocolVersion::TLSv1_3), hello.selected_version());
+        assert_eq!(ProtocolVersion::TLSv1_3, hello.negotiated_version());
+        assert!(hello.ext_alpn().is_none());
+    }
+
+    #[test]
+    fn test_parse_server_hello_tls12_alpn() {
+        let mut data = vec![0x03, 0x03];
+        data.extend_from_slice(&[0xaa; 32]); // random
+        data.push(0x00); // session id
+        data.extend_from_slice(&[0xc0, 0x2f]); // cipher suite
+        data.push(0x00); // compression
+        data.extend_from_slice(&[0x00, 0x11]); // extensions length
+        data.extend_from_slice(&[0xff, 0x01, 0x00, 0x01, 0x00]); // renegotiation info
+        data.extend_from_slice(&[0x00, 0x10, 0x00, 0x08, 0x00, 0x06, 0x05]); // alpn
+        data.extend_from_slice(b"h2c/1");
+
+        let hello = parse_server_hello(&data).unwrap();
+        assert_eq!(ProtocolVersion::TLSv1_2, hello.protocol_version());
+        assert_eq!(
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            hello.cipher_suite()
+        );
+        assert_eq!(None, hello.selected_version());
+        assert_eq!(ProtocolVersion::TLSv1_2, hello.negotiated_version());
+        assert_eq!(Some(&ApplicationProtocol::from(b"h2c/1")), hello.ext_alpn());
+    }
+
+    #[test]
+    fn test_parse_server_hello_errors() {
+        assert!(parse_server_hello(&[]).is_err());
+        assert!(parse_server_hello(&[0x03, 0x03, 0x00]).is_err());
+
+        let mut data = vec![0x03, 0x03];
+        data.extend_from_slice(&[0xaa; 32]); // random
+        data.push(0x00); // session id
+        data.extend_from_slice(&[0xc0, 0x2f]); // cipher suite
+        data.push(0x00); // compression
+        data.extend_from_slice(&[0x00, 0x07]); // extensions length
+        data.extend_from_slice(&[0x00, 0x10, 0x00, 0x03, 0x00, 0x01, 0x05]); // invalid alpn
+        assert!(parse_server_hello(&data).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+use super::ServerAuthData;
+use super::cache::{pem_block_der, pem_blocks};
+use crate::tls::{
+    DataEncoding,
+    der::{TAG_SEQUENCE, read_der, x509_validity},
+};
+use rama_core::{
+    error::{ErrorContext, OpaqueError},
+    graceful::ShutdownGuard,
+    telemetry::tracing,
+};
+use rama_utils::macros::generate_set_and_with;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone)]
+/// Paths of the files from which [`ServerAuthData`] is loaded.
+pub struct ServerAuthFiles {
+    cert_chain: PathBuf,
+    private_key: PathBuf,
+    ocsp: Option<PathBuf>,
+}
+
+impl ServerAuthFiles {
+    /// Create a new [`ServerAuthFiles`] for the given PEM-encoded
+    /// certificate chain (leaf first) and private key.
+    pub fn new(cert_chain: impl Into<PathBuf>, private_key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_chain: cert_chain.into(),
+            private_key: private_key.into(),
+            ocsp: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the path of the DER-encoded OCSP response to staple.
+        pub fn ocsp(mut self, path: PathBuf) -> Self {
+            self.ocsp = Some(path);
+            self
+        }
+    }
+
+    /// Path of the PEM-encoded certificate chain.
+    pub fn cert_chain(&self) -> &Path {
+        &self.cert_chain
+    }
+
+    /// Path of the PEM-encoded private key.
+    pub fn private_key(&self) -> &Path {
+        &self.private_key
+    }
+
+    /// Load the [`ServerAuthData`] from the files.
+    ///
+    /// The certificates are checked to be X.509 certificates and the private key
+    /// to be DER-encoded, it is up to the tls implementation to check whether
+    /// or not the private key matches the certificate (see [`ReloadableServerAuth::set_validator`]).
+    pub fn load(&self) -> Result<ServerAuthData, OpaqueError> {
+        let cert_chain = fs::read_to_string(&self.cert_chain)
+            .with_context(|| format!("read cert chain file {}", self.cert_chain.display()))?;
+        let mut certs = pem_blocks(&cert_chain)
+            .filter(|(label, _)| *label == "CERTIFICATE")
+            .peekable();
+        if certs.peek().is_none() {
+            return Err(OpaqueError::from_display(format!(
+                "no certificate found in cert chain file {}",
+                self.cert_chain.display()
+            )));
+        }
+        for (index, (_, block)) in certs.enumerate() {
+            if pem_block_der(block)
+                .and_then(|der| x509_validity(&der))
+                .is_none()
+            {
+                return Err(OpaqueError::from_display(format!(
+                    "invalid certificate #{index} in cert chain file {}",
+                    self.cert_chain.display()
+                )));
+            }
+        }
+
+        let private_key = fs::read_to_string(&self.private_key)
+            .with_context(|| format!("read private key file {}", self.private_key.display()))?;
+        let key = pem_blocks(&private_key).find(|(label, _)| label.ends_with("PRIVATE KEY"));
+        let Some((_, key)) = key else {
+            return Err(OpaqueError::from_display(format!(
+                "no private key found in private key file {}",
+                self.private_key.display()
+            )));
+        };
+        if !pem_block_der(key)
+            .and_then(|der| read_der(&der).map(|(tag, _, _)| tag == TAG_SEQUENCE))
+            .unwrap_or_default()
+        {
+            return Err(OpaqueError::from_display(format!(
+                "invalid private key in private key file {}",
+                self.private_key.display()
+            )));
+        }
+
+        let ocsp = match &self.ocsp {
+            Some(path) => Some(
+                fs::read(path)
+                    .with_context(|| format!("read ocsp response file {}", path.display()))?,
+            )
+            .filter(|ocsp| !ocsp.is_empty()),
+            None => None,
+        };
+
+        Ok(ServerAuthData {
+            private_key: DataEncoding::Pem(private_key.try_into().context("empty private key")?),
+            cert_chain: DataEncoding::Pem(cert_chain.try_into().context("empty cert chain")?),
+            ocsp,
+        })
+    }
+
+    /// Modification time and size of each file, used to detect changes.
+    fn fingerprint(&self) -> Vec<Option<(SystemTime, u64)>> {
+        [
+            Some(&self.cert_chain),
+            Some(&self.private_key),
+            self.ocsp.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// [`ServerAuthData`] loaded from [`ServerAuthFiles`], which can be reloaded
+/// without restarting the server, e.g. to rotate certificates.
+///
+/// Use [`ReloadableServerAuth::watch`] to reload on change of the files
+/// (or SIGHUP on unix), or [`ReloadableServerAuth::reload`] to reload manually.
+///
+/// Reloaded data is validated before it is swapped in, by [`ServerAuthFiles::load`]
+/// and the validators set by the tls implementations using this data
+/// (e.g. to check that the private key matches the certificate).
+/// In case the reload fails the previous data is kept.
+pub struct ReloadableServerAuth {
+    files: Arc<ServerAuthFiles>,
+    state: Arc<RwLock<ReloadState>>,
+}
+
+type Validator = Arc<dyn Fn(&ServerAuthData) -> Result<(), OpaqueError> + Send + Sync>;
+
+struct ReloadState {
+    generation: u64,
+    data: Arc<ServerAuthData>,
+    /// fingerprint of the files of the last (attempted) reload
+    fingerprint: Vec<Option<(SystemTime, u64)>>,
+    /// validators by name, such that each tls implementation sets its validator only once
+    validators: Vec<(&'static str, Validator)>,
+}
+
+impl fmt::Debug for ReloadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadState")
+            .field("generation", &self.generation)
+            .field("data", &self.data)
+            .field("fingerprint", &self.fingerprint)
+            .field("validators", &self.validators.len())
+            .finish()
+    }
+}
+
+impl ReloadableServerAuth {
+    /// Create a new [`ReloadableServerAuth`], loading the initial data from the given files.
+    pub fn try_new(files: ServerAuthFiles) -> Result<Self, OpaqueError> {
+        let fingerprint = files.fingerprint();
+        let data = files.load().context("load initial server auth data")?;
+        Ok(Self {
+            files: Arc::new(files),
+            state: Arc::new(RwLock::new(ReloadState {
+                generation: 0,
+                data: Arc::new(data),
+                fingerprint,
+                validators: Vec::new(),
+            })),
+        })
+    }
+
+    /// [`ServerAuthFiles`] from which the data is (re)loaded.
+    pub fn files(&self) -> &ServerAuthFiles {
+        &self.files
+    }
+
+    /// Current [`ServerAuthData`] together with its generation,
+    /// which is incremented each time the data is reloaded.
+    pub fn current(&self) -> (u64, Arc<ServerAuthData>) {
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        (state.generation, state.data.clone())
+    }
+
+    /// Set a validator which reloaded data has to pass before it is swapped in,
+    /// used by tls implementations to reject data they are not able to use
+    /// (e.g. a private key which does not match the certificate).
+    ///
+    /// A validator previously set with the same name (e.g. the name of the tls implementation)
+    /// is replaced, such that creating multiple acceptors for the same data
+    /// does not multiply the validation work.
+    ///
+    /// The validator is shared by all clones of this [`ReloadableServerAuth`].
+    pub fn set_validator<F>(&self, name: &'static str, validator: F)
+    where
+        F: Fn(&ServerAuthData) -> Result<(), OpaqueError> + Send + Sync + 'static,
+    {
+        let validator: Validator = Arc::new(validator);
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        match state.validators.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = validator,
+            None => state.validators.push((name, validator)),
+        }
+    }
+
+    /// Reload the data from the files, keeping the previous data in case
+    /// it cannot be loaded or does not pass validation.
+    pub fn reload(&self) -> Result<(), OpaqueError> {
+        let fingerprint = self.files.fingerprint();
+        let validators = self
+            .state
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .validators
+            .clone();
+        let result = self.files.load().and_then(|data| {
+            validators.iter().try_for_each(|(name, validate)| {
+                validate(&data).with_context(|| format!("validate server auth data for {name}"))
+            })?;
+            Ok(data)
+        });
+
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        // do not retry a failed reload until the files changed again
+        state.fingerprint = fingerprint;
+        let data = result.context("reload server auth data")?;
+        state.generation += 1;
+        state.data = Arc::new(data);
+        Ok(())
+    }
+
+    /// Reload the data in case the files changed since the last (attempted) reload,
+    /// returning whether or not the data was reloaded.
+    pub fn reload_if_changed(&self) -> Result<bool, OpaqueError> {
+        let changed = {
+            let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+            state.fingerprint != self.files.fingerprint()
+        };
+        if changed {
+            self.reload()?;
+        }
+        Ok(changed)
+    }
+
+    /// Watch the files for changes, checked at the given interval,
+    /// and reload the data when they do. On unix the data is
+    /// also (unconditionally) reloaded when the process receives a SIGHUP.
+    ///
+    /// Errors are logged, this future completes once the [`ShutdownGuard`] is cancelled.
+    pub async fn watch(self, guard: ShutdownGuard, poll_interval: Duration) {
+        #[cfg(unix)]
+        let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => Some(signal),
+            Err(err) => {
+                tracing::warn!("failed to listen for SIGHUP, only watching files: {err:?}");
+                None
+            }
+        };
+
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // first tick completes immediately
+        interval.tick().await;
+
+        loop {
+            #[cfg(unix)]
+            let forced = tokio::select! {
+                _ = guard.cancelled() => return,
+                _ = interval.tick() => false,
+                _ = async {
+                    match sighup.as_mut() {
+                        Some(signal) => signal.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => true,
+            };
+            #[cfg(not(unix))]
+            let forced = tokio::select! {
+                _ = guard.cancelled() => return,
+                _ = interval.tick() => false,
+            };
+
+            let result = if forced {
+                self.reload().map(|()| true)
+            } else {
+                self.reload_if_changed()
+            };
+            match result {
+                Ok(true) => tracing::info!(
+                    "reloaded server auth data from {}",
+                    self.files.cert_chain.display()
+                ),
+                Ok(false) => (),
+                Err(err) => tracing::error!(
+                    "failed to reload server auth data, keep using previous data: {err:?}"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::der::TAG_INTEGER;
+    use base64::Engine;
+    use rama_core::graceful::Shutdown;
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        assert!(content.len() < 0x80);
+        let mut der = vec![tag, content.len() as u8];
+        der.extend_from_slice(content);
+        der
+    }
+
+    fn to_pem(label: &str, der: &[u8]) -> String {
+        format!(
+            "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+            base64::engine::general_purpose::STANDARD.encode(der)
+        )
+    }
+
+    /// Minimal (unsigned) PEM-encoded certificate, up to and including its validity.
+    fn cert_pem(serial: u8) -> String {
+        let validity = [der(0x17, b"700101000000Z"), der(0x17, b"491231235959Z")].concat();
+        let tbs = [
+            der(TAG_INTEGER, &[serial]),
+            der(TAG_SEQUENCE, &[]),
+            der(TAG_SEQUENCE, &[]),
+            der(TAG_SEQUENCE, &validity),
+        ]
+        .concat();
+        to_pem("CERTIFICATE", &der(TAG_SEQUENCE, &der(TAG_SEQUENCE, &tbs)))
+    }
+
+    fn key_pem() -> String {
+        to_pem("PRIVATE KEY", &der(TAG_SEQUENCE, &der(TAG_INTEGER, &[0])))
+    }
+
+    fn pem(data: &DataEncoding) -> &str {
+        match data {
+            DataEncoding::Pem(pem) => pem.as_str(),
+            _ => panic!("expected PEM data"),
+        }
+    }
+
+    #[test]
+    fn test_reloadable_server_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.crt.pem");
+        let key_path = dir.path().join("server.key.pem");
+        let ocsp_path = dir.path().join("server.ocsp.der");
+        fs::write(&cert_path, cert_pem(1)).unwrap();
+        fs::write(&key_path, key_pem()).unwrap();
+        fs::write(&ocsp_path, [1, 2, 3]).unwrap();
+
+        let auth = ReloadableServerAuth::try_new(
+            ServerAuthFiles::new(&cert_path, &key_path).with_ocsp(ocsp_path.clone()),
+        )
+        .unwrap();
+        let (generation, data) = auth.current();
+        assert_eq!(generation, 0);
+        assert_eq!(pem(&data.cert_chain), cert_pem(1));
+        assert_eq!(data.ocsp.as_deref(), Some(&[1, 2, 3][..]));
+        assert!(!auth.reload_if_changed().unwrap());
+
+        // invalid data is not swapped in
+        fs::write(&key_path, "not a key").unwrap();
+        assert!(auth.reload().is_err());
+        assert_eq!(auth.current().0, 0);
+        // ... nor retried until the files change again
+        assert!(!auth.reload_if_changed().unwrap());
+
+        // a certificate which does not parse is rejected as well
+        fs::write(&key_path, key_pem()).unwrap();
+        fs::write(
+            &cert_path,
+            format!(
+                "{}-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n",
+                cert_pem(2)
+            ),
+        )
+        .unwrap();
+        assert!(auth.reload().is_err());
+        assert_eq!(auth.current().0, 0);
+
+        fs::write(&cert_path, cert_pem(2)).unwrap();
+        auth.reload().unwrap();
+        let (generation, data) = auth.current();
+        assert_eq!(generation, 1);
+        assert_eq!(pem(&data.cert_chain), cert_pem(2));
+        assert_eq!(pem(&data.private_key), key_pem());
+    }
+
+    #[test]
+    fn test_reloadable_server_auth_validator() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.crt.pem");
+        let key_path = dir.path().join("server.key.pem");
+        fs::write(&cert_path, cert_pem(1)).unwrap();
+        fs::write(&key_path, key_pem()).unwrap();
+
+        let auth =
+            ReloadableServerAuth::try_new(ServerAuthFiles::new(&cert_path, &key_path)).unwrap();
+        let validations = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // setting a validator with the same name again replaces it
+        for _ in 0..3 {
+            let expected = cert_pem(1);
+            let validations = validations.clone();
+            auth.clone().set_validator("test", move |data| {
+                validations.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                if pem(&data.cert_chain) == expected {
+                    Ok(())
+                } else {
+                    Err(OpaqueError::from_display("cert does not match key"))
+                }
+            });
+        }
+        assert_eq!(auth.state.read().unwrap().validators.len(), 1);
+
+        fs::write(&cert_path, cert_pem(2)).unwrap();
+        assert!(auth.reload().is_err());
+        assert_eq!(auth.current().0, 0);
+        assert_eq!(pem(&auth.current().1.cert_chain), cert_pem(1));
+
+        fs::write(&cert_path, cert_pem(1)).unwrap();
+        auth.reload().unwrap();
+        assert_eq!(auth.current().0, 1);
+        assert_eq!(validations.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_reloadable_server_auth_invalid_initial_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.crt.pem");
+        let key_path = dir.path().join("server.key.pem");
+        fs::write(&cert_path, key_pem()).unwrap();
+        fs::write(&key_path, key_pem()).unwrap();
+        assert!(
+            ReloadableServerAuth::try_new(ServerAuthFiles::new(&cert_path, &key_path)).is_err()
+        );
+        assert!(
+            ReloadableServerAuth::try_new(ServerAuthFiles::new(
+                dir.path().join("missing.pem"),
+                &key_path
+            ))
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reloadable_server_auth_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.crt.pem");
+        let key_path = dir.path().join("server.key.pem");
+        fs::write(&cert_path, cert_pem(1)).unwrap();
+        fs::write(&key_path, key_pem()).unwrap();
+
+        let auth =
+            ReloadableServerAuth::try_new(ServerAuthFiles::new(&cert_path, &key_path)).unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let shutdown = Shutdown::new(async move {
+            let _ = rx.await;
+        });
+        shutdown.spawn_task_fn({
+            let auth = auth.clone();
+            async move |guard| auth.watch(guard, Duration::from_millis(10)).await
+        });
+
+        // ensure the modification time differs on coarse grained file systems
+        fs::write(&cert_path, cert_pem(2)).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&cert_path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while auth.current().0 == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(pem(&auth.current().1.cert_chain), cert_pem(2));
+
+        // the watcher stops once the shutdown is triggered
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+}
@@ -3,6 +3,7 @@ use rama_core::{
     Context,
     combinators::Either,
     error::{BoxError, ErrorContext, OpaqueError},
+    layer::timeout::Deadline,
 };
 use rama_dns::{DnsOverwrite, DnsResolver, GlobalDnsResolver};
 use rama_net::{
@@ -189,12 +190,31 @@ where
 }
 
 /// Establish a [`TcpStream`] connection for the given [`Authority`].
+///
+/// The [`Deadline`] found in the [`Context`] (if any) is respected,
+/// such that dns resolution and connection establishment fail once it expires.
 pub async fn tcp_connect<State, Dns, Connector>(
     ctx: &Context<State>,
     authority: Authority,
     dns: Dns,
     connector: Connector,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError>
+where
+    State: Clone + Send + Sync + 'static,
+    Dns: DnsResolver + Clone,
+    Connector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
+{
+    Deadline::run_within(ctx, tcp_connect_authority(ctx, authority, dns, connector))
+        .await
+        .context("establish tcp client connection")?
+}
+
+async fn tcp_connect_authority<State, Dns, Connector>(
+    ctx: &Context<State>,
+    authority: Authority,
+    dns: Dns,
+    connector: Connector,
+) -> Result<(TcpStream, SocketAddr), OpaqueError>
 where
     State: Clone + Send + Sync + 'static,
     Dns: DnsResolver + Clone,
@@ -8,6 +8,9 @@ use rama_core::telemetry::tracing::{self, Instrument, trace_root_span};
 use rama_net::address::SocketAddress;
 use rama_net::socket::Interface;
 use rama_net::stream::SocketInfo;
+
+#[cfg(target_os = "linux")]
+use rama_net::stream::TcpSyn;
 use std::fmt;
 use std::pin::pin;
 use std::sync::Arc;
@@ -22,6 +25,8 @@ use crate::TcpStream;
 /// Builder for `TcpListener`.
 pub struct TcpListenerBuilder<S> {
     ttl: Option<u32>,
+    #[cfg(target_os = "linux")]
+    save_syn: bool,
     state: S,
 }
 
@@ -30,10 +35,11 @@ where
     S: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TcpListenerBuilder")
-            .field("ttl", &self.ttl)
-            .field("state", &self.state)
-            .finish()
+        let mut d = f.debug_struct("TcpListenerBuilder");
+        d.field("ttl", &self.ttl);
+        #[cfg(target_os = "linux")]
+        d.field("save_syn", &self.save_syn);
+        d.field("state", &self.state).finish()
     }
 }
 
@@ -42,6 +48,8 @@ impl TcpListenerBuilder<()> {
     pub fn new() -> Self {
         Self {
             ttl: None,
+            #[cfg(target_os = "linux")]
+            save_syn: false,
             state: (),
         }
     }
@@ -57,6 +65,8 @@ impl<S: Clone> Clone for TcpListenerBuilder<S> {
     fn clone(&self) -> Self {
         Self {
             ttl: self.ttl,
+            #[cfg(target_os = "linux")]
+            save_syn: self.save_syn,
             state: self.state.clone(),
         }
     }
@@ -73,6 +83,22 @@ impl<S> TcpListenerBuilder<S> {
             self
         }
     }
+
+    #[cfg(target_os = "linux")]
+    rama_utils::macros::generate_set_and_with! {
+        /// Sets the value for the `TCP_SAVE_SYN` option on this socket.
+        ///
+        /// If enabled, the SYN packet of each accepted connection is captured
+        /// and made available as a [`TcpSyn`] extension to the served service,
+        /// which can be used for passive TCP fingerprinting (e.g. [`Ja4T`]).
+        ///
+        /// [`TcpSyn`]: rama_net::stream::TcpSyn
+        /// [`Ja4T`]: rama_net::fingerprint::Ja4T
+        pub fn save_syn(mut self, save_syn: bool) -> Self {
+            self.save_syn = save_syn;
+            self
+        }
+    }
 }
 
 impl<S> TcpListenerBuilder<S>
@@ -81,7 +107,12 @@ where
 {
     /// Create a new `TcpListenerBuilder` with the given state.
     pub fn with_state(state: S) -> Self {
-        Self { ttl: None, state }
+        Self {
+            ttl: None,
+            #[cfg(target_os = "linux")]
+            save_syn: false,
+            state,
+        }
     }
 }
 
@@ -110,10 +141,13 @@ where
             inner.set_ttl(ttl).context("set ttl on tcp listener")?;
         }
 
-        Ok(TcpListener {
-            inner,
-            state: self.state,
-        })
+        #[cfg(target_os = "linux")]
+        if self.save_syn {
+            rama_net::socket::syn::set_tcp_save_syn(&inner, true)
+                .context("set tcp save syn on tcp listener")?;
+        }
+
+        Ok(TcpListener::new(inner, self.state))
     }
 
     #[cfg(any(windows, unix))]
@@ -124,6 +158,11 @@ where
         self,
         socket: rama_net::socket::core::Socket,
     ) -> Result<TcpListener<S>, BoxError> {
+        #[cfg(target_os = "linux")]
+        if self.save_syn {
+            rama_net::socket::syn::set_tcp_save_syn(&socket, true)
+                .context("set tcp save syn on socket")?;
+        }
         tokio::task::spawn_blocking(|| bind_socket_internal(self.state, socket))
             .await
             .context("await blocking bind socket task")?
@@ -137,10 +176,12 @@ where
         self,
         name: N,
     ) -> Result<TcpListener<S>, BoxError> {
-        tokio::task::spawn_blocking(|| {
+        tokio::task::spawn_blocking(move || {
             let name = name.try_into().map_err(Into::<BoxError>::into)?;
             let socket = SocketOptions {
                 device: Some(name),
+                #[cfg(target_os = "linux")]
+                tcp_save_syn: self.save_syn.then_some(true),
                 ..SocketOptions::default_tcp()
             }
             .try_build_socket()
@@ -176,6 +217,8 @@ where
 /// using one of the `serve` methods such as [`TcpListener::serve`].
 pub struct TcpListener<S> {
     inner: TokioTcpListener,
+    #[cfg(target_os = "linux")]
+    save_syn: bool,
     state: S,
 }
 
@@ -184,10 +227,11 @@ where
     S: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TcpListener")
-            .field("inner", &self.inner)
-            .field("state", &self.state)
-            .finish()
+        let mut d = f.debug_struct("TcpListener");
+        d.field("inner", &self.inner);
+        #[cfg(target_os = "linux")]
+        d.field("save_syn", &self.save_syn);
+        d.field("state", &self.state).finish()
     }
 }
 
@@ -261,13 +305,23 @@ where
     listener
         .set_nonblocking(true)
         .context("set socket as non-blocking")?;
-    Ok(TcpListener {
-        inner: TokioTcpListener::from_std(listener)?,
+    Ok(TcpListener::new(
+        TokioTcpListener::from_std(listener)?,
         state,
-    })
+    ))
 }
 
 impl<S> TcpListener<S> {
+    fn new(inner: TokioTcpListener, state: S) -> Self {
+        Self {
+            // the option might as well be set on a socket created by the user
+            #[cfg(target_os = "linux")]
+            save_syn: rama_net::socket::syn::tcp_save_syn(&inner).unwrap_or_default(),
+            inner,
+            state,
+        }
+    }
+
     /// Returns the local address that this listener is bound to.
     ///
     /// This can be useful, for example, when binding to port 0 to figure out
@@ -298,10 +352,7 @@ impl<S> TcpListener<S> {
 
 impl From<TokioTcpListener> for TcpListener<()> {
     fn from(value: TokioTcpListener) -> Self {
-        Self {
-            inner: value,
-            state: (),
-        }
+        Self::new(value, ())
     }
 }
 
@@ -321,10 +372,7 @@ impl TryFrom<std::net::TcpListener> for TcpListener<()> {
 
     fn try_from(value: std::net::TcpListener) -> Result<Self, Self::Error> {
         value.set_nonblocking(true)?;
-        Ok(Self {
-            inner: TokioTcpListener::from_std(value)?,
-            state: (),
-        })
+        Ok(Self::new(TokioTcpListener::from_std(value)?, ()))
     }
 }
 
@@ -334,6 +382,8 @@ impl TcpListener<()> {
     pub fn with_state<S>(self, state: S) -> TcpListener<S> {
         TcpListener {
             inner: self.inner,
+            #[cfg(target_os = "linux")]
+            save_syn: self.save_syn,
             state,
         }
     }
@@ -361,6 +411,8 @@ where
     {
         let ctx = Context::new(self.state, Executor::new());
         let service = Arc::new(service);
+        #[cfg(target_os = "linux")]
+        let save_syn = self.save_syn;
 
         loop {
             let (socket, peer_addr) = match self.inner.accept().await {
@@ -392,6 +444,10 @@ where
             tokio::spawn(
                 async move {
                     ctx.insert(SocketInfo::new(local_addr, peer_addr));
+                    #[cfg(target_os = "linux")]
+                    if save_syn {
+                        ctx.maybe_insert(take_tcp_syn(&socket));
+                    }
 
                     let _ = service.serve(ctx, socket).await;
                 }
@@ -411,6 +467,8 @@ where
     {
         let ctx: Context<State> = Context::new(self.state, Executor::graceful(guard.clone()));
         let service = Arc::new(service);
+        #[cfg(target_os = "linux")]
+        let save_syn = self.save_syn;
         let mut cancelled_fut = pin!(guard.cancelled());
 
         loop {
@@ -442,6 +500,10 @@ where
 
                             guard.spawn_task(async move {
                                 ctx.insert(SocketInfo::new(local_addr, peer_addr));
+                                #[cfg(target_os = "linux")]
+                                if save_syn {
+                                    ctx.maybe_insert(take_tcp_syn(&socket));
+                                }
                                 let _ = service.serve(ctx, socket).await;
                             }.instrument(span));
                         }
@@ -455,6 +517,20 @@ where
     }
 }
 
+#[cfg(target_os = "linux")]
+fn take_tcp_syn(socket: &TcpStream) -> Option<TcpSyn> {
+    match rama_net::socket::syn::tcp_saved_syn(socket) {
+        Ok(Some(packet)) => TcpSyn::parse(&packet)
+            .inspect_err(|err| tracing::debug!("failed to parse saved tcp syn: {err:?}"))
+            .ok(),
+        Ok(None) => None,
+        Err(err) => {
+            tracing::debug!("failed to take saved tcp syn: {err:?}");
+            None
+        }
+    }
+}
+
 async fn handle_accept_err(err: io::Error) {
     if rama_net::conn::is_connection_error(&err) {
         tracing::trace!("TCP accept error: connect error: {err:?}");
@@ -1,13 +1,15 @@
 use crate::RamaTryInto;
 use rama_boring_tokio::SslStream;
 use rama_core::error::{BoxError, ErrorExt, OpaqueError};
+use rama_core::layer::timeout::Deadline;
 use rama_core::telemetry::tracing;
 use rama_core::{Context, Layer, Service};
 use rama_net::address::Host;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::tls::ApplicationProtocol;
-use rama_net::tls::client::NegotiatedTlsParameters;
+use rama_net::tls::client::{NegotiatedTlsParameters, ServerHelloRecorder};
+use rama_net::tls::server::ServerHello;
 use rama_net::transport::TryRefIntoTransportContext;
 use rama_utils::macros::generate_set_and_with;
 use std::fmt;
@@ -131,6 +133,11 @@ impl Default for TlsConnectorLayer<ConnectorKindAuto> {
 /// only if the request requires a secure connection. You can instead use
 /// [`TlsConnector::secure_only`] to force the connector to always
 /// establish a secure connection.
+///
+/// Once a secure connection is established the [`NegotiatedTlsParameters`]
+/// and the [`ServerHello`] received from the server are added to the [`Context`],
+/// which can be used to fingerprint the server (e.g. JA3S, JA4S and JA4X)
+/// using the types found in [`rama_net::fingerprint`].
 pub struct TlsConnector<S, K = ConnectorKindAuto> {
     inner: S,
     connector_data: Option<Arc<TlsConnectorDataBuilder>>,
@@ -262,7 +269,9 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = self.connector_data(&mut ctx)?;
-        let (stream, negotiated_params) = handshake(connector_data, host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (stream, negotiated_params, server_hello) =
+            handshake(connector_data, deadline, host, conn).await?;
 
         tracing::trace!(
             server.address = %transport_ctx.authority.host(),
@@ -271,6 +280,7 @@ where
         );
 
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -314,9 +324,12 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = self.connector_data(&mut ctx)?;
-        let (conn, negotiated_params) = handshake(connector_data, host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (conn, negotiated_params, server_hello) =
+            handshake(connector_data, deadline, host, conn).await?;
         let conn = TlsStream::new(conn);
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         Ok(EstablishedClientConnection { ctx, req, conn })
     }
@@ -359,8 +372,11 @@ where
         };
 
         let connector_data = self.connector_data(&mut ctx)?;
-        let (stream, negotiated_params) = handshake(connector_data, host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (stream, negotiated_params, server_hello) =
+            handshake(connector_data, deadline, host, conn).await?;
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         tracing::trace!("TlsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -401,29 +417,46 @@ where
     };
 
     let server_host = data.server_name.map(Host::Name).unwrap_or(server_host);
-    let stream: SslStream<T> =
-        rama_boring_tokio::connect(data.config, server_host.to_string().as_str(), stream)
-            .await
-            .map_err(|err| match err.as_io_error() {
-                Some(err) => OpaqueError::from_display(err.to_string())
-                    .context("boring ssl connector: connect")
-                    .into_boxed(),
-                None => OpaqueError::from_display("boring ssl connector: connect").into_boxed(),
-            })?;
+    let stream: SslStream<ServerHelloRecorder<T>> = rama_boring_tokio::connect(
+        data.config,
+        server_host.to_string().as_str(),
+        ServerHelloRecorder::new(stream),
+    )
+    .await
+    .map_err(|err| match err.as_io_error() {
+        Some(err) => OpaqueError::from_display(err.to_string())
+            .context("boring ssl connector: connect")
+            .into_boxed(),
+        None => OpaqueError::from_display("boring ssl connector: connect").into_boxed(),
+    })?;
     Ok(TlsStream::new(stream))
 }
 
 async fn handshake<T>(
     connector_data: TlsConnectorData,
+    deadline: Option<Deadline>,
     server_host: Host,
     stream: T,
-) -> Result<(SslStream<T>, NegotiatedTlsParameters), BoxError>
+) -> Result<
+    (
+        SslStream<ServerHelloRecorder<T>>,
+        NegotiatedTlsParameters,
+        Option<ServerHello>,
+    ),
+    BoxError,
+>
 where
     T: Stream + Unpin,
 {
     let store_server_certificate_chain = connector_data.store_server_certificate_chain;
-    let TlsStream { inner: stream } =
-        tls_connect(server_host, stream, Some(connector_data)).await?;
+    let connect = tls_connect(server_host, stream, Some(connector_data));
+    let TlsStream { inner: stream } = match deadline {
+        Some(deadline) => deadline
+            .run(connect)
+            .await
+            .map_err(|err| err.context("boring ssl connector: handshake"))??,
+        None => connect.await?,
+    };
 
     let params = match stream.ssl().session() {
         Some(ssl_session) => {
@@ -464,7 +497,9 @@ where
         }
     };
 
-    Ok((stream, params))
+    let server_hello = stream.get_ref().server_hello().cloned();
+
+    Ok((stream, params, server_hello))
 }
 
 #[non_exhaustive]
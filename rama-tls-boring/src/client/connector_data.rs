@@ -6,10 +6,14 @@ use rama_boring::{
     hash::MessageDigest,
     pkey::{PKey, Private},
     rsa::Rsa,
-    ssl::{ConnectConfiguration, SslCurve, SslSignatureAlgorithm, SslVerifyMode, SslVersion},
+    ssl::{
+        BoxCustomVerifyFinish, ConnectConfiguration, NameType, SslAlert, SslCurve, SslRef,
+        SslSignatureAlgorithm, SslVerifyError, SslVerifyMode, SslVersion,
+    },
     x509::{
         X509,
         extension::{BasicConstraints, KeyUsage, SubjectKeyIdentifier},
+        store::X509StoreBuilder,
     },
 };
 use rama_core::telemetry::tracing::{debug, trace};
@@ -25,7 +29,10 @@ use rama_net::tls::{
     DataEncoding,
     client::{ClientAuth, ClientHelloExtension},
 };
-use rama_net::{address::Domain, tls::client::ServerVerifyMode};
+use rama_net::{
+    address::{Domain, Host},
+    tls::client::{ServerVerifyMode, verify_certificate_pins},
+};
 use rama_utils::macros::generate_set_and_with;
 use std::{fmt, sync::Arc};
 
@@ -146,7 +153,6 @@ pub struct ConnectorConfigClientAuth {
 
 impl TlsConnectorDataBuilder {
     implement_copy_getters!(
-        server_verify_mode: Option<ServerVerifyMode>,
         min_ssl_version: Option<SslVersion>,
         max_ssl_version: Option<SslVersion>,
         record_size_limit: Option<u16>,
@@ -158,6 +164,7 @@ impl TlsConnectorDataBuilder {
     );
 
     implement_reference_getters!(
+        server_verify_mode: Option<ServerVerifyMode>,
         cipher_list: Option<Vec<u16>>,
         extension_order: Option<Vec<u16>>,
         alpn_protos: Option<Bytes>,
@@ -541,7 +548,7 @@ impl TlsConnectorDataBuilder {
             }
         }
 
-        match self.server_verify_mode().unwrap_or_default() {
+        match self.server_verify_mode().cloned().unwrap_or_default() {
             ServerVerifyMode::Auto => {
                 trace!("boring connector: server verify mode: auto (default verifier)");
             } // nothing explicit to do
@@ -549,6 +556,71 @@ impl TlsConnectorDataBuilder {
                 trace!("boring connector: server verify mode: disable");
                 cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
             }
+            ServerVerifyMode::TrustAnchors(roots) => {
+                trace!("boring connector: server verify mode: trust anchors");
+                let mut store_builder = X509StoreBuilder::new()
+                    .context("build (boring) ssl connector: create x509 store builder")?;
+                for cert in x509_certs_from_data_encoding(roots)
+                    .context("build (boring) ssl connector: parse trust anchors")?
+                {
+                    store_builder
+                        .add_cert(cert)
+                        .context("build (boring) ssl connector: add trust anchor")?;
+                }
+                cfg_builder
+                    .set_verify_cert_store(store_builder.build())
+                    .context("build (boring) ssl connector: set verify cert store")?;
+            }
+            ServerVerifyMode::Pinned(pins) => {
+                trace!(
+                    "boring connector: server verify mode: pinned ({} pin(s))",
+                    pins.len()
+                );
+                cfg_builder.set_custom_verify_callback(SslVerifyMode::PEER, move |ssl| {
+                    let cert = ssl
+                        .peer_certificate()
+                        .ok_or(SslVerifyError::Invalid(SslAlert::BAD_CERTIFICATE))?;
+                    let cert = cert
+                        .to_der()
+                        .map_err(|_| SslVerifyError::Invalid(SslAlert::INTERNAL_ERROR))?;
+                    verify_certificate_pins(&pins, &cert).map_err(|err| {
+                        debug!("boring connector: server verify mode: pinned: {err}");
+                        SslVerifyError::Invalid(SslAlert::BAD_CERTIFICATE)
+                    })
+                });
+            }
+            ServerVerifyMode::Dynamic(verifier) => {
+                trace!("boring connector: server verify mode: dynamic");
+                cfg_builder.set_async_custom_verify_callback(SslVerifyMode::PEER, move |ssl| {
+                    let cert_chain = ssl
+                        .peer_cert_chain()
+                        .map(|chain| {
+                            chain
+                                .iter()
+                                .map(|cert| cert.to_der())
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                        .transpose()
+                        .map_err(|_| SslAlert::INTERNAL_ERROR)?
+                        .unwrap_or_default();
+                    let server_name = ssl
+                        .servername(NameType::HOST_NAME)
+                        .and_then(|name| Host::try_from(name).ok());
+
+                    let verifier = verifier.clone();
+                    Ok(Box::pin(async move {
+                        match verifier.verify_cert(cert_chain, server_name).await {
+                            Ok(()) => {
+                                Ok(Box::new(|_: &mut SslRef| Ok(())) as BoxCustomVerifyFinish)
+                            }
+                            Err(err) => {
+                                debug!("boring connector: server verify mode: dynamic: {err}");
+                                Err(SslAlert::BAD_CERTIFICATE)
+                            }
+                        }
+                    }))
+                });
+            }
         }
 
         if let Some(auth) = self.client_auth() {
@@ -718,7 +790,7 @@ impl TlsConnectorDataBuilder {
             cipher_suites = cfg.cipher_suites.as_ref().or(cipher_suites);
             keylog_intent = cfg.key_logger.as_ref().or(keylog_intent);
             client_auth = cfg.client_auth.as_ref().or(client_auth);
-            server_verify_mode = cfg.server_verify_mode.or(server_verify_mode);
+            server_verify_mode = cfg.server_verify_mode.as_ref().or(server_verify_mode);
             store_server_certificate_chain =
                 store_server_certificate_chain || cfg.store_server_certificate_chain;
 
@@ -941,23 +1013,8 @@ impl TlsConnectorDataBuilder {
             }
             Some(ClientAuth::Single(data)) => {
                 // server TLS Certs
-                let cert_chain = match data.cert_chain {
-                    DataEncoding::Der(raw_data) => vec![X509::from_der(&raw_data[..]).context(
-                        "boring/TlsConnectorData: parse x509 client cert from DER content",
-                    )?],
-                    DataEncoding::DerStack(raw_data_list) => raw_data_list
-                        .into_iter()
-                        .map(|raw_data| {
-                            X509::from_der(&raw_data[..]).context(
-                                "boring/TlsConnectorData: parse x509 client cert from DER content",
-                            )
-                        })
-                        .collect::<Result<Vec<_>, _>>()?,
-                    DataEncoding::Pem(raw_data) => X509::stack_from_pem(raw_data.as_bytes())
-                        .context(
-                        "boring/TlsConnectorData: parse x509 client cert chain from PEM content",
-                    )?,
-                };
+                let cert_chain = x509_certs_from_data_encoding(data.cert_chain)
+                    .context("boring/TlsConnectorData: parse x509 client cert chain")?;
 
                 // server TLS key
                 let private_key = match data.private_key {
@@ -992,7 +1049,7 @@ impl TlsConnectorDataBuilder {
             min_ssl_version,
             max_ssl_version,
             verify_algorithm_prefs,
-            server_verify_mode,
+            server_verify_mode: server_verify_mode.cloned(),
             client_auth,
             store_server_certificate_chain: Some(store_server_certificate_chain),
             grease_enabled: Some(grease_enabled),
@@ -1032,6 +1089,22 @@ impl TryFrom<ClientHello> for TlsConnectorDataBuilder {
     }
 }
 
+fn x509_certs_from_data_encoding(data: DataEncoding) -> Result<Vec<X509>, OpaqueError> {
+    match data {
+        DataEncoding::Der(raw_data) => Ok(vec![
+            X509::from_der(&raw_data[..]).context("parse x509 cert from DER content")?,
+        ]),
+        DataEncoding::DerStack(raw_data_list) => raw_data_list
+            .into_iter()
+            .map(|raw_data| {
+                X509::from_der(&raw_data[..]).context("parse x509 cert from DER content")
+            })
+            .collect(),
+        DataEncoding::Pem(raw_data) => X509::stack_from_pem(raw_data.as_bytes())
+            .context("parse x509 cert chain from PEM content"),
+    }
+}
+
 fn self_signed_client_auth() -> Result<(Vec<X509>, PKey<Private>), OpaqueError> {
     let rsa = Rsa::generate(4096).context("generate 4096 RSA key")?;
     let privkey = PKey::from_rsa(rsa).context("create private key from 4096 RSA key")?;
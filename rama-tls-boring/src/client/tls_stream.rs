@@ -4,18 +4,19 @@ use pin_project_lite::pin_project;
 use rama_boring::ssl::SslRef;
 use rama_boring_tokio::SslStream;
 use rama_net::stream::Stream;
+use rama_net::tls::client::ServerHelloRecorder;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 pin_project! {
     /// A stream which can be either a secure or a plain stream.
     pub struct TlsStream<S> {
         #[pin]
-        pub(super) inner: SslStream<S>,
+        pub(super) inner: SslStream<ServerHelloRecorder<S>>,
     }
 }
 
 impl<S> TlsStream<S> {
-    pub(super) fn new(inner: SslStream<S>) -> Self {
+    pub(super) fn new(inner: SslStream<ServerHelloRecorder<S>>) -> Self {
         Self { inner }
     }
 
@@ -4,6 +4,7 @@ use pin_project_lite::pin_project;
 use rama_boring::ssl::SslRef;
 use rama_boring_tokio::SslStream;
 use rama_net::stream::Stream;
+use rama_net::tls::client::ServerHelloRecorder;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 pin_project! {
@@ -15,7 +16,7 @@ pin_project! {
 }
 
 impl<S> AutoTlsStream<S> {
-    pub(super) fn secure(inner: SslStream<S>) -> Self {
+    pub(super) fn secure(inner: SslStream<ServerHelloRecorder<S>>) -> Self {
         Self {
             inner: AutoTlsStreamData::Secure { inner },
         }
@@ -48,7 +49,7 @@ pin_project! {
     /// A stream which can be either a secure or a plain stream.
     enum AutoTlsStreamData<S> {
         /// A secure stream.
-        Secure{ #[pin] inner: SslStream<S> },
+        Secure{ #[pin] inner: SslStream<ServerHelloRecorder<S>> },
         /// A plain stream.
         Plain { #[pin] inner: S },
     }
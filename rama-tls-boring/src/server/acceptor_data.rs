@@ -11,7 +11,6 @@ use crate::core::{
         extension::{BasicConstraints, KeyUsage, SubjectKeyIdentifier},
     },
 };
-use moka::sync::Cache;
 use parking_lot::Mutex;
 use rama_boring::{
     ssl::{ClientHello, NameType, SelectCertError, SslAcceptorBuilder, SslRef},
@@ -26,12 +25,12 @@ use rama_net::{
         ApplicationProtocol, DataEncoding, KeyLogIntent, ProtocolVersion,
         client::ClientHello as RamaClientHello,
         server::{
-            CacheKind, ClientVerifyMode, DynamicIssuer, SelfSignedData, ServerAuth, ServerAuthData,
-            ServerCertIssuerKind,
+            ClientVerifyMode, DynamicIssuer, IssuedCertCache, ReloadableServerAuth, SelfSignedData,
+            ServerAuth, ServerAuthData, ServerCertIssuerKind,
         },
     },
 };
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 /// Internal data used as configuration/input for the [`super::TlsAcceptorService`].
@@ -65,9 +64,10 @@ pub(super) struct TlsCertSource {
 #[derive(Debug, Clone)]
 enum TlsCertSourceKind {
     InMemory(IssuedCert),
+    Reloadable(ReloadableCert),
     InMemoryIssuer {
         /// Cache for certs already issued
-        cert_cache: Option<Cache<Host, IssuedCert>>,
+        cert_cache: Option<IssuedCertCache<IssuedCert>>,
         /// Private Key for issueing
         ca_key: PKey<Private>,
         /// CA Cert to be used for issueing
@@ -76,7 +76,7 @@ enum TlsCertSourceKind {
     DynamicIssuer {
         issuer: DynamicIssuer,
         /// Cache for certs already issued
-        cert_cache: Option<Cache<Host, IssuedCert>>,
+        cert_cache: Option<IssuedCertCache<IssuedCert>>,
     },
 }
 
@@ -86,6 +86,55 @@ struct IssuedCert {
     key: PKey<Private>,
 }
 
+#[derive(Debug, Clone)]
+/// Cert of a [`ReloadableServerAuth`], parsed again once its data is reloaded.
+struct ReloadableCert {
+    auth: ReloadableServerAuth,
+    current: Arc<Mutex<(u64, Arc<ReloadedCert>)>>,
+}
+
+#[derive(Debug)]
+struct ReloadedCert {
+    issued_cert: IssuedCert,
+    ocsp: Option<Vec<u8>>,
+}
+
+impl ReloadableCert {
+    fn try_new(auth: ReloadableServerAuth) -> Result<Self, OpaqueError> {
+        let (generation, data) = auth.current();
+        let reloaded_cert = server_auth_data_to_reloaded_cert(&data)
+            .context("boring/TlsAcceptorData: load initial reloadable cert")?;
+        // reject reloaded data which cannot be used before it is swapped in
+        auth.set_validator("boring", |data| {
+            server_auth_data_to_reloaded_cert(data).map(|_| ())
+        });
+        Ok(Self {
+            auth,
+            current: Arc::new(Mutex::new((generation, Arc::new(reloaded_cert)))),
+        })
+    }
+
+    /// Current cert, parsing it first in case the data was reloaded.
+    fn current(&self) -> Arc<ReloadedCert> {
+        let (generation, data) = self.auth.current();
+        let mut current = self.current.lock();
+        if current.0 != generation {
+            // generation is updated even on error, so invalid data is only tried once
+            current.0 = generation;
+            match server_auth_data_to_reloaded_cert(&data) {
+                Ok(reloaded_cert) => {
+                    tracing::debug!("boring: use reloaded cert");
+                    current.1 = Arc::new(reloaded_cert);
+                }
+                Err(err) => tracing::error!(
+                    "boring: invalid reloaded cert, keep using previous cert: {err:?}"
+                ),
+            }
+        }
+        current.1.clone()
+    }
+}
+
 impl TlsCertSource {
     pub(super) async fn issue_certs(
         self,
@@ -95,23 +144,7 @@ impl TlsCertSource {
     ) -> Result<SslAcceptorBuilder, OpaqueError> {
         match self.kind {
             TlsCertSourceKind::InMemory(issued_cert) => {
-                for (i, ca_cert) in issued_cert.cert_chain.iter().enumerate() {
-                    if i == 0 {
-                        builder
-                            .set_certificate(ca_cert.as_ref())
-                            .context("build boring ssl acceptor: set Leaf CA certificate (x509)")?;
-                    } else {
-                        builder.add_extra_chain_cert(ca_cert.clone()).context(
-                            "build boring ssl acceptor: add extra chain certificate (x509)",
-                        )?;
-                    }
-                }
-                builder
-                    .set_private_key(issued_cert.key.as_ref())
-                    .context("build boring ssl acceptor: set private key")?;
-                builder
-                    .check_private_key()
-                    .context("build boring ssl acceptor: check private key")?;
+                set_issued_cert(&mut builder, &issued_cert)?;
 
                 if let Some(maybe_client_hello) = maybe_client_hello {
                     let cb_maybe_client_hello = maybe_client_hello.clone();
@@ -131,6 +164,43 @@ impl TlsCertSource {
                     });
                 }
             }
+            TlsCertSourceKind::Reloadable(reloadable_cert) => {
+                let reloaded_cert = reloadable_cert.current();
+                set_issued_cert(&mut builder, &reloaded_cert.issued_cert)?;
+
+                let cb_maybe_client_hello = maybe_client_hello.clone();
+                if cb_maybe_client_hello.is_some() || reloaded_cert.ocsp.is_some() {
+                    builder.set_select_certificate_callback(move |client_hello| {
+                        if let Some(cb_maybe_client_hello) = &cb_maybe_client_hello {
+                            let maybe_client_hello =
+                                match RamaClientHello::rama_try_from(&client_hello) {
+                                    Ok(ch) => Some(ch),
+                                    Err(err) => {
+                                        tracing::warn!(
+                                            "failed to extract boringssl client hello: {err:?}"
+                                        );
+                                        None
+                                    }
+                                };
+                            *cb_maybe_client_hello.lock() = maybe_client_hello;
+                        }
+
+                        if let Some(ocsp) = &reloaded_cert.ocsp {
+                            let mut client_hello = client_hello;
+                            client_hello
+                                .ssl_mut()
+                                .set_ocsp_status(ocsp)
+                                .map_err(|err| {
+                                    tracing::error!(
+                                        "boring: select certificate callback: set ocsp status: {err:?}"
+                                    );
+                                    SelectCertError::ERROR
+                                })?;
+                        }
+                        Ok(())
+                    });
+                }
+            }
             TlsCertSourceKind::InMemoryIssuer {
                 cert_cache,
                 ca_key,
@@ -169,9 +239,12 @@ impl TlsCertSource {
                                 SelectCertError::ERROR
                             })?,
                         Some(cert_cache) => cert_cache
-                            .try_get_with(host.clone(), || {
-                                issue_cert_for_ca(host.clone(), &ca_cert, &ca_key)
-                            })
+                            .try_get_with(
+                                host.clone(),
+                                || issue_cert_for_ca(host.clone(), &ca_cert, &ca_key),
+                                server_auth_data_to_private_key_and_ca_chain,
+                                issued_cert_to_server_auth_data,
+                            )
                             .context("fresh issue of cert + insert")
                             .map_err(|err| {
                                 tracing::error!(
@@ -218,23 +291,27 @@ impl TlsCertSource {
                     let server_name = server_name.clone();
 
                     Ok(Box::pin(async move {
-                        let issued_cert = if let Some(cached_cert) = cert_cache.as_ref().and_then(|cert_cache| cert_cache.get(&host)) {
+                        let cached_cert = match &cert_cache {
+                            Some(cert_cache) => cert_cache.get_async(&host, server_auth_data_to_private_key_and_ca_chain).await,
+                            None => None,
+                        };
+                        let issued_cert = if let Some(cached_cert) = cached_cert {
                             cached_cert
                         } else {
                             let auth_data = issuer.issue_cert(rama_client_hello, server_name).await.map_err(|err| {
                                 tracing::error!("boring: dynamic cert issuer failed: {err:?}");
                                 AsyncSelectCertError{}
                             })?;
-                            server_auth_data_to_private_key_and_ca_chain(&auth_data).map_err(|err| {
+                            let issued_cert = server_auth_data_to_private_key_and_ca_chain(&auth_data).map_err(|err| {
                                 tracing::error!("boring: server_auth_data to key and ca chain failed: {err:?}");
                                 AsyncSelectCertError{}
-                            })?
+                            })?;
+                            if let Some(cert_cache) = &cert_cache {
+                                cert_cache.insert_async(host.clone(), issued_cert.clone(), move |_| Ok(auth_data)).await;
+                            }
+                            issued_cert
                         };
 
-                        if let Some(cert_cache) = cert_cache {
-                            cert_cache.insert(host.clone(), issued_cert.clone());
-                        }
-
                         let apply_cert = Box::new(move |client_hello: ClientHello<'_>| {
                             let mut client_hello = client_hello;
                             let ssl_ref = client_hello.ssl_mut();
@@ -301,17 +378,12 @@ impl TryFrom<rama_net::tls::server::ServerConfig> for TlsAcceptorData {
 
                 TlsCertSourceKind::InMemory(issued_cert)
             }
+            ServerAuth::Reloadable(auth) => {
+                TlsCertSourceKind::Reloadable(ReloadableCert::try_new(auth)?)
+            }
 
             ServerAuth::CertIssuer(data) => {
-                let cert_cache = match data.cache_kind {
-                    CacheKind::Disabled => None,
-                    CacheKind::MemCache { max_size } => Some(
-                        Cache::builder()
-                            .time_to_live(Duration::from_secs(60 * 60 * 24 * 89))
-                            .max_capacity(max_size.into())
-                            .build(),
-                    ),
-                };
+                let cert_cache = IssuedCertCache::try_from_cache_kind(data.cache_kind);
 
                 match data.kind {
                     ServerCertIssuerKind::SelfSigned(data) => {
@@ -423,6 +495,83 @@ fn server_auth_data_to_private_key_and_ca_chain(
     })
 }
 
+/// Parse the [`ServerAuthData`], validating that the private key matches the leaf cert.
+fn server_auth_data_to_reloaded_cert(data: &ServerAuthData) -> Result<ReloadedCert, OpaqueError> {
+    let issued_cert = server_auth_data_to_private_key_and_ca_chain(data)?;
+    let leaf_public_key = issued_cert
+        .cert_chain
+        .first()
+        .context("boring/TlsAcceptorData: empty cert chain")?
+        .public_key()
+        .context("boring/TlsAcceptorData: get public key of leaf cert")?;
+    if !leaf_public_key.public_eq(&issued_cert.key) {
+        return Err(OpaqueError::from_display(
+            "boring/TlsAcceptorData: private key does not match leaf cert",
+        ));
+    }
+    Ok(ReloadedCert {
+        issued_cert,
+        ocsp: data.ocsp.clone(),
+    })
+}
+
+/// Set the issued cert (chain) and private key used by all connections of the acceptor.
+fn set_issued_cert(
+    builder: &mut SslAcceptorBuilder,
+    issued_cert: &IssuedCert,
+) -> Result<(), OpaqueError> {
+    for (i, ca_cert) in issued_cert.cert_chain.iter().enumerate() {
+        if i == 0 {
+            builder
+                .set_certificate(ca_cert.as_ref())
+                .context("build boring ssl acceptor: set Leaf CA certificate (x509)")?;
+        } else {
+            builder
+                .add_extra_chain_cert(ca_cert.clone())
+                .context("build boring ssl acceptor: add extra chain certificate (x509)")?;
+        }
+    }
+    builder
+        .set_private_key(issued_cert.key.as_ref())
+        .context("build boring ssl acceptor: set private key")?;
+    builder
+        .check_private_key()
+        .context("build boring ssl acceptor: check private key")?;
+    Ok(())
+}
+
+fn issued_cert_to_server_auth_data(
+    issued_cert: &IssuedCert,
+) -> Result<ServerAuthData, OpaqueError> {
+    let private_key = issued_cert
+        .key
+        .private_key_to_pem_pkcs8()
+        .context("boring/TlsAcceptorData: encode private key as PEM")?;
+    let mut cert_chain = Vec::new();
+    for cert in &issued_cert.cert_chain {
+        cert_chain.extend(
+            cert.to_pem()
+                .context("boring/TlsAcceptorData: encode x509 cert as PEM")?,
+        );
+    }
+
+    Ok(ServerAuthData {
+        private_key: DataEncoding::Pem(
+            String::from_utf8(private_key)
+                .context("boring/TlsAcceptorData: private key PEM as utf-8")?
+                .try_into()
+                .context("boring/TlsAcceptorData: private key PEM as non-empty string")?,
+        ),
+        cert_chain: DataEncoding::Pem(
+            String::from_utf8(cert_chain)
+                .context("boring/TlsAcceptorData: cert chain PEM as utf-8")?
+                .try_into()
+                .context("boring/TlsAcceptorData: cert chain PEM as non-empty string")?,
+        ),
+        ocsp: None,
+    })
+}
+
 fn issue_cert_for_ca(
     host: Host,
     ca_cert: &X509,
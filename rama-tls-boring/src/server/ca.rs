@@ -0,0 +1,583 @@
+use crate::core::{
+    asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{Id, PKey, PKeyRef, Private},
+    rand::rand_bytes,
+    rsa::Rsa,
+    x509::{
+        X509, X509Builder, X509NameBuilder, X509NameRef, X509Ref,
+        extension::{
+            AuthorityKeyIdentifier, BasicConstraints, ExtendedKeyUsage, KeyUsage,
+            SubjectAlternativeName, SubjectKeyIdentifier,
+        },
+    },
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_core::telemetry::tracing;
+use rama_net::{
+    address::Host,
+    tls::{
+        DataEncoding,
+        client::ClientHello,
+        server::{DynamicCertIssuer, KeyAlgorithm, SelfSignedData, ServerAuthData},
+    },
+};
+use rama_utils::macros::generate_set_and_with;
+
+/// PKCS#8 (v1) header of an Ed25519 private key, to be followed by its 32 byte seed.
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Generate a new private key using the given [`KeyAlgorithm`].
+fn generate_private_key(algorithm: KeyAlgorithm) -> Result<PKey<Private>, OpaqueError> {
+    match algorithm {
+        KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096 => {
+            let bits = if algorithm == KeyAlgorithm::Rsa2048 {
+                2048
+            } else {
+                4096
+            };
+            let rsa = Rsa::generate(bits).with_context(|| format!("generate {bits} RSA key"))?;
+            PKey::from_rsa(rsa).context("create private key from RSA key")
+        }
+        KeyAlgorithm::EcdsaP256 | KeyAlgorithm::EcdsaP384 => {
+            let nid = if algorithm == KeyAlgorithm::EcdsaP256 {
+                Nid::X9_62_PRIME256V1
+            } else {
+                Nid::SECP384R1
+            };
+            let group = EcGroup::from_curve_name(nid).context("create EC group")?;
+            let ec_key = EcKey::generate(&group).context("generate EC key")?;
+            PKey::from_ec_key(ec_key).context("create private key from EC key")
+        }
+        KeyAlgorithm::Ed25519 => {
+            // boring does not expose Ed25519 key generation,
+            // so we create the key from a random seed wrapped in a PKCS#8 structure instead
+            let mut der = ED25519_PKCS8_PREFIX.to_vec();
+            let mut seed = [0; 32];
+            rand_bytes(&mut seed).context("generate random Ed25519 seed")?;
+            der.extend_from_slice(&seed);
+            PKey::private_key_from_pkcs8(&der).context("create private key from Ed25519 seed")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Certificate authority (CA) which issues leaf certificates on the fly,
+/// e.g. for each server name intercepted by a MITM proxy.
+///
+/// The root CA can be generated using [`CaCertIssuer::generate`] or
+/// loaded from PEM using [`CaCertIssuer::try_from_pem`]. Clients
+/// will only trust the issued certificates if they trust this root CA,
+/// which can be exported using [`CaCertIssuer::ca_cert_pem`].
+///
+/// It implements [`DynamicCertIssuer`] so it can be used as a
+/// [`ServerCertIssuerKind::Dynamic`] issuer by the boring [`TlsAcceptorData`].
+///
+/// [`ServerCertIssuerKind::Dynamic`]: rama_net::tls::server::ServerCertIssuerKind::Dynamic
+/// [`TlsAcceptorData`]: super::TlsAcceptorData
+pub struct CaCertIssuer {
+    ca_cert: X509,
+    ca_key: PKey<Private>,
+    key_algorithm: KeyAlgorithm,
+    validity_days: u32,
+}
+
+impl CaCertIssuer {
+    /// Default amount of days a generated root CA is valid for.
+    pub const DEFAULT_CA_VALIDITY_DAYS: u32 = 3650;
+
+    /// Default amount of days an issued leaf certificate is valid for.
+    pub const DEFAULT_VALIDITY_DAYS: u32 = 90;
+
+    /// Create a new [`CaCertIssuer`] using the given root CA certificate and its private key.
+    pub fn new(ca_cert: X509, ca_key: PKey<Private>) -> Result<Self, OpaqueError> {
+        if ca_key.id() == Id::ED25519 {
+            return Err(OpaqueError::from_display(
+                "Ed25519 keys are not supported for CA certificates",
+            ));
+        }
+        let ca_pub_key = ca_cert.public_key().context("get public key of CA cert")?;
+        if !ca_key.public_eq(&ca_pub_key) {
+            return Err(OpaqueError::from_display(
+                "CA private key does not match the CA certificate",
+            ));
+        }
+        Ok(Self {
+            ca_cert,
+            ca_key,
+            key_algorithm: KeyAlgorithm::default(),
+            validity_days: Self::DEFAULT_VALIDITY_DAYS,
+        })
+    }
+
+    /// Create a new [`CaCertIssuer`] using a newly generated root CA,
+    /// valid for [`Self::DEFAULT_CA_VALIDITY_DAYS`] days.
+    pub fn generate(
+        data: SelfSignedData,
+        key_algorithm: KeyAlgorithm,
+    ) -> Result<Self, OpaqueError> {
+        Self::generate_with_validity(data, key_algorithm, Self::DEFAULT_CA_VALIDITY_DAYS)
+    }
+
+    /// Create a new [`CaCertIssuer`] using a newly generated root CA,
+    /// valid for the given amount of days.
+    pub fn generate_with_validity(
+        data: SelfSignedData,
+        key_algorithm: KeyAlgorithm,
+        validity_days: u32,
+    ) -> Result<Self, OpaqueError> {
+        if key_algorithm == KeyAlgorithm::Ed25519 {
+            return Err(OpaqueError::from_display(
+                "Ed25519 keys are not supported for CA certificates",
+            ));
+        }
+        let ca_key = generate_private_key(key_algorithm).context("generate CA private key")?;
+        let ca_cert =
+            generate_ca_cert(&data, &ca_key, validity_days).context("generate CA cert")?;
+        Self::new(ca_cert, ca_key)
+    }
+
+    /// Create a new [`CaCertIssuer`] using a PEM-encoded root CA certificate and private key.
+    pub fn try_from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, OpaqueError> {
+        let ca_cert = X509::from_pem(cert_pem).context("parse CA cert from PEM content")?;
+        let ca_key =
+            PKey::private_key_from_pem(key_pem).context("parse CA private key from PEM content")?;
+        Self::new(ca_cert, ca_key)
+    }
+
+    generate_set_and_with! {
+        /// Set the [`KeyAlgorithm`] used for the key pair of issued leaf certificates.
+        ///
+        /// By default [`KeyAlgorithm::EcdsaP256`] is used.
+        pub fn key_algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+            self.key_algorithm = algorithm;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the amount of days issued leaf certificates are valid for.
+        ///
+        /// By default issued certificates are valid for [`Self::DEFAULT_VALIDITY_DAYS`] days.
+        pub fn validity_days(mut self, days: u32) -> Self {
+            self.validity_days = days;
+            self
+        }
+    }
+
+    /// Root CA certificate used to sign the issued certificates.
+    pub fn ca_cert(&self) -> &X509Ref {
+        &self.ca_cert
+    }
+
+    /// Private key of the root CA.
+    pub fn ca_key(&self) -> &PKeyRef<Private> {
+        &self.ca_key
+    }
+
+    /// PEM-encoded root CA certificate,
+    /// which has to be trusted by clients in order to trust the issued certificates.
+    pub fn ca_cert_pem(&self) -> Result<Vec<u8>, OpaqueError> {
+        self.ca_cert.to_pem().context("encode CA cert as PEM")
+    }
+
+    /// PEM-encoded (PKCS#8) private key of the root CA.
+    pub fn ca_key_pem(&self) -> Result<Vec<u8>, OpaqueError> {
+        self.ca_key
+            .private_key_to_pem_pkcs8()
+            .context("encode CA private key as PEM")
+    }
+
+    /// Issue a new leaf certificate for the given [`Host`],
+    /// returning the certificate chain (leaf first, root CA last) and the private key of the leaf.
+    pub fn issue(&self, host: &Host) -> Result<(Vec<X509>, PKey<Private>), OpaqueError> {
+        tracing::trace!(
+            "CaCertIssuer: issue {} cert for host {host}",
+            self.key_algorithm
+        );
+        let key = generate_private_key(self.key_algorithm).context("generate leaf private key")?;
+        let cert = generate_leaf_cert(
+            host,
+            &key,
+            self.key_algorithm,
+            &self.ca_cert,
+            &self.ca_key,
+            self.validity_days,
+        )
+        .with_context(|| format!("generate leaf cert for host {host}"))?;
+        Ok((vec![cert, self.ca_cert.clone()], key))
+    }
+
+    /// Same as [`Self::issue`], but returning the issued data as PEM-encoded [`ServerAuthData`].
+    pub fn issue_server_auth_data(&self, host: &Host) -> Result<ServerAuthData, OpaqueError> {
+        let (cert_chain, key) = self.issue(host)?;
+
+        let mut cert_chain_pem = Vec::new();
+        for cert in cert_chain {
+            cert_chain_pem.extend(cert.to_pem().context("encode issued cert as PEM")?);
+        }
+        let key_pem = key
+            .private_key_to_pem_pkcs8()
+            .context("encode issued private key as PEM")?;
+
+        Ok(ServerAuthData {
+            private_key: DataEncoding::Pem(
+                String::from_utf8(key_pem)
+                    .context("PEM-encoded private key as utf-8")?
+                    .try_into()
+                    .context("PEM-encoded private key as non-empty string")?,
+            ),
+            cert_chain: DataEncoding::Pem(
+                String::from_utf8(cert_chain_pem)
+                    .context("PEM-encoded cert chain as utf-8")?
+                    .try_into()
+                    .context("PEM-encoded cert chain as non-empty string")?,
+            ),
+            ocsp: None,
+        })
+    }
+}
+
+impl DynamicCertIssuer for CaCertIssuer {
+    async fn issue_cert(
+        &self,
+        client_hello: ClientHello,
+        server_name: Option<Host>,
+    ) -> Result<ServerAuthData, OpaqueError> {
+        let host = client_hello
+            .ext_server_name()
+            .cloned()
+            .map(Host::Name)
+            .or(server_name)
+            .context("CaCertIssuer: no server name available to issue cert for")?;
+        // key generation (e.g. RSA) can take a while, so keep it off the async runtime
+        let issuer = self.clone();
+        tokio::task::spawn_blocking(move || issuer.issue_server_auth_data(&host))
+            .await
+            .context("CaCertIssuer: join issue cert task")?
+    }
+}
+
+fn message_digest_for_key(key: &PKeyRef<Private>) -> MessageDigest {
+    if key.id() == Id::EC && key.bits() > 256 {
+        MessageDigest::sha384()
+    } else {
+        MessageDigest::sha256()
+    }
+}
+
+fn random_serial_number(cert_builder: &mut X509Builder) -> Result<(), OpaqueError> {
+    let mut serial = BigNum::new().context("x509 cert builder: create big num (serial)")?;
+    serial
+        .rand(159, MsbOption::MAYBE_ZERO, false)
+        .context("x509 cert builder: randomise serial number (big num)")?;
+    let serial = serial
+        .to_asn1_integer()
+        .context("x509 cert builder: convert serial to ASN1 integer")?;
+    cert_builder
+        .set_serial_number(&serial)
+        .context("x509 cert builder: set serial number")
+}
+
+fn set_validity(cert_builder: &mut X509Builder, validity_days: u32) -> Result<(), OpaqueError> {
+    // backdate a day to be lenient towards clients with a clock that is behind
+    let not_before = Asn1Time::from_unix(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .context("x509 cert builder: get unix time")?
+            .as_secs()
+            .saturating_sub(24 * 60 * 60) as _,
+    )
+    .context("x509 cert builder: create ASN1Time for yesterday")?;
+    cert_builder
+        .set_not_before(&not_before)
+        .context("x509 cert builder: set not before to yesterday")?;
+    let not_after = Asn1Time::days_from_now(validity_days)
+        .context("x509 cert builder: create ASN1Time for not after")?;
+    cert_builder
+        .set_not_after(&not_after)
+        .context("x509 cert builder: set not after")
+}
+
+fn generate_ca_cert(
+    data: &SelfSignedData,
+    ca_key: &PKey<Private>,
+    validity_days: u32,
+) -> Result<X509, OpaqueError> {
+    let mut x509_name = X509NameBuilder::new().context("create x509 name builder")?;
+    x509_name
+        .append_entry_by_nid(
+            Nid::ORGANIZATIONNAME,
+            data.organisation_name.as_deref().unwrap_or("Anonymous"),
+        )
+        .context("append organisation name to x509 name builder")?;
+    let common_name = data
+        .common_name
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "rama CA".to_owned());
+    x509_name
+        .append_entry_by_nid(Nid::COMMONNAME, &common_name)
+        .context("append common name to x509 name builder")?;
+    let x509_name = x509_name.build();
+
+    let mut cert_builder = X509::builder().context("create x509 (cert) builder")?;
+    cert_builder
+        .set_version(2)
+        .context("x509 cert builder: set version = 2")?;
+    random_serial_number(&mut cert_builder)?;
+    cert_builder
+        .set_subject_name(&x509_name)
+        .context("x509 cert builder: set subject name")?;
+    cert_builder
+        .set_issuer_name(&x509_name)
+        .context("x509 cert builder: set issuer (self-signed)")?;
+    cert_builder
+        .set_pubkey(ca_key)
+        .context("x509 cert builder: set public key using private key (ref)")?;
+    set_validity(&mut cert_builder, validity_days)?;
+
+    cert_builder
+        .append_extension(
+            BasicConstraints::new()
+                .critical()
+                .ca()
+                .build()
+                .context("x509 cert builder: build basic constraints")?,
+        )
+        .context("x509 cert builder: add basic constraints as x509 extension")?;
+    cert_builder
+        .append_extension(
+            KeyUsage::new()
+                .critical()
+                .key_cert_sign()
+                .crl_sign()
+                .digital_signature()
+                .build()
+                .context("x509 cert builder: create key usage")?,
+        )
+        .context("x509 cert builder: add key usage x509 extension")?;
+
+    let subject_key_identifier = SubjectKeyIdentifier::new()
+        .build(&cert_builder.x509v3_context(None, None))
+        .context("x509 cert builder: build subject key id")?;
+    cert_builder
+        .append_extension(subject_key_identifier)
+        .context("x509 cert builder: add subject key id x509 extension")?;
+
+    cert_builder
+        .sign(ca_key, message_digest_for_key(ca_key))
+        .context("x509 cert builder: sign cert")?;
+
+    Ok(cert_builder.build())
+}
+
+fn generate_leaf_cert(
+    host: &Host,
+    key: &PKey<Private>,
+    key_algorithm: KeyAlgorithm,
+    ca_cert: &X509Ref,
+    ca_key: &PKey<Private>,
+    validity_days: u32,
+) -> Result<X509, OpaqueError> {
+    let mut x509_name = X509NameBuilder::new().context("create x509 name builder")?;
+    if let Some(organisation_name) = ca_organisation_name(ca_cert.subject_name()) {
+        x509_name
+            .append_entry_by_nid(Nid::ORGANIZATIONNAME, &organisation_name)
+            .context("append organisation name to x509 name builder")?;
+    }
+    x509_name
+        .append_entry_by_nid(Nid::COMMONNAME, host.to_string().as_str())
+        .context("append common name to x509 name builder")?;
+    let x509_name = x509_name.build();
+
+    let mut cert_builder = X509::builder().context("create x509 (cert) builder")?;
+    cert_builder
+        .set_version(2)
+        .context("x509 cert builder: set version = 2")?;
+    random_serial_number(&mut cert_builder)?;
+    cert_builder
+        .set_issuer_name(ca_cert.subject_name())
+        .context("x509 cert builder: set issuer name")?;
+    cert_builder
+        .set_subject_name(&x509_name)
+        .context("x509 cert builder: set subject name")?;
+    cert_builder
+        .set_pubkey(key)
+        .context("x509 cert builder: set public key using private key (ref)")?;
+    set_validity(&mut cert_builder, validity_days)?;
+
+    cert_builder
+        .append_extension(
+            BasicConstraints::new()
+                .critical()
+                .build()
+                .context("x509 cert builder: build basic constraints")?,
+        )
+        .context("x509 cert builder: add basic constraints as x509 extension")?;
+
+    let mut key_usage = KeyUsage::new();
+    key_usage.critical().digital_signature();
+    if matches!(key_algorithm, KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096) {
+        key_usage.key_encipherment();
+    }
+    cert_builder
+        .append_extension(
+            key_usage
+                .build()
+                .context("x509 cert builder: create key usage")?,
+        )
+        .context("x509 cert builder: add key usage x509 extension")?;
+    cert_builder
+        .append_extension(
+            ExtendedKeyUsage::new()
+                .server_auth()
+                .build()
+                .context("x509 cert builder: create extended key usage")?,
+        )
+        .context("x509 cert builder: add extended key usage x509 extension")?;
+
+    let mut subject_alt_name = SubjectAlternativeName::new();
+    match host {
+        Host::Name(domain) => {
+            subject_alt_name.dns(domain.as_str());
+        }
+        Host::Address(addr) => {
+            subject_alt_name.ip(addr.to_string().as_str());
+        }
+    }
+    let subject_alt_name = subject_alt_name
+        .build(&cert_builder.x509v3_context(Some(ca_cert), None))
+        .context("x509 cert builder: build subject alt name")?;
+    cert_builder
+        .append_extension(subject_alt_name)
+        .context("x509 cert builder: add subject alt name")?;
+
+    let subject_key_identifier = SubjectKeyIdentifier::new()
+        .build(&cert_builder.x509v3_context(Some(ca_cert), None))
+        .context("x509 cert builder: build subject key id")?;
+    cert_builder
+        .append_extension(subject_key_identifier)
+        .context("x509 cert builder: add subject key id x509 extension")?;
+
+    let auth_key_identifier = AuthorityKeyIdentifier::new()
+        .keyid(false)
+        .issuer(false)
+        .build(&cert_builder.x509v3_context(Some(ca_cert), None))
+        .context("x509 cert builder: build auth key id")?;
+    cert_builder
+        .append_extension(auth_key_identifier)
+        .context("x509 cert builder: set auth key id extension")?;
+
+    cert_builder
+        .sign(ca_key, message_digest_for_key(ca_key))
+        .context("x509 cert builder: sign cert")?;
+
+    Ok(cert_builder.build())
+}
+
+fn ca_organisation_name(name: &X509NameRef) -> Option<String> {
+    name.entries_by_nid(Nid::ORGANIZATIONNAME)
+        .next()
+        .and_then(|entry| entry.data().as_utf8().ok())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::address::Domain;
+
+    fn test_issuer() -> CaCertIssuer {
+        CaCertIssuer::generate(
+            SelfSignedData {
+                organisation_name: Some("rama test".to_owned()),
+                ..Default::default()
+            },
+            KeyAlgorithm::EcdsaP256,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_issue_leaf_cert() {
+        let issuer = test_issuer();
+        let host = Host::Name(Domain::from_static("example.com"));
+
+        for algorithm in [
+            KeyAlgorithm::Rsa2048,
+            KeyAlgorithm::EcdsaP256,
+            KeyAlgorithm::EcdsaP384,
+            KeyAlgorithm::Ed25519,
+        ] {
+            let issuer = issuer.clone().with_key_algorithm(algorithm);
+            let (cert_chain, key) = issuer.issue(&host).unwrap();
+            assert_eq!(2, cert_chain.len());
+
+            let leaf = &cert_chain[0];
+            assert!(key.public_eq(&leaf.public_key().unwrap()));
+            assert!(leaf.verify(issuer.ca_key()).unwrap());
+            assert_eq!(
+                Some("example.com"),
+                leaf.subject_alt_names()
+                    .unwrap()
+                    .iter()
+                    .next()
+                    .and_then(|name| name.dnsname())
+            );
+            assert_eq!(
+                Some("rama test".to_owned()),
+                ca_organisation_name(leaf.subject_name())
+            );
+        }
+    }
+
+    #[test]
+    fn test_ca_pem_roundtrip() {
+        let issuer = test_issuer();
+        let loaded = CaCertIssuer::try_from_pem(
+            &issuer.ca_cert_pem().unwrap(),
+            &issuer.ca_key_pem().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            issuer.ca_cert().to_der().unwrap(),
+            loaded.ca_cert().to_der().unwrap()
+        );
+
+        let other = test_issuer();
+        assert!(
+            CaCertIssuer::try_from_pem(
+                &issuer.ca_cert_pem().unwrap(),
+                &other.ca_key_pem().unwrap()
+            )
+            .is_err()
+        );
+
+        assert!(CaCertIssuer::generate(SelfSignedData::default(), KeyAlgorithm::Ed25519).is_err());
+    }
+
+    #[test]
+    fn test_issue_server_auth_data() {
+        let issuer = test_issuer();
+        let data = issuer
+            .issue_server_auth_data(&Host::Address([127, 0, 0, 1].into()))
+            .unwrap();
+        let DataEncoding::Pem(cert_chain) = data.cert_chain else {
+            panic!("expected PEM cert chain");
+        };
+        assert_eq!(
+            2,
+            X509::stack_from_pem(cert_chain.as_bytes()).unwrap().len()
+        );
+        let DataEncoding::Pem(key) = data.private_key else {
+            panic!("expected PEM private key");
+        };
+        PKey::private_key_from_pem(key.as_bytes()).unwrap();
+    }
+}
@@ -21,3 +21,7 @@ pub use service::TlsAcceptorService;
 mod layer;
 #[doc(inline)]
 pub use layer::TlsAcceptorLayer;
+
+mod ca;
+#[doc(inline)]
+pub use ca::CaCertIssuer;
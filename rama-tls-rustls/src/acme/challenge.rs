@@ -0,0 +1,292 @@
+use crate::dep::pki_types::PrivatePkcs8KeyDer;
+use crate::dep::rcgen;
+use crate::dep::rustls::sign::CertifiedKey;
+use aws_lc_rs::digest;
+use rama_core::{
+    Context, Service,
+    error::{ErrorContext, OpaqueError},
+};
+use rama_http_types::{Body, Method, Request, Response, StatusCode, header};
+use rama_net::address::Domain;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, RwLock},
+};
+
+/// Path prefix under which HTTP-01 challenges are served.
+pub const HTTP01_CHALLENGE_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// The type of ACME challenge used to prove control over a domain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ChallengeType {
+    #[default]
+    /// HTTP-01 challenge, served over plain text http on port 80
+    /// by the [`Http01ChallengeService`].
+    Http01,
+    /// TLS-ALPN-01 challenge, served by the tls acceptor on port 443
+    /// using the [`CertIssuerConfigProvider`].
+    ///
+    /// [`CertIssuerConfigProvider`]: crate::server::CertIssuerConfigProvider
+    TlsAlpn01,
+}
+
+impl ChallengeType {
+    /// The ACME identifier of the challenge type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http01 => "http-01",
+            Self::TlsAlpn01 => "tls-alpn-01",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Pending ACME challenges, shared between the [`AcmeIssuer`]
+/// which creates them and the services which respond to them.
+///
+/// [`AcmeIssuer`]: super::AcmeIssuer
+pub struct AcmeChallenges {
+    inner: Arc<RwLock<AcmeChallengesInner>>,
+}
+
+#[derive(Debug, Default)]
+struct AcmeChallengesInner {
+    http01: HashMap<String, String>,
+    /// certs of the pending challenges per domain, keyed by their token,
+    /// as multiple authorizations can be pending for the same domain
+    tls_alpn01: HashMap<Domain, Vec<(String, Arc<CertifiedKey>)>>,
+}
+
+impl AcmeChallenges {
+    /// Create a new (empty) [`AcmeChallenges`] store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Key authorization for the pending HTTP-01 challenge with the given token.
+    pub fn http01_key_authorization(&self, token: &str) -> Option<String> {
+        self.inner
+            .read()
+            .ok()
+            .and_then(|inner| inner.http01.get(token).cloned())
+    }
+
+    /// Certificate for the pending TLS-ALPN-01 challenge of the given domain,
+    /// the most recent one in case multiple challenges are pending for it.
+    pub fn tls_alpn01_cert(&self, domain: &Domain) -> Option<Arc<CertifiedKey>> {
+        self.inner.read().ok().and_then(|inner| {
+            inner
+                .tls_alpn01
+                .get(domain)
+                .and_then(|pending| pending.last())
+                .map(|(_, cert)| cert.clone())
+        })
+    }
+
+    pub(crate) fn insert_http01(&self, token: String, key_authorization: String) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.http01.insert(token, key_authorization);
+        }
+    }
+
+    pub(crate) fn remove_http01(&self, token: &str) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.http01.remove(token);
+        }
+    }
+
+    pub(crate) fn insert_tls_alpn01(
+        &self,
+        domain: Domain,
+        token: String,
+        key_authorization: &str,
+    ) -> Result<(), OpaqueError> {
+        let cert = Arc::new(tls_alpn01_cert(&domain, key_authorization)?);
+        if let Ok(mut inner) = self.inner.write() {
+            let pending = inner.tls_alpn01.entry(domain).or_default();
+            pending.retain(|(pending_token, _)| *pending_token != token);
+            pending.push((token, cert));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove_tls_alpn01(&self, domain: &Domain, token: &str) {
+        if let Ok(mut inner) = self.inner.write() {
+            if let Some(pending) = inner.tls_alpn01.get_mut(domain) {
+                pending.retain(|(pending_token, _)| pending_token != token);
+                if pending.is_empty() {
+                    inner.tls_alpn01.remove(domain);
+                }
+            }
+        }
+    }
+}
+
+/// Create the self-signed certificate used to respond to a TLS-ALPN-01 challenge (RFC 8737),
+/// which contains the digest of the key authorization in the critical `acmeIdentifier` extension.
+fn tls_alpn01_cert(domain: &Domain, key_authorization: &str) -> Result<CertifiedKey, OpaqueError> {
+    let key_pair = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)
+        .context("acme: tls-alpn-01: generate key pair")?;
+    let mut params = rcgen::CertificateParams::new(vec![domain.as_str().to_owned()])
+        .context("acme: tls-alpn-01: create cert params")?;
+    let key_authorization_digest = digest::digest(&digest::SHA256, key_authorization.as_bytes());
+    params.custom_extensions = vec![rcgen::CustomExtension::new_acme_identifier(
+        key_authorization_digest.as_ref(),
+    )];
+    let cert = params
+        .self_signed(&key_pair)
+        .context("acme: tls-alpn-01: create self-signed cert")?;
+
+    // not using `CertifiedKey::from_der`, as its key check rejects the critical acme extension
+    let signing_key = crate::dep::rustls::crypto::aws_lc_rs::default_provider()
+        .key_provider
+        .load_private_key(PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into())
+        .context("acme: tls-alpn-01: load private key")?;
+    Ok(CertifiedKey::new(vec![cert.der().clone()], signing_key))
+}
+
+#[derive(Debug, Clone)]
+/// Service which responds to pending HTTP-01 challenges,
+/// served under [`HTTP01_CHALLENGE_PATH_PREFIX`].
+///
+/// Requests for unknown tokens (or other paths) are responded to with a `404 Not Found`,
+/// use a router or matcher in case this service has to be combined with other services.
+pub struct Http01ChallengeService {
+    challenges: AcmeChallenges,
+}
+
+impl Http01ChallengeService {
+    /// Create a new [`Http01ChallengeService`] for the given [`AcmeChallenges`].
+    pub fn new(challenges: AcmeChallenges) -> Self {
+        Self { challenges }
+    }
+}
+
+impl<State, ReqBody> Service<State, Request<ReqBody>> for Http01ChallengeService
+where
+    State: Clone + Send + Sync + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let key_authorization = if req.method() == Method::GET {
+            req.uri()
+                .path()
+                .strip_prefix(HTTP01_CHALLENGE_PATH_PREFIX)
+                .and_then(|token| self.challenges.http01_key_authorization(token))
+        } else {
+            None
+        };
+
+        let resp = match key_authorization {
+            Some(key_authorization) => {
+                let mut resp = Response::new(Body::from(key_authorization));
+                resp.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static("application/octet-stream"),
+                );
+                resp
+            }
+            None => {
+                let mut resp = Response::new(Body::empty());
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                resp
+            }
+        };
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::BodyExtractExt;
+
+    #[tokio::test]
+    async fn test_http01_challenge_service() {
+        let challenges = AcmeChallenges::new();
+        let svc = Http01ChallengeService::new(challenges.clone());
+
+        let request = |path: &str| {
+            Request::builder()
+                .uri(format!("http://example.com{path}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let resp = svc
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        challenges.insert_http01("token".to_owned(), "token.thumbprint".to_owned());
+        let resp = svc
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.try_into_string().await.unwrap(), "token.thumbprint");
+
+        let resp = svc
+            .serve(Context::default(), request("/token"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        challenges.remove_http01("token");
+        let resp = svc
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_tls_alpn01_cert() {
+        let challenges = AcmeChallenges::new();
+        let domain = Domain::from_static("example.com");
+        assert!(challenges.tls_alpn01_cert(&domain).is_none());
+
+        challenges
+            .insert_tls_alpn01(domain.clone(), "token".to_owned(), "token.thumbprint")
+            .unwrap();
+        let cert = challenges.tls_alpn01_cert(&domain).unwrap();
+        let digest = digest::digest(&digest::SHA256, b"token.thumbprint");
+        let der = cert.end_entity_cert().unwrap().as_ref();
+        assert!(der.windows(32).any(|window| window == digest.as_ref()));
+
+        // a concurrent challenge for the same domain only removes its own entry
+        challenges
+            .insert_tls_alpn01(domain.clone(), "other".to_owned(), "other.thumbprint")
+            .unwrap();
+        let other_cert = challenges.tls_alpn01_cert(&domain).unwrap();
+        assert!(!Arc::ptr_eq(&cert, &other_cert));
+        challenges.remove_tls_alpn01(&domain, "other");
+        assert!(Arc::ptr_eq(
+            &cert,
+            &challenges.tls_alpn01_cert(&domain).unwrap()
+        ));
+
+        challenges.remove_tls_alpn01(&domain, "unknown");
+        assert!(challenges.tls_alpn01_cert(&domain).is_some());
+        challenges.remove_tls_alpn01(&domain, "token");
+        assert!(challenges.tls_alpn01_cert(&domain).is_none());
+    }
+}
@@ -0,0 +1,1339 @@
+use super::account::AccountKey;
+use super::challenge::{AcmeChallenges, ChallengeType};
+use super::proto::{
+    Authorization, Directory, Identifier, Order, PROBLEM_BAD_NONCE, Problem, Status,
+};
+use crate::dep::rcgen;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rama_core::error::{BoxError, ErrorContext, ErrorExt, OpaqueError};
+use rama_core::rt::Executor;
+use rama_core::telemetry::tracing;
+use rama_core::{Context, Service};
+use rama_http_types::dep::http_body_util::BodyExt;
+use rama_http_types::{Body, HeaderMap, Method, Request, Response, StatusCode, header};
+use rama_net::address::{Domain, Host};
+use rama_net::tls::DataEncoding;
+use rama_net::tls::client::ClientHello;
+use rama_net::tls::server::{DynamicCertCache, DynamicCertIssuer, ServerAuthData};
+use rama_utils::macros::generate_set_and_with;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+/// Directory url of the Let's Encrypt production environment.
+pub const LETS_ENCRYPT_PRODUCTION_DIRECTORY: &str =
+    "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Directory url of the Let's Encrypt staging environment,
+/// which has far more generous rate limits but issues untrusted certificates.
+pub const LETS_ENCRYPT_STAGING_DIRECTORY: &str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+const REPLAY_NONCE: &str = "replay-nonce";
+
+/// Max interval after which a failed order is retried,
+/// unless a larger [`AcmeIssuer::with_retry_interval`] is configured.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+/// [`DynamicCertIssuer`] which obtains certificates from an ACME (RFC 8555) server,
+/// such as Let's Encrypt, for the server name (SNI) of each client hello.
+///
+/// Issued certificates are kept (in memory) by the issuer itself, and renewed in the background
+/// once 2/3 of their validity has passed. The current certificate keeps being served
+/// while the renewal is in progress, or in case it failed.
+/// Concurrent handshakes for a domain without certificate all wait on the same order.
+///
+/// A failed order for a domain is not retried before its retry interval passed
+/// (see [`AcmeIssuer::with_retry_interval`]), handshakes for a domain without
+/// certificate fail right away in the meantime. This, together with
+/// [`AcmeIssuer::with_allowed_domains`], protects the rate limits of the ACME server.
+///
+/// Orders run in the background using the configured [`Executor`],
+/// use [`Executor::graceful`] to have pending orders awaited on shutdown.
+///
+/// As the renewed certificates are only returned by the issuer, use [`CacheKind::Disabled`]
+/// for the [`CertIssuerConfigProvider`] using it, and [`AcmeIssuer::with_cert_cache`]
+/// (e.g. with a [`DiskCertCache`]) to keep the certificates across restarts.
+///
+/// The ACME account is registered on first use, agreeing to the terms of service of the CA.
+///
+/// [`CacheKind::Disabled`]: rama_net::tls::server::CacheKind::Disabled
+/// [`CertIssuerConfigProvider`]: crate::server::CertIssuerConfigProvider
+/// [`DiskCertCache`]: rama_net::tls::server::DiskCertCache
+pub struct AcmeIssuer<S> {
+    directory_url: String,
+    http_client: S,
+    account_key: AccountKey,
+    contact: Vec<String>,
+    challenge_type: ChallengeType,
+    challenges: AcmeChallenges,
+    allowed_domains: Option<Vec<Domain>>,
+    poll_interval: Duration,
+    poll_attempts: usize,
+    cert_cache: Option<DynamicCertCache>,
+    retry_interval: Duration,
+    executor: Executor,
+    state: Arc<Mutex<AcmeState>>,
+}
+
+#[derive(Debug, Default)]
+/// State shared between all clones of an [`AcmeIssuer`].
+struct AcmeState {
+    directory: Option<Directory>,
+    account_url: Option<String>,
+    nonce: Option<String>,
+    certs: HashMap<Domain, AcmeCert>,
+    /// pending orders, resolved with the result of the order once completed
+    orders: HashMap<Domain, watch::Receiver<Option<OrderResult>>>,
+    /// last failed order per domain, which is not retried before its retry time
+    failed_orders: HashMap<Domain, FailedOrder>,
+}
+
+type OrderResult = Result<ServerAuthData, Arc<OpaqueError>>;
+
+#[derive(Debug)]
+/// Consecutive failed orders for a domain.
+struct FailedOrder {
+    failures: u32,
+    retry_at: SystemTime,
+    error: Arc<OpaqueError>,
+}
+
+#[derive(Debug)]
+/// Certificate issued by the ACME server.
+struct AcmeCert {
+    data: ServerAuthData,
+    renew_at: SystemTime,
+    expires_at: SystemTime,
+}
+
+impl AcmeCert {
+    fn try_new(data: ServerAuthData) -> Result<Self, OpaqueError> {
+        let (not_before, not_after) = data
+            .leaf_cert_validity()
+            .context("acme: parse validity of issued cert")?;
+        let validity = not_after.duration_since(not_before).unwrap_or_default();
+        Ok(Self {
+            data,
+            renew_at: not_before + validity / 3 * 2,
+            expires_at: not_after,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// Fully collected response of the ACME server.
+struct AcmeResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl AcmeResponse {
+    fn json<T: DeserializeOwned>(&self) -> Result<T, OpaqueError> {
+        serde_json::from_slice(&self.body).context("acme: decode json response")
+    }
+
+    fn replay_nonce(&self) -> Option<String> {
+        self.headers
+            .get(REPLAY_NONCE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned)
+    }
+
+    fn location(&self) -> Result<String, OpaqueError> {
+        self.headers
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned)
+            .context("acme: missing Location header in response")
+    }
+}
+
+impl<S> AcmeIssuer<S> {
+    /// Create a new [`AcmeIssuer`] for the ACME server with the given directory url,
+    /// e.g. [`LETS_ENCRYPT_PRODUCTION_DIRECTORY`].
+    ///
+    /// The http client is used for all requests made to the ACME server.
+    pub fn new(directory_url: impl Into<String>, http_client: S, account_key: AccountKey) -> Self {
+        Self {
+            directory_url: directory_url.into(),
+            http_client,
+            account_key,
+            contact: Vec::new(),
+            challenge_type: ChallengeType::default(),
+            challenges: AcmeChallenges::new(),
+            allowed_domains: None,
+            poll_interval: Duration::from_secs(2),
+            poll_attempts: 30,
+            cert_cache: None,
+            retry_interval: Duration::from_secs(5 * 60),
+            executor: Executor::default(),
+            state: Arc::new(Mutex::new(AcmeState::default())),
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the contact urls (e.g. `mailto:admin@example.com`) of the ACME account.
+        pub fn contact(mut self, contact: Vec<String>) -> Self {
+            self.contact = contact;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`ChallengeType`] used to prove control over the domains.
+        pub fn challenge_type(mut self, challenge_type: ChallengeType) -> Self {
+            self.challenge_type = challenge_type;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`AcmeChallenges`] store in which pending challenges are registered,
+        /// in case it has to be shared with services created before this issuer.
+        pub fn challenges(mut self, challenges: AcmeChallenges) -> Self {
+            self.challenges = challenges;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Only issue certificates for the given domains (and their subdomains).
+        ///
+        /// Without this restriction any server name sent by a client is requested,
+        /// which allows clients to exhaust the rate limits of the ACME server.
+        pub fn allowed_domains(mut self, domains: Vec<Domain>) -> Self {
+            self.allowed_domains = Some(domains);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the interval at which pending authorizations and orders are polled.
+        pub fn poll_interval(mut self, interval: Duration) -> Self {
+            self.poll_interval = interval;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the max amount of times pending authorizations and orders are polled.
+        pub fn poll_attempts(mut self, attempts: usize) -> Self {
+            self.poll_attempts = attempts;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Persist the issued certificates in the given cache (e.g. a [`DiskCertCache`]),
+        /// from which they are loaded (and renewed when due) after a restart.
+        ///
+        /// [`DiskCertCache`]: rama_net::tls::server::DiskCertCache
+        pub fn cert_cache(mut self, cache: DynamicCertCache) -> Self {
+            self.cert_cache = Some(cache);
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the interval after which a failed order for a domain is retried
+        /// (5 minutes by default), doubled for each consecutive failure up to an hour.
+        pub fn retry_interval(mut self, interval: Duration) -> Self {
+            self.retry_interval = interval;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`Executor`] used to run the orders in the background.
+        pub fn executor(mut self, executor: Executor) -> Self {
+            self.executor = executor;
+            self
+        }
+    }
+
+    /// [`AcmeChallenges`] in which the pending challenges of this issuer are registered.
+    ///
+    /// Pass it to an [`Http01ChallengeService`] for [`ChallengeType::Http01`] or to the
+    /// [`CertIssuerConfigProvider`] for [`ChallengeType::TlsAlpn01`].
+    ///
+    /// [`Http01ChallengeService`]: super::Http01ChallengeService
+    /// [`CertIssuerConfigProvider`]: crate::server::CertIssuerConfigProvider
+    pub fn acme_challenges(&self) -> &AcmeChallenges {
+        &self.challenges
+    }
+
+    /// [`AccountKey`] of the ACME account used by this issuer.
+    pub fn account_key(&self) -> &AccountKey {
+        &self.account_key
+    }
+
+    fn is_allowed(&self, domain: &Domain) -> bool {
+        self.allowed_domains.as_ref().is_none_or(|allowed| {
+            allowed
+                .iter()
+                .any(|parent| parent == domain || parent.is_parent_of(domain))
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, AcmeState> {
+        // state is always left consistent, so a poisoned lock can be recovered
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S> AcmeIssuer<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    /// Order a certificate for the given domains, proving control over each of them
+    /// using the configured [`ChallengeType`].
+    pub async fn issue(&self, domains: &[Domain]) -> Result<ServerAuthData, OpaqueError> {
+        if domains.is_empty() {
+            return Err(OpaqueError::from_display(
+                "acme: no domains to order a cert for",
+            ));
+        }
+
+        let directory = self.directory().await?;
+        let identifiers: Vec<_> = domains
+            .iter()
+            .map(|domain| Identifier::dns(domain.as_str()))
+            .collect();
+        let resp = self
+            .post(
+                &directory.new_order,
+                Some(&json!({ "identifiers": identifiers })),
+            )
+            .await
+            .context("acme: create order")?;
+        let order_url = resp.location()?;
+        let order: Order = resp.json()?;
+
+        for authorization_url in &order.authorizations {
+            self.authorize(authorization_url).await?;
+        }
+
+        let order: Order = self
+            .poll(&order_url, |order: &Order| order.status != Status::Pending)
+            .await?;
+        if order.status != Status::Ready {
+            return Err(order_error(
+                &order,
+                "acme: order is not ready to be finalized",
+            ));
+        }
+
+        let key_pair = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)
+            .context("acme: generate cert key pair")?;
+        let mut params = rcgen::CertificateParams::new(
+            domains
+                .iter()
+                .map(|domain| domain.as_str().to_owned())
+                .collect::<Vec<_>>(),
+        )
+        .context("acme: create csr params")?;
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        let csr = params
+            .serialize_request(&key_pair)
+            .context("acme: create csr")?;
+        self.post(
+            &order.finalize,
+            Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })),
+        )
+        .await
+        .context("acme: finalize order")?;
+
+        let order: Order = self
+            .poll(&order_url, |order: &Order| {
+                !matches!(order.status, Status::Ready | Status::Processing)
+            })
+            .await?;
+        if order.status != Status::Valid {
+            return Err(order_error(&order, "acme: order is not valid"));
+        }
+        let certificate_url = order
+            .certificate
+            .context("acme: missing certificate url in valid order")?;
+        let resp = self
+            .post(&certificate_url, None)
+            .await
+            .context("acme: download certificate")?;
+        let cert_chain =
+            String::from_utf8(resp.body).context("acme: decode certificate chain as PEM")?;
+
+        Ok(ServerAuthData {
+            private_key: DataEncoding::Pem(
+                key_pair
+                    .serialize_pem()
+                    .try_into()
+                    .context("acme: encode private key as PEM")?,
+            ),
+            cert_chain: DataEncoding::Pem(
+                cert_chain
+                    .try_into()
+                    .context("acme: empty certificate chain")?,
+            ),
+            ocsp: None,
+        })
+    }
+
+    /// Complete the given authorization, in case it is still pending.
+    async fn authorize(&self, url: &str) -> Result<(), OpaqueError> {
+        let authorization: Authorization = self
+            .post(url, None)
+            .await
+            .context("acme: get authorization")?
+            .json()?;
+        match authorization.status {
+            Status::Valid => return Ok(()),
+            Status::Pending => (),
+            status => {
+                return Err(OpaqueError::from_display(format!(
+                    "acme: authorization for {} has unexpected status {status:?}",
+                    authorization.identifier.value,
+                )));
+            }
+        }
+
+        let domain: Domain = authorization
+            .identifier
+            .value
+            .parse()
+            .context("acme: parse authorization identifier as domain")?;
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.kind == self.challenge_type.as_str())
+            .with_context(|| {
+                format!(
+                    "acme: challenge {} not offered for {domain}",
+                    self.challenge_type.as_str()
+                )
+            })?;
+        let token = challenge
+            .token
+            .as_deref()
+            .context("acme: missing challenge token")?;
+        let key_authorization = self.account_key.key_authorization(token);
+
+        match self.challenge_type {
+            ChallengeType::Http01 => self
+                .challenges
+                .insert_http01(token.to_owned(), key_authorization),
+            ChallengeType::TlsAlpn01 => self.challenges.insert_tls_alpn01(
+                domain.clone(),
+                token.to_owned(),
+                &key_authorization,
+            )?,
+        }
+
+        let result = async {
+            self.post(&challenge.url, Some(&json!({})))
+                .await
+                .context("acme: respond to challenge")?;
+            self.poll(url, |authorization: &Authorization| {
+                authorization.status != Status::Pending
+            })
+            .await
+        }
+        .await;
+
+        match self.challenge_type {
+            ChallengeType::Http01 => self.challenges.remove_http01(token),
+            ChallengeType::TlsAlpn01 => self.challenges.remove_tls_alpn01(&domain, token),
+        }
+
+        let authorization = result?;
+        if authorization.status == Status::Valid {
+            return Ok(());
+        }
+        let problem = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.kind == self.challenge_type.as_str())
+            .and_then(|challenge| challenge.error.clone())
+            .unwrap_or_default();
+        Err(OpaqueError::from_std(problem).context(format!(
+            "acme: authorization for {domain} failed with status {:?}",
+            authorization.status
+        )))
+    }
+
+    /// Poll the resource at the given url until it is done.
+    async fn poll<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        done: impl Fn(&T) -> bool,
+    ) -> Result<T, OpaqueError> {
+        for _ in 0..self.poll_attempts {
+            let value: T = self
+                .post(url, None)
+                .await
+                .context("acme: poll resource")?
+                .json()?;
+            if done(&value) {
+                return Ok(value);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+        Err(OpaqueError::from_display(format!(
+            "acme: resource {url} still pending after {} attempts",
+            self.poll_attempts
+        )))
+    }
+
+    async fn directory(&self) -> Result<Directory, OpaqueError> {
+        if let Some(directory) = self.state().directory.clone() {
+            return Ok(directory);
+        }
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(&self.directory_url)
+            .body(Body::empty())
+            .context("acme: create directory request")?;
+        let resp = self.send(req).await?;
+        if !resp.status.is_success() {
+            return Err(response_error(&resp).context("acme: get directory"));
+        }
+        let directory: Directory = resp.json()?;
+        self.state().directory = Some(directory.clone());
+        Ok(directory)
+    }
+
+    async fn nonce(&self) -> Result<String, OpaqueError> {
+        if let Some(nonce) = self.state().nonce.take() {
+            return Ok(nonce);
+        }
+
+        // the fresh nonce is returned as is, rather than shared through the state,
+        // such that concurrent requests cannot take each other's nonce
+        let directory = self.directory().await?;
+        let req = Request::builder()
+            .method(Method::HEAD)
+            .uri(&directory.new_nonce)
+            .body(Body::empty())
+            .context("acme: create new nonce request")?;
+        self.send(req)
+            .await?
+            .replay_nonce()
+            .context("acme: missing Replay-Nonce header in new nonce response")
+    }
+
+    /// Url of the ACME account, registering the account if not yet done.
+    async fn account_url(&self) -> Result<String, OpaqueError> {
+        if let Some(url) = self.state().account_url.clone() {
+            return Ok(url);
+        }
+
+        let directory = self.directory().await?;
+        let resp = self
+            .post_jws(
+                &directory.new_account,
+                None,
+                Some(&json!({
+                    "termsOfServiceAgreed": true,
+                    "contact": self.contact,
+                })),
+            )
+            .await
+            .context("acme: register account")?;
+        let url = resp.location()?;
+        tracing::debug!("acme: using account {url}");
+        self.state().account_url = Some(url.clone());
+        Ok(url)
+    }
+
+    /// Make an authenticated POST (or POST-as-GET when no payload is given) request.
+    async fn post(
+        &self,
+        url: &str,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<AcmeResponse, OpaqueError> {
+        let account_url = self.account_url().await?;
+        self.post_jws(url, Some(&account_url), payload).await
+    }
+
+    async fn post_jws(
+        &self,
+        url: &str,
+        kid: Option<&str>,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<AcmeResponse, OpaqueError> {
+        let mut retried = false;
+        loop {
+            let nonce = self.nonce().await?;
+            let body = self.account_key.sign(url, &nonce, kid, payload)?;
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(Body::from(body))
+                .context("acme: create request")?;
+            let resp = self.send(req).await?;
+            // keep the nonce of the response for the next request
+            if let Some(nonce) = resp.replay_nonce() {
+                self.state().nonce = Some(nonce);
+            }
+            if resp.status.is_success() {
+                return Ok(resp);
+            }
+
+            let problem: Problem = serde_json::from_slice(&resp.body).unwrap_or_default();
+            if !retried && problem.kind.as_deref() == Some(PROBLEM_BAD_NONCE) {
+                tracing::trace!("acme: nonce rejected for {url}, retrying with fresh nonce");
+                retried = true;
+                continue;
+            }
+            return Err(OpaqueError::from_std(problem)
+                .context(format!("acme: POST {url}: status {}", resp.status)));
+        }
+    }
+
+    /// Send a request to the ACME server.
+    async fn send(&self, req: Request) -> Result<AcmeResponse, OpaqueError> {
+        let uri = req.uri().clone();
+        let resp = self
+            .http_client
+            .serve(Context::default(), req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .with_context(|| format!("acme: send request to {uri}"))?;
+
+        let (parts, body) = resp.into_parts();
+        let body = body
+            .collect()
+            .await
+            .with_context(|| format!("acme: read response body from {uri}"))?
+            .to_bytes()
+            .to_vec();
+
+        Ok(AcmeResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        })
+    }
+}
+
+fn response_error(resp: &AcmeResponse) -> OpaqueError {
+    let problem: Problem = serde_json::from_slice(&resp.body).unwrap_or_default();
+    OpaqueError::from_std(problem).context(format!("status {}", resp.status))
+}
+
+fn order_error(order: &Order, msg: &'static str) -> OpaqueError {
+    OpaqueError::from_std(order.error.clone().unwrap_or_default())
+        .context(format!("{msg}: status {:?}", order.status))
+}
+
+impl<S> AcmeIssuer<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>> + Clone,
+{
+    /// Current cert for the given domain, ordering one in case there is no valid cert yet,
+    /// and renewing it in the background once due.
+    async fn cert(&self, domain: Domain) -> Result<ServerAuthData, OpaqueError> {
+        if self.cert_cache.is_some() && !self.state().certs.contains_key(&domain) {
+            self.load_cached_cert(&domain).await;
+        }
+
+        let now = SystemTime::now();
+        let mut order = {
+            let mut state = self.state();
+            let current = state
+                .certs
+                .get(&domain)
+                .filter(|cert| now < cert.expires_at)
+                .map(|cert| (cert.data.clone(), now >= cert.renew_at));
+            let failed = state
+                .failed_orders
+                .get(&domain)
+                .filter(|failed| now < failed.retry_at)
+                .map(|failed| failed.error.clone());
+            match (current, failed) {
+                // keep serving the current cert until the failed renewal can be retried
+                (Some((data, false)), _) | (Some((data, true)), Some(_)) => return Ok(data),
+                (Some((data, true)), None) => {
+                    // keep serving the current cert while it is renewed
+                    self.order(&mut state, domain);
+                    return Ok(data);
+                }
+                (None, Some(err)) => {
+                    return Err(OpaqueError::from_std(err).context(format!(
+                        "acme: order for {domain} failed recently, not yet retried"
+                    )));
+                }
+                (None, None) => self.order(&mut state, domain),
+            }
+        };
+
+        let result = order
+            .wait_for(Option::is_some)
+            .await
+            .map(|result| result.clone())
+            .context("acme: order task stopped")?;
+        match result {
+            Some(Ok(data)) => Ok(data),
+            Some(Err(err)) => Err(OpaqueError::from_std(err)),
+            None => Err(OpaqueError::from_display("acme: order task stopped")),
+        }
+    }
+
+    /// Pending order for the given domain, starting it if there is none.
+    ///
+    /// The order runs in its own task, such that a renewal completes
+    /// even though nobody is waiting on it, and as the service futures
+    /// are not guaranteed to be Sync.
+    fn order(&self, state: &mut AcmeState, domain: Domain) -> watch::Receiver<Option<OrderResult>> {
+        if let Some(order) = state.orders.get(&domain) {
+            return order.clone();
+        }
+
+        let (tx, rx) = watch::channel(None);
+        state.orders.insert(domain.clone(), rx.clone());
+
+        let issuer = self.clone();
+        self.executor.spawn_task(async move {
+            tracing::debug!("acme: order cert for {domain}");
+            let result = issuer
+                .issue(std::slice::from_ref(&domain))
+                .await
+                .and_then(AcmeCert::try_new);
+            if let Ok(cert) = &result {
+                issuer.store_cached_cert(&domain, &cert.data).await;
+            }
+
+            let result = {
+                let mut state = issuer.state();
+                state.orders.remove(&domain);
+                match result {
+                    Ok(cert) => {
+                        let data = cert.data.clone();
+                        state.failed_orders.remove(&domain);
+                        state.certs.insert(domain, cert);
+                        Ok(data)
+                    }
+                    Err(err) => {
+                        let err = Arc::new(err);
+                        let retry_interval =
+                            issuer.record_failed_order(&mut state, domain.clone(), err.clone());
+                        tracing::warn!(
+                            "acme: order cert for {domain} failed, retry in {retry_interval:?}: {err:?}"
+                        );
+                        Err(err)
+                    }
+                }
+            };
+            let _ = tx.send(Some(result));
+        });
+        rx
+    }
+
+    /// Record a failed order for the given domain, returning the interval after which it is retried.
+    fn record_failed_order(
+        &self,
+        state: &mut AcmeState,
+        domain: Domain,
+        error: Arc<OpaqueError>,
+    ) -> Duration {
+        let now = SystemTime::now();
+        let max_retry_interval = self.retry_interval.max(MAX_RETRY_INTERVAL);
+        // forget failures of domains which were not retried for a while
+        state
+            .failed_orders
+            .retain(|_, failed| failed.retry_at + max_retry_interval > now);
+
+        let failures = state
+            .failed_orders
+            .get(&domain)
+            .map_or(0, |failed| failed.failures)
+            .saturating_add(1);
+        let retry_interval = self
+            .retry_interval
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(max_retry_interval);
+        state.failed_orders.insert(
+            domain,
+            FailedOrder {
+                failures,
+                retry_at: now + retry_interval,
+                error,
+            },
+        );
+        retry_interval
+    }
+
+    async fn load_cached_cert(&self, domain: &Domain) {
+        let Some(cache) = self.cert_cache.clone() else {
+            return;
+        };
+        let host = Host::Name(domain.clone());
+        let result = tokio::task::spawn_blocking(move || cache.get(&host))
+            .await
+            .context("acme: join cert cache get task")
+            .and_then(|result| result)
+            .and_then(|data| data.map(AcmeCert::try_new).transpose());
+        match result {
+            Ok(Some(cert)) => {
+                self.state().certs.entry(domain.clone()).or_insert(cert);
+            }
+            Ok(None) => (),
+            Err(err) => tracing::warn!("acme: load cert for {domain} from cert cache: {err:?}"),
+        }
+    }
+
+    async fn store_cached_cert(&self, domain: &Domain, data: &ServerAuthData) {
+        let Some(cache) = self.cert_cache.clone() else {
+            return;
+        };
+        let host = Host::Name(domain.clone());
+        let data = data.clone();
+        let result = tokio::task::spawn_blocking(move || cache.insert(&host, &data))
+            .await
+            .context("acme: join cert cache insert task")
+            .and_then(|result| result);
+        if let Err(err) = result {
+            tracing::warn!("acme: store cert for {domain} in cert cache: {err:?}");
+        }
+    }
+}
+
+impl<S> DynamicCertIssuer for AcmeIssuer<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>> + Clone,
+{
+    fn issue_cert(
+        &self,
+        client_hello: ClientHello,
+        server_name: Option<Host>,
+    ) -> impl Future<Output = Result<ServerAuthData, OpaqueError>> + Send + Sync + '_ {
+        let domain = client_hello
+            .ext_server_name()
+            .cloned()
+            .or(match server_name {
+                Some(Host::Name(domain)) => Some(domain),
+                _ => None,
+            });
+
+        async move {
+            let domain = domain.context("acme: no server name to issue cert for")?;
+            if !self.is_allowed(&domain) {
+                return Err(OpaqueError::from_display(format!(
+                    "acme: domain {domain} is not allowed"
+                )));
+            }
+            self.cert(domain).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Http01ChallengeService;
+    use super::*;
+    use rama_core::service::service_fn;
+    use rama_http_types::BodyExtractExt;
+    use rama_net::tls::server::DiskCertCache;
+    use std::collections::HashSet;
+    use std::convert::Infallible;
+
+    const BASE: &str = "https://acme.test";
+    const TOKEN: &str = "token";
+
+    #[derive(Debug, Default)]
+    struct MockState {
+        nonce_counter: usize,
+        issued_nonces: HashSet<String>,
+        rejected_first_order: bool,
+        validated: bool,
+        finalized: bool,
+        cert_domains: Vec<String>,
+        orders: usize,
+        fail_orders: bool,
+        failed_orders: usize,
+        renewal_due: bool,
+    }
+
+    /// Minimal ACME server, validating challenges using the shared [`AcmeChallenges`].
+    fn mock_acme_server(
+        key_authorization: String,
+        challenges: AcmeChallenges,
+        domain: Domain,
+    ) -> impl Service<(), Request, Response = Response, Error = Infallible> + Clone {
+        mock_acme_server_with_state(key_authorization, challenges, domain, Default::default())
+    }
+
+    fn mock_acme_server_with_state(
+        key_authorization: String,
+        challenges: AcmeChallenges,
+        domain: Domain,
+        state: Arc<tokio::sync::Mutex<MockState>>,
+    ) -> impl Service<(), Request, Response = Response, Error = Infallible> + Clone {
+        service_fn(move |req: Request| {
+            let state = state.clone();
+            let challenges = challenges.clone();
+            let key_authorization = key_authorization.clone();
+            let domain = domain.clone();
+            async move {
+                let path = req.uri().path().to_owned();
+                let method = req.method().clone();
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+
+                let (kid, payload) = if method == Method::POST {
+                    let jws: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                    let protected: serde_json::Value = serde_json::from_slice(
+                        &URL_SAFE_NO_PAD
+                            .decode(jws["protected"].as_str().unwrap())
+                            .unwrap(),
+                    )
+                    .unwrap();
+                    assert_eq!(protected["url"], format!("{BASE}{path}"));
+                    let nonce = protected["nonce"].as_str().unwrap();
+                    assert!(
+                        state.lock().await.issued_nonces.remove(nonce),
+                        "nonce {nonce} re-used or unknown"
+                    );
+                    let payload = URL_SAFE_NO_PAD
+                        .decode(jws["payload"].as_str().unwrap())
+                        .unwrap();
+                    let payload: Option<serde_json::Value> = if payload.is_empty() {
+                        None
+                    } else {
+                        Some(serde_json::from_slice(&payload).unwrap())
+                    };
+                    (protected["kid"].as_str().map(ToOwned::to_owned), payload)
+                } else {
+                    (None, None)
+                };
+                if method == Method::POST && path != "/new-account" {
+                    assert_eq!(kid.as_deref(), Some("https://acme.test/acct/1"));
+                }
+
+                let order = |state: &MockState| {
+                    let status = match (state.validated, state.finalized) {
+                        (false, _) => "pending",
+                        (true, false) => "ready",
+                        (true, true) => "valid",
+                    };
+                    json!({
+                        "status": status,
+                        "authorizations": [format!("{BASE}/authz/1")],
+                        "finalize": format!("{BASE}/order/1/finalize"),
+                        "certificate": state.finalized.then(|| format!("{BASE}/cert/1")),
+                    })
+                };
+
+                let mut state = state.lock().await;
+                let mut resp = match (method, path.as_str()) {
+                    (Method::GET, "/directory") => json_response(json!({
+                        "newNonce": format!("{BASE}/new-nonce"),
+                        "newAccount": format!("{BASE}/new-account"),
+                        "newOrder": format!("{BASE}/new-order"),
+                    })),
+                    (Method::HEAD, "/new-nonce") => Response::new(Body::empty()),
+                    (Method::POST, "/new-account") => {
+                        assert!(kid.is_none());
+                        assert_eq!(payload.unwrap()["termsOfServiceAgreed"], true);
+                        let mut resp = json_response(json!({"status": "valid"}));
+                        *resp.status_mut() = StatusCode::CREATED;
+                        location(resp, "/acct/1")
+                    }
+                    (Method::POST, "/new-order") if !state.rejected_first_order => {
+                        state.rejected_first_order = true;
+                        let mut resp = json_response(json!({
+                            "type": PROBLEM_BAD_NONCE,
+                            "detail": "bad nonce",
+                        }));
+                        *resp.status_mut() = StatusCode::BAD_REQUEST;
+                        resp
+                    }
+                    (Method::POST, "/new-order") if state.fail_orders => {
+                        state.failed_orders += 1;
+                        let mut resp = json_response(json!({
+                            "type": "urn:ietf:params:acme:error:rateLimited",
+                            "detail": "too many orders",
+                        }));
+                        *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                        resp
+                    }
+                    (Method::POST, "/new-order") => {
+                        state.orders += 1;
+                        state.finalized = false;
+                        assert_eq!(
+                            payload.unwrap()["identifiers"],
+                            json!([{"type": "dns", "value": domain.as_str()}])
+                        );
+                        let mut resp = location(json_response(order(&state)), "/order/1");
+                        *resp.status_mut() = StatusCode::CREATED;
+                        resp
+                    }
+                    (Method::POST, "/authz/1") => json_response(json!({
+                        "identifier": {"type": "dns", "value": domain.as_str()},
+                        "status": if state.validated { "valid" } else { "pending" },
+                        "challenges": [
+                            {"type": "http-01", "url": format!("{BASE}/chall/http"), "token": TOKEN},
+                            {"type": "tls-alpn-01", "url": format!("{BASE}/chall/tls"), "token": TOKEN},
+                        ],
+                    })),
+                    (Method::POST, "/chall/http") => {
+                        let resp = Http01ChallengeService::new(challenges)
+                            .serve(
+                                Context::default(),
+                                Request::builder()
+                                    .uri(format!(
+                                        "http://{domain}{HTTP01_PATH}{TOKEN}",
+                                        HTTP01_PATH = super::super::HTTP01_CHALLENGE_PATH_PREFIX
+                                    ))
+                                    .body(Body::empty())
+                                    .unwrap(),
+                            )
+                            .await
+                            .unwrap();
+                        state.validated =
+                            resp.try_into_string().await.unwrap() == key_authorization;
+                        json_response(json!({"status": "processing"}))
+                    }
+                    (Method::POST, "/chall/tls") => {
+                        let digest = aws_lc_rs::digest::digest(
+                            &aws_lc_rs::digest::SHA256,
+                            key_authorization.as_bytes(),
+                        );
+                        state.validated = challenges.tls_alpn01_cert(&domain).is_some_and(|cert| {
+                            cert.end_entity_cert()
+                                .unwrap()
+                                .as_ref()
+                                .windows(32)
+                                .any(|window| window == digest.as_ref())
+                        });
+                        json_response(json!({"status": "processing"}))
+                    }
+                    (Method::POST, "/order/1") => json_response(order(&state)),
+                    (Method::POST, "/order/1/finalize") => {
+                        assert!(state.validated);
+                        assert!(payload.unwrap()["csr"].is_string());
+                        state.finalized = true;
+                        state.cert_domains.push(domain.to_string());
+                        json_response(order(&state))
+                    }
+                    (Method::POST, "/cert/1") => {
+                        let key_pair = rcgen::KeyPair::generate().unwrap();
+                        let mut params =
+                            rcgen::CertificateParams::new(vec![domain.to_string()]).unwrap();
+                        if state.renewal_due {
+                            // far past 2/3 of its validity, but not yet expired
+                            params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+                            params.not_after = rcgen::date_time_ymd(1970, 1, 1)
+                                + SystemTime::now()
+                                    .duration_since(SystemTime::UNIX_EPOCH)
+                                    .unwrap()
+                                + Duration::from_secs(60 * 60 * 24);
+                        }
+                        let cert = params.self_signed(&key_pair).unwrap();
+                        Response::new(Body::from(cert.pem()))
+                    }
+                    (method, path) => panic!("unexpected request: {method} {path}"),
+                };
+
+                state.nonce_counter += 1;
+                let nonce = format!("nonce-{}", state.nonce_counter);
+                state.issued_nonces.insert(nonce.clone());
+                resp.headers_mut()
+                    .insert(REPLAY_NONCE, nonce.parse().unwrap());
+                Ok::<_, Infallible>(resp)
+            }
+        })
+    }
+
+    fn json_response(value: serde_json::Value) -> Response {
+        Response::new(Body::from(value.to_string()))
+    }
+
+    fn location(mut resp: Response, path: &str) -> Response {
+        resp.headers_mut()
+            .insert(header::LOCATION, format!("{BASE}{path}").parse().unwrap());
+        resp
+    }
+
+    async fn test_issue(challenge_type: ChallengeType) {
+        let account_key = AccountKey::generate().unwrap();
+        let challenges = AcmeChallenges::new();
+        let domain = Domain::from_static("example.com");
+        let server = mock_acme_server(
+            account_key.key_authorization(TOKEN),
+            challenges.clone(),
+            domain.clone(),
+        );
+
+        let issuer = AcmeIssuer::new(format!("{BASE}/directory"), server, account_key)
+            .with_challenges(challenges.clone())
+            .with_challenge_type(challenge_type)
+            .with_poll_interval(Duration::from_millis(1));
+
+        let auth_data = issuer.issue(std::slice::from_ref(&domain)).await.unwrap();
+        let DataEncoding::Pem(cert_chain) = auth_data.cert_chain else {
+            panic!("expected PEM cert chain");
+        };
+        assert!(
+            cert_chain
+                .as_str()
+                .starts_with("-----BEGIN CERTIFICATE-----")
+        );
+        let DataEncoding::Pem(private_key) = auth_data.private_key else {
+            panic!("expected PEM private key");
+        };
+        assert!(private_key.as_str().contains("PRIVATE KEY"));
+
+        // challenges are cleaned up once the order completed
+        assert!(challenges.http01_key_authorization(TOKEN).is_none());
+        assert!(challenges.tls_alpn01_cert(&domain).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_http01() {
+        test_issue(ChallengeType::Http01).await;
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_tls_alpn01() {
+        test_issue(ChallengeType::TlsAlpn01).await;
+    }
+
+    fn client_hello(domain: &Domain) -> ClientHello {
+        ClientHello::new(
+            rama_net::tls::ProtocolVersion::TLSv1_3,
+            vec![],
+            vec![],
+            vec![rama_net::tls::client::ClientHelloExtension::ServerName(
+                Some(domain.clone()),
+            )],
+        )
+    }
+
+    fn cert_chain(data: &ServerAuthData) -> &str {
+        match &data.cert_chain {
+            DataEncoding::Pem(pem) => pem.as_str(),
+            _ => panic!("expected PEM cert chain"),
+        }
+    }
+
+    fn test_issuer(
+        state: Arc<tokio::sync::Mutex<MockState>>,
+    ) -> AcmeIssuer<impl Service<(), Request, Response = Response, Error = Infallible> + Clone>
+    {
+        let account_key = AccountKey::generate().unwrap();
+        let challenges = AcmeChallenges::new();
+        let server = mock_acme_server_with_state(
+            account_key.key_authorization(TOKEN),
+            challenges.clone(),
+            Domain::from_static("example.com"),
+            state,
+        );
+        AcmeIssuer::new(format!("{BASE}/directory"), server, account_key)
+            .with_challenges(challenges)
+            .with_poll_interval(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_single_flight() {
+        let state = Arc::new(tokio::sync::Mutex::new(MockState::default()));
+        let issuer = test_issuer(state.clone());
+        let domain = Domain::from_static("example.com");
+
+        let (a, b) = tokio::join!(
+            issuer.issue_cert(client_hello(&domain), None),
+            issuer.issue_cert(client_hello(&domain), None),
+        );
+        assert_eq!(cert_chain(&a.unwrap()), cert_chain(&b.unwrap()));
+        assert_eq!(state.lock().await.orders, 1);
+
+        // served from the issuer once issued
+        issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert_eq!(state.lock().await.orders, 1);
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_concurrent_nonces() {
+        let issuer = test_issuer(Default::default());
+
+        // each fresh nonce is handed to the request which asked for it
+        let (a, b, c) = tokio::join!(issuer.nonce(), issuer.nonce(), issuer.nonce());
+        let nonces: HashSet<_> = [a.unwrap(), b.unwrap(), c.unwrap()].into();
+        assert_eq!(nonces.len(), 3);
+        assert!(issuer.state().nonce.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_renewal() {
+        let state = Arc::new(tokio::sync::Mutex::new(MockState {
+            renewal_due: true,
+            ..Default::default()
+        }));
+        let issuer = test_issuer(state.clone());
+        let domain = Domain::from_static("example.com");
+
+        let first = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+
+        // the current cert is served while it is renewed in the background
+        let served = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert_eq!(cert_chain(&first), cert_chain(&served));
+        wait_for_orders(&issuer).await;
+        let renewed = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert_ne!(cert_chain(&first), cert_chain(&renewed));
+        // the renewed cert is due for renewal as well
+        wait_for_orders(&issuer).await;
+        assert!(state.lock().await.orders >= 2);
+
+        // ... as well as in case the renewal failed, which is not retried right away
+        state.lock().await.fail_orders = true;
+        let current = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert!(!issuer.state().orders.is_empty());
+        wait_for_orders(&issuer).await;
+        let served = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert_eq!(cert_chain(&current), cert_chain(&served));
+        assert!(issuer.state().orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_failed_order_backoff() {
+        let state = Arc::new(tokio::sync::Mutex::new(MockState {
+            fail_orders: true,
+            ..Default::default()
+        }));
+        let issuer = test_issuer(state.clone());
+        let domain = Domain::from_static("example.com");
+
+        issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap_err();
+        assert_eq!(state.lock().await.failed_orders, 1);
+
+        // not ordered again until the retry interval passed
+        let err = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed recently"));
+        assert_eq!(state.lock().await.failed_orders, 1);
+        assert!(issuer.state().orders.is_empty());
+
+        // retried once the retry interval passed, doubling it on failure
+        let failed_order = issuer.state().failed_orders.remove(&domain).unwrap();
+        issuer.state().failed_orders.insert(
+            domain.clone(),
+            FailedOrder {
+                retry_at: SystemTime::now(),
+                ..failed_order
+            },
+        );
+        issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap_err();
+        assert_eq!(state.lock().await.failed_orders, 2);
+        let failed_order = issuer.state().failed_orders.remove(&domain).unwrap();
+        assert_eq!(failed_order.failures, 2);
+        assert!(failed_order.retry_at > SystemTime::now() + Duration::from_secs(9 * 60));
+
+        // a successful order forgets about the failures
+        state.lock().await.fail_orders = false;
+        issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert!(issuer.state().failed_orders.is_empty());
+    }
+
+    async fn wait_for_orders<S>(issuer: &AcmeIssuer<S>) {
+        while !issuer.state().orders.is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_cert_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_cache = DynamicCertCache::new(DiskCertCache::new(dir.path()));
+        let domain = Domain::from_static("example.com");
+
+        let issuer = test_issuer(Default::default()).with_cert_cache(cert_cache.clone());
+        let issued = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+
+        // loaded from the cache after a restart
+        let issuer = AcmeIssuer::new(
+            format!("{BASE}/directory"),
+            service_fn(async |_req: Request| -> Result<Response, Infallible> {
+                panic!("no request expected for a cached cert")
+            }),
+            AccountKey::generate().unwrap(),
+        )
+        .with_cert_cache(cert_cache);
+        let cached = issuer
+            .issue_cert(client_hello(&domain), None)
+            .await
+            .unwrap();
+        assert_eq!(cert_chain(&issued), cert_chain(&cached));
+    }
+
+    #[tokio::test]
+    async fn test_acme_issuer_allowed_domains() {
+        let issuer = AcmeIssuer::new(
+            format!("{BASE}/directory"),
+            service_fn(async |_req: Request| -> Result<Response, Infallible> {
+                panic!("no request expected for a domain which is not allowed")
+            }),
+            AccountKey::generate().unwrap(),
+        )
+        .with_allowed_domains(vec![Domain::from_static("example.com")]);
+
+        assert!(issuer.is_allowed(&Domain::from_static("example.com")));
+        assert!(issuer.is_allowed(&Domain::from_static("www.example.com")));
+        assert!(!issuer.is_allowed(&Domain::from_static("example.org")));
+
+        let err = issuer
+            .issue_cert(
+                ClientHello::new(
+                    rama_net::tls::ProtocolVersion::TLSv1_3,
+                    vec![],
+                    vec![],
+                    vec![rama_net::tls::client::ClientHelloExtension::ServerName(
+                        Some(Domain::from_static("example.org")),
+                    )],
+                ),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not allowed"));
+    }
+}
@@ -0,0 +1,41 @@
+//! ACME (RFC 8555) support for rama, to obtain certificates from
+//! a CA such as Let's Encrypt on demand.
+//!
+//! The [`AcmeIssuer`] is a [`DynamicCertIssuer`] which orders a certificate
+//! for the server name (SNI) of a client hello, proving control over the domain using either:
+//!
+//! - [`ChallengeType::Http01`]: served by the [`Http01ChallengeService`] on port 80;
+//! - [`ChallengeType::TlsAlpn01`]: served by the [`CertIssuerConfigProvider`] on port 443,
+//!   enabled by passing the [`AcmeIssuer::acme_challenges`] to
+//!   [`CertIssuerConfigProvider::with_acme_challenges`].
+//!
+//! Issued certificates are renewed by the [`AcmeIssuer`] itself, once 2/3 of their validity
+//! has passed, so use [`CacheKind::Disabled`] for the [`CertIssuerConfigProvider`] and
+//! [`AcmeIssuer::with_cert_cache`] with a [`DiskCertCache`] for certificates to survive restarts.
+//!
+//! # Testing
+//!
+//! Use [Pebble](https://github.com/letsencrypt/pebble) as a local ACME server,
+//! by passing its directory url (`https://localhost:14000/dir` by default) and an http client
+//! which trusts its (test) root certificate. Run Pebble with `PEBBLE_VA_ALWAYS_VALID=1`
+//! in order to test without it having to reach the challenge services.
+//!
+//! [`DynamicCertIssuer`]: rama_net::tls::server::DynamicCertIssuer
+//! [`CacheKind::Disabled`]: rama_net::tls::server::CacheKind::Disabled
+//! [`DiskCertCache`]: rama_net::tls::server::DiskCertCache
+//! [`CertIssuerConfigProvider`]: crate::server::CertIssuerConfigProvider
+//! [`CertIssuerConfigProvider::with_acme_challenges`]: crate::server::CertIssuerConfigProvider::with_acme_challenges
+
+mod account;
+mod challenge;
+mod issuer;
+mod proto;
+
+#[doc(inline)]
+pub use account::AccountKey;
+#[doc(inline)]
+pub use challenge::{
+    AcmeChallenges, ChallengeType, HTTP01_CHALLENGE_PATH_PREFIX, Http01ChallengeService,
+};
+#[doc(inline)]
+pub use issuer::{AcmeIssuer, LETS_ENCRYPT_PRODUCTION_DIRECTORY, LETS_ENCRYPT_STAGING_DIRECTORY};
@@ -0,0 +1,91 @@
+//! ACME (RFC 8555) protocol objects, limited to what is used by the [`super::AcmeIssuer`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Problem type returned by the server in case the nonce was rejected,
+/// in which case the request has to be retried with a fresh nonce.
+pub(super) const PROBLEM_BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Directory {
+    pub(super) new_nonce: String,
+    pub(super) new_account: String,
+    pub(super) new_order: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Status {
+    Pending,
+    Ready,
+    Processing,
+    Valid,
+    Invalid,
+    Revoked,
+    Deactivated,
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Identifier {
+    #[serde(rename = "type")]
+    pub(super) kind: String,
+    pub(super) value: String,
+}
+
+impl Identifier {
+    pub(super) fn dns(value: impl Into<String>) -> Self {
+        Self {
+            kind: "dns".to_owned(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Order {
+    pub(super) status: Status,
+    pub(super) authorizations: Vec<String>,
+    pub(super) finalize: String,
+    pub(super) certificate: Option<String>,
+    pub(super) error: Option<Problem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Authorization {
+    pub(super) identifier: Identifier,
+    pub(super) status: Status,
+    pub(super) challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Challenge {
+    #[serde(rename = "type")]
+    pub(super) kind: String,
+    pub(super) url: String,
+    pub(super) token: Option<String>,
+    pub(super) error: Option<Problem>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+/// Problem document (RFC 7807) returned by the server in case of errors.
+pub(super) struct Problem {
+    #[serde(rename = "type")]
+    pub(super) kind: Option<String>,
+    pub(super) detail: Option<String>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "acme problem: {}: {}",
+            self.kind.as_deref().unwrap_or("unknown"),
+            self.detail.as_deref().unwrap_or("no details"),
+        )
+    }
+}
+
+impl std::error::Error for Problem {}
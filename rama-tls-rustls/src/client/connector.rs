@@ -1,17 +1,19 @@
 use super::TlsConnectorData;
-use crate::dep::tokio_rustls::{TlsConnector as RustlsConnector, client::TlsStream};
+use crate::dep::tokio_rustls::{self, TlsConnector as RustlsConnector};
 use crate::types::TlsTunnel;
 use crate::{RamaInto, RamaTryFrom};
 use pin_project_lite::pin_project;
 use rama_core::error::ErrorContext;
 use rama_core::error::{BoxError, ErrorExt, OpaqueError};
+use rama_core::layer::timeout::Deadline;
 use rama_core::telemetry::tracing;
 use rama_core::{Context, Layer, Service};
 use rama_net::address::Host;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::tls::ApplicationProtocol;
-use rama_net::tls::client::NegotiatedTlsParameters;
+use rama_net::tls::client::{NegotiatedTlsParameters, ServerHelloRecorder};
+use rama_net::tls::server::ServerHello;
 use rama_net::transport::TryRefIntoTransportContext;
 use std::fmt;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -132,6 +134,11 @@ impl Default for TlsConnectorLayer<ConnectorKindAuto> {
 /// only if the request requires a secure connection. You can instead use
 /// [`TlsConnector::secure_only`] to force the connector to always
 /// establish a secure connection.
+///
+/// Once a secure connection is established the [`NegotiatedTlsParameters`]
+/// and the [`ServerHello`] received from the server are added to the [`Context`],
+/// which can be used to fingerprint the server (e.g. JA3S, JA4S and JA4X)
+/// using the types found in [`rama_net::fingerprint`].
 pub struct TlsConnector<S, K = ConnectorKindAuto> {
     inner: S,
     connector_data: Option<TlsConnectorData>,
@@ -278,7 +285,10 @@ where
         );
 
         let connector_data = ctx.get::<TlsConnectorData>().cloned();
-        let (stream, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (stream, negotiated_params, server_hello) = self
+            .handshake(connector_data, deadline, server_host, conn)
+            .await?;
 
         tracing::trace!(
             server.address = %transport_ctx.authority.host(),
@@ -288,6 +298,7 @@ where
         );
 
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -333,8 +344,12 @@ where
         let server_host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get::<TlsConnectorData>().cloned();
-        let (conn, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (conn, negotiated_params, server_hello) = self
+            .handshake(connector_data, deadline, server_host, conn)
+            .await?;
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         Ok(EstablishedClientConnection { ctx, req, conn })
     }
@@ -379,8 +394,12 @@ where
         };
 
         let connector_data = ctx.get::<TlsConnectorData>().cloned();
-        let (conn, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let deadline = ctx.get::<Deadline>().copied();
+        let (conn, negotiated_params, server_hello) = self
+            .handshake(connector_data, deadline, server_host, conn)
+            .await?;
         ctx.insert(negotiated_params);
+        ctx.maybe_insert(server_hello);
 
         tracing::trace!("TlsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -397,9 +416,10 @@ impl<S, K> TlsConnector<S, K> {
     async fn handshake<T>(
         &self,
         connector_data: Option<TlsConnectorData>,
+        deadline: Option<Deadline>,
         server_host: Host,
         stream: T,
-    ) -> Result<(TlsStream<T>, NegotiatedTlsParameters), BoxError>
+    ) -> Result<(TlsStream<T>, NegotiatedTlsParameters, Option<ServerHello>), BoxError>
     where
         T: Stream + Unpin,
     {
@@ -407,16 +427,49 @@ impl<S, K> TlsConnector<S, K> {
             .or(self.connector_data.clone())
             .unwrap_or(TlsConnectorData::new_http_auto()?);
 
-        let server_name = rustls_pki_types::ServerName::rama_try_from(
-            connector_data.server_name.unwrap_or(server_host),
-        )?;
+        let server_host = connector_data.server_name.unwrap_or(server_host);
+        let server_name = rustls_pki_types::ServerName::rama_try_from(server_host.clone())?;
+
+        if connector_data.server_verifier.is_some()
+            && connector_data
+                .client_config
+                .client_auth_cert_resolver
+                .has_certs()
+        {
+            return Err(OpaqueError::from_display(
+                "dynamic server verifier cannot be combined with client auth",
+            )
+            .into());
+        }
 
         let connector = RustlsConnector::from(connector_data.client_config);
 
-        let stream = connector.connect(server_name, stream).await?;
+        // rustls does not expose the ServerHello, so it is recorded from the stream
+        let handshake = connector.connect(server_name, ServerHelloRecorder::new(stream));
+        let mut stream = match deadline {
+            Some(deadline) => deadline.run(handshake).await.context("tls handshake")??,
+            None => handshake.await?,
+        };
+        let server_hello = stream.get_mut().0.take_server_hello();
 
         let (_, conn_data_ref) = stream.get_ref();
 
+        if let Some(verifier) = connector_data.server_verifier {
+            let cert_chain = conn_data_ref
+                .peer_certificates()
+                .map(|chain| chain.iter().map(|cert| cert.to_vec()).collect())
+                .unwrap_or_default();
+            // no SNI is sent for ip addresses, same as for other tls clients
+            let server_name = match server_host {
+                Host::Name(_) => Some(server_host),
+                Host::Address(_) => None,
+            };
+            verifier
+                .verify_cert(cert_chain, server_name)
+                .await
+                .context("verify server certificate chain")?;
+        }
+
         let server_certificate_chain = if connector_data.store_server_certificate_chain {
             conn_data_ref.peer_certificates().map(RamaInto::rama_into)
         } else {
@@ -434,10 +487,16 @@ impl<S, K> TlsConnector<S, K> {
             peer_certificate_chain: server_certificate_chain,
         };
 
-        Ok((stream, params))
+        Ok((stream, params, server_hello))
     }
 }
 
+/// A client TLS stream, as established by the [`TlsConnector`].
+///
+/// The inner stream is wrapped in a [`ServerHelloRecorder`], given rustls does not
+/// expose the [`ServerHello`] itself. It is a passthrough once the handshake is complete.
+pub type TlsStream<S> = tokio_rustls::client::TlsStream<ServerHelloRecorder<S>>;
+
 pin_project! {
     /// A stream which can be either a secure or a plain stream.
     pub struct AutoTlsStream<S> {
@@ -559,6 +618,14 @@ pub struct ConnectorKindTunnel {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::TlsConnectorDataBuilder;
+    use crate::dep::pki_types::CertificateDer;
+    use crate::server::{TlsAcceptorDataBuilder, self_signed_server_auth};
+    use rama_net::address::Domain;
+    use rama_net::tls::DataEncoding;
+    use rama_net::tls::client::{CertificatePin, DynamicCertVerifier, ServerVerifyMode};
+    use rama_net::tls::server::SelfSignedData;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn assert_send() {
@@ -573,4 +640,187 @@ mod tests {
 
         assert_sync::<TlsConnectorLayer>();
     }
+
+    #[derive(Debug, Clone, Default)]
+    struct RecordingVerifier {
+        observed: Arc<Mutex<Option<(usize, Option<Host>)>>>,
+    }
+
+    impl DynamicCertVerifier for RecordingVerifier {
+        async fn verify_cert(
+            &self,
+            cert_chain: Vec<Vec<u8>>,
+            server_name: Option<Host>,
+        ) -> Result<(), OpaqueError> {
+            *self.observed.lock().unwrap() = Some((cert_chain.len(), server_name));
+            Ok(())
+        }
+    }
+
+    async fn handshake_with_verify_mode(
+        mode: impl FnOnce(&[CertificateDer<'static>]) -> ServerVerifyMode,
+    ) -> Result<(), BoxError> {
+        let (cert_chain, key_der) = self_signed_server_auth(SelfSignedData {
+            subject_alternative_names: Some(vec!["localhost".to_owned()]),
+            ..Default::default()
+        })
+        .expect("self signed data");
+        let connector_data = TlsConnectorDataBuilder::new()
+            .with_server_verify_mode(mode(&cert_chain))
+            .unwrap()
+            .build();
+        let server_config = TlsAcceptorDataBuilder::new(cert_chain, key_der)
+            .unwrap()
+            .into_rustls_config();
+        let acceptor = crate::dep::tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+
+        let connector = TlsConnector::secure(());
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let (result, _) = tokio::join!(
+            connector.handshake(
+                Some(connector_data),
+                None,
+                Host::Name(Domain::from_static("localhost")),
+                client_stream,
+            ),
+            acceptor.accept(server_stream),
+        );
+        result.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_records_server_hello() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (cert_chain, key_der) = self_signed_server_auth(SelfSignedData::default()).unwrap();
+        let connector_data = TlsConnectorDataBuilder::new()
+            .with_server_verify_mode(ServerVerifyMode::Disable)
+            .unwrap()
+            .build();
+        let server_config = TlsAcceptorDataBuilder::new(cert_chain, key_der)
+            .unwrap()
+            .into_rustls_config();
+        let acceptor = crate::dep::tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+
+        let connector = TlsConnector::secure(());
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let (client, server) = tokio::join!(
+            connector.handshake(
+                Some(connector_data),
+                None,
+                Host::Name(Domain::from_static("localhost")),
+                client_stream,
+            ),
+            acceptor.accept(server_stream),
+        );
+        let (mut client, params, server_hello) = client.unwrap();
+        let mut server = server.unwrap();
+
+        let server_hello = server_hello.expect("recorded server hello");
+        assert_eq!(params.protocol_version, server_hello.negotiated_version());
+
+        // the stream remains usable after the session was moved into it
+        client.write_all(b"ping").await.unwrap();
+        client.flush().await.unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"ping", &buf);
+        server.write_all(b"pong").await.unwrap();
+        server.flush().await.unwrap();
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"pong", &buf);
+    }
+
+    #[tokio::test]
+    async fn test_server_verify_mode() {
+        handshake_with_verify_mode(|_| ServerVerifyMode::Auto)
+            .await
+            .expect_err("self-signed chain is not trusted by default");
+
+        handshake_with_verify_mode(|_| ServerVerifyMode::Disable)
+            .await
+            .unwrap();
+
+        handshake_with_verify_mode(|chain| {
+            let ca = chain.last().unwrap().to_vec();
+            ServerVerifyMode::TrustAnchors(DataEncoding::Der(ca))
+        })
+        .await
+        .unwrap();
+
+        handshake_with_verify_mode(|_| {
+            let ca = self_signed_server_auth(SelfSignedData::default())
+                .unwrap()
+                .0;
+            ServerVerifyMode::TrustAnchors(DataEncoding::Der(ca.last().unwrap().to_vec()))
+        })
+        .await
+        .expect_err("chain is not issued by the trust anchor");
+
+        handshake_with_verify_mode(|chain| {
+            ServerVerifyMode::Pinned(vec![
+                CertificatePin::CertSha256([0; 32]),
+                CertificatePin::spki_sha256_from_der(&chain[0]).unwrap(),
+            ])
+        })
+        .await
+        .unwrap();
+
+        handshake_with_verify_mode(|chain| {
+            // only the end-entity certificate is pinned
+            ServerVerifyMode::Pinned(vec![CertificatePin::cert_sha256_from_der(
+                chain.last().unwrap(),
+            )])
+        })
+        .await
+        .expect_err("end-entity certificate does not match pin");
+
+        let verifier = RecordingVerifier::default();
+        handshake_with_verify_mode(|_| verifier.clone().into())
+            .await
+            .unwrap();
+        assert_eq!(
+            Some((2, Some(Host::Name(Domain::from_static("localhost"))))),
+            verifier.observed.lock().unwrap().clone()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_verifier_server_name_for_ip() {
+        let (cert_chain, key_der) = self_signed_server_auth(SelfSignedData::default()).unwrap();
+        let verifier = RecordingVerifier::default();
+        let connector_data = TlsConnectorDataBuilder::new()
+            .with_server_verify_mode(verifier.clone().into())
+            .unwrap()
+            .build();
+        let server_config = TlsAcceptorDataBuilder::new(cert_chain, key_der)
+            .unwrap()
+            .into_rustls_config();
+        let acceptor = crate::dep::tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+
+        let connector = TlsConnector::secure(());
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let (result, _) = tokio::join!(
+            connector.handshake(
+                Some(connector_data),
+                None,
+                Host::Address([127, 0, 0, 1].into()),
+                client_stream,
+            ),
+            acceptor.accept(server_stream),
+        );
+        result.unwrap();
+        assert_eq!(Some((2, None)), verifier.observed.lock().unwrap().clone());
+    }
+
+    #[test]
+    fn test_dynamic_verifier_refuses_client_auth() {
+        let (cert_chain, key_der) = crate::client::self_signed_client_auth().unwrap();
+        assert!(
+            TlsConnectorDataBuilder::new_with_client_auth(cert_chain, key_der)
+                .unwrap()
+                .with_server_verify_mode(RecordingVerifier::default().into())
+                .is_err()
+        );
+    }
 }
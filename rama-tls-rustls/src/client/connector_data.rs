@@ -3,10 +3,12 @@ use crate::dep::rcgen::{self, KeyPair};
 use crate::dep::rustls::RootCertStore;
 use crate::dep::rustls::{ALL_VERSIONS, ClientConfig};
 use crate::key_log::KeyLogFile;
-use crate::verify::NoServerCertVerifier;
+use crate::verify::{NoServerCertVerifier, PinnedServerCertVerifier};
 use rama_core::error::{BoxError, ErrorContext, OpaqueError};
 use rama_net::address::Host;
-use rama_net::tls::{ApplicationProtocol, KeyLogIntent};
+use rama_net::tls::client::{DynamicVerifier, ServerVerifyMode};
+use rama_net::tls::{ApplicationProtocol, DataEncoding, KeyLogIntent};
+use rustls::client::WebPkiServerVerifier;
 use rustls::client::danger::ServerCertVerifier;
 use std::sync::{Arc, OnceLock};
 
@@ -19,6 +21,10 @@ pub struct TlsConnectorData {
     pub client_config: Arc<ClientConfig>,
     pub server_name: Option<Host>,
     pub store_server_certificate_chain: bool,
+    /// verifier of the server certificate chain, run once the handshake is complete
+    ///
+    /// Cannot be combined with client auth, see [`TlsConnectorDataBuilder::set_server_verify_mode`].
+    pub server_verifier: Option<DynamicVerifier>,
 }
 
 impl From<ClientConfig> for TlsConnectorData {
@@ -34,6 +40,7 @@ impl From<Arc<ClientConfig>> for TlsConnectorData {
             client_config: value,
             server_name: None,
             store_server_certificate_chain: false,
+            server_verifier: None,
         }
     }
 }
@@ -75,6 +82,7 @@ pub struct TlsConnectorDataBuilder {
     client_config: rustls::ClientConfig,
     server_name: Option<Host>,
     store_server_certificate_chain: bool,
+    server_verifier: Option<DynamicVerifier>,
 }
 
 impl Default for TlsConnectorDataBuilder {
@@ -103,6 +111,7 @@ impl TlsConnectorDataBuilder {
             client_config: config,
             server_name: None,
             store_server_certificate_chain: false,
+            server_verifier: None,
         }
     }
 
@@ -121,6 +130,7 @@ impl TlsConnectorDataBuilder {
             client_config: config,
             server_name: None,
             store_server_certificate_chain: false,
+            server_verifier: None,
         })
     }
 
@@ -194,6 +204,66 @@ impl TlsConnectorDataBuilder {
         self
     }
 
+    /// Set the [`ServerVerifyMode`] that will be used to verify the server
+    ///
+    /// In case of [`ServerVerifyMode::Dynamic`] the handshake signatures are verified by rustls,
+    /// while the certificate chain is verified by the [`DynamicVerifier`] once the handshake is complete,
+    /// as rustls only supports blocking verifiers. During the handshake any certificate chain is accepted,
+    /// which is why this mode returns an error in case client auth is configured,
+    /// given the client certificate would otherwise be sent to a not yet verified server.
+    pub fn set_server_verify_mode(
+        &mut self,
+        mode: ServerVerifyMode,
+    ) -> Result<&mut Self, OpaqueError> {
+        let provider = self.client_config.crypto_provider().clone();
+        self.server_verifier = None;
+        match mode {
+            ServerVerifyMode::Auto => {
+                let verifier =
+                    WebPkiServerVerifier::builder_with_provider(client_root_certs(), provider)
+                        .build()
+                        .context("rustls/TlsConnectorData: build default server verifier")?;
+                Ok(self.set_cert_verifier(verifier))
+            }
+            ServerVerifyMode::Disable => Ok(self.set_no_cert_verifier()),
+            ServerVerifyMode::TrustAnchors(roots) => {
+                let mut root_store = RootCertStore::empty();
+                for cert in certs_from_data_encoding(roots)? {
+                    root_store
+                        .add(cert)
+                        .context("rustls/TlsConnectorData: add trust anchor")?;
+                }
+                let verifier =
+                    WebPkiServerVerifier::builder_with_provider(Arc::new(root_store), provider)
+                        .build()
+                        .context("rustls/TlsConnectorData: build trust anchor server verifier")?;
+                Ok(self.set_cert_verifier(verifier))
+            }
+            ServerVerifyMode::Pinned(pins) => Ok(self.set_cert_verifier(Arc::new(
+                PinnedServerCertVerifier::new(pins, provider.signature_verification_algorithms),
+            ))),
+            ServerVerifyMode::Dynamic(verifier) => {
+                if self.client_config.client_auth_cert_resolver.has_certs() {
+                    return Err(OpaqueError::from_display(
+                        "rustls/TlsConnectorData: dynamic server verifier cannot be combined with client auth",
+                    ));
+                }
+                self.server_verifier = Some(verifier);
+                Ok(
+                    self.set_cert_verifier(Arc::new(PinnedServerCertVerifier::deferred(
+                        provider.signature_verification_algorithms,
+                    ))),
+                )
+            }
+        }
+    }
+
+    /// Same as [`Self::set_server_verify_mode`] but consuming self
+    pub fn with_server_verify_mode(mut self, mode: ServerVerifyMode) -> Result<Self, OpaqueError> {
+        self.set_server_verify_mode(mode)?;
+        Ok(self)
+    }
+
     /// Set servername that will be used for SNI
     pub fn set_server_name(&mut self, server_name: Host) -> &mut Self {
         self.server_name = Some(server_name);
@@ -230,6 +300,7 @@ impl TlsConnectorDataBuilder {
             client_config: Arc::new(self.client_config),
             server_name: self.server_name,
             store_server_certificate_chain: self.store_server_certificate_chain,
+            server_verifier: self.server_verifier,
         }
     }
 }
@@ -245,6 +316,23 @@ pub fn client_root_certs() -> Arc<RootCertStore> {
         .clone()
 }
 
+pub(crate) fn certs_from_data_encoding(
+    data: DataEncoding,
+) -> Result<Vec<CertificateDer<'static>>, OpaqueError> {
+    match data {
+        DataEncoding::Der(raw_data) => Ok(vec![CertificateDer::from(raw_data)]),
+        DataEncoding::DerStack(raw_data_list) => Ok(raw_data_list
+            .into_iter()
+            .map(CertificateDer::from)
+            .collect()),
+        DataEncoding::Pem(raw_data) => {
+            rustls_pemfile::certs(&mut std::io::BufReader::new(raw_data.as_bytes()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("rustls/TlsConnectorData: parse certificates from PEM content")
+        }
+    }
+}
+
 pub fn self_signed_client_auth()
 -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), OpaqueError> {
     // Create a client end entity cert.
@@ -4,10 +4,11 @@ mod connector;
 #[doc(inline)]
 pub use connector::{
     AutoTlsStream, ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel, TlsConnector,
-    TlsConnectorLayer,
+    TlsConnectorLayer, TlsStream,
 };
 
 mod connector_data;
+pub(crate) use connector_data::certs_from_data_encoding;
 #[doc(inline)]
 pub use connector_data::{
     TlsConnectorData, TlsConnectorDataBuilder, client_root_certs, self_signed_client_auth,
@@ -17,6 +17,8 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
+#[cfg(feature = "acme")]
+pub mod acme;
 pub mod client;
 pub mod server;
 pub mod verify;
@@ -2,11 +2,13 @@ use crate::dep::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use crate::dep::rcgen::{self, KeyPair};
 use crate::dep::rustls;
 use crate::key_log::KeyLogFile;
+use crate::server::ReloadableCertResolver;
 use rama_core::error::{ErrorContext, OpaqueError};
 use rama_net::address::{Domain, Host};
-use rama_net::tls::server::SelfSignedData;
+use rama_net::tls::server::{ReloadableServerAuth, SelfSignedData};
 use rama_net::tls::{ApplicationProtocol, KeyLogIntent};
 use rustls::ALL_VERSIONS;
+use rustls::server::ResolvesServerCertUsingSni;
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -145,6 +147,24 @@ impl TlsAcceptorDataBuilder {
         })
     }
 
+    /// Create a [`TlsAcceptorDataBuilder`] support all tls versions, using no client auth, and the
+    /// certificate chain and private key of the [`ReloadableServerAuth`], which are
+    /// picked up by new connections once reloaded
+    pub fn try_new_reloadable(auth: ReloadableServerAuth) -> Result<Self, OpaqueError> {
+        let mut config = rustls::ServerConfig::builder_with_protocol_versions(ALL_VERSIONS)
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(ResolvesServerCertUsingSni::new()));
+        // the resolver loads the key using the crypto provider selected by the config builder
+        config.cert_resolver = Arc::new(
+            ReloadableCertResolver::try_new(auth, config.crypto_provider().clone())
+                .context("new tls acceptor builder with reloadable server auth")?,
+        );
+
+        Ok(Self {
+            server_config: config,
+        })
+    }
+
     /// If [`KeyLogIntent::Environment`] is set to a path, create a key logger that will write to that path
     /// and set it in the current config
     pub fn set_env_key_logger(&mut self) -> Result<&mut Self, OpaqueError> {
@@ -0,0 +1,572 @@
+use crate::dep::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use crate::dep::rcgen::{
+    self, BasicConstraints, CertificateParams, DistinguishedName, DnType, DnValue,
+    ExtendedKeyUsagePurpose, IsCa, KeyIdMethod, KeyPair, KeyUsagePurpose, RsaKeySize, SanType,
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_core::telemetry::tracing;
+use rama_net::{
+    address::Host,
+    tls::{
+        DataEncoding,
+        client::ClientHello,
+        der::{
+            TAG_BIT_STRING, TAG_BOOLEAN, TAG_IA5_STRING, TAG_OCTET_STRING, TAG_OID,
+            TAG_PRINTABLE_STRING, TAG_SEQUENCE, TAG_SET, TAG_UTF8_STRING, TAG_X509_EXTENSIONS,
+            decode_oid, expect_der, read_der, x509_tbs_certificate,
+        },
+        server::{DynamicCertIssuer, KeyAlgorithm, SelfSignedData, ServerAuthData},
+    },
+};
+use rama_utils::macros::generate_set_and_with;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Generate a new key pair using the given [`KeyAlgorithm`].
+fn generate_key_pair(algorithm: KeyAlgorithm) -> Result<KeyPair, OpaqueError> {
+    match algorithm {
+        KeyAlgorithm::Rsa2048 => {
+            KeyPair::generate_rsa_for(&rcgen::PKCS_RSA_SHA256, RsaKeySize::_2048)
+        }
+        KeyAlgorithm::Rsa4096 => {
+            KeyPair::generate_rsa_for(&rcgen::PKCS_RSA_SHA256, RsaKeySize::_4096)
+        }
+        KeyAlgorithm::EcdsaP256 => KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256),
+        KeyAlgorithm::EcdsaP384 => KeyPair::generate_for(&rcgen::PKCS_ECDSA_P384_SHA384),
+        KeyAlgorithm::Ed25519 => KeyPair::generate_for(&rcgen::PKCS_ED25519),
+    }
+    .with_context(|| format!("generate {algorithm} key pair"))
+}
+
+#[derive(Clone)]
+/// Certificate authority (CA) which issues leaf certificates on the fly,
+/// e.g. for each server name intercepted by a MITM proxy.
+///
+/// The root CA can be generated using [`CaCertIssuer::generate`] or
+/// loaded from PEM using [`CaCertIssuer::try_from_pem`], e.g. as created by `rama tls ca init`.
+/// Clients will only trust the issued certificates if they trust this root CA,
+/// which can be exported using [`CaCertIssuer::ca_cert_der`].
+///
+/// It implements [`DynamicCertIssuer`] so it can be used as a
+/// [`ServerCertIssuerKind::Dynamic`] issuer by the rustls [`CertIssuerConfigProvider`].
+///
+/// [`ServerCertIssuerKind::Dynamic`]: rama_net::tls::server::ServerCertIssuerKind::Dynamic
+/// [`CertIssuerConfigProvider`]: super::CertIssuerConfigProvider
+pub struct CaCertIssuer {
+    ca: Arc<Ca>,
+    key_algorithm: KeyAlgorithm,
+    validity_days: u32,
+}
+
+struct Ca {
+    /// certificate as generated by rcgen, only used for its (issuer) parameters
+    issuer: rcgen::Certificate,
+    /// certificate as served to clients
+    cert: CertificateDer<'static>,
+    key: KeyPair,
+}
+
+impl fmt::Debug for CaCertIssuer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaCertIssuer")
+            .field("ca_cert", &self.ca.cert)
+            .field("key_algorithm", &self.key_algorithm)
+            .field("validity_days", &self.validity_days)
+            .finish()
+    }
+}
+
+impl CaCertIssuer {
+    /// Default amount of days a generated root CA is valid for.
+    pub const DEFAULT_CA_VALIDITY_DAYS: u32 = 3650;
+
+    /// Default amount of days an issued leaf certificate is valid for.
+    pub const DEFAULT_VALIDITY_DAYS: u32 = 90;
+
+    /// Create a new [`CaCertIssuer`] using the given DER-encoded root CA certificate and its private key.
+    ///
+    /// Only single valued subject name attributes encoded as UTF8String,
+    /// PrintableString or IA5String are supported, which covers the CA
+    /// certificates created by rama, openssl and most other tools.
+    pub fn try_new(
+        ca_cert: CertificateDer<'static>,
+        ca_key: &PrivateKeyDer<'_>,
+    ) -> Result<Self, OpaqueError> {
+        let ca_key = KeyPair::try_from(ca_key).context("parse CA private key")?;
+        if ca_key.algorithm() == &rcgen::PKCS_ED25519 {
+            return Err(OpaqueError::from_display(
+                "Ed25519 keys are not supported for CA certificates",
+            ));
+        }
+
+        let info = parse_ca_cert(&ca_cert).context("parse CA cert")?;
+        if info.public_key != ca_key.public_key_raw() {
+            return Err(OpaqueError::from_display(
+                "CA private key does not match the CA certificate",
+            ));
+        }
+
+        // rcgen can only issue certificates using an rcgen certificate as issuer,
+        // so we re-create one with the same subject and key identifier
+        let mut params = ca_params(0);
+        params.distinguished_name =
+            distinguished_name_from_der(info.subject).context("parse CA subject name")?;
+        if let Some(key_id) = info.subject_key_id {
+            params.key_identifier_method = KeyIdMethod::PreSpecified(key_id.to_vec());
+        }
+        let issuer = params
+            .self_signed(&ca_key)
+            .context("create rcgen CA cert")?;
+        let reproduced = parse_ca_cert(issuer.der()).context("parse rcgen CA cert")?;
+        if reproduced.subject != info.subject {
+            return Err(OpaqueError::from_display(
+                "CA subject name uses an unsupported encoding",
+            ));
+        }
+
+        Ok(Self {
+            ca: Arc::new(Ca {
+                issuer,
+                cert: ca_cert,
+                key: ca_key,
+            }),
+            key_algorithm: KeyAlgorithm::default(),
+            validity_days: Self::DEFAULT_VALIDITY_DAYS,
+        })
+    }
+
+    /// Create a new [`CaCertIssuer`] using a newly generated root CA,
+    /// valid for [`Self::DEFAULT_CA_VALIDITY_DAYS`] days.
+    pub fn generate(
+        data: SelfSignedData,
+        key_algorithm: KeyAlgorithm,
+    ) -> Result<Self, OpaqueError> {
+        Self::generate_with_validity(data, key_algorithm, Self::DEFAULT_CA_VALIDITY_DAYS)
+    }
+
+    /// Create a new [`CaCertIssuer`] using a newly generated root CA,
+    /// valid for the given amount of days.
+    pub fn generate_with_validity(
+        data: SelfSignedData,
+        key_algorithm: KeyAlgorithm,
+        validity_days: u32,
+    ) -> Result<Self, OpaqueError> {
+        if key_algorithm == KeyAlgorithm::Ed25519 {
+            return Err(OpaqueError::from_display(
+                "Ed25519 keys are not supported for CA certificates",
+            ));
+        }
+        let ca_key = generate_key_pair(key_algorithm).context("generate CA key pair")?;
+
+        let mut params = ca_params(validity_days);
+        params.distinguished_name.push(
+            DnType::OrganizationName,
+            data.organisation_name.as_deref().unwrap_or("Anonymous"),
+        );
+        params.distinguished_name.push(
+            DnType::CommonName,
+            data.common_name
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "rama CA".to_owned()),
+        );
+        let issuer = params.self_signed(&ca_key).context("generate CA cert")?;
+
+        Ok(Self {
+            ca: Arc::new(Ca {
+                cert: issuer.der().clone(),
+                issuer,
+                key: ca_key,
+            }),
+            key_algorithm: KeyAlgorithm::default(),
+            validity_days: Self::DEFAULT_VALIDITY_DAYS,
+        })
+    }
+
+    /// Create a new [`CaCertIssuer`] using a PEM-encoded root CA certificate and private key.
+    pub fn try_from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, OpaqueError> {
+        let ca_cert = rustls_pemfile::certs(&mut std::io::BufReader::new(cert_pem))
+            .next()
+            .context("no CA cert found in PEM content")?
+            .context("parse CA cert from PEM content")?;
+        let ca_key = rustls_pemfile::private_key(&mut std::io::BufReader::new(key_pem))
+            .context("parse CA private key from PEM content")?
+            .context("no CA private key found in PEM content")?;
+        Self::try_new(ca_cert, &ca_key)
+    }
+
+    generate_set_and_with! {
+        /// Set the [`KeyAlgorithm`] used for the key pair of issued leaf certificates.
+        ///
+        /// By default [`KeyAlgorithm::EcdsaP256`] is used.
+        pub fn key_algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+            self.key_algorithm = algorithm;
+            self
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the amount of days issued leaf certificates are valid for.
+        ///
+        /// By default issued certificates are valid for [`Self::DEFAULT_VALIDITY_DAYS`] days.
+        pub fn validity_days(mut self, days: u32) -> Self {
+            self.validity_days = days;
+            self
+        }
+    }
+
+    /// DER-encoded root CA certificate,
+    /// which has to be trusted by clients in order to trust the issued certificates.
+    pub fn ca_cert_der(&self) -> &CertificateDer<'static> {
+        &self.ca.cert
+    }
+
+    /// DER-encoded (PKCS#8) private key of the root CA.
+    pub fn ca_key_der(&self) -> PrivateKeyDer<'static> {
+        PrivatePkcs8KeyDer::from(self.ca.key.serialize_der()).into()
+    }
+
+    /// Issue a new leaf certificate for the given [`Host`],
+    /// returning the certificate chain (leaf first, root CA last) and the private key of the leaf.
+    pub fn issue(
+        &self,
+        host: &Host,
+    ) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), OpaqueError> {
+        tracing::trace!(
+            "CaCertIssuer: issue {} cert for host {host}",
+            self.key_algorithm
+        );
+        let key = generate_key_pair(self.key_algorithm).context("generate leaf key pair")?;
+
+        let mut params = CertificateParams::default();
+        set_validity(&mut params, self.validity_days);
+        if let Some(organisation_name) = self
+            .ca
+            .issuer
+            .params()
+            .distinguished_name
+            .get(&DnType::OrganizationName)
+        {
+            params
+                .distinguished_name
+                .push(DnType::OrganizationName, organisation_name.clone());
+        }
+        params
+            .distinguished_name
+            .push(DnType::CommonName, host.to_string());
+        params.subject_alt_names = vec![match host {
+            Host::Name(domain) => SanType::DnsName(
+                domain
+                    .as_str()
+                    .try_into()
+                    .context("domain as subject alt name")?,
+            ),
+            Host::Address(addr) => SanType::IpAddress(*addr),
+        }];
+        params.is_ca = IsCa::ExplicitNoCa;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        if matches!(
+            self.key_algorithm,
+            KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096
+        ) {
+            params.key_usages.push(KeyUsagePurpose::KeyEncipherment);
+        }
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+
+        let cert = params
+            .signed_by(&key, &self.ca.issuer, &self.ca.key)
+            .with_context(|| format!("generate leaf cert for host {host}"))?;
+        Ok((
+            vec![cert.der().clone(), self.ca.cert.clone()],
+            PrivatePkcs8KeyDer::from(key.serialize_der()).into(),
+        ))
+    }
+
+    /// Same as [`Self::issue`], but returning the issued data as DER-encoded [`ServerAuthData`].
+    pub fn issue_server_auth_data(&self, host: &Host) -> Result<ServerAuthData, OpaqueError> {
+        let (cert_chain, key) = self.issue(host)?;
+        Ok(ServerAuthData {
+            private_key: DataEncoding::Der(key.secret_der().to_vec()),
+            cert_chain: DataEncoding::DerStack(
+                cert_chain.into_iter().map(|cert| cert.to_vec()).collect(),
+            ),
+            ocsp: None,
+        })
+    }
+}
+
+impl DynamicCertIssuer for CaCertIssuer {
+    async fn issue_cert(
+        &self,
+        client_hello: ClientHello,
+        server_name: Option<Host>,
+    ) -> Result<ServerAuthData, OpaqueError> {
+        let host = client_hello
+            .ext_server_name()
+            .cloned()
+            .map(Host::Name)
+            .or(server_name)
+            .context("CaCertIssuer: no server name available to issue cert for")?;
+        // key generation (e.g. RSA) can take a while, so keep it off the async runtime
+        let issuer = self.clone();
+        tokio::task::spawn_blocking(move || issuer.issue_server_auth_data(&host))
+            .await
+            .context("CaCertIssuer: join issue cert task")?
+    }
+}
+
+/// Parameters of a root CA certificate, valid for the given amount of days.
+fn ca_params(validity_days: u32) -> CertificateParams {
+    let mut params = CertificateParams::default();
+    set_validity(&mut params, validity_days);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    params
+}
+
+/// Set the validity of a certificate to the given amount of days,
+/// backdated a day to be lenient towards clients with a clock that is behind.
+fn set_validity(params: &mut CertificateParams, validity_days: u32) {
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    let now = rcgen::date_time_ymd(1970, 1, 1)
+        + SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+    params.not_before = now - DAY;
+    params.not_after = now + DAY * validity_days;
+}
+
+/// Fields of a DER-encoded X.509 CA certificate needed to issue certificates with it.
+struct CaCertInfo<'a> {
+    /// DER-encoded subject name
+    subject: &'a [u8],
+    /// public key, as found in the SubjectPublicKeyInfo
+    public_key: &'a [u8],
+    subject_key_id: Option<&'a [u8]>,
+}
+
+/// DER-encoded content of the subjectKeyIdentifier (2.5.29.14) object identifier
+const OID_SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+
+fn parse_ca_cert(cert: &[u8]) -> Result<CaCertInfo<'_>, OpaqueError> {
+    let invalid = || OpaqueError::from_display("invalid DER-encoded certificate");
+
+    let mut tbs = x509_tbs_certificate(cert).ok_or_else(invalid)?;
+    // serial number, signature algorithm, issuer and validity
+    for _ in 0..4 {
+        tbs = read_der(tbs).ok_or_else(invalid)?.2;
+    }
+
+    let (_, rest) = expect_der(tbs, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let subject = &tbs[..tbs.len() - rest.len()];
+
+    let (spki, mut tbs) = expect_der(rest, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (_, spki) = expect_der(spki, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (public_key, _) = expect_der(spki, TAG_BIT_STRING).ok_or_else(invalid)?;
+    // skip the unused bits byte
+    let public_key = public_key.get(1..).ok_or_else(invalid)?;
+
+    let mut subject_key_id = None;
+    while let Some((tag, content, rest)) = read_der(tbs) {
+        tbs = rest;
+        if tag != TAG_X509_EXTENSIONS {
+            continue;
+        }
+        let (mut extensions, _) = expect_der(content, TAG_SEQUENCE).ok_or_else(invalid)?;
+        while let Some((extension, rest)) = expect_der(extensions, TAG_SEQUENCE) {
+            extensions = rest;
+            let (oid, mut extension) = expect_der(extension, TAG_OID).ok_or_else(invalid)?;
+            if oid != OID_SUBJECT_KEY_IDENTIFIER {
+                continue;
+            }
+            if extension.first() == Some(&TAG_BOOLEAN) {
+                extension = read_der(extension).ok_or_else(invalid)?.2;
+            }
+            let (value, _) = expect_der(extension, TAG_OCTET_STRING).ok_or_else(invalid)?;
+            let (key_id, _) = expect_der(value, TAG_OCTET_STRING).ok_or_else(invalid)?;
+            subject_key_id = Some(key_id);
+        }
+    }
+
+    Ok(CaCertInfo {
+        subject,
+        public_key,
+        subject_key_id,
+    })
+}
+
+/// Parse a DER-encoded X.509 name into an rcgen [`DistinguishedName`].
+fn distinguished_name_from_der(name: &[u8]) -> Result<DistinguishedName, OpaqueError> {
+    let invalid = || OpaqueError::from_display("invalid DER-encoded name");
+
+    let (mut rdns, _) = expect_der(name, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let mut distinguished_name = DistinguishedName::new();
+    while !rdns.is_empty() {
+        let (rdn, rest) = expect_der(rdns, TAG_SET).ok_or_else(invalid)?;
+        rdns = rest;
+
+        let (attribute, rest) = expect_der(rdn, TAG_SEQUENCE).ok_or_else(invalid)?;
+        if !rest.is_empty() {
+            return Err(OpaqueError::from_display(
+                "multi-valued name attributes are not supported",
+            ));
+        }
+        let (oid, value) = expect_der(attribute, TAG_OID).ok_or_else(invalid)?;
+        let dn_type = DnType::from_oid(&decode_oid(oid).ok_or_else(invalid)?);
+        if distinguished_name.get(&dn_type).is_some() {
+            return Err(OpaqueError::from_display(
+                "duplicate name attributes are not supported",
+            ));
+        }
+
+        let (tag, value, _) = read_der(value).ok_or_else(invalid)?;
+        let value = std::str::from_utf8(value).context("name attribute as utf-8")?;
+        let dn_value = match tag {
+            TAG_UTF8_STRING => DnValue::Utf8String(value.to_owned()),
+            TAG_PRINTABLE_STRING => {
+                DnValue::PrintableString(value.try_into().context("printable string")?)
+            }
+            TAG_IA5_STRING => DnValue::Ia5String(value.try_into().context("ia5 string")?),
+            _ => {
+                return Err(OpaqueError::from_display(format!(
+                    "unsupported name attribute string type: {tag:#04x}"
+                )));
+            }
+        };
+        distinguished_name.push(dn_type, dn_value);
+    }
+    Ok(distinguished_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rustls::{
+        RootCertStore,
+        client::{WebPkiServerVerifier, danger::ServerCertVerifier},
+        pki_types::{ServerName, UnixTime},
+    };
+    use rama_net::address::Domain;
+
+    fn test_issuer() -> CaCertIssuer {
+        CaCertIssuer::generate(
+            SelfSignedData {
+                organisation_name: Some("rama test".to_owned()),
+                ..Default::default()
+            },
+            KeyAlgorithm::EcdsaP256,
+        )
+        .unwrap()
+    }
+
+    fn verify(issuer: &CaCertIssuer, cert_chain: &[CertificateDer<'static>], name: &str) {
+        let mut roots = RootCertStore::empty();
+        roots.add(issuer.ca_cert_der().clone()).unwrap();
+        WebPkiServerVerifier::builder(Arc::new(roots))
+            .build()
+            .unwrap()
+            .verify_server_cert(
+                &cert_chain[0],
+                &cert_chain[1..],
+                &ServerName::try_from(name.to_owned()).unwrap(),
+                &[],
+                UnixTime::now(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_issue_leaf_cert() {
+        let issuer = test_issuer();
+        let host = Host::Name(Domain::from_static("example.com"));
+
+        for algorithm in [
+            KeyAlgorithm::Rsa2048,
+            KeyAlgorithm::EcdsaP256,
+            KeyAlgorithm::EcdsaP384,
+            KeyAlgorithm::Ed25519,
+        ] {
+            let issuer = issuer.clone().with_key_algorithm(algorithm);
+            let (cert_chain, key) = issuer.issue(&host).unwrap();
+            assert_eq!(2, cert_chain.len());
+            verify(&issuer, &cert_chain, "example.com");
+            assert_eq!(
+                KeyPair::try_from(&key).unwrap().public_key_raw(),
+                parse_ca_cert(&cert_chain[0]).unwrap().public_key
+            );
+        }
+
+        let (cert_chain, _) = issuer.issue(&Host::Address([127, 0, 0, 1].into())).unwrap();
+        verify(&issuer, &cert_chain, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_ca_der_roundtrip() {
+        let issuer = test_issuer();
+        let loaded =
+            CaCertIssuer::try_new(issuer.ca_cert_der().clone(), &issuer.ca_key_der()).unwrap();
+        assert_eq!(issuer.ca_cert_der(), loaded.ca_cert_der());
+
+        // certs issued by the loaded CA are trusted by clients trusting the original CA
+        let (cert_chain, _) = loaded
+            .issue(&Host::Name(Domain::from_static("example.com")))
+            .unwrap();
+        verify(&issuer, &cert_chain, "example.com");
+
+        let other = test_issuer();
+        assert!(CaCertIssuer::try_new(issuer.ca_cert_der().clone(), &other.ca_key_der()).is_err());
+
+        assert!(CaCertIssuer::generate(SelfSignedData::default(), KeyAlgorithm::Ed25519).is_err());
+    }
+
+    #[test]
+    fn test_ca_from_foreign_pem() {
+        // CA created by another tool, using PrintableString name attributes
+        let mut params = ca_params(1);
+        params.distinguished_name.push(
+            DnType::CountryName,
+            DnValue::PrintableString("BE".try_into().unwrap()),
+        );
+        params.distinguished_name.push(
+            DnType::CommonName,
+            DnValue::PrintableString("foreign CA".try_into().unwrap()),
+        );
+        params.key_identifier_method = KeyIdMethod::Sha384;
+        let key = KeyPair::generate_for(&rcgen::PKCS_ECDSA_P384_SHA384).unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        let issuer =
+            CaCertIssuer::try_from_pem(cert.pem().as_bytes(), key.serialize_pem().as_bytes())
+                .unwrap();
+        let (cert_chain, _) = issuer
+            .issue(&Host::Name(Domain::from_static("example.com")))
+            .unwrap();
+        assert_eq!(cert.der(), &cert_chain[1]);
+        verify(&issuer, &cert_chain, "example.com");
+    }
+
+    #[test]
+    fn test_issue_server_auth_data() {
+        let issuer = test_issuer();
+        let data = issuer
+            .issue_server_auth_data(&Host::Address([127, 0, 0, 1].into()))
+            .unwrap();
+        let DataEncoding::DerStack(cert_chain) = data.cert_chain else {
+            panic!("expected DER cert chain");
+        };
+        assert_eq!(2, cert_chain.len());
+        let DataEncoding::Der(key) = data.private_key else {
+            panic!("expected DER private key");
+        };
+        KeyPair::try_from(key.as_slice()).unwrap();
+    }
+}
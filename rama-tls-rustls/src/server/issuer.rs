@@ -0,0 +1,408 @@
+use super::DynamicConfigProvider;
+use crate::RamaFrom;
+use crate::client::certs_from_data_encoding;
+use crate::dep::pki_types::PrivateKeyDer;
+use crate::dep::rustls::{
+    self, ALL_VERSIONS,
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert, ResolvesServerCertUsingSni},
+    sign::CertifiedKey,
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Host;
+use rama_net::tls::DataEncoding;
+use rama_net::tls::client::ClientHello as RamaClientHello;
+use rama_net::tls::server::{
+    CacheKind, DynamicIssuer, IssuedCertCache, ServerAuthData, ServerCertIssuerData,
+    ServerCertIssuerKind,
+};
+use rama_utils::macros::generate_set_and_with;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+/// A [`DynamicConfigProvider`] which issues the server certificate on the fly
+/// using a [`DynamicIssuer`], caching the issued certificates as defined by the [`CacheKind`].
+///
+/// Certificates are cached by the server name (SNI) of the client hello,
+/// certificates issued for a client hello without server name are not cached.
+pub struct CertIssuerConfigProvider {
+    issuer: DynamicIssuer,
+    cert_cache: Option<IssuedCertCache<Arc<CertifiedKey>>>,
+    base_config: Arc<rustls::ServerConfig>,
+    #[cfg(feature = "acme")]
+    acme_challenges: Option<crate::acme::AcmeChallenges>,
+}
+
+impl CertIssuerConfigProvider {
+    /// Create a new [`CertIssuerConfigProvider`] for the given issuer and [`CacheKind`].
+    pub fn new(issuer: DynamicIssuer, cache_kind: CacheKind) -> Self {
+        let base_config = rustls::ServerConfig::builder_with_protocol_versions(ALL_VERSIONS)
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(ResolvesServerCertUsingSni::new()));
+        Self {
+            issuer,
+            cert_cache: IssuedCertCache::try_from_cache_kind(cache_kind),
+            base_config: Arc::new(base_config),
+            #[cfg(feature = "acme")]
+            acme_challenges: None,
+        }
+    }
+
+    generate_set_and_with! {
+        /// Set the [`rustls::ServerConfig`] used as the base for the config of each connection,
+        /// e.g. to define the supported alpn protocols.
+        ///
+        /// The certificate resolver of the config is replaced by the issued certificate.
+        pub fn base_config(mut self, config: rustls::ServerConfig) -> Self {
+            self.base_config = Arc::new(config);
+            self
+        }
+    }
+
+    #[cfg(feature = "acme")]
+    generate_set_and_with! {
+        /// Respond to pending ACME TLS-ALPN-01 challenges of the given [`AcmeChallenges`],
+        /// for client hellos which only offer the `acme-tls/1` alpn protocol (RFC 8737).
+        ///
+        /// Client hellos which offer other alpn protocols as well are not
+        /// considered to be validation requests, and are served as usual.
+        ///
+        /// [`AcmeChallenges`]: crate::acme::AcmeChallenges
+        pub fn acme_challenges(mut self, challenges: crate::acme::AcmeChallenges) -> Self {
+            self.acme_challenges = Some(challenges);
+            self
+        }
+    }
+
+    #[cfg(feature = "acme")]
+    /// Resolve the config for an ACME TLS-ALPN-01 validation request (RFC 8737),
+    /// returns `None` if the client hello is not such a request,
+    /// which requires the alpn protocols to be exactly `[acme-tls/1]`.
+    fn acme_tls_alpn01_config(
+        &self,
+        client_hello: &RamaClientHello,
+    ) -> Option<Result<Arc<rustls::ServerConfig>, OpaqueError>> {
+        use rama_net::tls::ApplicationProtocol;
+
+        let challenges = self.acme_challenges.as_ref()?;
+        let is_validation_request = client_hello
+            .ext_alpn()
+            .is_some_and(|alpn| alpn == [ApplicationProtocol::ACME_TLS]);
+        if !is_validation_request {
+            return None;
+        }
+
+        // never fall back to issuing a cert for a validation request,
+        // as that would order a new cert while validating the current order
+        let Some(certified_key) = client_hello
+            .ext_server_name()
+            .and_then(|domain| challenges.tls_alpn01_cert(domain))
+        else {
+            return Some(Err(OpaqueError::from_display(
+                "rustls/CertIssuerConfigProvider: no pending acme tls-alpn-01 challenge for server name",
+            )));
+        };
+
+        let mut config = (*self.base_config).clone();
+        config.cert_resolver = Arc::new(IssuedCertResolver(certified_key));
+        config.alpn_protocols = vec![ApplicationProtocol::ACME_TLS.as_bytes().to_vec()];
+        Some(Ok(Arc::new(config)))
+    }
+
+    async fn issue_certified_key(
+        &self,
+        host: Option<Host>,
+        client_hello: RamaClientHello,
+    ) -> Result<Arc<CertifiedKey>, OpaqueError> {
+        let provider = self.base_config.crypto_provider();
+        if let (Some(host), Some(cert_cache)) = (&host, &self.cert_cache) {
+            let cached = cert_cache
+                .get_async(host, |data| {
+                    certified_key_from_server_auth_data(data, provider).map(Arc::new)
+                })
+                .await;
+            if let Some(certified_key) = cached {
+                return Ok(certified_key);
+            }
+        }
+
+        let auth_data = self
+            .issuer
+            .issue_cert(client_hello, None)
+            .await
+            .context("rustls/CertIssuerConfigProvider: issue cert")?;
+        let certified_key = Arc::new(certified_key_from_server_auth_data(&auth_data, provider)?);
+
+        if let (Some(host), Some(cert_cache)) = (host, &self.cert_cache) {
+            cert_cache
+                .insert_async(host, certified_key.clone(), move |_| Ok(auth_data))
+                .await;
+        }
+        Ok(certified_key)
+    }
+}
+
+impl TryFrom<ServerCertIssuerData> for CertIssuerConfigProvider {
+    type Error = OpaqueError;
+
+    fn try_from(data: ServerCertIssuerData) -> Result<Self, Self::Error> {
+        match data.kind {
+            ServerCertIssuerKind::Dynamic(issuer) => Ok(Self::new(issuer, data.cache_kind)),
+            ServerCertIssuerKind::SelfSigned(_) | ServerCertIssuerKind::Single(_) => {
+                Err(OpaqueError::from_display(
+                    "rustls/CertIssuerConfigProvider: only dynamic cert issuers are supported",
+                ))
+            }
+        }
+    }
+}
+
+impl DynamicConfigProvider for CertIssuerConfigProvider {
+    fn get_config(
+        &self,
+        client_hello: ClientHello<'_>,
+    ) -> impl Future<Output = Result<Arc<rustls::ServerConfig>, OpaqueError>> + Send {
+        let host = client_hello
+            .server_name()
+            .and_then(|name| name.parse::<Host>().ok());
+        let client_hello = RamaClientHello::rama_from(client_hello);
+
+        #[cfg(feature = "acme")]
+        let acme_config = self.acme_tls_alpn01_config(&client_hello);
+
+        async move {
+            #[cfg(feature = "acme")]
+            if let Some(config) = acme_config {
+                return config;
+            }
+
+            let certified_key = self.issue_certified_key(host, client_hello).await?;
+            let mut config = (*self.base_config).clone();
+            config.cert_resolver = Arc::new(IssuedCertResolver(certified_key));
+            Ok(Arc::new(config))
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Resolver which always resolves to the issued cert.
+struct IssuedCertResolver(Arc<CertifiedKey>);
+
+impl ResolvesServerCert for IssuedCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+}
+
+pub(super) fn certified_key_from_server_auth_data(
+    data: &ServerAuthData,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, OpaqueError> {
+    let private_key = match &data.private_key {
+        DataEncoding::Der(raw_data) => PrivateKeyDer::try_from(raw_data.clone())
+            .map_err(OpaqueError::from_display)
+            .context("rustls/CertIssuerConfigProvider: parse private key from DER content")?,
+        DataEncoding::DerStack(raw_data_list) => PrivateKeyDer::try_from(
+            raw_data_list
+                .first()
+                .context("rustls/CertIssuerConfigProvider: get first private key raw data")?
+                .clone(),
+        )
+        .map_err(OpaqueError::from_display)
+        .context("rustls/CertIssuerConfigProvider: parse private key from DER content")?,
+        DataEncoding::Pem(raw_data) => {
+            rustls_pemfile::private_key(&mut std::io::BufReader::new(raw_data.as_bytes()))
+                .context("rustls/CertIssuerConfigProvider: parse private key from PEM content")?
+                .context("rustls/CertIssuerConfigProvider: no private key found in PEM content")?
+        }
+    };
+    let cert_chain = certs_from_data_encoding(data.cert_chain.clone())?;
+
+    let mut certified_key = CertifiedKey::from_der(cert_chain, private_key, provider)
+        .context("rustls/CertIssuerConfigProvider: create certified key")?;
+    certified_key.ocsp = data.ocsp.clone();
+    Ok(certified_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::tokio_rustls::{LazyConfigAcceptor, TlsConnector};
+    use crate::server::self_signed_server_auth;
+    use crate::verify::NoServerCertVerifier;
+    use rama_net::address::Domain;
+    use rama_net::tls::server::{
+        DiskCertCache, DynamicCertIssuer, SelfSignedData, ServerCertCache,
+    };
+    use std::num::NonZeroU64;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Default)]
+    struct CountingIssuer {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl DynamicCertIssuer for CountingIssuer {
+        async fn issue_cert(
+            &self,
+            client_hello: RamaClientHello,
+            _server_name: Option<Host>,
+        ) -> Result<ServerAuthData, OpaqueError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            let (cert_chain, key) = self_signed_server_auth(SelfSignedData {
+                subject_alternative_names: Some(vec![
+                    client_hello.ext_server_name().unwrap().to_string(),
+                ]),
+                ..Default::default()
+            })?;
+            Ok(ServerAuthData {
+                private_key: DataEncoding::Der(key.secret_der().to_vec()),
+                cert_chain: DataEncoding::DerStack(
+                    cert_chain.into_iter().map(|cert| cert.to_vec()).collect(),
+                ),
+                ocsp: None,
+            })
+        }
+    }
+
+    async fn handshake(provider: &CertIssuerConfigProvider) {
+        handshake_with_alpn(provider, vec![]).await.unwrap();
+    }
+
+    /// Handshake offering the given alpn protocols,
+    /// returning the negotiated alpn protocol and the server cert.
+    async fn handshake_with_alpn(
+        provider: &CertIssuerConfigProvider,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<(Option<Vec<u8>>, Vec<u8>), rama_core::error::BoxError> {
+        let mut client_config = rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::new()))
+            .with_no_client_auth();
+        client_config.alpn_protocols = alpn_protocols;
+        let connector = TlsConnector::from(Arc::new(client_config));
+
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let (client_result, server_result) = tokio::join!(
+            connector.connect("example.com".try_into().unwrap(), client_stream),
+            async {
+                let start = LazyConfigAcceptor::new(Default::default(), server_stream).await?;
+                let config = provider.get_config(start.client_hello()).await?;
+                start
+                    .into_stream(config)
+                    .await
+                    .map_err(rama_core::error::BoxError::from)
+            },
+        );
+        server_result?;
+        let client_stream = client_result?;
+        let (_, conn) = client_stream.get_ref();
+        Ok((
+            conn.alpn_protocol().map(ToOwned::to_owned),
+            conn.peer_certificates().unwrap()[0].to_vec(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_cert_issuer_config_provider_cache() {
+        for (cache_kind, expected_count) in [
+            (CacheKind::Disabled, 2),
+            (
+                CacheKind::MemCache {
+                    max_size: NonZeroU64::new(8).unwrap(),
+                },
+                1,
+            ),
+        ] {
+            let issuer = CountingIssuer::default();
+            let provider =
+                CertIssuerConfigProvider::new(DynamicIssuer::new(issuer.clone()), cache_kind);
+            handshake(&provider).await;
+            handshake(&provider).await;
+            assert_eq!(issuer.count.load(Ordering::SeqCst), expected_count);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cert_issuer_config_provider_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let issuer = CountingIssuer::default();
+
+        // a new provider (e.g. after a restart) re-uses the persisted certs
+        for _ in 0..2 {
+            let provider = CertIssuerConfigProvider::new(
+                DynamicIssuer::new(issuer.clone()),
+                CacheKind::Disk(DiskCertCache::new(dir.path())),
+            );
+            handshake(&provider).await;
+            handshake(&provider).await;
+        }
+        assert_eq!(issuer.count.load(Ordering::SeqCst), 1);
+
+        let host = Host::Name(Domain::from_static("example.com"));
+        let evicted = DiskCertCache::new(dir.path())
+            .with_time_to_live(Duration::ZERO)
+            .evict_expired()
+            .unwrap();
+        assert_eq!(evicted, 1);
+        let provider = CertIssuerConfigProvider::new(
+            DynamicIssuer::new(issuer.clone()),
+            CacheKind::Disk(DiskCertCache::new(dir.path())),
+        );
+        handshake(&provider).await;
+        assert_eq!(issuer.count.load(Ordering::SeqCst), 2);
+        let cached = DiskCertCache::new(dir.path()).get(&host).unwrap();
+        assert!(cached.is_some());
+    }
+
+    #[cfg(feature = "acme")]
+    #[tokio::test]
+    async fn test_cert_issuer_config_provider_acme_tls_alpn01() {
+        use crate::acme::AcmeChallenges;
+
+        let challenges = AcmeChallenges::new();
+        let issuer = CountingIssuer::default();
+        let provider =
+            CertIssuerConfigProvider::new(DynamicIssuer::new(issuer.clone()), CacheKind::Disabled)
+                .with_acme_challenges(challenges.clone());
+        let acme_tls = b"acme-tls/1".to_vec();
+
+        // validation requests without pending challenge are rejected
+        assert!(
+            handshake_with_alpn(&provider, vec![acme_tls.clone()])
+                .await
+                .is_err()
+        );
+
+        challenges
+            .insert_tls_alpn01(
+                Domain::from_static("example.com"),
+                "token".to_owned(),
+                "token.thumbprint",
+            )
+            .unwrap();
+        let (alpn, cert) = handshake_with_alpn(&provider, vec![acme_tls.clone()])
+            .await
+            .unwrap();
+        assert_eq!(alpn, Some(acme_tls.clone()));
+        let expected_cert = challenges
+            .tls_alpn01_cert(&Domain::from_static("example.com"))
+            .unwrap();
+        assert_eq!(cert, expected_cert.end_entity_cert().unwrap().to_vec());
+
+        // regular handshakes are not affected by pending challenges
+        let (_, cert) = handshake_with_alpn(&provider, vec![b"h2".to_vec()])
+            .await
+            .unwrap();
+        assert_ne!(cert, expected_cert.end_entity_cert().unwrap().to_vec());
+        assert_eq!(issuer.count.load(Ordering::SeqCst), 1);
+
+        // ... nor are those which offer other protocols next to acme-tls/1
+        let (_, cert) = handshake_with_alpn(&provider, vec![b"h2".to_vec(), acme_tls.clone()])
+            .await
+            .unwrap();
+        assert_ne!(cert, expected_cert.end_entity_cert().unwrap().to_vec());
+        assert_eq!(issuer.count.load(Ordering::SeqCst), 2);
+    }
+}
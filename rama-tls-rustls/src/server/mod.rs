@@ -26,3 +26,15 @@ mod acceptor_data;
 pub use acceptor_data::{
     DynamicConfigProvider, TlsAcceptorData, TlsAcceptorDataBuilder, self_signed_server_auth,
 };
+
+mod ca;
+#[doc(inline)]
+pub use ca::CaCertIssuer;
+
+mod issuer;
+#[doc(inline)]
+pub use issuer::CertIssuerConfigProvider;
+
+mod reload;
+#[doc(inline)]
+pub use reload::ReloadableCertResolver;
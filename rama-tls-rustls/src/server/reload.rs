@@ -0,0 +1,130 @@
+use super::issuer::certified_key_from_server_auth_data;
+use crate::dep::rustls::{
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_core::telemetry::tracing;
+use rama_net::tls::server::ReloadableServerAuth;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+/// [`ResolvesServerCert`] which resolves to the certificate of a [`ReloadableServerAuth`],
+/// such that certificates can be rotated without restarting the server.
+///
+/// Reloaded data is validated (e.g. whether or not the private key matches the certificate)
+/// before it is swapped in, in case it is invalid the reload fails
+/// and the previous certificate keeps being served.
+pub struct ReloadableCertResolver {
+    auth: ReloadableServerAuth,
+    provider: Arc<CryptoProvider>,
+    current: Arc<Mutex<(u64, Arc<CertifiedKey>)>>,
+}
+
+impl ReloadableCertResolver {
+    /// Create a new [`ReloadableCertResolver`] for the given [`ReloadableServerAuth`],
+    /// using the [`CryptoProvider`] to load the private key.
+    pub fn try_new(
+        auth: ReloadableServerAuth,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self, OpaqueError> {
+        let (generation, data) = auth.current();
+        let certified_key = certified_key_from_server_auth_data(&data, &provider)
+            .context("rustls/ReloadableCertResolver: load initial cert")?;
+
+        let validator_provider = provider.clone();
+        auth.set_validator("rustls", move |data| {
+            certified_key_from_server_auth_data(data, &validator_provider)
+                .map(|_| ())
+                .context("rustls/ReloadableCertResolver: validate reloaded cert")
+        });
+
+        Ok(Self {
+            auth,
+            provider,
+            current: Arc::new(Mutex::new((generation, Arc::new(certified_key)))),
+        })
+    }
+
+    /// Current certificate, loading it first in case the data was reloaded.
+    fn current(&self) -> Arc<CertifiedKey> {
+        let (generation, data) = self.auth.current();
+        let mut current = self.current.lock().unwrap_or_else(|err| err.into_inner());
+        if current.0 != generation {
+            // generation is updated even on error, so invalid data is only tried once
+            current.0 = generation;
+            match certified_key_from_server_auth_data(&data, &self.provider) {
+                Ok(certified_key) => {
+                    tracing::debug!("rustls/ReloadableCertResolver: use reloaded cert");
+                    current.1 = Arc::new(certified_key);
+                }
+                Err(err) => tracing::error!(
+                    "rustls/ReloadableCertResolver: invalid reloaded cert, keep using previous cert: {err:?}"
+                ),
+            }
+        }
+        current.1.clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rcgen;
+    use crate::dep::rustls::crypto::aws_lc_rs;
+    use rama_net::tls::server::ServerAuthFiles;
+
+    fn self_signed(domain: &str) -> (String, String) {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec![domain.to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        (cert.pem(), key_pair.serialize_pem())
+    }
+
+    #[test]
+    fn test_reloadable_cert_resolver() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.crt.pem");
+        let key_path = dir.path().join("server.key.pem");
+        let ocsp_path = dir.path().join("server.ocsp.der");
+
+        let (cert, key) = self_signed("example.com");
+        std::fs::write(&cert_path, &cert).unwrap();
+        std::fs::write(&key_path, &key).unwrap();
+        std::fs::write(&ocsp_path, [1, 2, 3]).unwrap();
+        let auth = ReloadableServerAuth::try_new(
+            ServerAuthFiles::new(&cert_path, &key_path).with_ocsp(ocsp_path),
+        )
+        .unwrap();
+        let resolver =
+            ReloadableCertResolver::try_new(auth.clone(), Arc::new(aws_lc_rs::default_provider()))
+                .unwrap();
+        let initial = resolver.current();
+        assert_eq!(initial.ocsp.as_deref(), Some(&[1, 2, 3][..]));
+
+        // a key which does not match the cert is rejected, keeping the previous cert
+        let (_, other_key) = self_signed("example.com");
+        std::fs::write(&key_path, other_key).unwrap();
+        assert!(auth.reload().is_err());
+        assert_eq!(auth.current().0, 0);
+        assert!(Arc::ptr_eq(&initial, &resolver.current()));
+
+        let (new_cert, new_key) = self_signed("example.com");
+        std::fs::write(&cert_path, &new_cert).unwrap();
+        std::fs::write(&key_path, new_key).unwrap();
+        auth.reload().unwrap();
+        let reloaded = resolver.current();
+        assert!(!Arc::ptr_eq(&initial, &reloaded));
+        assert_ne!(initial.cert, reloaded.cert);
+        assert!(Arc::ptr_eq(&reloaded, &resolver.current()));
+    }
+}
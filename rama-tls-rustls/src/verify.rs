@@ -1,12 +1,14 @@
 //! TLS Verify support for Rustls usage in Rama.
 //!
-//! ... or rather the lack of verification where it is not needed.
+//! ... or rather the lack of (full) verification where it is not needed.
 
 use crate::dep::rustls::{
-    DigitallySignedStruct, SignatureScheme,
+    CertificateError, DigitallySignedStruct, SignatureScheme,
     client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{WebPkiSupportedAlgorithms, verify_tls12_signature, verify_tls13_signature},
     pki_types::{CertificateDer, ServerName, UnixTime},
 };
+use rama_net::tls::client::{CertificatePin, verify_certificate_pins};
 
 /// Cert verifier that does not verify the server certificate.
 #[derive(Debug)]
@@ -74,3 +76,74 @@ impl ServerCertVerifier for NoServerCertVerifier {
         ]
     }
 }
+
+/// Cert verifier that does not verify the server certificate chain against root certificates,
+/// but only that the end-entity certificate matches any of the given pins.
+///
+/// Unlike the [`NoServerCertVerifier`] the handshake signatures are verified,
+/// such that the server is proven to own the key of the pinned certificate.
+#[derive(Debug)]
+pub struct PinnedServerCertVerifier {
+    pins: Option<Vec<CertificatePin>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl PinnedServerCertVerifier {
+    /// Create a new [`PinnedServerCertVerifier`] for the given pins,
+    /// using the given algorithms to verify the handshake signatures.
+    pub fn new(pins: Vec<CertificatePin>, algorithms: WebPkiSupportedAlgorithms) -> Self {
+        Self {
+            pins: Some(pins),
+            algorithms,
+        }
+    }
+
+    /// Create a verifier which accepts any server certificate,
+    /// used when the certificate chain is verified after the handshake instead.
+    pub(crate) fn deferred(algorithms: WebPkiSupportedAlgorithms) -> Self {
+        Self {
+            pins: None,
+            algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(pins) = &self.pins {
+            verify_certificate_pins(pins, end_entity).map_err(|_| {
+                rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)
+            })?;
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -5,7 +5,9 @@ use rama_http_types::{
         h2::{PseudoHeaderOrder, frame::EarlyFrameCapture},
     },
 };
-use rama_net::fingerprint::{HttpRequestInput, Ja4H, Ja4HComputeError};
+use rama_net::fingerprint::{
+    Http2Fingerprint, Http2FingerprintComputeError, HttpRequestInput, Ja4H, Ja4HComputeError,
+};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -151,6 +153,13 @@ impl HttpProfile {
             })
         })
     }
+
+    /// Compute the (Akamai) [`Http2Fingerprint`] for the h2 settings in this [`HttpProfile`].
+    ///
+    /// See [`Http2Settings::fingerprint`] for more information.
+    pub fn h2_fingerprint(&self) -> Result<Http2Fingerprint, Http2FingerprintComputeError> {
+        self.h2.settings.fingerprint()
+    }
 }
 
 impl<'de> Deserialize<'de> for HttpProfile {
@@ -268,3 +277,21 @@ pub struct Http2Settings {
     /// Frames to be sent at the start of a stream.
     pub early_frames: Option<EarlyFrameCapture>,
 }
+
+impl Http2Settings {
+    /// Compute the (Akamai) [`Http2Fingerprint`] for these [`Http2Settings`],
+    /// which can be compared against the fingerprint of an incoming h2 request.
+    ///
+    /// Both the pseudo headers and early frames are required for this.
+    pub fn fingerprint(&self) -> Result<Http2Fingerprint, Http2FingerprintComputeError> {
+        let early_frames = self
+            .early_frames
+            .as_ref()
+            .ok_or(Http2FingerprintComputeError::MissingEarlyFrames)?;
+        let pseudo_headers = self
+            .http_pseudo_headers
+            .as_ref()
+            .ok_or(Http2FingerprintComputeError::MissingPseudoHeaders)?;
+        Http2Fingerprint::compute_from_early_frames(early_frames.as_slice(), pseudo_headers)
+    }
+}
@@ -12,3 +12,161 @@ pub enum LatencyUnit {
     /// Use nanoseconds.
     Nanos,
 }
+
+/// A histogram of latencies, which allows to cheaply estimate latency percentiles.
+///
+/// Latencies are recorded with microsecond precision in buckets of exponentially
+/// increasing width, such that the relative error of an estimated percentile
+/// is at most 12.5%, regardless of the magnitude of the latencies recorded.
+#[derive(Clone)]
+pub struct LatencyHistogram {
+    buckets: Box<[u64; LatencyHistogram::BUCKET_COUNT]>,
+    count: u64,
+}
+
+impl std::fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LatencyHistogram")
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    /// Values below this amount of microseconds get their own bucket.
+    const LINEAR_BUCKETS: u64 = 16;
+    /// Amount of sub buckets per power of two (starting from [`Self::LINEAR_BUCKETS`]).
+    const SUB_BUCKET_BITS: u32 = 3;
+    /// Latencies above `2^MAX_EXP` microseconds (about 12 days) are clamped.
+    const MAX_EXP: u32 = 40;
+    const BUCKET_COUNT: usize = Self::LINEAR_BUCKETS as usize
+        + ((Self::MAX_EXP - Self::LINEAR_BUCKETS.ilog2() + 1) << Self::SUB_BUCKET_BITS) as usize;
+
+    /// Create a new empty [`LatencyHistogram`].
+    pub fn new() -> Self {
+        Self {
+            buckets: Box::new([0; Self::BUCKET_COUNT]),
+            count: 0,
+        }
+    }
+
+    /// Record a single latency.
+    pub fn record(&mut self, latency: std::time::Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.buckets[Self::bucket_index(micros)] += 1;
+        self.count += 1;
+    }
+
+    /// Amount of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if no latencies were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Remove all recorded latencies.
+    pub fn clear(&mut self) {
+        self.buckets.fill(0);
+        self.count = 0;
+    }
+
+    /// Estimate the latency at the given percentile (e.g. `95.0` for p95),
+    /// or `None` if no latencies were recorded.
+    ///
+    /// The percentile is clamped to the `[0, 100]` range.
+    pub fn percentile(&self, percentile: f64) -> Option<std::time::Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let percentile = percentile.clamp(0.0, 100.0);
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+
+        let mut seen = 0;
+        for (index, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(std::time::Duration::from_micros(Self::bucket_upper_bound(
+                    index,
+                )));
+            }
+        }
+        None
+    }
+
+    fn bucket_index(micros: u64) -> usize {
+        if micros < Self::LINEAR_BUCKETS {
+            return micros as usize;
+        }
+        let exp = micros.ilog2().min(Self::MAX_EXP);
+        let micros = micros.min((1 << (Self::MAX_EXP + 1)) - 1);
+        let sub = (micros >> (exp - Self::SUB_BUCKET_BITS)) & ((1 << Self::SUB_BUCKET_BITS) - 1);
+        Self::LINEAR_BUCKETS as usize
+            + (((exp - Self::LINEAR_BUCKETS.ilog2()) << Self::SUB_BUCKET_BITS) as usize)
+            + sub as usize
+    }
+
+    fn bucket_upper_bound(index: usize) -> u64 {
+        if index < Self::LINEAR_BUCKETS as usize {
+            return index as u64;
+        }
+        let index = index - Self::LINEAR_BUCKETS as usize;
+        let exp = (index >> Self::SUB_BUCKET_BITS) as u32 + Self::LINEAR_BUCKETS.ilog2();
+        let sub = (index & ((1 << Self::SUB_BUCKET_BITS) - 1)) as u64;
+        let width = 1 << (exp - Self::SUB_BUCKET_BITS);
+        (((1 << Self::SUB_BUCKET_BITS) + sub) * width) + width - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn latency_histogram_empty() {
+        let histogram = LatencyHistogram::new();
+        assert!(histogram.is_empty());
+        assert_eq!(None, histogram.percentile(50.0));
+    }
+
+    #[test]
+    fn latency_histogram_bucket_bounds() {
+        for micros in [0, 1, 15, 16, 17, 100, 1_000, 123_456, 10_000_000, u64::MAX] {
+            let index = LatencyHistogram::bucket_index(micros);
+            assert!(index < LatencyHistogram::BUCKET_COUNT, "{micros}");
+            let upper = LatencyHistogram::bucket_upper_bound(index);
+            if micros < (1 << (LatencyHistogram::MAX_EXP + 1)) {
+                assert!(upper >= micros, "{micros}: {upper}");
+                assert!(upper - micros <= micros / 8, "{micros}: {upper}");
+            }
+        }
+    }
+
+    #[test]
+    fn latency_histogram_percentiles() {
+        let mut histogram = LatencyHistogram::new();
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        assert_eq!(100, histogram.count());
+
+        let p50 = histogram.percentile(50.0).unwrap();
+        assert!(p50 >= Duration::from_millis(50) && p50 <= Duration::from_millis(57));
+        let p99 = histogram.percentile(99.0).unwrap();
+        assert!(p99 >= Duration::from_millis(99) && p99 <= Duration::from_millis(112));
+        let p100 = histogram.percentile(200.0).unwrap();
+        assert!(p100 >= Duration::from_millis(100));
+
+        histogram.clear();
+        assert!(histogram.is_empty());
+    }
+}